@@ -1,25 +1,98 @@
 use std::{
+    cell::RefCell,
     cmp,
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     ffi::{OsStr, OsString},
     fmt,
     fs::{self, DirEntry},
     io,
     ops::{Index, IndexMut, Range},
     path::{Path, PathBuf},
-    process::Command,
+    time::Instant,
 };
 
 use anyhow::{Context, bail};
-use argh::FromArgs;
-use serde::{Deserialize, Serialize};
+use argh::{ArgsInfo, FromArgs};
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use tera::Tera;
-use tracing::{debug, instrument};
-
+use tracing::{debug, info, instrument, trace, warn};
+
+mod a11y;
+mod annotation;
+mod author;
+mod bench;
+mod budget;
+mod builder;
+mod cache;
+mod check;
+mod clean;
+mod config;
+mod critical;
+mod css;
+mod deploy;
+mod diagnostic;
+mod diff;
 mod djot;
+mod email;
+mod epub;
+mod errors;
+mod export;
+mod feed;
+mod format;
+mod functions;
+mod generated;
+mod hooks;
+mod host;
+mod ical;
+mod import;
+mod info;
+mod inline;
+mod links;
+mod man;
+mod manifest;
+mod meta;
+mod metadata_export;
+mod notebook;
+mod pipeline;
+mod plugin;
+mod podcast;
+#[doc(hidden)]
+pub mod protect;
+mod prune;
+mod remote;
+mod rules;
+mod serve;
+mod site_cache;
+mod sitemap;
+mod source;
+mod spellcheck;
+mod summary;
+mod taxonomy;
+mod theme;
+mod translation;
+mod typography;
+mod undefined;
+mod virtual_page;
+mod warnings;
+mod webring;
+
+pub use bench::{BenchCmd, CountingAllocator, TimingLayer, bench};
+pub use builder::{PageWritten, SiteBuild, SiteBuilder};
+pub use check::{CheckCmd, check};
+pub use clean::{CleanCmd, clean};
+pub use deploy::{DeployCmd, deploy};
+pub use diff::{DiffCmd, diff};
+pub use errors::{USAGE_EXIT_CODE, exit_code, exit_statuses};
+pub use export::{ExportCmd, export};
+pub use import::{ImportCmd, import};
+pub use man::{ManCmd, man};
+pub use serve::{ServeCmd, serve};
+pub use summary::{BuildStats, PROGRESS_TARGET};
+pub use warnings::{Warning, WarningLayer};
 
 /// Build the static site.
-#[derive(FromArgs, Debug)]
+#[derive(FromArgs, ArgsInfo, Debug)]
 #[argh(subcommand, name = "build")]
 pub struct BuildCmd {
     /// path to the input directory
@@ -33,6 +106,30 @@ pub struct BuildCmd {
     /// render the site without debug information
     #[argh(switch)]
     release: bool,
+
+    /// write the graph of links between pages to this file, as GraphViz DOT
+    /// for `.dot` files and JSON otherwise
+    #[argh(option)]
+    link_graph: Option<PathBuf>,
+
+    /// keep building the other pages after a page fails, and report every
+    /// error at the end
+    #[argh(switch)]
+    keep_going: bool,
+
+    /// fail the build if there are any warnings
+    #[argh(switch)]
+    deny_warnings: bool,
+
+    /// also print warnings and errors to stdout for other tools, `github`
+    /// prints GitHub Actions annotations
+    #[argh(option)]
+    output_format: Option<annotation::OutputFormat>,
+
+    /// don't fetch anything over the network, and use cached responses of
+    /// earlier builds however old they are
+    #[argh(switch)]
+    offline: bool,
 }
 
 impl BuildCmd {
@@ -178,6 +275,24 @@ impl ContentSlug {
         path
     }
 
+    /// The directory of the section this page belongs to, which for an index
+    /// page is the section above its own directory.
+    fn containing_dir(&self) -> Option<&Path> {
+        match self.stem {
+            ContentSlugStem::Index => self.parent.parent(),
+            ContentSlugStem::Other(_) => Some(&self.parent),
+        }
+    }
+
+    /// The directory of the pages beneath this one, which is the directory of
+    /// an index page, or the directory named after any other page.
+    fn section_dir(&self) -> PathBuf {
+        match &self.stem {
+            ContentSlugStem::Index => self.parent.clone(),
+            ContentSlugStem::Other(os_string) => self.parent.join(os_string),
+        }
+    }
+
     fn make_subpage_range(&self) -> Range<Self> {
         match &self.stem {
             ContentSlugStem::Index => {
@@ -231,26 +346,140 @@ struct Content {
     files: BTreeMap<ContentSlug, ContentFile>,
 }
 
-#[derive(Debug, Clone)]
+impl Content {
+    /// Apply the `cascade` frontmatter field of every section index page as
+    /// defaults to all pages below that section.
+    ///
+    /// This happens before any page is rendered, since section index pages are
+    /// rendered after the pages they contain.
+    fn apply_cascades(&mut self, release: bool) -> anyhow::Result<()> {
+        let mut cascades = BTreeMap::new();
+        for (slug, file) in &self.files {
+            if !matches!(slug.stem, ContentSlugStem::Index)
+                || !matches!(file.original_media_type, MediaType::Djot)
+            {
+                continue;
+            }
+
+            let content = fs::read_to_string(&file.input.full_path)
+                .context("failed to read section index file")?;
+            let frontmatter = match djot::read_frontmatter(&file.input.full_path, &content) {
+                Ok(frontmatter) => frontmatter,
+                // The page is rendered without its frontmatter outside of release
+                // builds, which warns about the error
+                Err(err) if !release => {
+                    debug!(%slug, %err, "Skipping cascade of invalid frontmatter");
+                    continue;
+                },
+                Err(err) => {
+                    return Err(err.context(format!("failed to read frontmatter of [{slug}]")));
+                },
+            };
+            if let Some(cascade) = frontmatter.as_ref().and_then(|fm| fm.0.get("cascade")) {
+                let Some(cascade) = cascade.as_object() else {
+                    bail!("invalid frontmatter field 'cascade' in [{slug}], expected an object");
+                };
+                cascades.insert(slug.parent.clone(), cascade.clone());
+            }
+        }
+
+        if cascades.is_empty() {
+            return Ok(());
+        }
+
+        for (slug, file) in &self.files {
+            if file.plan.is_empty() {
+                continue;
+            }
+
+            // Merge from the outermost section inwards, so nearer sections win. An index
+            // page's own cascade only applies to the pages below it.
+            let is_index = matches!(slug.stem, ContentSlugStem::Index);
+            let mut defaults = serde_json::Map::new();
+            for section in slug
+                .parent
+                .ancestors()
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+            {
+                if is_index && section == slug.parent {
+                    continue;
+                }
+                if let Some(cascade) = cascades.get(section) {
+                    defaults.extend(cascade.clone());
+                }
+            }
+
+            if !defaults.is_empty() {
+                debug!(%slug, ?defaults, "Applying cascaded frontmatter");
+                self.metadata[slug]
+                    .set_frontmatter(Frontmatter(tera::Value::Object(defaults)))
+                    .context(format!("in frontmatter cascaded to [{slug}]"))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove every page marked as a draft, so it is neither written nor listed
+    /// anywhere.
+    fn remove_drafts(&mut self) {
+        let drafts = self
+            .metadata
+            .0
+            .iter()
+            .filter(|(_, md)| md.draft)
+            .map(|(slug, _)| slug.clone())
+            .collect::<Vec<_>>();
+
+        for slug in drafts {
+            debug!(%slug, "Removing draft page from release build");
+            self.metadata.0.remove(&slug);
+            self.files.remove(&slug);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum MediaType {
     Other(Option<String>),
     Djot,
     Html,
+    Text,
+    Notebook,
 }
 
 impl MediaType {
+    fn from_extension(extension: Option<&str>) -> Self {
+        match extension {
+            Some("dj") => MediaType::Djot,
+            Some("html") => MediaType::Html,
+            Some("ipynb") => MediaType::Notebook,
+            Some("txt") => MediaType::Text,
+            Some(other) => MediaType::Other(Some(other.into())),
+            None => MediaType::Other(None),
+        }
+    }
+
     fn extension(&self) -> String {
         match self {
             MediaType::Other(ext) => ext.as_ref().cloned().unwrap_or_default(),
             MediaType::Djot => "dj".into(),
             MediaType::Html => "html".into(),
+            MediaType::Text => "txt".into(),
+            MediaType::Notebook => "ipynb".into(),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum Transform {
     RenderDjot,
+    RenderNotebook,
+    WrapText,
+    /// Pipe the content through an external command from a configured pipeline
+    RunCommand(Vec<String>),
     ApplyTemplate,
 }
 
@@ -258,6 +487,34 @@ enum Transform {
 #[serde(transparent)]
 struct Frontmatter(tera::Value);
 
+impl Frontmatter {
+    /// Deserialize a single field, naming the field in the error if it has the
+    /// wrong shape
+    fn typed_field<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        let Some(value) = self.0.get(key) else {
+            return Ok(None);
+        };
+
+        serde_json::from_value(value.clone())
+            .map(Some)
+            .context(format!("invalid frontmatter field '{key}'"))
+    }
+}
+
+/// Parse a frontmatter date, which is either a full RFC 3339 timestamp or a
+/// plain `YYYY-MM-DD` date.
+fn parse_date(date: &str) -> anyhow::Result<DateTime<FixedOffset>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(date) {
+        return Ok(timestamp);
+    }
+
+    let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
+        bail!("date [{date}] is not an RFC 3339 timestamp or YYYY-MM-DD date")
+    };
+
+    Ok(date.and_time(Default::default()).and_utc().fixed_offset())
+}
+
 #[derive(Debug, Serialize)]
 struct Metadata {
     #[serde(flatten)]
@@ -266,11 +523,74 @@ struct Metadata {
     debug: bool,
     url_path: PathBuf,
     slug: ContentSlug,
+    /// Path of the content file the page is rendered from
+    #[serde(skip)]
+    source_path: PathBuf,
     is_article: bool,
+    // The well-known frontmatter fields below are validated when the frontmatter
+    // is set, and are already available to templates through the flattened
+    // frontmatter, so they are only serialized when their name differs
+    #[serde(skip)]
+    date: Option<DateTime<FixedOffset>>,
+    #[serde(skip)]
+    tags: Vec<String>,
+    #[serde(skip)]
+    draft: bool,
+    #[serde(skip)]
+    summary: Option<String>,
+    #[serde(skip)]
+    description: Option<String>,
+    /// The description for the `<meta>` tag, falling back to the summary and
+    /// then the first paragraph of the content
+    meta_description: Option<String>,
+    /// The `keywords` field as a list, which is also accepted as a
+    /// comma-separated string
+    #[serde(rename = "meta_keywords")]
+    keywords: Vec<String>,
     bibliography_file: Option<String>,
+    /// Name of the CSL style used for citations, defaults to IEEE
+    #[serde(skip)]
+    citation_style: Option<String>,
+    /// Path of the template to render this page with, relative to the template
+    /// directory, instead of the one found by lookup
+    #[serde(skip)]
+    template: Option<String>,
+    #[serde(skip)]
+    sitemap: sitemap::SitemapHints,
+    /// The authors listed in the frontmatter, resolved to their profiles
+    byline: Vec<author::Author>,
+    /// URL paths of the other pages on the site that this page links to
+    #[serde(skip)]
+    outgoing_links: BTreeSet<String>,
+    /// The pages that link to this page
+    backlinks: Vec<links::PageLink>,
+    /// The page content after rendering, but before any template is applied
+    #[serde(skip)]
+    rendered_content: Option<String>,
+    /// Files other than the content file that were read to render the page,
+    /// like bibliographies and CSV data
+    #[serde(skip)]
+    dependencies: BTreeSet<PathBuf>,
+    /// Thumbnails of the images in the galleries of the page
+    #[serde(skip)]
+    thumbnails: Vec<djot::Thumbnail>,
+    /// The parsed library of the bibliography file, shared with every other
+    /// page that cites from the same file
+    #[serde(skip)]
+    bibliography: Option<djot::Bibliography>,
 }
 
+/// The URL path of the page served for unknown paths, which is rendered from
+/// `content/404.dj`
+const NOT_FOUND_URL_PATH: &str = "/404.html";
+
 impl Metadata {
+    /// Whether this is the page served for unknown paths, which is rendered
+    /// like any other page but isn't listed anywhere.
+    fn is_not_found(&self) -> bool {
+        self.url_path == Path::new(NOT_FOUND_URL_PATH)
+    }
+
     fn new(args: &BuildCmd, slug: &ContentSlug, content_file: &ContentFile) -> Self {
         Self {
             frontmatter: None,
@@ -278,10 +598,79 @@ impl Metadata {
             debug: !args.release,
             url_path: Path::new("/").join(slug.parent.join(content_file.output_filename())),
             slug: slug.clone(),
+            source_path: content_file.input.full_path.clone(),
             is_article: content_file.is_article(),
+            date: None,
+            tags: vec![],
+            draft: false,
+            summary: None,
+            description: None,
+            meta_description: None,
+            keywords: vec![],
             bibliography_file: None,
+            citation_style: None,
+            template: None,
+            sitemap: sitemap::SitemapHints::default(),
+            byline: vec![],
+            outgoing_links: BTreeSet::new(),
+            backlinks: vec![],
+            rendered_content: None,
+            dependencies: BTreeSet::new(),
+            thumbnails: vec![],
+            bibliography: None,
         }
     }
+
+    /// Validate the well-known fields of the frontmatter and store it.
+    ///
+    /// If the page already has frontmatter, like defaults cascaded from a
+    /// section, the new fields are merged over the existing ones.
+    fn set_frontmatter(&mut self, frontmatter: Frontmatter) -> anyhow::Result<()> {
+        let tera::Value::Object(fields) = frontmatter.0 else {
+            bail!("frontmatter must be a JSON object");
+        };
+        let frontmatter = match self.frontmatter.take() {
+            Some(Frontmatter(tera::Value::Object(mut defaults))) => {
+                defaults.extend(fields);
+                Frontmatter(tera::Value::Object(defaults))
+            },
+            _ => Frontmatter(tera::Value::Object(fields)),
+        };
+
+        self.date = frontmatter
+            .typed_field::<String>("date")?
+            .map(|date| parse_date(&date))
+            .transpose()
+            .context("invalid frontmatter field 'date'")?;
+        self.tags = frontmatter.typed_field("tags")?.unwrap_or_default();
+        self.draft = frontmatter.typed_field("draft")?.unwrap_or_default();
+        self.summary = frontmatter.typed_field("summary")?;
+        self.description = frontmatter.typed_field("description")?;
+        self.keywords = frontmatter
+            .typed_field::<meta::Keywords>("keywords")?
+            .map(meta::Keywords::into_vec)
+            .unwrap_or_default();
+        self.bibliography_file = frontmatter.typed_field("bibliography")?;
+        self.citation_style = frontmatter.typed_field("citation_style")?;
+        self.template = frontmatter.typed_field("template")?;
+        self.sitemap = sitemap::SitemapHints::from_frontmatter(&frontmatter)?;
+        self.frontmatter = Some(frontmatter);
+
+        Ok(())
+    }
+
+    fn frontmatter_field(&self, key: &str) -> Option<&tera::Value> {
+        self.frontmatter.as_ref()?.0.get(key)
+    }
+
+    /// The strings in a frontmatter field containing a list, like taxonomy terms
+    fn frontmatter_str_list(&self, key: &str) -> impl Iterator<Item = &str> {
+        self.frontmatter_field(key)
+            .and_then(tera::Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(tera::Value::as_str)
+    }
 }
 
 #[derive(Debug, Default)]
@@ -307,16 +696,94 @@ impl MetadataContainer {
         assert!(prev.is_none());
     }
 
+    /// The pages directly in the section of the given index page, leaving out
+    /// the page for unknown paths.
     fn subpages(&self, slug: &ContentSlug) -> Vec<&Metadata> {
         let range = slug.make_subpage_range();
         let subpages = self
             .0
             .range(range.clone())
             .map(|(_, md)| md)
+            .filter(|md| !md.is_not_found())
             .collect::<Vec<_>>();
         debug!(?range, ?subpages, "Collected subpages");
         subpages
     }
+
+    /// The index page of the section in the given directory, if it has one.
+    fn section_index(&self, dir: &Path) -> Option<&Metadata> {
+        self.0
+            .iter()
+            .find(|(slug, _)| slug.parent == dir && matches!(slug.stem, ContentSlugStem::Index))
+            .map(|(_, md)| md)
+    }
+
+    /// The other pages in the same section as the given page, leaving out the
+    /// index page of the section. The siblings of an index page are the pages
+    /// of the section above.
+    fn siblings(&self, slug: &ContentSlug) -> Vec<&Metadata> {
+        let Some(dir) = slug.containing_dir() else {
+            return vec![];
+        };
+        let section_index = ContentSlug {
+            parent: dir.to_path_buf(),
+            stem: ContentSlugStem::Index,
+            extension: None,
+        };
+        self.subpages(&section_index)
+            .into_iter()
+            .filter(|md| md.slug != *slug)
+            .collect()
+    }
+
+    /// The index pages of the sections that contain the given page, starting
+    /// from the root of the site and ending with its parent section.
+    ///
+    /// Directories without an index page are skipped.
+    fn ancestors(&self, slug: &ContentSlug) -> Vec<&Metadata> {
+        let mut ancestors = slug
+            .containing_dir()
+            .into_iter()
+            .flat_map(Path::ancestors)
+            .filter_map(|dir| self.section_index(dir))
+            .collect::<Vec<_>>();
+        ancestors.reverse();
+        ancestors
+    }
+
+    /// Every page beneath the given page at any depth, unlike
+    /// [`MetadataContainer::subpages`] which only has the direct children, also
+    /// leaving out the page for unknown paths.
+    fn descendants(&self, slug: &ContentSlug) -> Vec<Descendant<'_>> {
+        let section_dir = slug.section_dir();
+        self.0
+            .iter()
+            .filter(|(other, md)| *other != slug && !md.is_not_found())
+            .filter_map(|(other, md)| {
+                let relative = other.parent.strip_prefix(&section_dir).ok()?;
+                let num_dirs = relative.components().count();
+                // The index page of a subsection stands for its directory
+                let depth = match other.stem {
+                    ContentSlugStem::Index => num_dirs,
+                    ContentSlugStem::Other(_) => num_dirs + 1,
+                };
+                Some(Descendant { depth, page: md })
+            })
+            .collect()
+    }
+}
+
+/// Text files that crawlers and other tools read directly, so they are copied
+/// as is instead of being rendered as a page
+const VERBATIM_TEXT_FILES: &[&str] = &["robots.txt", "humans.txt", "ads.txt", "security.txt"];
+
+fn is_verbatim_text(path: &Path) -> bool {
+    path.file_name()
+        .and_then(OsStr::to_str)
+        .is_some_and(|name| VERBATIM_TEXT_FILES.contains(&name))
+        || path
+            .components()
+            .any(|component| component.as_os_str() == ".well-known")
 }
 
 #[derive(Debug)]
@@ -325,22 +792,52 @@ struct ContentFile {
     original_media_type: MediaType,
     current_media_type: MediaType,
     plan: Vec<Transform>,
+    /// Where to look up templates, if not in the template directory itself
+    template_root: Option<rules::TemplateRoot>,
 }
 
 impl ContentFile {
-    fn from_input(input: BuildFile) -> Self {
-        let current_media_type = match input.full_path.extension().and_then(OsStr::to_str) {
-            Some("dj") => MediaType::Djot,
-            Some("html") => MediaType::Html,
-            Some(other) => MediaType::Other(Some(other.into())),
-            None => MediaType::Other(None),
+    fn from_input(input: BuildFile, pipelines: &BTreeMap<String, config::PipelineConfig>) -> Self {
+        let extension = input.full_path.extension().and_then(OsStr::to_str);
+        let current_media_type = match extension {
+            Some("txt") if is_verbatim_text(&input.full_path) => {
+                MediaType::Other(Some("txt".into()))
+            },
+            extension => MediaType::from_extension(extension),
         };
 
+        if let Some(pipeline) = extension.and_then(|extension| pipelines.get(extension)) {
+            let output_media_type = match &pipeline.output_extension {
+                Some(output_extension) => MediaType::from_extension(Some(output_extension)),
+                None => current_media_type.clone(),
+            };
+            let plan = pipeline
+                .steps
+                .iter()
+                .map(|step| match step {
+                    config::PipelineStep::Djot => Transform::RenderDjot,
+                    config::PipelineStep::Notebook => Transform::RenderNotebook,
+                    config::PipelineStep::Text => Transform::WrapText,
+                    config::PipelineStep::Command(args) => Transform::RunCommand(args.clone()),
+                    config::PipelineStep::Template => Transform::ApplyTemplate,
+                })
+                .collect();
+
+            return Self {
+                input,
+                original_media_type: current_media_type,
+                current_media_type: output_media_type,
+                plan,
+                template_root: None,
+            };
+        }
+
         let mut file = Self {
             input,
             original_media_type: current_media_type.clone(),
             current_media_type,
             plan: vec![],
+            template_root: None,
         };
 
         // Add steps to the plan based on various characteristics
@@ -351,6 +848,16 @@ impl ContentFile {
             file.current_media_type = MediaType::Html;
         }
 
+        if matches!(file.current_media_type, MediaType::Notebook) {
+            file.plan.push(Transform::RenderNotebook);
+            file.current_media_type = MediaType::Html;
+        }
+
+        if matches!(file.current_media_type, MediaType::Text) {
+            file.plan.push(Transform::WrapText);
+            file.current_media_type = MediaType::Html;
+        }
+
         if matches!(file.current_media_type, MediaType::Html) {
             file.plan.push(Transform::ApplyTemplate);
         }
@@ -358,6 +865,23 @@ impl ContentFile {
         file
     }
 
+    /// A file that is copied to the output as is, whatever its type.
+    fn copy_only(input: BuildFile) -> Self {
+        let media_type = MediaType::Other(
+            input
+                .full_path
+                .extension()
+                .map(|extension| extension.to_string_lossy().into_owned()),
+        );
+        Self {
+            input,
+            original_media_type: media_type.clone(),
+            current_media_type: media_type,
+            plan: vec![],
+            template_root: None,
+        }
+    }
+
     fn output_filename(&self) -> OsString {
         let mut full_path = self.input.full_path.clone();
         full_path.set_extension(self.current_media_type.extension());
@@ -366,73 +890,186 @@ impl ContentFile {
     }
 
     fn is_article(&self) -> bool {
-        matches!(self.original_media_type, MediaType::Djot)
+        matches!(
+            self.original_media_type,
+            MediaType::Djot | MediaType::Notebook
+        )
     }
 
+    /// Run every transform that happens before templating, which is where the
+    /// page metadata is extracted.
+    ///
+    /// Returns `None` if the file has no transforms and should be copied as is.
     #[instrument(skip_all, fields(%slug))]
-    fn process(
+    fn render(
         &self,
         args: &BuildCmd,
-        tera: &Tera,
-        templates: &Templates,
+        config: &config::SiteConfig,
+        remote: &remote::Remote,
         metadata: &mut MetadataContainer,
         slug: &ContentSlug,
-    ) -> anyhow::Result<()> {
-        let output_folder = self.create_output_parent(args, slug)?;
+    ) -> anyhow::Result<Option<String>> {
         if self.plan.is_empty() {
-            debug!("Plan is empty, copying file directly to output location");
-            let output_path = output_folder.join(self.output_filename());
-
-            fs::copy(&self.input.full_path, output_path)
-                .context("failed to copy file to output")?;
-            return Ok(());
+            return Ok(None);
         }
 
         let mut content =
             fs::read_to_string(&self.input.full_path).context("failed to read content file")?;
 
-        for step in self.plan.iter().copied() {
+        for step in &self.plan {
             debug!(?step, "Applying step");
             match step {
                 Transform::RenderDjot => {
-                    content = djot::render(&self.input, metadata, slug, &content)
-                        .context("parsing djot content to HTML")?;
+                    content = djot::render(
+                        &self.input,
+                        config,
+                        metadata,
+                        slug,
+                        &content,
+                        remote,
+                        args.release,
+                    )
+                    .context("parsing djot content to HTML")?;
+                    metadata[slug].rendered_content = Some(content.clone());
                 },
-                Transform::ApplyTemplate => {
-                    let Some(template) = templates.find_template(slug, &self.current_media_type)
-                    else {
-                        debug!(%slug, "Did not find template for content");
-                        continue;
-                    };
+                Transform::RenderNotebook => {
+                    content =
+                        notebook::render(&self.input, config, metadata, slug, &content, remote)
+                            .context("rendering notebook to HTML")?;
+                    metadata[slug].rendered_content = Some(content.clone());
+                },
+                Transform::WrapText => {
+                    content = format!("<pre>{}</pre>", feed::escape_xml(&content));
+                    metadata[slug].rendered_content = Some(content.clone());
+                },
+                Transform::RunCommand(args) => {
+                    content = pipeline::run_command(args, &self.input.full_path, content)
+                        .context("running pipeline command")?;
+                    metadata[slug].rendered_content = Some(content.clone());
+                },
+                // Templates are applied in `write`, once the metadata for every page is
+                // available
+                Transform::ApplyTemplate => {},
+            }
+        }
+
+        if let (MediaType::Html, Some(typography)) = (&self.current_media_type, &config.typography)
+        {
+            content = typography::apply(typography, &content);
+            if metadata[slug].rendered_content.is_some() {
+                metadata[slug].rendered_content = Some(content.clone());
+            }
+        }
+        meta::describe(config, &mut metadata[slug]);
+
+        Ok(Some(content))
+    }
+
+    #[instrument(skip_all, fields(%slug))]
+    fn write(
+        &self,
+        renderer: &TemplateRenderer,
+        metadata: &MetadataContainer,
+        slug: &ContentSlug,
+        content: Option<String>,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        let args = renderer.args;
+        let output_folder = self.create_output_parent(args, slug)?;
+        let Some(mut content) = content else {
+            debug!("Plan is empty, copying file directly to output location");
+            let output_path = output_folder.join(self.output_filename());
 
-                    let template_path = &template
-                        .full_path
-                        .strip_prefix(args.template_dir())
-                        .unwrap();
-                    debug!(template = %template_path.display(), "Rendering with template");
-                    let subpages = metadata.subpages(slug);
-                    let context = TemplateContext {
-                        content,
-                        metadata: &metadata[slug],
-                        subpages,
-                        release: args.release,
+            fs::copy(&self.input.full_path, output_path)
+                .context("failed to copy file to output")?;
+            return Ok(None);
+        };
+
+        let mut used_template = None;
+        if self.plan.contains(&Transform::ApplyTemplate) {
+            let template_path = match &metadata[slug].template {
+                Some(name) => {
+                    let Some(template) = renderer.templates.find_named_template(&[Path::new(name)])
+                    else {
+                        bail!("template [{name}] set in the frontmatter does not exist");
                     };
-                    let tera_context = tera::Context::from_serialize(&context)
-                        .context("failed to create tera context")?;
-                    content = tera
-                        .render(template_path.to_str().unwrap(), &tera_context)
-                        .context("failed to render template")?;
+                    Some(template.to_path_buf())
                 },
+                None => match &self.template_root {
+                    Some(root) => renderer.templates.find_template(
+                        &root.dir,
+                        &root.page_path,
+                        &self.current_media_type,
+                    ),
+                    None => renderer.templates.find_template(
+                        Path::new(""),
+                        &slug.as_path(),
+                        &self.current_media_type,
+                    ),
+                }
+                .map(Path::to_path_buf),
+            };
+
+            if let Some(template_path) = template_path {
+                debug!(template = %template_path.display(), "Rendering with template");
+                let subpages = metadata.subpages(slug);
+                let ancestors = metadata.ancestors(slug);
+                let context = TemplateContext {
+                    content,
+                    metadata: &metadata[slug],
+                    subpages,
+                    descendants: metadata.descendants(slug),
+                    parent: ancestors.last().copied(),
+                    ancestors,
+                    siblings: metadata.siblings(slug),
+                    feeds: feed::page_feeds(&renderer.site.feed_links, &metadata[slug]),
+                    translations: translation::page_translations(
+                        &renderer.site.translations,
+                        &metadata[slug],
+                    ),
+                    meta_tags: meta::meta_tags(&metadata[slug]),
+                    release: args.release,
+                    site: renderer.site,
+                };
+                content = renderer.render(&template_path, &self.input.full_path, &context)?;
+                used_template = Some(template_path);
+            } else if self.current_media_type == MediaType::Html
+                && renderer
+                    .config
+                    .requires_template(args.release, &slug.as_path())
+            {
+                return Err(errors::FailureClass::Template.error(format!(
+                    "no template found for [{slug}], add one or list the page in \
+                     `untemplated_pages`"
+                )));
+            } else if self.current_media_type == MediaType::Html
+                && !renderer.config.is_untemplated(&slug.as_path())
+            {
+                warn!(%slug, "No template found for page, writing it without one");
+            } else {
+                debug!(%slug, "Did not find template for content");
             }
         }
 
+        if self.current_media_type == MediaType::Html {
+            content = renderer.plugins.page(&metadata[slug], content)?;
+        }
+        if self.current_media_type == MediaType::Html && renderer.config.inject_meta_tags {
+            content = meta::inject_meta_tags(&metadata[slug], &content);
+        }
+        if self.current_media_type == MediaType::Html
+            && protect::is_protected(renderer.config, &metadata[slug])
+        {
+            debug!(%slug, "Encrypting protected page");
+            content = protect::protect_page(renderer.config, &metadata[slug], &content)?;
+        }
+
         let output_path = output_folder.join(self.output_filename());
         debug!(input = %self.input.full_path.display(), output = %output_path.display(), "Ensured output folder for content exists");
 
         fs::write(&output_path, content).context("failed to write content file")?;
         debug!(output_path = %output_path.display(), "Written content file");
 
-        Ok(())
+        Ok(used_template)
     }
 
     fn create_output_parent(
@@ -455,7 +1092,180 @@ struct TemplateContext<'a> {
     #[serde(flatten)]
     metadata: &'a Metadata,
     subpages: Vec<&'a Metadata>,
+    descendants: Vec<Descendant<'a>>,
+    /// The index page of the closest section that contains this page
+    parent: Option<&'a Metadata>,
+    /// The index pages of every section that contains this page, from the root
+    /// of the site to the parent
+    ancestors: Vec<&'a Metadata>,
+    /// The other pages in the same section
+    siblings: Vec<&'a Metadata>,
+    /// The feeds relevant to this page, for `<link rel="alternate">` tags
+    feeds: Vec<&'a feed::FeedLink>,
+    /// The translations of this page including itself, for `<link
+    /// rel="alternate" hreflang="...">` tags
+    translations: &'a [translation::Translation],
+    /// The description and keywords `<meta>` tags of this page
+    meta_tags: String,
     release: bool,
+    #[serde(flatten)]
+    site: &'a SiteTemplateContext<'a>,
+}
+
+/// A page beneath another page, along with how deep it is.
+#[derive(Debug, Serialize)]
+struct Descendant<'a> {
+    /// 1 for the pages directly beneath, including the index pages of
+    /// subsections, and one more for each directory below that
+    depth: usize,
+    #[serde(flatten)]
+    page: &'a Metadata,
+}
+
+/// Everything needed to apply templates, shared by content pages and generated
+/// pages.
+struct TemplateRenderer<'a> {
+    args: &'a BuildCmd,
+    config: &'a config::SiteConfig,
+    tera: &'a Tera,
+    templates: &'a Templates,
+    plugins: &'a plugin::Plugins,
+    site: &'a SiteTemplateContext<'a>,
+    /// Every template that has been rendered directly
+    used_templates: RefCell<BTreeSet<PathBuf>>,
+}
+
+impl TemplateRenderer<'_> {
+    /// Render the template at the given path, relative to the template
+    /// directory, for a page given by its content file or, for generated pages,
+    /// its output path.
+    fn render(
+        &self,
+        template: &Path,
+        page: &Path,
+        context: &impl Serialize,
+    ) -> anyhow::Result<String> {
+        self.used_templates
+            .borrow_mut()
+            .insert(template.to_path_buf());
+        let template_name = template.to_str().unwrap();
+        let mut context_value =
+            serde_json::to_value(context).context("failed to create tera context")?;
+        trace!(template = template_name, context = %context_value, "Rendering template");
+
+        // Outside of strict mode, each missing variable is filled in with a placeholder
+        // and the template is rendered again, until it renders or fails for another
+        // reason
+        let mut num_filled = 0;
+        loop {
+            let tera_context = tera::Context::from_value(context_value.clone())
+                .context("failed to create tera context")?;
+            let error = match self.tera.render(template_name, &tera_context) {
+                Ok(rendered) => return Ok(rendered),
+                Err(error) => error,
+            };
+
+            if self.config.strict_variables || num_filled >= undefined::MAX_FILLED_VARIABLES {
+                return Err(self.locate_error(template, page, error));
+            }
+            let Some(variable) = undefined::undefined_variable(&error) else {
+                return Err(self.locate_error(template, page, error));
+            };
+
+            let placeholder = if self.args.release {
+                String::new()
+            } else {
+                format!("[undefined: {variable}]")
+            };
+            if !undefined::fill_variable(&mut context_value, &variable, &placeholder) {
+                return Err(self.locate_error(template, page, error));
+            }
+            warn!(
+                template = template_name,
+                variable, "Template references an undefined variable"
+            );
+            num_filled += 1;
+        }
+    }
+
+    /// Point a render error at the first place in the templates that mentions
+    /// the name it is about, like a missing variable or an unknown filter.
+    ///
+    /// The templates that Tera says the error happened in are searched first,
+    /// then the template being rendered and the templates it extends, then
+    /// every other template. Without a name to look for, the error points at
+    /// the first of those templates.
+    fn locate_error(&self, template: &Path, page: &Path, error: tera::Error) -> anyhow::Error {
+        let name = undefined::error_subject(&error);
+        let template_name = template.to_str().unwrap_or_default();
+        let parents = self
+            .tera
+            .get_template(template_name)
+            .map(|template| template.parents.clone())
+            .unwrap_or_default();
+        let likely = undefined::failing_templates(&error)
+            .into_iter()
+            .chain([template_name.to_owned()])
+            .chain(parents)
+            .map(|name| TemplateSlug(PathBuf::from(name)))
+            .collect::<Vec<_>>();
+        let candidates = likely
+            .iter()
+            .filter_map(|slug| self.templates.files.get_key_value(slug))
+            .chain(&self.templates.files);
+
+        let message = |slug: &TemplateSlug| {
+            format!(
+                "failed to render template [{}] for [{}]",
+                slug.0.display(),
+                page.display()
+            )
+        };
+        let mut diagnostic = None;
+        for (slug, file) in candidates {
+            let Ok(source) = fs::read_to_string(&file.full_path) else {
+                continue;
+            };
+            let found = diagnostic::Diagnostic::new(&file.full_path, message(slug));
+            match &name {
+                Some(name) if source.contains(name.as_str()) => {
+                    diagnostic = Some(found.at_first(&source, name));
+                    break;
+                },
+                _ => {
+                    diagnostic.get_or_insert(found);
+                },
+            }
+        }
+
+        match diagnostic {
+            Some(diagnostic) => anyhow::Error::new(error).context(diagnostic),
+            None => {
+                anyhow::Error::new(error).context(message(&TemplateSlug(template.to_path_buf())))
+            },
+        }
+    }
+}
+
+/// Template values that are the same for every page rendered in a build.
+#[derive(Debug, Serialize)]
+struct SiteTemplateContext<'a> {
+    /// Every page on the site in slug order, except the 404 page, which
+    /// templates can narrow down using the `filter`, `sort`, and `slice`
+    /// filters
+    all_pages: Vec<&'a Metadata>,
+    taxonomies: BTreeMap<String, taxonomy::Taxonomy<'a>>,
+    all_authors: BTreeMap<String, author::AuthorProfile<'a>>,
+    /// The neighbors of the site in each webring it is a member of
+    webrings: BTreeMap<String, webring::Webring>,
+    extra: &'a serde_json::Map<String, serde_json::Value>,
+    build: &'a info::BuildInfo,
+    /// Every feed of the site, which pages pick their own feeds from
+    #[serde(skip)]
+    feed_links: Vec<feed::FeedLink>,
+    /// Every set of translations, which pages pick their own set from
+    #[serde(skip)]
+    translations: BTreeMap<String, Vec<translation::Translation>>,
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -465,36 +1275,64 @@ struct TemplateSlug(PathBuf);
 #[derive(Debug)]
 struct Templates {
     files: BTreeMap<TemplateSlug, BuildFile>,
+    /// The templates that come from the theme, rather than the site itself
+    theme_templates: BTreeSet<PathBuf>,
 }
 
 impl Templates {
-    fn initialize_template_engine(args: &BuildCmd) -> anyhow::Result<Tera> {
-        let template_dir = args.template_dir();
-        let template_glob = format!("{}/**/*.html", template_dir.display());
-        let tera = Tera::new(&template_glob).context("failed to initialize template engine")?;
+    fn initialize_template_engine(
+        &self,
+        config: &config::SiteConfig,
+        remote: &remote::Remote,
+    ) -> anyhow::Result<Tera> {
+        let mut tera = Tera::default();
+        tera.add_template_files(self.files.iter().map(|(TemplateSlug(name), file)| {
+            (&file.full_path, Some(name.to_string_lossy().into_owned()))
+        }))
+        .context("failed to initialize template engine")?;
+        functions::register_functions(&mut tera, config, remote);
 
         debug!(engine = ?tera, "Created templating engine");
 
         Ok(tera)
     }
 
-    fn find_template(&self, slug: &ContentSlug, media_type: &MediaType) -> Option<&BuildFile> {
-        let mut slug_path = slug.as_path();
+    /// Return the first template that exists out of the candidates, given as
+    /// paths relative to the template directory
+    fn find_named_template<'a>(&self, candidates: &[&'a Path]) -> Option<&'a Path> {
+        candidates.iter().copied().find(|candidate| {
+            self.files
+                .contains_key(&TemplateSlug(candidate.to_path_buf()))
+        })
+    }
+
+    /// Find the template for the page at the given path, relative to the `root`
+    /// directory of templates, and return its path relative to the template
+    /// directory.
+    fn find_template(
+        &self,
+        root: &Path,
+        page_path: &Path,
+        media_type: &MediaType,
+    ) -> Option<&Path> {
+        let mut slug_path = root.join(page_path);
         slug_path.set_extension(media_type.extension());
-        if let Some(file) = self.files.get(&TemplateSlug(slug_path)) {
-            return Some(file);
+        if let Some((TemplateSlug(name), _)) = self.files.get_key_value(&TemplateSlug(slug_path)) {
+            return Some(name);
         }
 
         let extension = media_type.extension();
-        let mut current_dir = Some(slug.parent.as_path());
+        let mut current_dir = page_path.parent();
         loop {
             let dir = current_dir.unwrap_or_else(|| Path::new(""));
 
             // Look for the `page.<ext>` in the current directory
-            let mut page_path = dir.join("page");
+            let mut page_path = root.join(dir).join("page");
             page_path.set_extension(extension.clone());
-            if let Some(file) = self.files.get(&TemplateSlug(page_path)) {
-                return Some(file);
+            if let Some((TemplateSlug(name), _)) =
+                self.files.get_key_value(&TemplateSlug(page_path))
+            {
+                return Some(name);
             }
 
             // If `dir` is empty, then we're in the `or_else` case from the top of the loop
@@ -509,31 +1347,107 @@ impl Templates {
 
 #[derive(Debug)]
 struct Site {
+    config: config::SiteConfig,
+    /// The directory of the theme, if the site has one
+    theme_dir: Option<PathBuf>,
+    authors: BTreeMap<String, author::Author>,
     content: Content,
     templates: Templates,
 }
 
 impl Site {
-    fn parse(args: &BuildCmd, build_files: BuildDirFiles) -> anyhow::Result<Self> {
+    /// Gather the input files and config, and separate them into a site.
+    fn load(args: &BuildCmd) -> anyhow::Result<Self> {
+        Self::load_with_config(args, Self::load_config(args)?)
+    }
+
+    fn load_config(args: &BuildCmd) -> anyhow::Result<config::SiteConfig> {
+        config::SiteConfig::load(&args.input_path)
+            .map_err(|err| errors::FailureClass::Config.wrap("failed to load site config", err))
+    }
+
+    /// Gather the input files, and separate them into a site with the given
+    /// config.
+    fn load_with_config(args: &BuildCmd, config: config::SiteConfig) -> anyhow::Result<Self> {
+        let mut build_files = BuildDirFiles::gather(&args.input_path)
+            .context("failed to collect input files from directory")?;
+
+        debug!(?build_files, "Collect input build files!");
+
+        source::overlay_sources(
+            &site_cache::site_cache_dir(&args.input_path),
+            &config.content_sources,
+            &mut build_files,
+        )
+        .context("failed to overlay content sources")?;
+
+        let theme_dir = theme::theme_dir(config.theme.as_ref(), &args.input_path)
+            .context("failed to find site theme")?;
+        let theme_files = theme_dir
+            .as_deref()
+            .map(BuildDirFiles::gather)
+            .transpose()
+            .context("failed to collect theme files")?;
+
+        let mut site = Site::parse(args, config, build_files, theme_files)
+            .context("failed to parse site structure from input files")?;
+        site.theme_dir = theme_dir;
+        Ok(site)
+    }
+
+    fn parse(
+        args: &BuildCmd,
+        config: config::SiteConfig,
+        build_files: BuildDirFiles,
+        theme_files: Option<BuildDirFiles>,
+    ) -> anyhow::Result<Self> {
         let mut metadata_container = MetadataContainer::default();
         let mut content_files = BTreeMap::new();
         let mut templates_files = BTreeMap::new();
+        let mut authors = BTreeMap::new();
+        let directory_rules = rules::DirectoryRulesSet::load(&config.directories, &build_files)
+            .context("failed to load directory rules")?;
 
         for (path, file) in build_files.files {
             if let Some(first_component) = path.components().next() {
                 if first_component.as_os_str() == "content" {
+                    let sub_path = path.strip_prefix("content")?;
+                    if sub_path
+                        .file_name()
+                        .is_some_and(|name| name == rules::RULES_FILENAME)
+                    {
+                        continue;
+                    }
+                    let rules = directory_rules.resolve(sub_path);
+                    if rules.exclude {
+                        debug!(path = %sub_path.display(), "Excluding content file by directory rules");
+                        continue;
+                    }
+
                     // Make sure that there are no content pages named `page.<ext>`, otherwise there
-                    // would be some confusion around what the related template is.
-                    if path.file_stem().map(|s| s == "page").unwrap_or(false) {
+                    // would be some confusion around what the related template is. Copied files
+                    // are never templated, so they can use any name.
+                    if !rules.copy_only && path.file_stem().map(|s| s == "page").unwrap_or(false) {
                         bail!(
                             "Cannot have a content page named 'page', found at {}",
                             path.display()
                         )
                     }
 
-                    let sub_path = path.strip_prefix("content")?;
-                    let slug = ContentSlug::from_path(sub_path)?;
-                    let content_file = ContentFile::from_input(file);
+                    let slug = ContentSlug::from_path(&rules.output_path)?;
+                    if content_files.contains_key(&slug) {
+                        bail!(
+                            "content file [{}] is written to [{slug}], which is already used by \
+                             another content file",
+                            sub_path.display()
+                        );
+                    }
+                    let mut content_file = if rules.copy_only {
+                        ContentFile::copy_only(file)
+                    } else {
+                        ContentFile::from_input(file, &config.pipelines)
+                    };
+                    content_file.template_root = rules.template_root;
                     let metadata = Metadata::new(args, &slug, &content_file);
                     metadata_container.insert(slug.clone(), metadata);
                     content_files.insert(slug, content_file);
@@ -548,50 +1462,244 @@ impl Site {
 
                     let sub_path = path.strip_prefix("templates")?.to_path_buf();
                     templates_files.insert(TemplateSlug(sub_path), file);
+                } else if first_component.as_os_str() == "authors" {
+                    let sub_path = path.strip_prefix("authors")?;
+                    let author = author::Author::load(sub_path, &file)?;
+                    authors.insert(author.id.clone(), author);
                 } else {
                     debug!(path = %path.display(), "Ignoring file not in a known directory");
                 }
             }
         }
 
+        // Theme files fill in whatever the site doesn't provide itself
+        let mut theme_templates = BTreeSet::new();
+        for (path, file) in theme_files.map(|files| files.files).unwrap_or_default() {
+            if let Ok(sub_path) = path.strip_prefix("templates") {
+                if path.extension().is_none_or(|ext| ext != "html") {
+                    debug!(path = %path.display(), "Ignoring non-HTML theme template");
+                    continue;
+                }
+                let slug = TemplateSlug(sub_path.to_path_buf());
+                if templates_files.contains_key(&slug) {
+                    debug!(template = %sub_path.display(), "Theme template is overridden by the site");
+                    continue;
+                }
+                theme_templates.insert(sub_path.to_path_buf());
+                templates_files.insert(slug, file);
+            } else if let Ok(sub_path) = path.strip_prefix("static") {
+                let slug = ContentSlug::from_path(sub_path)?;
+                if content_files.contains_key(&slug) {
+                    debug!(path = %sub_path.display(), "Theme static file is overridden by the site");
+                    continue;
+                }
+                let content_file = ContentFile::copy_only(file);
+                let metadata = Metadata::new(args, &slug, &content_file);
+                metadata_container.insert(slug.clone(), metadata);
+                content_files.insert(slug, content_file);
+            } else {
+                debug!(path = %path.display(), "Ignoring theme file not in a known directory");
+            }
+        }
+
         Ok(Site {
+            config,
+            theme_dir: None,
+            authors,
             content: Content {
                 metadata: metadata_container,
                 files: content_files,
             },
             templates: Templates {
                 files: templates_files,
+                theme_templates,
             },
         })
     }
 
-    fn format_output(args: &BuildCmd) -> anyhow::Result<()> {
-        // Format all code in output using prettier
-        // prettier --write --no-config --ignore-path '' site.out/
-        let prettier_output = Command::new("prettier")
-            .arg("--write")
-            .arg("--no-config")
-            .arg("--ignore-path")
-            .arg("''")
-            .arg(args.output_path.display().to_string())
-            .output()
-            .context("failed to execute  output code using prettier")?;
+    /// Find the files that each written page was built from: its content file,
+    /// the data files it read, and every template its own template reads. A
+    /// section index also depends on every content file below it, since it
+    /// lists them, and a page whose templates read the metadata of other
+    /// pages, like `siblings` or `all_pages`, depends on every content file.
+    fn collect_dependencies(
+        args: &BuildCmd,
+        site: &Site,
+        page_templates: &BTreeMap<&ContentSlug, Option<PathBuf>>,
+        used_templates: &BTreeSet<PathBuf>,
+    ) -> anyhow::Result<BuildReport> {
+        let references = check::template_references(&site.templates)?;
+        let cross_page = check::cross_page_templates(&site.templates)?;
+        let template_path = |name: PathBuf| {
+            let file = site.templates.files.get(&TemplateSlug(name.clone()));
+            file.map_or_else(
+                || args.template_dir().join(name),
+                |file| file.full_path.clone(),
+            )
+        };
+        let mut report = BuildReport {
+            external_dirs: site.theme_dir.iter().cloned().collect(),
+            ..BuildReport::default()
+        };
 
-        if !prettier_output.status.success() {
-            let stdout = String::from_utf8_lossy(&prettier_output.stdout);
-            let stderr = String::from_utf8_lossy(&prettier_output.stderr);
-            debug!(%stdout, %stderr, "Failed 'prettier' output");
-            bail!("Execution of 'prettier' returned an unsuccessful status code")
-        } else {
-            debug!("Successfully executed 'prettier' to format site output")
+        for (slug, template) in page_templates {
+            let page = &site.content.files[*slug].input.full_path;
+            let mut dependencies = BTreeSet::from([page.clone()]);
+            if let Some(metadata) = site.content.metadata.0.get(*slug) {
+                dependencies.extend(metadata.dependencies.iter().cloned());
+            }
+            let templates = check::reachable_templates(&references, template);
+            if templates.iter().any(|name| cross_page.contains(name)) {
+                dependencies.extend(
+                    site.content
+                        .files
+                        .values()
+                        .map(|file| file.input.full_path.clone()),
+                );
+            }
+            dependencies.extend(templates.into_iter().map(template_path));
+            for dependency in dependencies {
+                report
+                    .dependents
+                    .entry(dependency)
+                    .or_default()
+                    .insert(page.clone());
+            }
         }
 
-        Ok(())
+        // Only for the index pages that were written, so that a partial build
+        // reports every dependency of the pages it wrote and nothing else
+        for (index_slug, index) in &site.content.files {
+            if !matches!(index_slug.stem, ContentSlugStem::Index)
+                || !page_templates.contains_key(index_slug)
+            {
+                continue;
+            }
+            for (slug, file) in &site.content.files {
+                if slug != index_slug && slug.parent.starts_with(&index_slug.parent) {
+                    report
+                        .dependents
+                        .entry(file.input.full_path.clone())
+                        .or_default()
+                        .insert(index.input.full_path.clone());
+                }
+            }
+        }
+
+        // Templates of generated pages, which every build writes anyway
+        for name in check::reachable_templates(&references, used_templates) {
+            report.dependents.entry(template_path(name)).or_default();
+        }
+
+        Ok(report)
+    }
+}
+
+/// What a build found out about the inputs of each page, so that later
+/// rebuilds can be limited to the pages an input change affects.
+#[derive(Debug, Default)]
+pub struct BuildReport {
+    /// The content files whose pages must be written again when a file
+    /// changes, keyed by the full path of the changed file.
+    ///
+    /// Templates that are only used by generated pages map to no content
+    /// files, since generated pages are written by every build.
+    pub dependents: BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+    /// Other directories than the input directory that files were read from,
+    /// like a theme
+    pub external_dirs: Vec<PathBuf>,
+    /// What the build wrote to the output
+    pub stats: summary::BuildStats,
+    /// The URL path of the page rendered from each content file, keyed by the
+    /// full path of the content file
+    pub page_urls: BTreeMap<PathBuf, String>,
+}
+
+impl BuildReport {
+    /// Add the dependencies found by a later, possibly partial, build.
+    ///
+    /// The dependencies of the pages the later build wrote replace the ones
+    /// recorded before, so that a page stops depending on a template it no
+    /// longer extends or includes.
+    pub fn merge(&mut self, other: BuildReport) {
+        let rewritten = other
+            .dependents
+            .values()
+            .flatten()
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        for dependents in self.dependents.values_mut() {
+            dependents.retain(|page| !rewritten.contains(page));
+        }
+        for (path, dependents) in other.dependents {
+            self.dependents.entry(path).or_default().extend(dependents);
+        }
+        self.external_dirs = other.external_dirs;
+        // Every page is rendered by a partial build too, so its URLs are complete
+        self.page_urls = other.page_urls;
+    }
+
+    /// The content files to write again after the given files changed, or
+    /// `None` if a changed file isn't known to any page and everything has to
+    /// be rebuilt.
+    pub fn affected_by<'a>(
+        &self,
+        changed: impl IntoIterator<Item = &'a PathBuf>,
+    ) -> Option<BTreeSet<PathBuf>> {
+        let mut affected = BTreeSet::new();
+        for path in changed {
+            affected.extend(self.dependents.get(path)?.iter().cloned());
+        }
+        Some(affected)
     }
 }
 
-#[tracing::instrument(skip_all)]
 pub fn build(args: BuildCmd) -> anyhow::Result<()> {
+    build_with_summary(&args).map(|_| ())
+}
+
+/// Build the whole site and summarize the warnings found along the way,
+/// failing if there were any and they are denied.
+pub fn build_with_summary(args: &BuildCmd) -> anyhow::Result<BuildReport> {
+    let start = Instant::now();
+    let (result, warnings) = warnings::collect(|| build_pages(args, None));
+    let summary = warnings::summarize(&warnings, args.deny_warnings);
+    if let Some(format) = args.output_format {
+        annotation::print_annotations(format, &args.input_path, &warnings, &result);
+    }
+    let report = result?;
+    summary::print_summary(
+        &report.stats,
+        warnings.len(),
+        start.elapsed(),
+        &args.output_path,
+    );
+    summary?;
+    Ok(report)
+}
+
+/// Build the site, writing only the pages of the `selected` content files if
+/// given.
+///
+/// Every page is still rendered so that the metadata of the whole site is
+/// known, and generated pages are always written. A partial build skips the
+/// steps that look at the whole output, like the podcast feed, pruning CSS,
+/// inlining assets, and writing the manifests.
+pub fn build_pages(
+    args: &BuildCmd,
+    selected: Option<&BTreeSet<PathBuf>>,
+) -> anyhow::Result<BuildReport> {
+    build_pages_with(args, selected, &mut |_| {})
+}
+
+/// Build the site like [`build_pages`], calling `on_page` after each page is
+/// written.
+#[tracing::instrument(name = "build_pages", skip_all)]
+fn build_pages_with(
+    args: &BuildCmd,
+    selected: Option<&BTreeSet<PathBuf>>,
+    on_page: &mut dyn FnMut(&builder::PageWritten),
+) -> anyhow::Result<BuildReport> {
     // Clean site output
     if let Err(err) = fs::remove_dir_all(&args.output_path) {
         match err.kind() {
@@ -607,11 +1715,6 @@ pub fn build(args: BuildCmd) -> anyhow::Result<()> {
         }
     }
 
-    let build_files = BuildDirFiles::gather(&args.input_path)
-        .context("failed to collect input files from directory")?;
-
-    debug!(?build_files, "Collect input build files!");
-
     // Next steps:
     //  1. Parse the files into a new structure with specific sub-fields for
     //     `content/`, `templates/`, and `static/`
@@ -633,10 +1736,20 @@ pub fn build(args: BuildCmd) -> anyhow::Result<()> {
     //  5. Files all folder are copied (after processing) to the output directory
     //     while maintaining their relative directory structure
 
-    let mut site = Site::parse(&args, build_files)
-        .context("failed to parse site structure from input files")?;
+    // The hooks before the build may write input files, so they run before
+    // any are read
+    let config = Site::load_config(args)?;
+    let hook_env = hooks::HookEnv::new(args, selected);
+    hooks::run_hook("before_build", &config.hooks.before_build, &hook_env)?;
+
+    let mut site = Site::load_with_config(args, config)?;
 
     debug!(?site, "Separated input files into distinct categories");
+    info!(
+        target: PROGRESS_TARGET,
+        num_files = site.content.files.len(),
+        "Loaded site"
+    );
 
     // For each `content/` file, run the following process:
     //  1. Use the extension to apply a transformation:
@@ -651,7 +1764,11 @@ pub fn build(args: BuildCmd) -> anyhow::Result<()> {
     // For each `static/` file, copy it directly to the `output_path` directory,
     // also maintaining directory structure.
 
-    let tera = Templates::initialize_template_engine(&args)?;
+    let site_cache = site_cache::site_cache_dir(&args.input_path);
+    let remote = remote::Remote::new(&site_cache, args.offline);
+    let mut tera = site
+        .templates
+        .initialize_template_engine(&site.config, &remote)?;
 
     if !args.output_path.exists() {
         fs::create_dir_all(&args.output_path).context("failed to create output directory")?;
@@ -661,23 +1778,287 @@ pub fn build(args: BuildCmd) -> anyhow::Result<()> {
         )
     }
 
+    site.content
+        .apply_cascades(args.release)
+        .context("failed to apply cascaded frontmatter")?;
+
+    // Render content files first, so that the metadata for every page is known
+    // before any templates are applied
+    let mut errors = errors::BuildErrors::new(args.keep_going);
+    let mut rendered = BTreeMap::new();
+    let plugins = plugin::Plugins::start(&site.config.plugins, &args.input_path)
+        .context("failed to start plugins")?;
+    for (slug, file) in &site.content.files {
+        let ctx = format!("Failed to render file [{}]", file.input.full_path.display());
+        let content = file
+            .render(
+                args,
+                &site.config,
+                &remote,
+                &mut site.content.metadata,
+                slug,
+            )
+            .and_then(|content| match content {
+                Some(html) if file.current_media_type == MediaType::Html => plugins
+                    .render(&site.config, &mut site.content.metadata[slug], html)
+                    .map(Some),
+                content => Ok(content),
+            })
+            .map_err(|err| errors::FailureClass::Content.wrap(ctx, err));
+        if let Some(content) = errors.record(content)? {
+            info!(target: PROGRESS_TARGET, %slug, "Rendered content");
+            rendered.insert(slug.clone(), content);
+        }
+    }
+
+    if args.release {
+        site.content.remove_drafts();
+    }
+
+    if let Some(spellcheck) = &site.config.spellcheck {
+        spellcheck::check_spelling(
+            spellcheck,
+            &args.input_path,
+            &site.content.metadata,
+            selected,
+        )
+        .context("failed to check spelling")?;
+    }
+
+    author::resolve_bylines(&site.authors, &mut site.content.metadata)
+        .context("failed to resolve page authors")?;
+    links::resolve_backlinks(&mut site.content.metadata);
+    check::warn_duplicate_pages(&site.content.metadata);
+
+    if let Some(graph_path) = &args.link_graph {
+        links::write_link_graph(&site.content.metadata, graph_path)
+            .context("failed to write link graph")?;
+    }
+
+    functions::register_pages(&mut tera, &site.content.metadata)
+        .context("failed to register pages function")?;
+
+    let build_info = info::BuildInfo::collect(&args.input_path);
+    let site_context = SiteTemplateContext {
+        all_pages: site
+            .content
+            .metadata
+            .0
+            .values()
+            .filter(|md| !md.is_not_found())
+            .collect(),
+        taxonomies: taxonomy::collect_taxonomies(&site.config, &site.content.metadata),
+        all_authors: author::collect_profiles(&site.authors, &site.content.metadata),
+        webrings: webring::collect_webrings(&site.config, &remote),
+        extra: &site.config.extra,
+        build: &build_info,
+        feed_links: feed::feed_links(&site.config, &site.content.metadata),
+        translations: translation::collect_translations(&site.config, &site.content.metadata),
+    };
+
+    let renderer = TemplateRenderer {
+        args,
+        config: &site.config,
+        tera: &tera,
+        templates: &site.templates,
+        plugins: &plugins,
+        site: &site_context,
+        used_templates: RefCell::default(),
+    };
+
     // Process content files
-    for (slug, file) in &mut site.content.files {
+    let mut page_templates = BTreeMap::new();
+    let mut stats = summary::BuildStats::default();
+    // The `after_page` hook runs once the output is post-processed, so that it
+    // sees the final file of each page
+    let mut page_envs = vec![];
+    for (slug, file) in &site.content.files {
+        if selected.is_some_and(|selected| !selected.contains(&file.input.full_path)) {
+            continue;
+        }
+        // Pages that failed to render have nothing to write
+        let Some(content) = rendered.remove(slug) else {
+            continue;
+        };
         let ctx = format!(
             "Failed to process file [{}] into output",
             file.input.full_path.display()
         );
-        file.process(
-            &args,
-            &tera,
-            &site.templates,
-            &mut site.content.metadata,
-            slug,
-        )
-        .context(ctx)?;
+        let template = file
+            .write(&renderer, &site.content.metadata, slug, content)
+            .map_err(|err| errors::FailureClass::Content.wrap(ctx, err));
+        if let Some(template) = errors.record(template)? {
+            if file.plan.is_empty() {
+                stats.assets += 1;
+            } else {
+                stats.pages += 1;
+                let url_path = &site.content.metadata[slug].url_path;
+                let output_path = args
+                    .output_path
+                    .join(url_path.strip_prefix("/").unwrap_or(url_path));
+                on_page(&builder::PageWritten {
+                    input_path: &file.input.full_path,
+                    output_path: &output_path,
+                    url_path,
+                });
+                page_envs.push(hook_env.with_page(&file.input.full_path, &output_path, url_path));
+            }
+            page_templates.insert(slug, template);
+        }
+    }
+    info!(
+        target: PROGRESS_TARGET,
+        num_pages = stats.pages,
+        num_assets = stats.assets,
+        "Wrote pages"
+    );
+
+    djot::write_thumbnails(
+        &site.config.gallery,
+        &site.content.metadata,
+        &args.output_path,
+    )
+    .context("failed to write gallery thumbnails")?;
+
+    let mut virtual_pages = virtual_page::VirtualPages::default();
+    taxonomy::add_taxonomy_pages(&renderer, &mut virtual_pages);
+    author::add_author_pages(&renderer, &mut virtual_pages);
+    generated::add_generated_pages(&renderer, &mut virtual_pages)
+        .context("failed to add generated pages")?;
+    feed::add_feeds(
+        &site.config,
+        &site.content.metadata,
+        &args.output_path,
+        &mut virtual_pages,
+    )
+    .context("failed to add feeds")?;
+    host::add_host_files(&site.config, &args.output_path, &mut virtual_pages)
+        .context("failed to add host configuration files")?;
+    stats.generated_pages = virtual_pages.num_pages();
+    virtual_pages
+        .write_all(&args.output_path)
+        .context("failed to write generated pages")?;
+    info!(
+        target: PROGRESS_TARGET,
+        num_pages = stats.generated_pages,
+        "Wrote generated pages"
+    );
+    // The podcast feed reads the size of each episode from the output, which a
+    // partial build doesn't write
+    if selected.is_none() {
+        podcast::write_podcast_feed(&site.config, &site.content.metadata, &args.output_path)
+            .context("failed to write podcast feed")?;
+    }
+    sitemap::write_sitemap(
+        &site.config,
+        &site.content.metadata,
+        &site_context.translations,
+        &args.output_path,
+    )
+    .context("failed to write sitemap")?;
+    ical::write_calendars(&site.config, &site.content.metadata, &args.output_path)
+        .context("failed to write event calendars")?;
+
+    let mut report = Site::collect_dependencies(
+        args,
+        &site,
+        &page_templates,
+        &renderer.used_templates.borrow(),
+    )
+    .context("failed to collect page dependencies")?;
+    report.stats = stats;
+    report.page_urls = site
+        .content
+        .metadata
+        .0
+        .values()
+        .map(|md| {
+            (
+                md.source_path.clone(),
+                md.url_path.to_string_lossy().into_owned(),
+            )
+        })
+        .collect();
+
+    if selected.is_some() {
+        debug!(
+            num_pages = page_templates.len(),
+            "Partial build, skipping whole site steps"
+        );
+        format::format_output(&site.config, &site_cache, &args.output_path)
+            .context("failed to format output")?;
+        for page_env in &page_envs {
+            errors.record(hooks::run_hook(
+                "after_page",
+                &site.config.hooks.after_page,
+                page_env,
+            ))?;
+        }
+        errors.finish()?;
+        hooks::run_hook("after_build", &site.config.hooks.after_build, &hook_env)?;
+        return Ok(report);
+    }
+
+    check::warn_unused_templates(&site.templates, &renderer.used_templates.borrow())
+        .context("failed to check for unused templates")?;
+
+    if let Some(prune_css) = &site.config.prune_css {
+        prune::prune_stylesheets(prune_css, &args.output_path)
+            .context("failed to prune unused CSS")?;
+    }
+    if let Some(inline_assets) = &site.config.inline_assets {
+        inline::inline_small_assets(inline_assets, &args.output_path)
+            .context("failed to inline small assets")?;
+    }
+    if let Some(method) = site.config.obfuscate_emails {
+        email::obfuscate_emails(method, &args.output_path)
+            .context("failed to obfuscate email addresses")?;
+    }
+
+    if args.release && site.config.critical_css {
+        critical::inline_critical_css(&args.output_path)
+            .context("failed to inline critical CSS")?;
+    }
+
+    format::format_output(&site.config, &site_cache, &args.output_path)
+        .context("failed to format output")?;
+    info!(target: PROGRESS_TARGET, "Post-processed output");
+
+    for page_env in &page_envs {
+        errors.record(hooks::run_hook(
+            "after_page",
+            &site.config.hooks.after_page,
+            page_env,
+        ))?;
+    }
+
+    check::warn_orphan_pages(&site.config, &site.content.metadata, &args.output_path)
+        .context("failed to check for orphan pages")?;
+    check::warn_broken_fragments(&site.config, &site.content.metadata, &args.output_path)
+        .context("failed to check links for broken fragments")?;
+    check::warn_missing_alt_text(&site.content.metadata, &args.output_path)
+        .context("failed to check images for alt text")?;
+    if site.config.accessibility_checks {
+        a11y::check_accessibility(&args.output_path)
+            .context("failed to check pages for accessibility problems")?;
+    }
+
+    // The manifest is gathered after formatting so that the hashes match the
+    // final bytes that are served
+    let manifest = manifest::OutputManifest::gather(&args.output_path)
+        .context("failed to gather output manifest")?;
+    budget::check_size_budgets(&site.config.size_budgets, &manifest, args.release)?;
+    manifest
+        .write_precache(&args.output_path)
+        .context("failed to write precache manifest")?;
+    if let Some(cache_control) = &site.config.cache_control {
+        cache::write_cache_control(cache_control, &manifest, &args.output_path)
+            .context("failed to write cache control manifest")?;
     }
+    info!(target: PROGRESS_TARGET, "Checked output");
 
-    Site::format_output(&args)?;
+    errors.finish()?;
+    hooks::run_hook("after_build", &site.config.hooks.after_build, &hook_env)?;
 
-    Ok(())
+    Ok(report)
 }
@@ -0,0 +1,149 @@
+use std::{collections::BTreeMap, path::Path, sync::LazyLock};
+
+use regex::Regex;
+use tracing::{debug, warn};
+
+use crate::build::{
+    check::{IMG_TAG, line_number, read_output_documents},
+    inline::attribute,
+};
+
+/// Matches the opening tag of a heading, capturing its level
+static HEADING: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<h([1-6])\b").unwrap());
+
+/// Matches a link element, capturing its opening tag and its content
+static ANCHOR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)(<a\b[^>]*>)(.*?)</a\s*>").unwrap());
+
+/// Matches the value of an `id` attribute inside a tag
+static ID_ATTR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"<[^>]*?\sid\s*=\s*["']([^"']*)["']"#).unwrap());
+
+/// Matches the opening `<html>` tag
+static HTML_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<html\b[^>]*>").unwrap());
+
+/// Matches a table element
+static TABLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<table\b.*?</table\s*>").unwrap());
+
+/// Matches a table header cell
+static TABLE_HEADER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<th\b").unwrap());
+
+/// Matches any tag, to find the text of an element
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+/// A single accessibility problem on a page.
+struct Problem {
+    line: usize,
+    message: String,
+}
+
+fn check_headings(document: &str, problems: &mut Vec<Problem>) {
+    let mut previous = None;
+    for captures in HEADING.captures_iter(document) {
+        let level = captures[1].parse::<u32>().unwrap_or_default();
+        if let Some(previous) = previous
+            && level > previous + 1
+        {
+            problems.push(Problem {
+                line: line_number(document, captures.get(0).unwrap().start()),
+                message: format!("Heading level jumps from h{previous} to h{level}"),
+            });
+        }
+        previous = Some(level);
+    }
+}
+
+fn check_link_text(document: &str, problems: &mut Vec<Problem>) {
+    for captures in ANCHOR.captures_iter(document) {
+        let tag = &captures[1];
+        let content = &captures[2];
+        let has_label = ["aria-label", "aria-labelledby", "title"]
+            .iter()
+            .any(|name| attribute(tag, name).is_some_and(|label| !label.trim().is_empty()));
+        let has_text = !TAG
+            .replace_all(content, "")
+            .replace("&nbsp;", "")
+            .trim()
+            .is_empty();
+        let has_image_text = IMG_TAG
+            .find_iter(content)
+            .any(|img| attribute(img.as_str(), "alt").is_some_and(|alt| !alt.trim().is_empty()));
+        if !has_label && !has_text && !has_image_text {
+            problems.push(Problem {
+                line: line_number(document, captures.get(0).unwrap().start()),
+                message: "Link has no text".into(),
+            });
+        }
+    }
+}
+
+fn check_duplicate_ids(document: &str, problems: &mut Vec<Problem>) {
+    let mut seen = BTreeMap::new();
+    for captures in ID_ATTR.captures_iter(document) {
+        let id = captures.get(1).unwrap();
+        let line = line_number(document, id.start());
+        if let Some(first_line) = seen.insert(id.as_str(), line) {
+            problems.push(Problem {
+                line,
+                message: format!(
+                    "Id [{}] is used more than once, first on line {first_line}",
+                    id.as_str()
+                ),
+            });
+        }
+    }
+}
+
+fn check_lang(document: &str, problems: &mut Vec<Problem>) {
+    match HTML_TAG.find(document) {
+        Some(tag) if attribute(tag.as_str(), "lang").is_some_and(|lang| !lang.is_empty()) => {},
+        tag => problems.push(Problem {
+            line: tag.map_or(1, |tag| line_number(document, tag.start())),
+            message: "Page has no `lang` attribute on its `<html>` element".into(),
+        }),
+    }
+}
+
+fn check_table_headers(document: &str, problems: &mut Vec<Problem>) {
+    for table in TABLE.find_iter(document) {
+        if !TABLE_HEADER.is_match(table.as_str()) {
+            problems.push(Problem {
+                line: line_number(document, table.start()),
+                message: "Table has no header cells".into(),
+            });
+        }
+    }
+}
+
+/// Warn about common accessibility problems in every page of the output:
+/// heading levels that skip a level, links without text, duplicate ids, a
+/// missing `lang` attribute, and tables without header cells.
+///
+/// Each problem is reported with the page and the approximate line it is on.
+#[tracing::instrument(skip_all)]
+pub fn check_accessibility(output_root: &Path) -> anyhow::Result<()> {
+    let mut num_problems = 0;
+    for (url, document) in read_output_documents(output_root)? {
+        if !url.ends_with(".html") {
+            continue;
+        }
+
+        let mut problems = vec![];
+        check_headings(&document, &mut problems);
+        check_link_text(&document, &mut problems);
+        check_duplicate_ids(&document, &mut problems);
+        check_lang(&document, &mut problems);
+        check_table_headers(&document, &mut problems);
+
+        problems.sort_by_key(|problem| problem.line);
+        for problem in &problems {
+            warn!(page = %url, line = problem.line, "{}", problem.message);
+        }
+        num_problems += problems.len();
+    }
+
+    debug!(num_problems, "Checked pages for accessibility problems");
+
+    Ok(())
+}
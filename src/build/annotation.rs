@@ -0,0 +1,165 @@
+use std::{path::Path, str::FromStr};
+
+use crate::build::{diagnostic::Diagnostic, errors::BuildErrors, warnings::Warning};
+
+/// Formats for reporting the problems of a build to other tools, in addition
+/// to the logs.
+#[derive(Debug, Clone, Copy)]
+pub enum OutputFormat {
+    /// Workflow commands that GitHub Actions turns into annotations on the
+    /// lines of a pull request
+    Github,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "github" => Ok(OutputFormat::Github),
+            _ => Err(format!("unknown output format [{s}], expected `github`")),
+        }
+    }
+}
+
+/// Escape the message of a workflow command.
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a property value of a workflow command.
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// A single annotation, pointing at a file and line if known.
+struct Annotation {
+    level: &'static str,
+    file: Option<String>,
+    line: Option<String>,
+    column: Option<String>,
+    message: String,
+}
+
+impl Annotation {
+    fn print(&self) {
+        let properties = [
+            ("file", &self.file),
+            ("line", &self.line),
+            ("col", &self.column),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some(format!("{name}={}", escape_property(value.as_ref()?))))
+        .collect::<Vec<_>>();
+        let separator = if properties.is_empty() { "" } else { " " };
+        println!(
+            "::{}{separator}{}::{}",
+            self.level,
+            properties.join(","),
+            escape_data(&self.message)
+        );
+    }
+}
+
+/// The annotation for a warning, which points at the content file of the page
+/// it is about when the warning only names its slug.
+fn warning_annotation(input_path: &Path, warning: &Warning) -> Annotation {
+    let fields = &warning.fields;
+    let mut annotation = match fields.get("file") {
+        // Diagnostics already show their location, so only their first line is
+        // kept
+        Some(file) => Annotation {
+            level: "warning",
+            file: Some(file.clone()),
+            line: fields.get("line").cloned(),
+            column: fields.get("column").cloned(),
+            message: warning
+                .message
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_owned(),
+        },
+        // Any line of other warnings is a line of the output, so it is kept in
+        // the message
+        None => Annotation {
+            level: "warning",
+            file: fields
+                .get("slug")
+                .map(|slug| input_path.join("content").join(slug).display().to_string()),
+            line: None,
+            column: None,
+            message: warning.message.clone(),
+        },
+    };
+
+    // The other fields are kept in the message, like the page of the warning
+    for (name, value) in fields {
+        let is_location = matches!(name.as_str(), "file" | "slug")
+            || (annotation.line.is_some() && matches!(name.as_str(), "line" | "column"));
+        if !is_location {
+            annotation.message.push_str(&format!(" {name}={value}"));
+        }
+    }
+    annotation
+}
+
+/// The annotation for an error, which points at the file of the first
+/// diagnostic in its chain of causes.
+fn error_annotation(err: &anyhow::Error) -> Annotation {
+    let diagnostic = err.downcast_ref::<Diagnostic>();
+    // Diagnostics already show their location, so only their first line is kept
+    let message = err
+        .chain()
+        .map(|cause| {
+            cause
+                .to_string()
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_owned()
+        })
+        .collect::<Vec<_>>()
+        .join(": ");
+    Annotation {
+        level: "error",
+        file: diagnostic.map(|diagnostic| diagnostic.path.display().to_string()),
+        line: diagnostic
+            .and_then(|diagnostic| diagnostic.position.as_ref())
+            .map(|position| position.line.to_string()),
+        column: diagnostic
+            .and_then(|diagnostic| diagnostic.position.as_ref())
+            .map(|position| position.column.to_string()),
+        message,
+    }
+}
+
+/// Print the warnings and errors of a build to stdout in the given format.
+pub fn print_annotations<T>(
+    format: OutputFormat,
+    input_path: &Path,
+    warnings: &[Warning],
+    result: &anyhow::Result<T>,
+) {
+    match format {
+        OutputFormat::Github => {
+            for warning in warnings {
+                warning_annotation(input_path, warning).print();
+            }
+
+            let Err(err) = result else {
+                return;
+            };
+            match err.downcast_ref::<BuildErrors>() {
+                Some(build_errors) => {
+                    for err in build_errors.errors() {
+                        error_annotation(err).print();
+                    }
+                },
+                None => error_annotation(err).print(),
+            }
+        },
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::build::{
+    BuildFile, Metadata, MetadataContainer, SiteTemplateContext, TemplateRenderer,
+    virtual_page::VirtualPages,
+};
+
+/// The frontmatter field listing the IDs of a page's authors
+const AUTHORS_FIELD: &str = "authors";
+
+/// The output directory for author pages
+const AUTHORS_DIR: &str = "authors";
+
+/// An author profile, loaded from `authors/<id>.json`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Author {
+    /// The file stem of the author's data file
+    #[serde(skip_deserializing)]
+    pub id: String,
+    pub name: String,
+    pub bio: Option<String>,
+    /// URL path or absolute URL of the author's avatar image
+    pub avatar: Option<String>,
+    /// URL path of the generated page for this author
+    #[serde(skip_deserializing)]
+    pub url_path: String,
+    /// Any other fields, like social links, that are passed through to
+    /// templates unchanged
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, tera::Value>,
+}
+
+impl Author {
+    pub fn load(path: &Path, file: &BuildFile) -> anyhow::Result<Self> {
+        if path.extension().map(|ext| ext != "json").unwrap_or(true) {
+            bail!(
+                "Author files must be JSON, found [{}] with missing or non-JSON extension",
+                path.display()
+            );
+        }
+        let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            bail!("Author file [{}] has no valid file name", path.display());
+        };
+
+        let content = fs::read_to_string(&file.full_path).context("failed to read author file")?;
+        let mut author: Self = serde_json::from_str(&content)
+            .context(format!("failed to parse author file [{}]", path.display()))?;
+        author.id = id.to_owned();
+        author.url_path = format!("/{}/", author_dir(id).display());
+
+        Ok(author)
+    }
+}
+
+/// An author along with every page they wrote.
+#[derive(Debug, Serialize)]
+pub struct AuthorProfile<'a> {
+    #[serde(flatten)]
+    pub author: &'a Author,
+    pub pages: Vec<&'a Metadata>,
+}
+
+#[derive(Debug, Serialize)]
+struct AuthorContext<'a> {
+    author: &'a AuthorProfile<'a>,
+    release: bool,
+    #[serde(flatten)]
+    site: &'a SiteTemplateContext<'a>,
+}
+
+#[derive(Debug, Serialize)]
+struct AuthorListContext<'a> {
+    release: bool,
+    #[serde(flatten)]
+    site: &'a SiteTemplateContext<'a>,
+}
+
+fn author_dir(id: &str) -> PathBuf {
+    Path::new(AUTHORS_DIR).join(id)
+}
+
+/// Fill in the byline of every page from the author IDs in its frontmatter.
+pub fn resolve_bylines(
+    authors: &BTreeMap<String, Author>,
+    metadata: &mut MetadataContainer,
+) -> anyhow::Result<()> {
+    for (slug, md) in &mut metadata.0 {
+        let byline = md
+            .frontmatter_str_list(AUTHORS_FIELD)
+            .map(|id| match authors.get(id) {
+                Some(author) => Ok(author.clone()),
+                None => bail!("Page [{slug}] lists unknown author [{id}]"),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        md.byline = byline;
+    }
+
+    Ok(())
+}
+
+/// Collect every author along with the pages that list them.
+pub fn collect_profiles<'a>(
+    authors: &'a BTreeMap<String, Author>,
+    metadata: &'a MetadataContainer,
+) -> BTreeMap<String, AuthorProfile<'a>> {
+    authors
+        .iter()
+        .map(|(id, author)| {
+            let pages = metadata
+                .0
+                .values()
+                .filter(|md| md.byline.iter().any(|byline| &byline.id == id))
+                .collect();
+            (id.clone(), AuthorProfile { author, pages })
+        })
+        .collect()
+}
+
+/// Register a page for each author using the `author.html` template, and a
+/// page listing all authors using the `author_list.html` template. Either is
+/// skipped if its template is missing.
+pub fn add_author_pages<'a>(renderer: &'a TemplateRenderer<'a>, pages: &mut VirtualPages<'a>) {
+    let release = renderer.args.release;
+
+    if let Some(template) = renderer
+        .templates
+        .find_named_template(&[Path::new("author_list.html")])
+    {
+        let context = AuthorListContext {
+            release,
+            site: renderer.site,
+        };
+        pages.add_template(
+            renderer,
+            template,
+            context,
+            Path::new(AUTHORS_DIR).join("index.html"),
+            "author list page",
+        );
+    } else {
+        debug!("No author list template found, skipping");
+    }
+
+    let Some(template) = renderer
+        .templates
+        .find_named_template(&[Path::new("author.html")])
+    else {
+        debug!("No author template found, skipping");
+        return;
+    };
+
+    for (id, profile) in &renderer.site.all_authors {
+        let context = AuthorContext {
+            author: profile,
+            release,
+            site: renderer.site,
+        };
+        pages.add_template(
+            renderer,
+            template,
+            context,
+            author_dir(id).join("index.html"),
+            format!("page for author [{id}]"),
+        );
+    }
+}
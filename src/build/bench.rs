@@ -0,0 +1,379 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs,
+    path::PathBuf,
+    process,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context as _, bail};
+use argh::{ArgsInfo, FromArgs};
+use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+    span,
+};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+use crate::build::{
+    BuildCmd, build_pages, serve,
+    summary::{self, PROGRESS_TARGET},
+};
+
+/// The prefix of the name of the output directory of a benchmark, followed by
+/// the process ID
+pub const SCRATCH_PREFIX: &str = "www-bench-";
+
+/// Build the site several times and report how long the builds took.
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(subcommand, name = "bench")]
+pub struct BenchCmd {
+    /// path to the input directory
+    #[argh(positional)]
+    input_path: PathBuf,
+
+    /// the number of builds to time, defaults to 5
+    #[argh(option, default = "5")]
+    runs: usize,
+
+    /// also time each stage of the build, like rendering content and
+    /// post-processing the output
+    #[argh(switch)]
+    stages: bool,
+
+    /// write the time spent in each span of the builds to this file as folded
+    /// stacks, which `inferno-flamegraph` or `flamegraph.pl` turn into a
+    /// flamegraph
+    #[argh(option)]
+    trace: Option<PathBuf>,
+
+    /// render the site without debug information
+    #[argh(switch)]
+    release: bool,
+
+    /// don't fetch anything over the network
+    #[argh(switch)]
+    offline: bool,
+}
+
+static NUM_ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static NUM_ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// A global allocator that counts the allocations of the process, for the
+/// allocation stats of benchmarks. Without it the stats are left out.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        NUM_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        NUM_ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        NUM_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        NUM_ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        NUM_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        NUM_ALLOCATED_BYTES.fetch_add(new_size as u64, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+/// The number of allocations and allocated bytes so far.
+fn allocation_counts() -> (u64, u64) {
+    (
+        NUM_ALLOCATIONS.load(Ordering::Relaxed),
+        NUM_ALLOCATED_BYTES.load(Ordering::Relaxed),
+    )
+}
+
+/// What was timed during a single build.
+#[derive(Debug, Default)]
+struct Timings {
+    /// The time since the previous progress log, along with the message of
+    /// the progress log that ended the stage, in the order of the stages
+    stages: Vec<(String, Duration)>,
+    /// The time spent in each stack of spans, not counting the spans inside
+    /// it, keyed by the names of the spans joined with `;`
+    stacks: BTreeMap<String, Duration>,
+    last_progress: Option<Instant>,
+}
+
+/// The timings of the build that is running, or `None` when no build is being
+/// timed.
+static TIMED: Mutex<Option<Timings>> = Mutex::new(None);
+
+/// A tracing layer that times the stages of a build and the spans inside it
+/// while [`time`] is running.
+pub struct TimingLayer;
+
+impl TimingLayer {
+    /// Whether the layer needs to see a span or event, so that it can be
+    /// filtered to those and doesn't enable every log.
+    pub fn is_timed(metadata: &tracing::Metadata<'_>) -> bool {
+        metadata.is_span() || metadata.target() == PROGRESS_TARGET
+    }
+}
+
+/// When a span was entered, and how long its children ran.
+struct SpanTiming {
+    entered: Option<Instant>,
+    busy: Duration,
+    children: Duration,
+}
+
+/// Finds the message of a progress log.
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{value:?}");
+        }
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for TimingLayer {
+    fn on_new_span(&self, _attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanTiming {
+                entered: None,
+                busy: Duration::ZERO,
+                children: Duration::ZERO,
+            });
+        }
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if let Some(timing) = span.extensions_mut().get_mut::<SpanTiming>() {
+            timing.entered = Some(Instant::now());
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(timing) = extensions.get_mut::<SpanTiming>()
+            && let Some(entered) = timing.entered.take()
+        {
+            timing.busy += entered.elapsed();
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some((busy, children)) = span
+            .extensions()
+            .get::<SpanTiming>()
+            .map(|timing| (timing.busy, timing.children))
+        else {
+            return;
+        };
+        if let Some(parent) = span.parent()
+            && let Some(timing) = parent.extensions_mut().get_mut::<SpanTiming>()
+        {
+            timing.children += busy;
+        }
+
+        let mut timed = TIMED.lock().expect("timings lock is not poisoned");
+        if let Some(timings) = timed.as_mut() {
+            let stack = span
+                .scope()
+                .from_root()
+                .map(|span| span.name())
+                .collect::<Vec<_>>()
+                .join(";");
+            *timings.stacks.entry(stack).or_default() += busy.saturating_sub(children);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() != PROGRESS_TARGET {
+            return;
+        }
+
+        let mut timed = TIMED.lock().expect("timings lock is not poisoned");
+        if let Some(timings) = timed.as_mut() {
+            let now = Instant::now();
+            let mut message = MessageVisitor::default();
+            event.record(&mut message);
+            if let Some(last_progress) = timings.last_progress {
+                *stage_entry(&mut timings.stages, message.0) += now - last_progress;
+            }
+            timings.last_progress = Some(now);
+        }
+    }
+}
+
+/// The entry of a stage, which is added after the others if it is new.
+fn stage_entry<T: Default>(stages: &mut Vec<(String, T)>, stage: String) -> &mut T {
+    let idx = match stages.iter().position(|(name, _)| *name == stage) {
+        Some(idx) => idx,
+        None => {
+            stages.push((stage, T::default()));
+            stages.len() - 1
+        },
+    };
+    &mut stages[idx].1
+}
+
+/// Run `f` and return the timings recorded while it ran, along with its
+/// result.
+fn time<R>(f: impl FnOnce() -> R) -> (R, Timings) {
+    *TIMED.lock().expect("timings lock is not poisoned") = Some(Timings {
+        last_progress: Some(Instant::now()),
+        ..Timings::default()
+    });
+    let result = f();
+    let timings = TIMED
+        .lock()
+        .expect("timings lock is not poisoned")
+        .take()
+        .unwrap_or_default();
+    (result, timings)
+}
+
+/// The minimum, median, and maximum of some measurements.
+fn spread<T: Copy + Ord>(values: &mut [T]) -> Option<(T, T, T)> {
+    values.sort_unstable();
+    Some((*values.first()?, values[values.len() / 2], *values.last()?))
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// Build the site the given number of times into a scratch directory, and
+/// print the spread of the build times, and of the stages and allocations if
+/// known.
+pub fn bench(cmd: BenchCmd) -> anyhow::Result<()> {
+    if cmd.runs == 0 {
+        bail!("the number of runs must be at least 1");
+    }
+
+    let output_path = serve::scratch_root().join(format!("{SCRATCH_PREFIX}{}", process::id()));
+    let args = BuildCmd {
+        input_path: cmd.input_path.clone(),
+        output_path: output_path.clone(),
+        release: cmd.release,
+        link_graph: None,
+        keep_going: false,
+        deny_warnings: false,
+        output_format: None,
+        offline: cmd.offline,
+    };
+
+    let mut durations = vec![];
+    let mut allocations = vec![];
+    let mut allocated_bytes = vec![];
+    let mut stages = Vec::<(String, Vec<Duration>)>::new();
+    let mut stacks = BTreeMap::<String, Duration>::new();
+    let mut result = Ok(());
+    for run in 1..=cmd.runs {
+        let (start_allocations, start_bytes) = allocation_counts();
+        let start = Instant::now();
+        let (report, timings) = time(|| build_pages(&args, None));
+        let duration = start.elapsed();
+        let (end_allocations, end_bytes) = allocation_counts();
+        if let Err(err) = report {
+            result = Err(err.context(format!("build {run} failed")));
+            break;
+        }
+
+        durations.push(duration);
+        allocations.push(end_allocations - start_allocations);
+        allocated_bytes.push(end_bytes - start_bytes);
+        for (stage, duration) in timings.stages {
+            stage_entry(&mut stages, stage).push(duration);
+        }
+        for (stack, duration) in timings.stacks {
+            *stacks.entry(stack).or_default() += duration;
+        }
+    }
+    let _ = fs::remove_dir_all(&output_path);
+    result?;
+
+    let mut report = String::new();
+    let row = |report: &mut String, label: &str, values: &mut [Duration]| {
+        if let Some((min, median, max)) = spread(values) {
+            let _ = writeln!(
+                report,
+                "{label:<24} {:>10} {:>10} {:>10}",
+                format_duration(min),
+                format_duration(median),
+                format_duration(max)
+            );
+        }
+    };
+    let _ = writeln!(
+        report,
+        "{:<24} {:>10} {:>10} {:>10}",
+        format!("{} runs", cmd.runs),
+        "min",
+        "median",
+        "max"
+    );
+    row(&mut report, "build", &mut durations);
+    if cmd.stages {
+        for (stage, durations) in &mut stages {
+            row(
+                &mut report,
+                &format!("  {}", stage.to_lowercase()),
+                durations,
+            );
+        }
+    }
+    // Without the counting allocator nothing is counted
+    if allocations.iter().any(|&count| count > 0) {
+        if let Some((min, median, max)) = spread(&mut allocations) {
+            let _ = writeln!(
+                report,
+                "{:<24} {min:>10} {median:>10} {max:>10}",
+                "allocations"
+            );
+        }
+        if let Some((min, median, max)) = spread(&mut allocated_bytes) {
+            let _ = writeln!(
+                report,
+                "{:<24} {:>10} {:>10} {:>10}",
+                "allocated",
+                summary::format_size(min),
+                summary::format_size(median),
+                summary::format_size(max)
+            );
+        }
+    }
+    print!("{report}");
+
+    if let Some(trace_path) = &cmd.trace {
+        let folded = stacks
+            .iter()
+            .map(|(stack, duration)| format!("{stack} {}\n", duration.as_micros()))
+            .collect::<String>();
+        fs::write(trace_path, folded).context("failed to write trace")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use std::path::Path;
+
+use tracing::{debug, warn};
+
+use crate::build::{config::SizeBudgetConfig, errors::FailureClass, manifest::OutputManifest};
+
+/// The files a budget applies to, like `.html files`.
+fn describe(budget: &SizeBudgetConfig) -> String {
+    match &budget.extension {
+        Some(extension) => format!(".{extension} files"),
+        None => "all files".into(),
+    }
+}
+
+/// Check the size of every output file against the configured budgets.
+///
+/// Each file over a budget is listed. In release builds this fails the build,
+/// otherwise the files are only warned about.
+#[tracing::instrument(skip_all)]
+pub fn check_size_budgets(
+    budgets: &[SizeBudgetConfig],
+    manifest: &OutputManifest,
+    release: bool,
+) -> anyhow::Result<()> {
+    let mut violations = vec![];
+    for budget in budgets {
+        let files = manifest
+            .files
+            .iter()
+            .filter(|(url, _)| match &budget.extension {
+                Some(extension) => Path::new(url)
+                    .extension()
+                    .is_some_and(|ext| ext == &**extension),
+                None => true,
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(max_file_size) = budget.max_file_size {
+            for (url, entry) in &files {
+                if entry.size > max_file_size {
+                    violations.push(format!(
+                        "[{url}] is {} bytes, over the budget of {max_file_size} bytes for {}",
+                        entry.size,
+                        describe(budget)
+                    ));
+                }
+            }
+        }
+
+        let total_size = files.iter().map(|(_, entry)| entry.size).sum::<u64>();
+        if let Some(max_total_size) = budget.max_total_size
+            && total_size > max_total_size
+        {
+            violations.push(format!(
+                "{} are {total_size} bytes together, over the budget of {max_total_size} bytes",
+                describe(budget)
+            ));
+        }
+        debug!(
+            files = describe(budget),
+            num_files = files.len(),
+            total_size,
+            "Checked size budget"
+        );
+    }
+
+    if violations.is_empty() {
+        return Ok(());
+    }
+    if release {
+        return Err(FailureClass::Check.error(format!(
+            "output is over its size budgets:\n  {}",
+            violations.join("\n  ")
+        )));
+    }
+    for violation in &violations {
+        warn!("Output is over its size budget: {violation}");
+    }
+    Ok(())
+}
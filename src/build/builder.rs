@@ -0,0 +1,127 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::build::{BuildCmd, build_pages_with, summary::BuildStats, warnings, warnings::Warning};
+
+/// A page that was just written to the output, passed to the callback of
+/// [`SiteBuilder::on_page`].
+#[derive(Debug)]
+pub struct PageWritten<'a> {
+    /// The content file the page was rendered from
+    pub input_path: &'a Path,
+    /// The file the page was written to
+    pub output_path: &'a Path,
+    /// The URL path the page is served from, like `/blog/post.html`
+    pub url_path: &'a Path,
+}
+
+/// What a finished build produced.
+#[derive(Debug)]
+pub struct SiteBuild {
+    /// What the build wrote to the output
+    pub stats: BuildStats,
+    /// The warnings logged during the build, which are only collected when a
+    /// [`WarningLayer`](crate::WarningLayer) is part of the tracing subscriber
+    pub warnings: Vec<Warning>,
+    /// The URL path of the page rendered from each content file, keyed by the
+    /// full path of the content file
+    pub page_urls: BTreeMap<PathBuf, String>,
+    /// How long the build took
+    pub duration: Duration,
+}
+
+/// Builds a site from another program, with the same options as `www build`.
+///
+/// ```no_run
+/// let site = www::SiteBuilder::new("site", "output")
+///     .release(true)
+///     .on_page(|page| println!("wrote {}", page.url_path.display()))
+///     .build()?;
+/// println!("{} pages", site.stats.pages);
+/// # anyhow::Ok(())
+/// ```
+pub struct SiteBuilder<'a> {
+    args: BuildCmd,
+    on_page: Box<dyn FnMut(&PageWritten) + 'a>,
+}
+
+impl<'a> SiteBuilder<'a> {
+    /// A builder for the site in the input directory, written to the output
+    /// directory, which is replaced by every build.
+    pub fn new(input_path: impl Into<PathBuf>, output_path: impl Into<PathBuf>) -> Self {
+        Self {
+            args: BuildCmd {
+                input_path: input_path.into(),
+                output_path: output_path.into(),
+                release: false,
+                link_graph: None,
+                keep_going: false,
+                deny_warnings: false,
+                output_format: None,
+                offline: false,
+            },
+            on_page: Box::new(|_| {}),
+        }
+    }
+
+    /// Render the site without debug information and drafts.
+    pub fn release(mut self, release: bool) -> Self {
+        self.args.release = release;
+        self
+    }
+
+    /// Keep building the other pages after a page fails, and report every
+    /// error at the end.
+    pub fn keep_going(mut self, keep_going: bool) -> Self {
+        self.args.keep_going = keep_going;
+        self
+    }
+
+    /// Fail the build if there are any warnings.
+    pub fn deny_warnings(mut self, deny_warnings: bool) -> Self {
+        self.args.deny_warnings = deny_warnings;
+        self
+    }
+
+    /// Don't fetch anything over the network, and use cached responses of
+    /// earlier builds however old they are.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.args.offline = offline;
+        self
+    }
+
+    /// Write the graph of links between pages to this file, as GraphViz DOT
+    /// for `.dot` files and JSON otherwise.
+    pub fn link_graph(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.link_graph = Some(path.into());
+        self
+    }
+
+    /// Call `on_page` after each page is written, in the order pages are
+    /// built.
+    pub fn on_page(mut self, on_page: impl FnMut(&PageWritten) + 'a) -> Self {
+        self.on_page = Box::new(on_page);
+        self
+    }
+
+    /// Build the whole site.
+    ///
+    /// Sites can be built on several threads at once, and each build only
+    /// collects the warnings logged on its own thread.
+    pub fn build(mut self) -> anyhow::Result<SiteBuild> {
+        let start = Instant::now();
+        let (result, warnings) =
+            warnings::collect(|| build_pages_with(&self.args, None, &mut self.on_page));
+        let report = result?;
+        warnings::summarize(&warnings, self.args.deny_warnings)?;
+        Ok(SiteBuild {
+            stats: report.stats,
+            warnings,
+            page_urls: report.page_urls,
+            duration: start.elapsed(),
+        })
+    }
+}
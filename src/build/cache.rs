@@ -0,0 +1,81 @@
+use std::{collections::BTreeMap, fmt::Write, fs, path::Path, sync::LazyLock};
+
+use anyhow::Context;
+use regex::Regex;
+use serde::Serialize;
+use tracing::debug;
+
+use crate::build::{
+    config::{CacheControlConfig, CacheControlFormat},
+    manifest::OutputManifest,
+};
+
+/// The file name of the JSON cache control manifest
+const JSON_FILENAME: &str = "cache-control.json";
+
+/// The file name of the headers file read by Netlify and Cloudflare Pages
+const HEADERS_FILENAME: &str = "_headers";
+
+/// Matches a file name with a content hash before the extension, like
+/// `style.3f2a9c1b.css` or `app-3f2a9c1b.js`
+static FINGERPRINTED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[.-][0-9a-fA-F]{8,}\.[^./]+$").unwrap());
+
+/// The recommended `Cache-Control` value for the file served at a URL path.
+pub fn cache_control_for<'a>(config: &'a CacheControlConfig, url: &str) -> &'a str {
+    let file_name = url.rsplit('/').next().unwrap_or_default();
+    if url.ends_with('/') || file_name.ends_with(".html") {
+        &config.html
+    } else if FINGERPRINTED.is_match(file_name) {
+        &config.immutable
+    } else {
+        &config.default
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct CacheControlManifest<'a>(BTreeMap<&'a str, &'a str>);
+
+/// Write the recommended `Cache-Control` value of every output file, either as
+/// a JSON object keyed by URL path or as a `_headers` file.
+#[tracing::instrument(skip_all)]
+pub fn write_cache_control(
+    config: &CacheControlConfig,
+    manifest: &OutputManifest,
+    output_root: &Path,
+) -> anyhow::Result<()> {
+    let (filename, content) = match config.format {
+        CacheControlFormat::Json => {
+            let values = manifest
+                .files
+                .keys()
+                .map(|url| (url.as_str(), cache_control_for(config, url)))
+                .collect();
+            let content = serde_json::to_string_pretty(&CacheControlManifest(values))
+                .context("failed to serialize cache control manifest")?;
+            (JSON_FILENAME, content)
+        },
+        CacheControlFormat::Headers => {
+            let mut content = String::new();
+            for url in manifest.files.keys() {
+                let value = cache_control_for(config, url);
+                // Index pages are requested by their directory
+                if let Some(dir) = url.strip_suffix("index.html") {
+                    writeln!(content, "{dir}\n  Cache-Control: {value}")?;
+                }
+                writeln!(content, "{url}\n  Cache-Control: {value}")?;
+            }
+            (HEADERS_FILENAME, content)
+        },
+    };
+
+    let path = output_root.join(filename);
+    fs::write(&path, content).context(format!(
+        "failed to write cache control manifest [{}]",
+        path.display()
+    ))?;
+    debug!(path = %path.display(), "Written cache control manifest");
+
+    Ok(())
+}
@@ -0,0 +1,524 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::LazyLock,
+};
+
+use anyhow::Context;
+use argh::{ArgsInfo, FromArgs};
+use regex::Regex;
+use tracing::{debug, info, warn};
+
+use crate::build::{
+    BuildCmd, BuildDirFiles, MetadataContainer, TemplateSlug, Templates, annotation::OutputFormat,
+    build_with_summary, config::SiteConfig, diagnostic::Diagnostic, inline::attribute,
+    links::resolve_internal, manifest::url_path, serve,
+};
+
+/// The prefix of the name of the directory a site is checked in, followed by
+/// the process ID
+pub const SCRATCH_PREFIX: &str = "www-check-";
+
+/// Build the site without keeping the output, reporting every page that fails
+/// along with the warnings found in the output.
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(subcommand, name = "check")]
+pub struct CheckCmd {
+    /// path to the input directory
+    #[argh(positional)]
+    input_path: PathBuf,
+
+    /// check the site as it is built for release, without drafts
+    #[argh(switch)]
+    release: bool,
+
+    /// stop at the first page that fails, instead of reporting every error
+    #[argh(switch)]
+    fail_fast: bool,
+
+    /// fail the check if there are any warnings
+    #[argh(switch)]
+    deny_warnings: bool,
+
+    /// also print warnings and errors to stdout for other tools, `github`
+    /// prints GitHub Actions annotations
+    #[argh(option)]
+    output_format: Option<OutputFormat>,
+
+    /// don't fetch anything over the network, and use cached responses of
+    /// earlier builds however old they are
+    #[argh(switch)]
+    offline: bool,
+}
+
+/// Build the site into a scratch directory that is removed afterwards.
+///
+/// Unlike `build`, the build keeps going after a page fails unless
+/// `--fail-fast` is given, so that every error is reported at once.
+pub fn check(cmd: CheckCmd) -> anyhow::Result<()> {
+    let output_path = serve::scratch_root().join(format!("{SCRATCH_PREFIX}{}", process::id()));
+    let args = BuildCmd {
+        input_path: cmd.input_path,
+        output_path: output_path.clone(),
+        release: cmd.release,
+        link_graph: None,
+        keep_going: !cmd.fail_fast,
+        deny_warnings: cmd.deny_warnings,
+        output_format: cmd.output_format,
+        offline: cmd.offline,
+    };
+    let result = build_with_summary(&args);
+    if let Err(err) = fs::remove_dir_all(&output_path) {
+        debug!(%err, "Failed to remove check output");
+    }
+    result?;
+
+    info!("Checked site without errors");
+
+    Ok(())
+}
+
+/// Matches the destination of `href` and `src` attributes
+static LINK_ATTR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?:href|src)\s*=\s*["']([^"']*)["']"#).unwrap());
+
+/// Matches the destination of `href` attributes
+static HREF_ATTR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"href\s*=\s*["']([^"']*)["']"#).unwrap());
+
+/// Matches the value of `id` attributes, and of the `name` attributes that
+/// anchors can also be targeted by
+static ID_ATTR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"[\s"'](?:id|name)\s*=\s*["']([^"']*)["']"#).unwrap());
+
+/// Matches an `<img>` tag
+pub static IMG_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<img\b[^>]*>").unwrap());
+
+/// Matches the template names referenced by `extends`, `include`, and `import`
+/// tags, including each name in an `include` list
+static TEMPLATE_REFERENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\{%-?\s*(?:extends|include|import)\s+(\[[^\]]*\]|"[^"]*"|'[^']*')"#).unwrap()
+});
+
+/// Matches the code of a Tera expression or statement, like `{{ ... }}`
+static TEMPLATE_CODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)\{[{%](.*?)[%}]\}").unwrap());
+
+/// Matches the template variables and functions that hold the metadata of
+/// other pages than the one being rendered
+static CROSS_PAGE_VARIABLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"\b(?:all_pages|pages|subpages|descendants|parent|ancestors|siblings|backlinks|translations|feeds|taxonomies|all_authors)\b",
+    )
+    .unwrap()
+});
+
+/// Matches a single quoted string
+static QUOTED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#""([^"]*)"|'([^']*)'"#).unwrap());
+
+/// Decode the handful of HTML entities that Tera's autoescaping produces in
+/// attribute values.
+pub fn decode_entities(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+
+    Cow::Owned(
+        text.replace("&#x2F;", "/")
+            .replace("&#47;", "/")
+            .replace("&quot;", "\"")
+            .replace("&#x27;", "'")
+            .replace("&#39;", "'")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&"),
+    )
+}
+
+/// The rendered HTML and XML files in the output directory, keyed by URL path.
+pub fn read_output_documents(output_root: &Path) -> anyhow::Result<BTreeMap<String, String>> {
+    let output_files =
+        BuildDirFiles::gather(output_root).context("failed to collect output files")?;
+
+    let mut documents = BTreeMap::new();
+    for (path, file) in output_files.files {
+        if !path
+            .extension()
+            .is_some_and(|ext| ext == "html" || ext == "xml")
+        {
+            continue;
+        }
+
+        let content = fs::read_to_string(&file.full_path).context(format!(
+            "failed to read output file [{}]",
+            file.full_path.display()
+        ))?;
+        documents.insert(url_path(&path), content);
+    }
+
+    Ok(documents)
+}
+
+/// Turn absolute links to the site itself, like the ones in feeds, into
+/// internal links.
+fn strip_base_url(config: &SiteConfig, dest: String) -> String {
+    let base_url = config
+        .base_url
+        .as_deref()
+        .map(|base_url| base_url.trim_end_matches('/'));
+
+    match base_url {
+        Some(base_url) if dest.starts_with(base_url) => {
+            let rest = &dest[base_url.len()..];
+            if rest.is_empty() {
+                "/".to_owned()
+            } else {
+                rest.to_owned()
+            }
+        },
+        _ => dest,
+    }
+}
+
+/// Find every internal link in the document, resolved to URL paths.
+pub fn document_links(config: &SiteConfig, url: &str, document: &str) -> BTreeSet<String> {
+    LINK_ATTR
+        .captures_iter(document)
+        .filter_map(|captures| {
+            let dest = strip_base_url(config, decode_entities(&captures[1]).into_owned());
+            resolve_internal(Path::new(url), &dest)
+        })
+        .collect()
+}
+
+/// Warn about every page that no other page, feed, or index links to.
+///
+/// Links are collected from the final output, so that listings generated by
+/// templates are taken into account.
+#[tracing::instrument(skip_all)]
+pub fn warn_orphan_pages(
+    config: &SiteConfig,
+    metadata: &MetadataContainer,
+    output_root: &Path,
+) -> anyhow::Result<()> {
+    let documents = read_output_documents(output_root)?;
+
+    let mut linked = BTreeSet::new();
+    for (url, document) in &documents {
+        let links = document_links(config, url, document);
+        linked.extend(links.into_iter().filter(|target| target != url));
+    }
+
+    let mut num_orphans = 0;
+    for md in metadata.0.values() {
+        let url = md.url_path.to_string_lossy();
+        if url == "/index.html" || !url.ends_with(".html") || md.is_not_found() {
+            continue;
+        }
+
+        if !linked.contains(url.as_ref()) {
+            warn!(page = %url, "Page is not linked from any other page, feed, or index");
+            num_orphans += 1;
+        }
+    }
+
+    debug!(num_orphans, "Checked for orphan pages");
+
+    Ok(())
+}
+
+/// Reduce a URL path to the form a reader would think of it as, ignoring case,
+/// the extension, and a trailing `index`.
+fn normalize_url_for_comparison(url: &str) -> String {
+    let url = url.to_lowercase();
+    let without_extension = match url.rsplit_once('.') {
+        Some((stem, _)) if !stem.ends_with('/') && !stem.is_empty() => stem,
+        _ => &url,
+    };
+    without_extension
+        .trim_end_matches("/index")
+        .trim_end_matches('/')
+        .to_owned()
+}
+
+/// Warn when pages share the same title, or have URLs that differ only by case
+/// or extension.
+#[tracing::instrument(skip_all)]
+pub fn warn_duplicate_pages(metadata: &MetadataContainer) {
+    let mut by_title: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    let mut by_url: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for md in metadata.0.values() {
+        let url = md.url_path.to_string_lossy().into_owned();
+        if !url.ends_with(".html") {
+            continue;
+        }
+
+        if let Some(title) = &md.title {
+            by_title
+                .entry(title.as_str())
+                .or_default()
+                .push(url.clone());
+        }
+        by_url
+            .entry(normalize_url_for_comparison(&url))
+            .or_default()
+            .push(url);
+    }
+
+    for (title, urls) in by_title {
+        if urls.len() > 1 {
+            warn!(title, pages = ?urls, "Multiple pages share the same title");
+        }
+    }
+
+    for urls in by_url.into_values() {
+        if urls.len() < 2 {
+            continue;
+        }
+
+        let unique_urls = urls.iter().collect::<BTreeSet<_>>();
+        if unique_urls.len() < urls.len() {
+            warn!(pages = ?urls, "Multiple content files are written to the same URL, one will overwrite the other");
+        } else {
+            warn!(pages = ?urls, "Pages have URLs that differ only by case or extension");
+        }
+    }
+}
+
+/// Warn about every internal link with a fragment, like `/blog/#intro`, that
+/// points at an id which doesn't exist on the target page.
+///
+/// Links to `#top` are skipped, since browsers scroll to the top of the page for
+/// them, as are links to pages that aren't HTML files in the output.
+///
+/// Each warning points at the link in the content file of the page when it can
+/// be found there, and otherwise at the link in the output.
+#[tracing::instrument(skip_all)]
+pub fn warn_broken_fragments(
+    config: &SiteConfig,
+    metadata: &MetadataContainer,
+    output_root: &Path,
+) -> anyhow::Result<()> {
+    let source_paths = metadata
+        .0
+        .values()
+        .map(|md| (md.url_path.to_string_lossy().into_owned(), &md.source_path))
+        .collect::<BTreeMap<_, _>>();
+    let documents = read_output_documents(output_root)?;
+    let documents = documents
+        .iter()
+        .filter(|(url, _)| url.ends_with(".html"))
+        .collect::<BTreeMap<_, _>>();
+    let ids = documents
+        .iter()
+        .map(|(url, document)| {
+            let ids = ID_ATTR
+                .captures_iter(document)
+                .map(|captures| decode_entities(&captures[1]).into_owned())
+                .collect::<BTreeSet<_>>();
+            (url.as_str(), ids)
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let mut num_broken = 0;
+    for (url, document) in &documents {
+        // Each link is reported once per page, at its first occurrence
+        let mut links = BTreeMap::new();
+        for captures in HREF_ATTR.captures_iter(document) {
+            let link = strip_base_url(config, decode_entities(&captures[1]).into_owned());
+            links
+                .entry(link)
+                .or_insert_with(|| captures.get(1).unwrap());
+        }
+        for (link, href) in links {
+            let Some((path, fragment)) = link.split_once('#') else {
+                continue;
+            };
+            if fragment.is_empty() || fragment == "top" {
+                continue;
+            }
+
+            let target = if path.is_empty() {
+                Some(url.to_string())
+            } else {
+                resolve_internal(Path::new(url), path)
+            };
+            let Some(target_ids) = target.and_then(|target| ids.get(target.as_str())) else {
+                continue;
+            };
+            if !target_ids.contains(fragment) {
+                let message = format!(
+                    "Link [{link}] points at a fragment that does not exist on the target page"
+                );
+                let in_source = source_paths.get(url.as_str()).and_then(|path| {
+                    let source = fs::read_to_string(path).ok()?;
+                    let offset = source.find(&format!("#{fragment}"))?;
+                    Some(Diagnostic::new(*path, &message).at_offset(
+                        &source,
+                        offset,
+                        fragment.len() + 1,
+                    ))
+                });
+                let diagnostic = in_source.unwrap_or_else(|| {
+                    Diagnostic::new(output_root.join(url.trim_start_matches('/')), &message)
+                        .at_offset(document, href.start(), href.len())
+                });
+                diagnostic.warn_broken_link();
+                num_broken += 1;
+            }
+        }
+    }
+
+    debug!(num_broken, "Checked links for broken fragments");
+
+    Ok(())
+}
+
+/// The line number of a byte offset in a document, starting at 1.
+pub fn line_number(document: &str, offset: usize) -> usize {
+    document[..offset].matches('\n').count() + 1
+}
+
+/// Warn about every image in the output without alternative text.
+///
+/// Images that are marked as decorative with `role="presentation"` or
+/// `aria-hidden="true"` are skipped, as are all images on pages that set
+/// `decorative_images = true` in their frontmatter.
+#[tracing::instrument(skip_all)]
+pub fn warn_missing_alt_text(
+    metadata: &MetadataContainer,
+    output_root: &Path,
+) -> anyhow::Result<()> {
+    let skipped_pages = metadata
+        .0
+        .values()
+        .filter(|md| {
+            md.frontmatter_field("decorative_images")
+                .and_then(tera::Value::as_bool)
+                .unwrap_or(false)
+        })
+        .map(|md| md.url_path.to_string_lossy().into_owned())
+        .collect::<BTreeSet<_>>();
+
+    let mut num_missing = 0;
+    for (url, document) in read_output_documents(output_root)? {
+        if !url.ends_with(".html") || skipped_pages.contains(&url) {
+            continue;
+        }
+
+        for tag in IMG_TAG.find_iter(&document) {
+            let is_decorative = attribute(tag.as_str(), "role")
+                .is_some_and(|role| role == "presentation")
+                || attribute(tag.as_str(), "aria-hidden").is_some_and(|hidden| hidden == "true");
+            let has_alt = attribute(tag.as_str(), "alt").is_some_and(|alt| !alt.trim().is_empty());
+            if is_decorative || has_alt {
+                continue;
+            }
+
+            let src = attribute(tag.as_str(), "src").unwrap_or_default();
+            warn!(
+                page = %url,
+                line = line_number(&document, tag.start()),
+                %src,
+                "Image has no alt text, add one or mark the image as decorative"
+            );
+            num_missing += 1;
+        }
+    }
+
+    debug!(num_missing, "Checked images for alt text");
+
+    Ok(())
+}
+
+/// The templates that each template references through `extends`, `include`,
+/// or `import`.
+pub fn template_references(
+    templates: &Templates,
+) -> anyhow::Result<BTreeMap<PathBuf, Vec<PathBuf>>> {
+    let mut references = BTreeMap::new();
+    for (TemplateSlug(name), file) in &templates.files {
+        let source = fs::read_to_string(&file.full_path).context(format!(
+            "failed to read template [{}]",
+            file.full_path.display()
+        ))?;
+        let referenced = TEMPLATE_REFERENCE
+            .captures_iter(&source)
+            .flat_map(|captures| {
+                QUOTED
+                    .captures_iter(&captures[1])
+                    .filter_map(|quoted| quoted.get(1).or(quoted.get(2)))
+                    .map(|name| PathBuf::from(name.as_str()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        references.insert(name.clone(), referenced);
+    }
+
+    Ok(references)
+}
+
+/// The templates that read the metadata of other pages, like `all_pages` or
+/// `siblings`, so that the pages rendered with them change whenever another
+/// page does.
+///
+/// Only the names are looked for, so a template that merely mentions one in
+/// its code is included too.
+pub fn cross_page_templates(templates: &Templates) -> anyhow::Result<BTreeSet<PathBuf>> {
+    let mut cross_page = BTreeSet::new();
+    for (TemplateSlug(name), file) in &templates.files {
+        let source = fs::read_to_string(&file.full_path).context(format!(
+            "failed to read template [{}]",
+            file.full_path.display()
+        ))?;
+        if TEMPLATE_CODE
+            .captures_iter(&source)
+            .any(|captures| CROSS_PAGE_VARIABLE.is_match(&captures[1]))
+        {
+            cross_page.insert(name.clone());
+        }
+    }
+    Ok(cross_page)
+}
+
+/// Every template that rendering the given templates reads, including the
+/// templates themselves.
+pub fn reachable_templates<'a>(
+    references: &BTreeMap<PathBuf, Vec<PathBuf>>,
+    roots: impl IntoIterator<Item = &'a PathBuf>,
+) -> BTreeSet<PathBuf> {
+    let mut reachable = BTreeSet::new();
+    let mut stack = roots.into_iter().cloned().collect::<Vec<_>>();
+    while let Some(name) = stack.pop() {
+        if !reachable.insert(name.clone()) {
+            continue;
+        }
+        if let Some(referenced) = references.get(&name) {
+            stack.extend(referenced.iter().cloned());
+        }
+    }
+    reachable
+}
+
+/// Warn about templates that were never used to render a page, either directly
+/// or through `extends`, `include`, or `import` from a template that was.
+#[tracing::instrument(skip_all)]
+pub fn warn_unused_templates(
+    templates: &Templates,
+    used_templates: &BTreeSet<PathBuf>,
+) -> anyhow::Result<()> {
+    let references = template_references(templates)?;
+    let reachable = reachable_templates(&references, used_templates);
+
+    for name in references.keys() {
+        // A theme is shared between sites, which won't all use every template
+        if !reachable.contains(name) && !templates.theme_templates.contains(name) {
+            warn!(template = %name.display(), "Template is never used to render a page");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use argh::{ArgsInfo, FromArgs};
+use tracing::{debug, info};
+
+use crate::build::{bench, check, deploy::github_pages, serve, site_cache::site_cache_dir};
+
+/// Remove the output directory and the caches and leftovers of earlier
+/// builds.
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(subcommand, name = "clean")]
+pub struct CleanCmd {
+    /// path to the input directory, whose caches are removed
+    #[argh(positional)]
+    input_path: PathBuf,
+
+    /// path to the output directory
+    #[argh(positional)]
+    output_path: PathBuf,
+
+    /// print what would be removed without removing anything
+    #[argh(switch)]
+    dry_run: bool,
+}
+
+/// Whether the process with the given ID may still be running. Without a way
+/// to tell, the process is assumed to be running.
+fn is_running(pid: &str) -> bool {
+    let proc = Path::new("/proc");
+    !proc.is_dir() || proc.join(pid).exists()
+}
+
+/// The entries of a directory whose name starts with `prefix`, followed by the
+/// ID of a process that is no longer running.
+fn leftovers(dir: &Path, prefix: &str, suffix: &str) -> io::Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+
+    let mut paths = vec![];
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(pid) = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(prefix))
+            .and_then(|name| name.strip_suffix(suffix))
+        else {
+            continue;
+        };
+        if pid.bytes().all(|b| b.is_ascii_digit()) && !is_running(pid) {
+            paths.push(entry.path());
+        }
+    }
+    Ok(paths)
+}
+
+/// Remove the output directory, the cache of the site with its cloned themes,
+/// checked out content sources, and cached remote responses and formatted
+/// files, and the scratch files left behind by `serve`, `check`, `bench`, and
+/// `deploy github-pages` processes that were stopped early.
+///
+/// The caches of other sites on the machine are left alone.
+pub fn clean(cmd: CleanCmd) -> anyhow::Result<()> {
+    let mut targets = vec![cmd.output_path.clone(), site_cache_dir(&cmd.input_path)];
+    targets.extend(
+        leftovers(&serve::scratch_root(), serve::SCRATCH_PREFIX, "")
+            .context("failed to find leftover serve output")?,
+    );
+    targets.extend(
+        leftovers(&serve::scratch_root(), check::SCRATCH_PREFIX, "")
+            .context("failed to find leftover check output")?,
+    );
+    targets.extend(
+        leftovers(&serve::scratch_root(), bench::SCRATCH_PREFIX, "")
+            .context("failed to find leftover bench output")?,
+    );
+    targets.extend(
+        leftovers(&env::temp_dir(), github_pages::INDEX_PREFIX, ".index")
+            .context("failed to find leftover GitHub Pages index files")?,
+    );
+
+    for target in targets {
+        let metadata = match fs::symlink_metadata(&target) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                debug!(path = %target.display(), "Nothing to remove");
+                continue;
+            },
+            Err(err) => {
+                return Err(err).context(format!("failed to read [{}]", target.display()));
+            },
+        };
+
+        info!(path = %target.display(), "Removing");
+        if cmd.dry_run {
+            continue;
+        }
+        if metadata.is_dir() {
+            fs::remove_dir_all(&target)
+        } else {
+            fs::remove_file(&target)
+        }
+        .context(format!("failed to remove [{}]", target.display()))?;
+    }
+
+    Ok(())
+}
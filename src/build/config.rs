@@ -0,0 +1,745 @@
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// The name of the site configuration file, found at the root of the input
+/// directory.
+pub const CONFIG_FILENAME: &str = "site.json";
+
+/// Site-wide configuration loaded from [`CONFIG_FILENAME`].
+///
+/// Every field is optional so that a site without a configuration file still
+/// builds.
+#[derive(Debug, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SiteConfig {
+    /// The absolute URL the site is served from, e.g. `https://example.com`
+    pub base_url: Option<String>,
+    /// The custom domain the site is hosted on, like `example.com`, which is
+    /// written to a `CNAME` file for GitHub Pages
+    pub custom_domain: Option<String>,
+    /// The name of the site, used in feeds
+    pub title: Option<String>,
+    /// The default author of all content on the site
+    pub author: Option<String>,
+    /// The language of the pages on the site, like `en`, which a page
+    /// overrides with `language` in its frontmatter
+    pub language: Option<String>,
+    /// What every feed contains, with overrides for individual feeds
+    pub feeds: FeedsConfig,
+    /// URL path of an XSL stylesheet linked from every feed, instead of the
+    /// built-in one
+    pub feed_stylesheet: Option<String>,
+    /// Settings for the podcast feed, if the site has a podcast section
+    pub podcast: Option<PodcastConfig>,
+    /// Replace `:shortcode:` symbols in djot content with emoji, off unless
+    /// present
+    pub emoji: Option<EmojiConfig>,
+    /// Render straight quotes, `--`, `---`, and `...` in djot content as curly
+    /// quotes, dashes, and ellipses, defaults to on. Code is never changed
+    #[serde(default = "default_true")]
+    pub smart_punctuation: bool,
+    /// Frontmatter fields whose text is inline djot markup, like `summary` or
+    /// `description`, which is rendered to HTML before it reaches templates
+    /// and feeds. Templates have to mark these fields as `safe`
+    pub djot_fields: Vec<String>,
+    /// HTML elements that djot spans and divs with the given class are
+    /// rendered as, like `kbd` for `[Ctrl]{.kbd}`
+    pub components: BTreeMap<String, ComponentConfig>,
+    /// Typographic fixes applied to the HTML of every page, off unless present
+    pub typography: Option<TypographyConfig>,
+    /// The ways that pages are classified, defaults to just `tags`
+    #[serde(default = "default_taxonomies")]
+    pub taxonomies: Vec<TaxonomyConfig>,
+    /// Fail the build when an HTML page has no template to render it with,
+    /// defaults to on for release builds
+    pub strict_templates: Option<bool>,
+    /// Content paths, or directories of content, that are intentionally
+    /// rendered without a template even in strict mode
+    pub untemplated_pages: Vec<PathBuf>,
+    /// Fail rendering when a template references an undefined variable,
+    /// defaults to on. When off, undefined variables render as an empty string
+    /// in release builds and as a visible `[undefined: name]` marker otherwise
+    #[serde(default = "default_true")]
+    pub strict_variables: bool,
+    /// Rules for the content directories at the given paths, which can also be
+    /// declared in a `.build.json` file within the directory
+    pub directories: BTreeMap<PathBuf, DirectoryRules>,
+    /// Inline local stylesheets and scripts smaller than a size threshold into
+    /// the pages that link them, off unless present
+    pub inline_assets: Option<InlineAssetsConfig>,
+    /// In release builds, inline the rules of each local stylesheet that a page
+    /// uses and load the full stylesheet without blocking the first paint
+    pub critical_css: bool,
+    /// Write a copy of each stylesheet with the selectors that no page uses
+    /// removed, off unless present
+    pub prune_css: Option<PruneCssConfig>,
+    /// Write the recommended `Cache-Control` value of every output file, for a
+    /// deploy script or host to apply, off unless present
+    pub cache_control: Option<CacheControlConfig>,
+    /// Limits on the size of output files, which fail release builds and are
+    /// warned about otherwise
+    pub size_budgets: Vec<SizeBudgetConfig>,
+    /// Warn about common accessibility problems in the rendered pages, like
+    /// skipped heading levels and links without text
+    pub accessibility_checks: bool,
+    /// Warn about misspelled words in the content of each page, off unless
+    /// present
+    pub spellcheck: Option<SpellcheckConfig>,
+    /// Transforms run on content files with the given extension, like `svg`,
+    /// in place of the built-in handling for that extension
+    pub pipelines: BTreeMap<String, PipelineConfig>,
+    /// Pages without a content file, keyed by their URL path like `/archive/`,
+    /// that are produced by rendering a template with the site metadata
+    pub generated_pages: BTreeMap<String, GeneratedPageConfig>,
+    /// Settings for the thumbnails of galleries
+    pub gallery: GalleryConfig,
+    /// Settings for the sitemap, which is written when `base_url` is set
+    pub sitemap: SitemapConfig,
+    /// Add the description and keywords `<meta>` tags of each page to its
+    /// `<head>`, unless its template already has them
+    pub inject_meta_tags: bool,
+    /// Hide email addresses and `mailto:` links in every page from scrapers,
+    /// either as `entities` or decoded by a script with `javascript`, off
+    /// unless present
+    pub obfuscate_emails: Option<EmailObfuscation>,
+    /// Encrypt pages with a password, so that they can only be read by someone
+    /// who knows it, off unless present. Pages are also protected with
+    /// `protected: true` in their frontmatter
+    pub protected_pages: Option<ProtectedPagesConfig>,
+    /// A theme providing templates and static files, which the files of the
+    /// site override. Defaults to the `theme/` directory if it exists
+    pub theme: Option<ThemeSource>,
+    /// Git repositories whose files are overlaid into the content tree before
+    /// the site is built, like shared notes
+    pub content_sources: Vec<ContentSource>,
+    /// Commands run at points of the build, like generating icons before it
+    /// or pinging a service after it
+    pub hooks: HooksConfig,
+    /// External programs, keyed by name, that are sent each page during the
+    /// build and can change its HTML or frontmatter
+    pub plugins: BTreeMap<String, PluginConfig>,
+    /// The command that formats or minifies the output files with each
+    /// extension, like `css`, in place. The paths of the files are appended to
+    /// the command. Unlisted extensions fall back to formatting HTML, CSS,
+    /// JavaScript, and JSON with prettier, and an empty command leaves files
+    /// with that extension alone
+    pub formatters: BTreeMap<String, Vec<String>>,
+    /// Webrings the site is a member of, keyed by the name that templates use
+    /// to find its neighbors in `webrings`
+    pub webrings: BTreeMap<String, WebringConfig>,
+    /// Arbitrary values passed to every template as `extra`, like social
+    /// handles or the navigation menu
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Where the theme of a site is read from.
+///
+/// A theme has the same layout as a site, with a `templates/` directory and a
+/// `static/` directory of files that are copied to the output as is.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ThemeSource {
+    /// A directory, relative to the input directory
+    Path(PathBuf),
+    /// A git repository, which is cloned once and reused by later builds
+    Git {
+        git: String,
+        /// The branch or tag to clone, defaults to the default branch
+        #[serde(default)]
+        rev: Option<String>,
+    },
+}
+
+/// A git repository of content, checked out at a pinned revision and overlaid
+/// into the `content/` directory of the site.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ContentSource {
+    /// URL of the repository
+    pub git: String,
+    /// The commit, tag or branch to check out. The checkout is reused by later
+    /// builds, so a branch is only fetched once
+    pub rev: String,
+    /// The directory of the repository to take files from, defaults to the
+    /// root of the repository
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// The directory under `content/` that the files are placed in, defaults to
+    /// `content/` itself
+    #[serde(default)]
+    pub mount: PathBuf,
+}
+
+/// Commands run at points of a build, each given as the program followed by
+/// its arguments.
+///
+/// They run from the input directory, with the paths of the build in `WWW_*`
+/// environment variables, and a failing command fails the build.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Run before the input files are read
+    pub before_build: Vec<Vec<String>>,
+    /// Run for each page once the output is written and post-processed, with
+    /// the paths of the page in `WWW_PAGE_*` variables
+    pub after_page: Vec<Vec<String>>,
+    /// Run after the whole output is written and checked
+    pub after_build: Vec<Vec<String>>,
+}
+
+/// An external program that runs for the whole build, exchanging a line of
+/// JSON with the build for each event about a page.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PluginConfig {
+    /// The program followed by its arguments, run from the input directory
+    pub command: Vec<String>,
+    /// The events the plugin is sent, defaults to all of them
+    #[serde(default = "default_plugin_events")]
+    pub events: Vec<PluginEvent>,
+}
+
+fn default_plugin_events() -> Vec<PluginEvent> {
+    vec![PluginEvent::Render, PluginEvent::Page]
+}
+
+/// A point of the build where plugins are sent a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginEvent {
+    /// The content of a page was rendered to HTML, before its template is
+    /// applied. Plugins can change the content and the frontmatter
+    Render,
+    /// The template of a page was applied, right before the page is written.
+    /// Plugins can change the HTML
+    Page,
+}
+
+/// A classification of pages, where each page lists the terms it belongs to
+/// under a frontmatter field of the same name as the taxonomy.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaxonomyConfig {
+    /// Name of the taxonomy, used as both the frontmatter field and the output
+    /// directory, e.g. `tags`
+    pub name: String,
+    /// Whether to generate a feed for each term
+    #[serde(default = "default_true")]
+    pub feed: bool,
+}
+
+impl SiteConfig {
+    /// Whether an HTML page with the given content path must have a template.
+    pub fn requires_template(&self, release: bool, content_path: &Path) -> bool {
+        self.strict_templates.unwrap_or(release) && !self.is_untemplated(content_path)
+    }
+
+    /// Whether the page with the given content path is listed as one that is
+    /// meant to be written without a template.
+    pub fn is_untemplated(&self, content_path: &Path) -> bool {
+        self.untemplated_pages
+            .iter()
+            .any(|allowed| content_path.starts_with(allowed))
+    }
+
+    /// The command that formats output files with the given extension, if
+    /// they are formatted at all.
+    pub fn formatter(&self, extension: &str) -> Option<Vec<String>> {
+        let command = match self.formatters.get(extension) {
+            Some(command) => command.clone(),
+            None if DEFAULT_FORMATTED_EXTENSIONS.contains(&extension) => {
+                ["prettier", "--write", "--no-config"]
+                    .map(String::from)
+                    .to_vec()
+            },
+            None => return None,
+        };
+        (!command.is_empty()).then_some(command)
+    }
+}
+
+/// The extensions of output files that are formatted with prettier unless the
+/// config says otherwise
+const DEFAULT_FORMATTED_EXTENSIONS: &[&str] = &["html", "css", "js", "json"];
+
+fn default_taxonomies() -> Vec<TaxonomyConfig> {
+    vec![TaxonomyConfig {
+        name: "tags".into(),
+        feed: true,
+    }]
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Configuration for replacing emoji shortcodes.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmojiConfig {
+    /// Shortcodes rendered as an image instead, mapped to the image URL. These
+    /// take priority over the built-in emoji, and can add custom shortcodes
+    pub images: BTreeMap<String, String>,
+}
+
+/// The HTML element that a djot class is rendered as.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ComponentConfig {
+    /// Name of the element, like `kbd` or `aside`
+    pub element: String,
+    /// Class added to the element, in place of the class that selected it
+    pub class: Option<String>,
+}
+
+/// The element types, like `h1` or `p`, that each typographic fix applies to.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TypographyConfig {
+    /// Join the last two words with a non-breaking space, so that the last
+    /// word never sits on a line of its own
+    pub widows: Vec<String>,
+    /// Wrap ampersands in `<span class="amp">`
+    pub ampersands: Vec<String>,
+    /// Wrap runs of capital letters in `<span class="caps">`
+    pub caps: Vec<String>,
+    /// Wrap opening quotes at the start of the element in
+    /// `<span class="hang-punct">`, so they can be hung into the margin
+    pub hanging_punctuation: Vec<String>,
+}
+
+/// Settings for the generated feeds.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeedsConfig {
+    /// Settings for every feed
+    pub defaults: FeedSettings,
+    /// Settings for single feeds, keyed by the path of the feed in the output,
+    /// like `feed.xml` or `blog/feed.xml`. Fields that are not set fall back to
+    /// the defaults
+    pub paths: BTreeMap<PathBuf, FeedSettings>,
+}
+
+impl FeedsConfig {
+    /// The settings for the feed at the given output path.
+    pub fn settings_for(&self, path: &Path) -> FeedSettings {
+        let Some(settings) = self.paths.get(path) else {
+            return self.defaults.clone();
+        };
+
+        FeedSettings {
+            content: settings.content.or(self.defaults.content),
+            limit: settings.limit.or(self.defaults.limit),
+            since: settings
+                .since
+                .clone()
+                .or_else(|| self.defaults.since.clone()),
+        }
+    }
+}
+
+/// Settings for a single feed.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeedSettings {
+    /// What to use as the body of each entry, defaults to the full content
+    pub content: Option<FeedContent>,
+    /// The maximum number of entries, newest first
+    pub limit: Option<usize>,
+    /// Leave out entries dated before this date
+    pub since: Option<String>,
+}
+
+/// The body of feed entries.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedContent {
+    /// The full rendered page content
+    #[default]
+    Full,
+    /// The `summary` frontmatter field
+    Summary,
+    /// The `description` frontmatter field
+    Description,
+}
+
+/// Settings for checking the spelling of pages.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpellcheckConfig {
+    /// The spellchecker, which reads text on stdin and writes each word it
+    /// doesn't know on its own line, defaults to `aspell list --lang=en`
+    pub command: Vec<String>,
+    /// A file of correctly spelled words, one per line and relative to the
+    /// input directory, like the names of projects
+    pub dictionary: Option<PathBuf>,
+}
+
+impl Default for SpellcheckConfig {
+    fn default() -> Self {
+        Self {
+            command: ["aspell", "list", "--lang=en"].map(String::from).to_vec(),
+            dictionary: None,
+        }
+    }
+}
+
+/// Settings for the sitemap.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SitemapConfig {
+    /// Write a separate sitemap for each top-level content directory, like
+    /// `sitemap-section-blog.xml`, and one for the pages at the root, listed by
+    /// a sitemap index, so that search console reports can be narrowed down to
+    /// a section. Sitemaps are also split when they would go over the limits
+    /// of the protocol
+    pub split_sections: bool,
+}
+
+/// Settings for galleries, whose thumbnails are generated with ImageMagick.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GalleryConfig {
+    /// The largest width and height of a thumbnail in pixels, defaults to 480
+    pub thumbnail_size: u32,
+}
+
+impl Default for GalleryConfig {
+    fn default() -> Self {
+        Self {
+            thumbnail_size: 480,
+        }
+    }
+}
+
+/// Membership of a webring, whose manifest is fetched at build time.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebringConfig {
+    /// URL of the JSON list of members, either as URLs or as objects with a
+    /// `url` and an optional `name`
+    pub manifest: String,
+    /// The URL the site is listed under, defaults to `base_url`
+    #[serde(default)]
+    pub url: Option<String>,
+    /// URL of the page of the ring, passed through to templates
+    #[serde(default)]
+    pub home: Option<String>,
+}
+
+/// How email addresses are hidden in the HTML of pages.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailObfuscation {
+    /// Every character is written as a numeric character reference, which
+    /// browsers show as normal
+    Entities,
+    /// Addresses are rotated by 13 letters and decoded by a script when the
+    /// page loads, so they are missing without JavaScript
+    Javascript,
+}
+
+/// Settings for password-protected pages.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProtectedPagesConfig {
+    /// The password every protected page is encrypted with, usually read from
+    /// the environment like `${PAGE_PASSWORD}`
+    pub password: String,
+    /// Content paths, or directories of content, that are protected
+    #[serde(default)]
+    pub pages: Vec<PathBuf>,
+    /// Iterations of PBKDF2 when deriving the key from the password, defaults
+    /// to 600000
+    #[serde(default = "default_protect_iterations")]
+    pub iterations: u32,
+}
+
+impl ProtectedPagesConfig {
+    /// Whether the content path is listed in `pages`.
+    pub fn lists(&self, content_path: &Path) -> bool {
+        self.pages
+            .iter()
+            .any(|protected| content_path.starts_with(protected))
+    }
+}
+
+fn default_protect_iterations() -> u32 {
+    600_000
+}
+
+/// Settings for inlining small assets.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InlineAssetsConfig {
+    /// Assets smaller than this many bytes are inlined, defaults to 2048
+    #[serde(default = "default_inline_max_size")]
+    pub max_size: u64,
+}
+
+fn default_inline_max_size() -> u64 {
+    2048
+}
+
+/// A limit on the size of the output files with an extension.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SizeBudgetConfig {
+    /// The extension of the files the budget applies to, like `html`, or every
+    /// file when missing
+    #[serde(default)]
+    pub extension: Option<String>,
+    /// The largest size in bytes of any single file
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+    /// The largest size in bytes of all the files together
+    #[serde(default)]
+    pub max_total_size: Option<u64>,
+}
+
+/// Settings for pruning unused CSS.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PruneCssConfig {
+    /// URL paths of the stylesheets to prune, like `/style.css`, defaults to
+    /// every stylesheet. The pruned copy of `style.css` is `style.pruned.css`
+    pub stylesheets: Vec<String>,
+    /// Classes that are always kept, like ones only added by scripts
+    pub keep_classes: Vec<String>,
+    /// File to write the list of removed selectors to, outside of the output
+    pub report: Option<PathBuf>,
+}
+
+/// The `Cache-Control` values recommended for each kind of output file.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheControlConfig {
+    /// Value for HTML pages, which should be revalidated soon after a deploy
+    pub html: String,
+    /// Value for files with a content hash in their name, like
+    /// `style.3f2a9c1b.css`, which never change
+    pub immutable: String,
+    /// Value for every other file
+    pub default: String,
+    /// Whether the values are written as `json` or as a `headers` file,
+    /// defaults to `json`
+    pub format: CacheControlFormat,
+}
+
+impl Default for CacheControlConfig {
+    fn default() -> Self {
+        Self {
+            html: "public, max-age=300".into(),
+            immutable: "public, max-age=31536000, immutable".into(),
+            default: "public, max-age=3600".into(),
+            format: CacheControlFormat::default(),
+        }
+    }
+}
+
+/// The format the cache control values are written in.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheControlFormat {
+    /// `cache-control.json`, an object keyed by URL path
+    #[default]
+    Json,
+    /// A `_headers` file, as read by Netlify and Cloudflare Pages
+    Headers,
+}
+
+/// A page that is rendered from a template alone.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeneratedPageConfig {
+    /// Path of the template, relative to the template directory
+    pub template: PathBuf,
+    pub title: Option<String>,
+    /// Arbitrary values passed to the template as `page`
+    #[serde(default)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// How the files in a content directory, and its subdirectories, are built.
+///
+/// When directories are nested, each field is taken from the innermost
+/// directory that sets it.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DirectoryRules {
+    /// Leave the files out of the build entirely
+    pub exclude: Option<bool>,
+    /// Copy the files to the output as they are, without rendering them
+    pub copy_only: Option<bool>,
+    /// Directory within `templates/` that templates for these pages are looked
+    /// up in, in place of the template directory itself
+    pub template_root: Option<PathBuf>,
+    /// Directory of the output that these files are written to, in place of
+    /// the directory the rules are declared for
+    pub output_prefix: Option<PathBuf>,
+}
+
+/// The transforms that a content file is put through.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PipelineConfig {
+    /// Transforms applied in order. An empty list copies the file as is
+    pub steps: Vec<PipelineStep>,
+    /// Extension of the output file, defaults to the extension of the input
+    pub output_extension: Option<String>,
+}
+
+/// A single transform in a pipeline.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PipelineStep {
+    /// Render djot to HTML, extracting the frontmatter
+    Djot,
+    /// Render a Jupyter notebook to HTML
+    Notebook,
+    /// Wrap plain text in a `<pre>` element
+    Text,
+    /// Pipe the content through an external command, given as the program
+    /// followed by its arguments, and use what it writes to stdout
+    Command(Vec<String>),
+    /// Render the result with the matching template, which must be the last
+    /// step
+    Template,
+}
+
+impl PipelineConfig {
+    fn validate(&self) -> anyhow::Result<()> {
+        if let Some(idx) = self
+            .steps
+            .iter()
+            .position(|step| matches!(step, PipelineStep::Template))
+            && idx + 1 != self.steps.len()
+        {
+            bail!("the `template` step must be the last step");
+        }
+        if self
+            .steps
+            .iter()
+            .any(|step| matches!(step, PipelineStep::Command(args) if args.is_empty()))
+        {
+            bail!("a `command` step must name the program to run");
+        }
+        Ok(())
+    }
+}
+
+/// Configuration for a podcast RSS feed generated from a single section.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PodcastConfig {
+    /// The content directory containing the episode pages, e.g. `podcast`
+    pub section: PathBuf,
+    /// Title of the podcast, defaults to the site title
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// URL path or absolute URL of the cover artwork
+    pub image: Option<String>,
+    pub language: Option<String>,
+    /// The iTunes category, e.g. `Technology`
+    pub category: Option<String>,
+    #[serde(default)]
+    pub explicit: bool,
+    pub owner_name: Option<String>,
+    pub owner_email: Option<String>,
+}
+
+impl SiteConfig {
+    pub fn load(input_root: &Path) -> anyhow::Result<Self> {
+        let config_path = input_root.join(CONFIG_FILENAME);
+        let config_content = match fs::read_to_string(&config_path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                debug!(config_path = %config_path.display(), "No site config found, using defaults");
+                // An empty object gets the same defaults as an empty config file
+                "{}".into()
+            },
+            Err(err) => {
+                return Err(err).context(format!(
+                    "failed to read site config [{}]",
+                    config_path.display()
+                ));
+            },
+        };
+
+        let mut config_value: serde_json::Value = serde_json::from_str(&config_content).context(
+            format!("failed to parse site config [{}]", config_path.display()),
+        )?;
+        interpolate_env(&mut config_value).context("failed to expand environment variables")?;
+        let config: Self = serde_json::from_value(config_value).context(format!(
+            "failed to parse site config [{}]",
+            config_path.display()
+        ))?;
+
+        for (extension, pipeline) in &config.pipelines {
+            pipeline
+                .validate()
+                .context(format!("invalid pipeline for [.{extension}] files"))?;
+        }
+
+        debug!(?config, "Loaded site config");
+
+        Ok(config)
+    }
+}
+
+/// Replace every `${NAME}` or `${NAME:-default}` in the string values of the
+/// config with the value of the environment variable `NAME`.
+fn interpolate_env(value: &mut serde_json::Value) -> anyhow::Result<()> {
+    match value {
+        serde_json::Value::String(text) => *text = expand_env_vars(text)?,
+        serde_json::Value::Array(values) => {
+            for value in values {
+                interpolate_env(value)?;
+            }
+        },
+        serde_json::Value::Object(map) => {
+            for value in map.values_mut() {
+                interpolate_env(value)?;
+            }
+        },
+        serde_json::Value::Null | serde_json::Value::Bool(_) | serde_json::Value::Number(_) => {},
+    }
+
+    Ok(())
+}
+
+fn expand_env_vars(text: &str) -> anyhow::Result<String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            bail!("unterminated environment variable reference in [{text}]");
+        };
+        let reference = &rest[(start + 2)..(start + len)];
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+
+        match (env::var(name), default) {
+            (Ok(value), _) => expanded.push_str(&value),
+            (Err(env::VarError::NotPresent), Some(default)) => expanded.push_str(default),
+            (Err(err), _) => {
+                return Err(err).context(format!("failed to read environment variable [{name}]"));
+            },
+        }
+
+        rest = &rest[(start + len + 1)..];
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
@@ -0,0 +1,114 @@
+use std::{collections::BTreeMap, fs, path::Path, sync::LazyLock};
+
+use anyhow::Context;
+use regex::{Captures, Regex};
+use tracing::debug;
+
+use crate::build::{
+    BuildDirFiles, css,
+    feed::escape_xml,
+    inline::{LINK_TAG, attribute},
+    links::resolve_internal,
+    manifest::url_path,
+};
+
+/// Matches a `url()` reference in a stylesheet
+static CSS_URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"url\(\s*(["']?)([^"')]+)(["']?)\s*\)"#).unwrap());
+
+/// Rewrite relative `url()` references to absolute URL paths, so that they
+/// still resolve once the rules are moved out of the stylesheet.
+fn absolute_urls(css: &str, stylesheet_url: &str) -> String {
+    CSS_URL
+        .replace_all(css, |captures: &Captures| {
+            let dest = &captures[2];
+            if dest.starts_with('/') || dest.contains(['?', '#']) {
+                return captures[0].to_owned();
+            }
+            match resolve_internal(Path::new(stylesheet_url), dest) {
+                Some(url) => format!("url({}{url}{})", &captures[1], &captures[3]),
+                None => captures[0].to_owned(),
+            }
+        })
+        .into_owned()
+}
+
+/// Inline the rules of every local stylesheet that a page uses, and load the
+/// full stylesheets without blocking the first paint.
+///
+/// There is no layout engine to find what is above the fold, so the rules
+/// kept are the ones whose selectors match an element, class, or ID on the
+/// page. The full stylesheet is preloaded and applied once it loads, with a
+/// `<noscript>` fallback.
+#[tracing::instrument(skip_all)]
+pub fn inline_critical_css(output_root: &Path) -> anyhow::Result<()> {
+    let output_files =
+        BuildDirFiles::gather(output_root).context("failed to collect output files")?;
+
+    // Stylesheets with comments removed and relative URLs made absolute, keyed by
+    // URL path
+    let mut stylesheets: BTreeMap<String, Option<String>> = BTreeMap::new();
+    let mut num_pages = 0;
+    for (path, file) in output_files.files {
+        if path.extension().is_none_or(|ext| ext != "html") {
+            continue;
+        }
+
+        let html = fs::read_to_string(&file.full_path).context(format!(
+            "failed to read output file [{}]",
+            file.full_path.display()
+        ))?;
+        let page_url = url_path(&path);
+        let mut used = css::UsedNames::default();
+        used.add_document(&html);
+
+        let replaced = LINK_TAG.replace_all(&html, |captures: &Captures| {
+            let tag = &captures[0];
+            let is_stylesheet = attribute(tag, "rel").is_some_and(|rel| {
+                rel.split_whitespace()
+                    .any(|rel| rel.eq_ignore_ascii_case("stylesheet"))
+            });
+            let Some(href) = attribute(tag, "href").filter(|_| is_stylesheet) else {
+                return tag.to_owned();
+            };
+            let Some(stylesheet_url) = resolve_internal(Path::new(&page_url), &href) else {
+                return tag.to_owned();
+            };
+            let stylesheet = stylesheets.entry(stylesheet_url.clone()).or_insert_with(|| {
+                let css = fs::read_to_string(output_root.join(stylesheet_url.trim_start_matches('/')))
+                    .ok()?;
+                Some(absolute_urls(&css::strip_comments(&css), &stylesheet_url))
+            });
+            let Some(stylesheet) = stylesheet else {
+                return tag.to_owned();
+            };
+
+            // A `@charset` rule is only valid at the start of a stylesheet file
+            let mut rules = css::parse(stylesheet);
+            rules.retain(|rule| !matches!(rule, css::Rule::Other(text) if text.starts_with("@charset")));
+            let critical = css::retain_selectors(&rules, &|selector| used.matches(selector), &mut vec![]);
+            debug!(page_url, %href, critical_len = critical.len(), "Inlining critical CSS");
+
+            let href = escape_xml(&href);
+            let media = attribute(tag, "media")
+                .map(|media| format!(r#" media="{}""#, escape_xml(&media)))
+                .unwrap_or_default();
+            format!(
+                "<style{media}>{critical}</style><link rel=\"preload\" href=\"{href}\" as=\"style\" \
+                 onload=\"this.onload=null;this.rel='stylesheet'\"><noscript>{tag}</noscript>"
+            )
+        });
+
+        if replaced != html {
+            fs::write(&file.full_path, replaced.as_ref()).context(format!(
+                "failed to write output file [{}]",
+                file.full_path.display()
+            ))?;
+            num_pages += 1;
+        }
+    }
+
+    debug!(num_pages, "Inlined critical CSS");
+
+    Ok(())
+}
@@ -0,0 +1,266 @@
+use std::{collections::BTreeSet, sync::LazyLock};
+
+use regex::Regex;
+
+/// Matches an opening tag and its attributes
+static OPEN_TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<([a-zA-Z][a-zA-Z0-9-]*)([^>]*)>").unwrap());
+
+/// Matches the value of a `class` or `id` attribute
+static CLASS_OR_ID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\b(class|id)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap()
+});
+
+/// Matches a comment
+static COMMENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)/\*.*?\*/").unwrap());
+
+/// At-rules whose body is a list of rules, rather than declarations
+const GROUPING_AT_RULES: &[&str] = &["@media", "@supports", "@layer", "@container", "@document"];
+
+/// A top-level rule of a stylesheet.
+#[derive(Debug)]
+pub enum Rule<'a> {
+    /// A style rule, with its selector list and declarations
+    Style { selectors: &'a str, body: &'a str },
+    /// A grouping at-rule like `@media`, which contains other rules
+    Group {
+        prelude: &'a str,
+        rules: Vec<Rule<'a>>,
+    },
+    /// Any other at-rule, like `@font-face` or `@keyframes`, which is always
+    /// kept as is
+    Other(&'a str),
+}
+
+/// Remove every comment from a stylesheet, so that it can be parsed.
+pub fn strip_comments(css: &str) -> String {
+    COMMENT.replace_all(css, "").into_owned()
+}
+
+/// Find the index of the `}` matching the `{` at `open`, skipping over strings.
+fn matching_brace(css: &str, open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut escaped = false;
+    for (idx, c) in css[open..].char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (c, quote) {
+            ('\\', _) => escaped = true,
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (_, Some(_)) => {},
+            ('{', None) => depth += 1,
+            ('}', None) => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + idx);
+                }
+            },
+            _ => {},
+        }
+    }
+    None
+}
+
+/// Parse a stylesheet, with comments already removed, into its rules.
+///
+/// Anything after a block that is never closed is dropped, the same as a
+/// browser would.
+pub fn parse(css: &str) -> Vec<Rule<'_>> {
+    let mut rules = vec![];
+    let mut rest = css.trim_start();
+    while let Some(idx) = rest.find(['{', ';']) {
+        if rest[idx..].starts_with(';') {
+            // Statements like `@import` and `@charset`
+            rules.push(Rule::Other(&rest[..=idx]));
+            rest = rest[(idx + 1)..].trim_start();
+            continue;
+        }
+
+        let Some(end) = matching_brace(rest, idx) else {
+            break;
+        };
+        let prelude = rest[..idx].trim();
+        let body = &rest[(idx + 1)..end];
+        rules.push(if !prelude.starts_with('@') {
+            Rule::Style {
+                selectors: prelude,
+                body,
+            }
+        } else if GROUPING_AT_RULES
+            .iter()
+            .any(|at_rule| prelude.starts_with(at_rule))
+        {
+            Rule::Group {
+                prelude,
+                rules: parse(body),
+            }
+        } else {
+            Rule::Other(&rest[..=end])
+        });
+        rest = rest[(end + 1)..].trim_start();
+    }
+
+    rules
+}
+
+/// Split a selector list on the commas that aren't nested in parentheses, like
+/// the ones in `:is(a, b)`.
+pub fn split_selectors(selectors: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (idx, c) in selectors.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(selectors[start..idx].trim());
+                start = idx + 1;
+            },
+            _ => {},
+        }
+    }
+    parts.push(selectors[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// The element names, classes, and IDs used in a set of HTML documents.
+#[derive(Debug, Default)]
+pub struct UsedNames {
+    elements: BTreeSet<String>,
+    classes: BTreeSet<String>,
+    ids: BTreeSet<String>,
+}
+
+impl UsedNames {
+    /// Add every name used in the HTML document.
+    pub fn add_document(&mut self, html: &str) {
+        for tag in OPEN_TAG.captures_iter(html) {
+            self.elements.insert(tag[1].to_ascii_lowercase());
+            for attr in CLASS_OR_ID.captures_iter(&tag[2]) {
+                let value = attr
+                    .get(2)
+                    .or_else(|| attr.get(3))
+                    .or_else(|| attr.get(4))
+                    .map_or("", |value| value.as_str());
+                let names = if attr[1].eq_ignore_ascii_case("class") {
+                    &mut self.classes
+                } else {
+                    &mut self.ids
+                };
+                names.extend(value.split_whitespace().map(str::to_owned));
+            }
+        }
+    }
+
+    /// Add a class name that is used even though it doesn't appear in any
+    /// document, like one added by a script.
+    pub fn add_class(&mut self, class: &str) {
+        self.classes.insert(class.to_owned());
+    }
+
+    /// Whether a single selector could match an element in the documents.
+    ///
+    /// This is conservative: only element names, classes, and IDs are checked,
+    /// so pseudo-classes and attribute selectors are assumed to match.
+    pub fn matches(&self, selector: &str) -> bool {
+        let mut chars = selector.chars().peekable();
+        let mut name = String::new();
+        // The kind of name being read, `None` for an element name
+        let mut kind: Option<char> = None;
+        let mut depth = 0usize;
+
+        let check = |kind: Option<char>, name: &str| {
+            if name.is_empty() {
+                return true;
+            }
+            match kind {
+                None => name == "*" || self.elements.contains(&name.to_ascii_lowercase()),
+                Some('.') => self.classes.contains(name),
+                Some('#') => self.ids.contains(name),
+                // Pseudo-classes and pseudo-elements
+                Some(_) => true,
+            }
+        };
+
+        while let Some(c) = chars.next() {
+            if depth > 0 {
+                match c {
+                    '(' | '[' => depth += 1,
+                    ')' | ']' => depth -= 1,
+                    _ => {},
+                }
+                continue;
+            }
+
+            match c {
+                '\\' => {
+                    if let Some(escaped) = chars.next() {
+                        name.push(escaped);
+                    }
+                },
+                '.' | '#' | ':' | '[' | '(' | ' ' | '>' | '+' | '~' => {
+                    if !check(kind, &name) {
+                        return false;
+                    }
+                    name.clear();
+                    kind = match c {
+                        '.' | '#' | ':' => Some(c),
+                        _ => None,
+                    };
+                    if c == ':' && chars.peek() == Some(&':') {
+                        chars.next();
+                    }
+                    if c == '[' || c == '(' {
+                        depth += 1;
+                        kind = Some(c);
+                    }
+                },
+                c => name.push(c),
+            }
+        }
+
+        check(kind, &name)
+    }
+}
+
+/// Write out the rules, keeping only the selectors that `keep` accepts.
+///
+/// Style rules with no selectors left are dropped, as are grouping rules with
+/// no rules left. Every dropped selector is added to `removed`.
+pub fn retain_selectors(
+    rules: &[Rule],
+    keep: &impl Fn(&str) -> bool,
+    removed: &mut Vec<String>,
+) -> String {
+    let mut css = String::new();
+    for rule in rules {
+        match rule {
+            Rule::Style { selectors, body } => {
+                let (kept, dropped): (Vec<_>, Vec<_>) = split_selectors(selectors)
+                    .into_iter()
+                    .partition(|selector| keep(selector));
+                removed.extend(dropped.into_iter().map(str::to_owned));
+                if !kept.is_empty() {
+                    css.push_str(&format!("{}{{{}}}\n", kept.join(","), body.trim()));
+                }
+            },
+            Rule::Group { prelude, rules } => {
+                let inner = retain_selectors(rules, keep, removed);
+                if !inner.is_empty() {
+                    css.push_str(&format!("{prelude}{{\n{inner}}}\n"));
+                }
+            },
+            Rule::Other(text) => {
+                css.push_str(text);
+                css.push('\n');
+            },
+        }
+    }
+    css
+}
@@ -0,0 +1,369 @@
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+use anyhow::Context;
+use argh::{ArgsInfo, FromArgs};
+use tracing::{debug, info};
+
+use crate::build::{
+    cache::cache_control_for,
+    config::{CacheControlConfig, SiteConfig},
+    errors::FailureClass,
+    manifest::OutputManifest,
+};
+
+pub mod github_pages;
+
+/// The key prefix of the objects that record the hash of every deployed file,
+/// one per deploy prefix, kept outside of the prefixes that are served
+const DEPLOY_MANIFEST_DIR: &str = ".www/deploy-manifests";
+
+/// The key that the deploy manifest used to be kept at, relative to the deploy
+/// prefix, where it was served along with the site
+const LEGACY_MANIFEST_KEY: &str = ".deploy-manifest.json";
+
+/// CloudFront charges per invalidation path, so past this many changed pages
+/// the whole distribution is invalidated instead
+const MAX_INVALIDATION_PATHS: usize = 100;
+
+/// Publish a built site.
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(subcommand, name = "deploy")]
+pub struct DeployCmd {
+    #[argh(subcommand)]
+    target: DeployTarget,
+}
+
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(subcommand)]
+enum DeployTarget {
+    S3(S3Cmd),
+    GitHubPages(github_pages::GitHubPagesCmd),
+}
+
+/// Upload the changed files of a built site to an S3 bucket, using the `aws`
+/// CLI.
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(subcommand, name = "s3")]
+struct S3Cmd {
+    /// path to the input directory, for the site config
+    #[argh(positional)]
+    input_path: PathBuf,
+
+    /// path to the built output directory
+    #[argh(positional)]
+    output_path: PathBuf,
+
+    /// name of the bucket to upload to
+    #[argh(option)]
+    bucket: String,
+
+    /// key prefix to upload under, defaults to the bucket root
+    #[argh(option)]
+    prefix: Option<String>,
+
+    /// S3 URI of the object that records the hashes of the deployed files,
+    /// which shouldn't be public. Defaults to a key under `.www/` in the
+    /// bucket, outside of the prefix, and is required without a prefix
+    #[argh(option)]
+    manifest_uri: Option<String>,
+
+    /// ID of a CloudFront distribution to invalidate the changed pages of
+    #[argh(option)]
+    distribution_id: Option<String>,
+
+    /// delete files from the bucket that are no longer in the output
+    #[argh(switch)]
+    delete: bool,
+
+    /// print what would be uploaded without changing anything
+    #[argh(switch)]
+    dry_run: bool,
+}
+
+pub fn deploy(cmd: DeployCmd) -> anyhow::Result<()> {
+    match cmd.target {
+        DeployTarget::S3(cmd) => deploy_s3(&cmd),
+        DeployTarget::GitHubPages(cmd) => github_pages::deploy_github_pages(&cmd),
+    }
+}
+
+/// The MIME type a file is served with, by extension.
+pub fn content_type(url: &str) -> &'static str {
+    let extension = Path::new(url)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "html" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "xml" => "application/xml",
+        "xsl" => "text/xsl",
+        "txt" => "text/plain; charset=utf-8",
+        "ics" => "text/calendar; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "pdf" => "application/pdf",
+        "epub" => "application/epub+zip",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "wasm" => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Run the `aws` CLI, optionally writing `stdin` to it, and return its output
+/// whether it succeeded or not.
+fn run_aws(args: &[&str], stdin: Option<&str>) -> anyhow::Result<Output> {
+    debug!(?args, "Running aws");
+    let mut child = Command::new("aws")
+        .args(args)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| FailureClass::Tool.wrap("failed to execute 'aws'", err))?;
+    if let (Some(input), Some(mut child_stdin)) = (stdin, child.stdin.take()) {
+        child_stdin
+            .write_all(input.as_bytes())
+            .context("failed to write to 'aws'")?;
+    }
+
+    child.wait_with_output().context("failed to wait for 'aws'")
+}
+
+/// Run the `aws` CLI, optionally writing `stdin` to it, and return its stdout.
+fn aws(args: &[&str], stdin: Option<&str>) -> anyhow::Result<String> {
+    let output = run_aws(args, stdin)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(FailureClass::Tool.error(format!(
+            "Execution of 'aws {}' returned an unsuccessful status code: {}",
+            args.first().copied().unwrap_or_default(),
+            stderr.trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Whether an object exists, found by listing it. `aws s3 ls` lists every
+/// object whose key starts with the given one, and exits with 1 without any
+/// output when there is none.
+fn object_exists(uri: &str) -> anyhow::Result<bool> {
+    let output = run_aws(&["s3", "ls", uri], None)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let file_name = uri.rsplit('/').next().unwrap_or_default();
+    match output.status.code() {
+        Some(0) => Ok(stdout
+            .lines()
+            .any(|line| line.split_whitespace().last() == Some(file_name))),
+        Some(1) if stdout.trim().is_empty() && stderr.trim().is_empty() => Ok(false),
+        _ => Err(FailureClass::Tool.error(format!(
+            "Execution of 'aws s3 ls' returned an unsuccessful status code: {}",
+            stderr.trim()
+        ))),
+    }
+}
+
+/// Fetch the hashes of the files in the last deploy, which are empty on the
+/// first deploy.
+fn fetch_deployed_hashes(manifest_uri: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    if !object_exists(manifest_uri).context("failed to look up deploy manifest")? {
+        debug!("No deploy manifest found, uploading every file");
+        return Ok(BTreeMap::new());
+    }
+    let content =
+        aws(&["s3", "cp", manifest_uri, "-"], None).context("failed to fetch deploy manifest")?;
+    serde_json::from_str(&content).context("failed to parse deploy manifest")
+}
+
+/// The paths to invalidate for a changed file, including the directory path
+/// that an index page is requested by.
+fn invalidation_paths(url: &str) -> Vec<String> {
+    match url.strip_suffix("index.html") {
+        Some(dir) => vec![dir.to_owned(), url.to_owned()],
+        None => vec![url.to_owned()],
+    }
+}
+
+fn deploy_s3(cmd: &S3Cmd) -> anyhow::Result<()> {
+    let config = SiteConfig::load(&cmd.input_path).context("failed to load site config")?;
+    let cache_control = config
+        .cache_control
+        .unwrap_or_else(CacheControlConfig::default);
+
+    let manifest =
+        OutputManifest::gather(&cmd.output_path).context("failed to gather output manifest")?;
+    let prefix = cmd
+        .prefix
+        .as_deref()
+        .map(|prefix| format!("{}/", prefix.trim_matches('/')))
+        .unwrap_or_default();
+    let object_uri = |url: &str| {
+        format!(
+            "s3://{}/{prefix}{}",
+            cmd.bucket,
+            url.trim_start_matches('/')
+        )
+    };
+    // Everything under the prefix is served, so the manifest is kept outside
+    // of it
+    let manifest_uri = match (&cmd.manifest_uri, prefix.strip_suffix('/')) {
+        (Some(uri), _) => uri.clone(),
+        (None, Some(prefix)) => format!("s3://{}/{DEPLOY_MANIFEST_DIR}/{prefix}.json", cmd.bucket),
+        (None, None) => {
+            return Err(FailureClass::Config.error(
+                "deploying to the bucket root needs `--manifest-uri`, so that the deploy \
+                 manifest isn't served with the site",
+            ));
+        },
+    };
+    let legacy_manifest_uri = object_uri(LEGACY_MANIFEST_KEY);
+
+    let mut deployed = fetch_deployed_hashes(&manifest_uri)?;
+    let migrating = deployed.is_empty()
+        && object_exists(&legacy_manifest_uri).context("failed to look up deploy manifest")?;
+    if migrating {
+        info!("Moving the deploy manifest out of the served files");
+        deployed = fetch_deployed_hashes(&legacy_manifest_uri)?;
+    }
+    let changed = manifest
+        .files
+        .iter()
+        .filter(|(url, entry)| deployed.get(*url) != Some(&entry.hash))
+        .map(|(url, _)| url.as_str())
+        .collect::<Vec<_>>();
+    let removed = deployed
+        .keys()
+        .filter(|url| !manifest.files.contains_key(*url))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+
+    for url in &changed {
+        let file_path = cmd.output_path.join(url.trim_start_matches('/'));
+        let file_path = file_path.to_string_lossy();
+        let uri = object_uri(url);
+        info!(url, "Uploading changed file");
+        if !cmd.dry_run {
+            aws(
+                &[
+                    "s3",
+                    "cp",
+                    &file_path,
+                    &uri,
+                    "--content-type",
+                    content_type(url),
+                    "--cache-control",
+                    cache_control_for(&cache_control, url),
+                ],
+                None,
+            )
+            .context(format!("failed to upload [{url}]"))?;
+        }
+    }
+
+    if cmd.delete {
+        for url in &removed {
+            info!(url, "Deleting removed file");
+            if !cmd.dry_run {
+                aws(&["s3", "rm", &object_uri(url)], None)
+                    .context(format!("failed to delete [{url}]"))?;
+            }
+        }
+    }
+
+    let mut hashes = manifest
+        .files
+        .iter()
+        .map(|(url, entry)| (url.clone(), entry.hash.clone()))
+        .collect::<BTreeMap<_, _>>();
+    if !cmd.delete {
+        // Files that are still in the bucket stay in the manifest, so a later
+        // `--delete` can find them
+        for url in &removed {
+            hashes.insert((*url).to_owned(), deployed[*url].clone());
+        }
+    }
+    if !cmd.dry_run {
+        let content =
+            serde_json::to_string_pretty(&hashes).context("failed to serialize deploy manifest")?;
+        aws(
+            &[
+                "s3",
+                "cp",
+                "-",
+                &manifest_uri,
+                "--content-type",
+                "application/json",
+            ],
+            Some(&content),
+        )
+        .context("failed to upload deploy manifest")?;
+        if migrating {
+            aws(&["s3", "rm", &legacy_manifest_uri], None)
+                .context("failed to delete old deploy manifest")?;
+        }
+    }
+
+    if let Some(distribution_id) = &cmd.distribution_id {
+        let removed = if cmd.delete { removed.as_slice() } else { &[] };
+        let mut paths = changed
+            .iter()
+            .chain(removed)
+            .filter(|url| url.ends_with(".html"))
+            .flat_map(|url| invalidation_paths(url))
+            .collect::<Vec<_>>();
+        if paths.len() > MAX_INVALIDATION_PATHS {
+            paths = vec!["/*".to_owned()];
+        }
+
+        if paths.is_empty() {
+            debug!("No pages changed, skipping invalidation");
+        } else {
+            info!(?paths, "Invalidating changed pages");
+            if !cmd.dry_run {
+                let mut args = vec![
+                    "cloudfront",
+                    "create-invalidation",
+                    "--distribution-id",
+                    distribution_id,
+                    "--paths",
+                ];
+                args.extend(paths.iter().map(String::as_str));
+                aws(&args, None).context("failed to create CloudFront invalidation")?;
+            }
+        }
+    }
+
+    info!(
+        num_changed = changed.len(),
+        num_removed = removed.len(),
+        "Deployed to S3"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+use anyhow::{Context, bail};
+use argh::{ArgsInfo, FromArgs};
+use tracing::{debug, info};
+
+use crate::build::{errors::FailureClass, info::BuildInfo};
+
+/// The prefix of the name of the temporary index file used while staging,
+/// followed by the process ID
+pub const INDEX_PREFIX: &str = "www-github-pages-";
+
+/// The file that stops GitHub Pages from running Jekyll on the output, which
+/// would drop directories starting with `_`
+const NOJEKYLL_FILENAME: &str = ".nojekyll";
+
+/// Commit a built site to a branch and push it, for GitHub Pages to serve.
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(subcommand, name = "github-pages")]
+pub struct GitHubPagesCmd {
+    /// path to the input directory, whose git repository the commit is made in
+    #[argh(positional)]
+    input_path: PathBuf,
+
+    /// path to the built output directory
+    #[argh(positional)]
+    output_path: PathBuf,
+
+    /// the branch to publish to, defaults to `gh-pages`
+    #[argh(option, default = "String::from(\"gh-pages\")")]
+    branch: String,
+
+    /// the remote name or repository URL to push to, defaults to `origin`
+    #[argh(option, default = "String::from(\"origin\")")]
+    remote: String,
+
+    /// make the commit on the local branch without pushing it
+    #[argh(switch)]
+    no_push: bool,
+}
+
+/// Run git in the repository of the input directory and return its trimmed
+/// stdout.
+fn git(
+    input_path: &Path,
+    args: &[&str],
+    configure: impl FnOnce(&mut Command),
+) -> anyhow::Result<String> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(input_path).args(args);
+    configure(&mut command);
+    debug!(?args, "Running git");
+
+    let output = command
+        .output()
+        .map_err(|err| FailureClass::Tool.wrap("failed to execute 'git'", err))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(FailureClass::Tool.error(format!(
+            "Execution of 'git {}' returned an unsuccessful status code: {}",
+            args.first().copied().unwrap_or_default(),
+            stderr.trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Commit the output directory on top of the publish branch, without touching
+/// the checked out branch or its index, then push it.
+///
+/// The commit message names the source commit the site was built from.
+pub fn deploy_github_pages(cmd: &GitHubPagesCmd) -> anyhow::Result<()> {
+    // Both are absolute, since staging runs git from the output directory
+    let input_path = &cmd
+        .input_path
+        .canonicalize()
+        .context("failed to find input directory")?;
+    let output_path = cmd
+        .output_path
+        .canonicalize()
+        .context("failed to find output directory")?;
+    let build_info = BuildInfo::collect(input_path);
+    let Some(source_commit) = &build_info.commit else {
+        bail!("input directory must be in a git repository to publish to GitHub Pages");
+    };
+
+    fs::write(output_path.join(NOJEKYLL_FILENAME), "").context("failed to write .nojekyll file")?;
+
+    // The current tip of the branch, if it has been published before. Without
+    // the remote branch, the local one is built on, so that its history is kept
+    let branch_ref = format!("refs/heads/{}", cmd.branch);
+    let parent = match git(input_path, &["fetch", &cmd.remote, &cmd.branch], |_| {}) {
+        Ok(_) => Some(git(input_path, &["rev-parse", "FETCH_HEAD"], |_| {})?),
+        Err(err) => {
+            let local = git(
+                input_path,
+                &["rev-parse", "--verify", "--quiet", &branch_ref],
+                |_| {},
+            )
+            .ok();
+            if local.is_some() {
+                debug!(%err, "Could not fetch the publish branch, building on the local branch");
+            } else {
+                debug!(%err, "Could not fetch the publish branch, assuming a first publish");
+            }
+            local
+        },
+    };
+
+    // Stage the output in a separate index, with the output as the work tree
+    let git_dir = git(input_path, &["rev-parse", "--absolute-git-dir"], |_| {})?;
+    let index_path = env::temp_dir().join(format!("{INDEX_PREFIX}{}.index", process::id()));
+    let with_output_tree = |command: &mut Command| {
+        command
+            .env("GIT_INDEX_FILE", &index_path)
+            .env("GIT_DIR", &git_dir)
+            .env("GIT_WORK_TREE", &output_path);
+    };
+    let staged = git(input_path, &["add", "--all", "--force", "."], |command| {
+        with_output_tree(command);
+        command.current_dir(&output_path);
+    })
+    .and_then(|_| git(input_path, &["write-tree"], with_output_tree));
+    let _ = fs::remove_file(&index_path);
+    let tree = staged.context("failed to stage output directory")?;
+
+    if let Some(parent) = &parent {
+        let parent_tree = git(
+            input_path,
+            &["rev-parse", &format!("{parent}^{{tree}}")],
+            |_| {},
+        )?;
+        if parent_tree == tree {
+            info!(
+                branch = cmd.branch,
+                "Output is unchanged, nothing to publish"
+            );
+            return Ok(());
+        }
+    }
+
+    let dirty = if build_info.dirty == Some(true) {
+        " with uncommitted changes"
+    } else {
+        ""
+    };
+    let message = format!("Publish site from {source_commit}{dirty}");
+    let mut args = vec!["commit-tree", tree.as_str(), "-m", message.as_str()];
+    if let Some(parent) = &parent {
+        args.extend(["-p", parent.as_str()]);
+    }
+    let commit = git(input_path, &args, |_| {})?;
+    git(input_path, &["update-ref", &branch_ref, &commit], |_| {})?;
+    info!(branch = cmd.branch, commit, "Committed site output");
+
+    if cmd.no_push {
+        return Ok(());
+    }
+    git(
+        input_path,
+        &[
+            "push",
+            &cmd.remote,
+            &format!("{commit}:refs/heads/{}", cmd.branch),
+        ],
+        |_| {},
+    )
+    .context(format!("failed to push to [{}]", cmd.remote))?;
+    info!(
+        remote = cmd.remote,
+        branch = cmd.branch,
+        "Published to GitHub Pages"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use std::{fmt, path::PathBuf};
+
+use tracing::warn;
+
+use crate::build::warnings::BROKEN_LINK_TARGET;
+
+/// A position in a source file, along with the line it is on so that it can be
+/// shown without reading the file again.
+#[derive(Debug, Clone)]
+pub struct Position {
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Column in characters, starting at 1
+    pub column: usize,
+    source_line: String,
+    /// Number of characters marked, starting at the column
+    len: usize,
+}
+
+/// A problem found in a source file, like a content page or a template.
+///
+/// It is displayed like a compiler error, with the location of the problem and
+/// the offending line of source marked underneath. The position is optional,
+/// for problems that can only be attributed to a whole file.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub message: String,
+    pub position: Option<Position>,
+}
+
+impl Diagnostic {
+    pub fn new(path: impl Into<PathBuf>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+            position: None,
+        }
+    }
+
+    /// Point at `len` bytes of the source, starting at the given byte offset.
+    pub fn at_offset(mut self, source: &str, offset: usize, len: usize) -> Self {
+        let mut offset = offset.min(source.len());
+        while !source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line_start = source[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+        let line_end = source[offset..]
+            .find('\n')
+            .map_or(source.len(), |idx| offset + idx);
+        let source_line = source[line_start..line_end].trim_end_matches('\r');
+        let marked = source_line
+            .get((offset - line_start)..)
+            .unwrap_or_default()
+            .chars()
+            .scan(0, |num_bytes, c| {
+                *num_bytes += c.len_utf8();
+                Some(*num_bytes)
+            })
+            .take_while(|&num_bytes| num_bytes <= len)
+            .count();
+
+        self.position = Some(Position {
+            line: source[..offset].matches('\n').count() + 1,
+            column: source[line_start..offset].chars().count() + 1,
+            source_line: source_line.to_owned(),
+            len: marked.max(1),
+        });
+        self
+    }
+
+    /// Point at a line and byte column of the source, both starting at 1.
+    pub fn at_line(self, source: &str, line: usize, column: usize) -> Self {
+        let line_start = source
+            .split_inclusive('\n')
+            .take(line.saturating_sub(1))
+            .map(str::len)
+            .sum::<usize>();
+        self.at_offset(source, line_start + column.saturating_sub(1), 1)
+    }
+
+    /// Point at the first occurrence of `needle` in the source, if there is
+    /// one.
+    pub fn at_first(self, source: &str, needle: &str) -> Self {
+        match source.find(needle) {
+            Some(offset) if !needle.is_empty() => self.at_offset(source, offset, needle.len()),
+            _ => self,
+        }
+    }
+
+    /// The location of the problem as `path:line:column`, or just the path if
+    /// there's no position.
+    pub fn location(&self) -> String {
+        match &self.position {
+            Some(position) => format!(
+                "{}:{}:{}",
+                self.path.display(),
+                position.line,
+                position.column
+            ),
+            None => self.path.display().to_string(),
+        }
+    }
+
+    /// Log the diagnostic as a warning, with its location as fields so that
+    /// they are kept when warnings are collected.
+    pub fn warn(&self) {
+        warn!(
+            file = %self.path.display(),
+            line = self.position.as_ref().map(|position| position.line),
+            column = self.position.as_ref().map(|position| position.column),
+            "{self}"
+        );
+    }
+
+    /// Log the diagnostic as a warning about a broken link, like [`Self::warn`].
+    pub fn warn_broken_link(&self) {
+        warn!(
+            target: BROKEN_LINK_TARGET,
+            file = %self.path.display(),
+            line = self.position.as_ref().map(|position| position.line),
+            column = self.position.as_ref().map(|position| position.column),
+            "{self}"
+        );
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.message)?;
+        write!(f, "  --> {}", self.location())?;
+
+        let Some(position) = &self.position else {
+            return Ok(());
+        };
+        let gutter = " ".repeat(position.line.to_string().len());
+        // Tabs are kept in the padding so the marker lines up with the source
+        let padding = position
+            .source_line
+            .chars()
+            .take(position.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        writeln!(f)?;
+        writeln!(f, "{gutter} |")?;
+        writeln!(f, "{} | {}", position.line, position.source_line)?;
+        write!(f, "{gutter} | {padding}{}", "^".repeat(position.len))
+    }
+}
+
+impl std::error::Error for Diagnostic {}
@@ -0,0 +1,212 @@
+use std::{collections::BTreeSet, fs, path::PathBuf};
+
+use anyhow::Context;
+use argh::{ArgsInfo, FromArgs};
+use tracing::{debug, info};
+
+use crate::build::{BuildDirFiles, manifest::url_path};
+
+/// Compare two output directories, listing the files that were added, removed,
+/// or changed, with a line diff of each changed HTML page.
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(subcommand, name = "diff")]
+pub struct DiffCmd {
+    /// path to the old output directory
+    #[argh(positional)]
+    old_path: PathBuf,
+
+    /// path to the new output directory
+    #[argh(positional)]
+    new_path: PathBuf,
+}
+
+/// A single line of a diff.
+#[derive(Debug, PartialEq, Eq)]
+enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Split HTML into one line per tag or run of text, with surrounding
+/// whitespace removed, so that formatting and minification don't show up as
+/// changes.
+fn normalize_html(html: &str) -> Vec<&str> {
+    let mut lines = vec![];
+    let mut rest = html;
+    while !rest.is_empty() {
+        let end = if rest.starts_with('<') {
+            rest.find('>').map_or(rest.len(), |end| end + 1)
+        } else {
+            rest.find('<').unwrap_or(rest.len())
+        };
+        let (part, remainder) = rest.split_at(end);
+        lines.extend(part.lines().map(str::trim).filter(|line| !line.is_empty()));
+        rest = remainder;
+    }
+    lines
+}
+
+/// The most cells of the table of common subsequence lengths, which keeps a
+/// diff of two large, mostly different pages from taking minutes and using
+/// gigabytes of memory
+const MAX_TABLE_CELLS: usize = 4_000_000;
+
+/// Diff two lists of lines using their longest common subsequence.
+///
+/// The common prefix and suffix are skipped first, which keeps the table small
+/// for the usual case of a few changed lines. When the lines in between would
+/// still need a table larger than `MAX_TABLE_CELLS`, they are all shown as
+/// removed and then added instead.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffLine<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..(old.len() - suffix)];
+    let new_mid = &new[prefix..(new.len() - suffix)];
+    let mut diff = old[..prefix]
+        .iter()
+        .map(|line| DiffLine::Same(line))
+        .collect::<Vec<_>>();
+    let same_suffix = old[(old.len() - suffix)..]
+        .iter()
+        .map(|line| DiffLine::Same(line));
+
+    if (old_mid.len() + 1).saturating_mul(new_mid.len() + 1) > MAX_TABLE_CELLS {
+        debug!(
+            num_old = old_mid.len(),
+            num_new = new_mid.len(),
+            "Too many changed lines to diff, replacing them all"
+        );
+        diff.extend(old_mid.iter().map(|line| DiffLine::Removed(line)));
+        diff.extend(new_mid.iter().map(|line| DiffLine::Added(line)));
+        diff.extend(same_suffix);
+        return diff;
+    }
+
+    // lengths[i][j] is the length of the common subsequence of old_mid[i..] and
+    // new_mid[j..]
+    let width = new_mid.len() + 1;
+    let mut lengths = vec![0u32; (old_mid.len() + 1) * width];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lengths[i * width + j] = if old_mid[i] == new_mid[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_mid.len() || j < new_mid.len() {
+        if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+            diff.push(DiffLine::Same(old_mid[i]));
+            i += 1;
+            j += 1;
+        } else if j < new_mid.len()
+            && (i == old_mid.len() || lengths[i * width + j + 1] > lengths[(i + 1) * width + j])
+        {
+            diff.push(DiffLine::Added(new_mid[j]));
+            j += 1;
+        } else {
+            diff.push(DiffLine::Removed(old_mid[i]));
+            i += 1;
+        }
+    }
+    diff.extend(same_suffix);
+    diff
+}
+
+/// Print the changed lines of a diff, grouped into hunks that start with the
+/// line number in the new file.
+fn print_diff(diff: &[DiffLine]) {
+    let mut new_line = 0;
+    let mut in_hunk = false;
+    for line in diff {
+        match line {
+            DiffLine::Same(_) => {
+                new_line += 1;
+                in_hunk = false;
+            },
+            DiffLine::Removed(text) | DiffLine::Added(text) => {
+                if !in_hunk {
+                    println!("  @@ line {} @@", new_line + 1);
+                    in_hunk = true;
+                }
+                if matches!(line, DiffLine::Added(_)) {
+                    new_line += 1;
+                    println!("  + {text}");
+                } else {
+                    println!("  - {text}");
+                }
+            },
+        }
+    }
+}
+
+/// Print every file that differs between the two output directories. HTML
+/// pages are compared after normalizing their whitespace, and their changed
+/// lines are printed.
+pub fn diff(cmd: DiffCmd) -> anyhow::Result<()> {
+    let old_files =
+        BuildDirFiles::gather(&cmd.old_path).context("failed to collect old output files")?;
+    let new_files =
+        BuildDirFiles::gather(&cmd.new_path).context("failed to collect new output files")?;
+
+    let paths = old_files
+        .files
+        .keys()
+        .chain(new_files.files.keys())
+        .collect::<BTreeSet<_>>();
+    let (mut num_added, mut num_removed, mut num_changed) = (0, 0, 0);
+    for path in paths {
+        let url = url_path(path);
+        let (old, new) = match (old_files.files.get(path), new_files.files.get(path)) {
+            (Some(old), Some(new)) => (old, new),
+            (None, _) => {
+                println!("added {url}");
+                num_added += 1;
+                continue;
+            },
+            (_, None) => {
+                println!("removed {url}");
+                num_removed += 1;
+                continue;
+            },
+        };
+
+        let read = |path: &PathBuf| {
+            fs::read(path).context(format!("failed to read output file [{}]", path.display()))
+        };
+        let (old_content, new_content) = (read(&old.full_path)?, read(&new.full_path)?);
+        if old_content == new_content {
+            continue;
+        }
+
+        if path.extension().is_some_and(|ext| ext == "html") {
+            let old_html = String::from_utf8_lossy(&old_content);
+            let new_html = String::from_utf8_lossy(&new_content);
+            let diff = diff_lines(&normalize_html(&old_html), &normalize_html(&new_html));
+            if diff.iter().all(|line| matches!(line, DiffLine::Same(_))) {
+                continue;
+            }
+            println!("changed {url}");
+            print_diff(&diff);
+        } else {
+            println!("changed {url}");
+        }
+        num_changed += 1;
+    }
+
+    info!(
+        num_added,
+        num_removed, num_changed, "Compared output directories"
+    );
+
+    Ok(())
+}
@@ -1,11 +1,58 @@
+use std::{collections::BTreeMap, ops::Range, path::Path};
+
 use anyhow::{Context, bail};
-use jotdown::{Container, Event};
-use tera::Value;
-use tracing::debug;
+use jotdown::{Attributes, Container, Event};
+use tracing::{debug, trace};
 
-use crate::build::{BuildFile, ContentSlug, Frontmatter, MetadataContainer};
+use crate::build::{
+    BuildFile, ContentSlug, Frontmatter, Metadata, MetadataContainer,
+    config::{ComponentConfig, SiteConfig},
+    diagnostic::Diagnostic,
+    feed, links,
+    remote::Remote,
+};
 
+mod audio;
 mod biblatex;
+mod components;
+mod csv;
+mod emoji;
+mod gallery;
+mod github;
+mod preview;
+
+pub use biblatex::Bibliography;
+pub use gallery::{Thumbnail, write_thumbnails};
+
+/// Find the index of the event that ends the div starting at `start`.
+fn div_end(events: &[Event<'_>], start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (idx, event) in events.iter().enumerate().skip(start) {
+        match event {
+            Event::Start(Container::Div { .. }, _) => depth += 1,
+            Event::End(Container::Div { .. }) => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            },
+            _ => {},
+        }
+    }
+    None
+}
+
+/// Whether a div has the class, either written after its opening fence like
+/// `::: gallery` or as an attribute like `{.gallery}`.
+fn is_div_of(name: &str, class: &str, attrs: &Attributes<'_>) -> bool {
+    class == name
+        || attrs.get_value("class").is_some_and(|classes| {
+            classes
+                .to_string()
+                .split_whitespace()
+                .any(|class| class == name)
+        })
+}
 
 fn collect_strings(events: &[Event<'_>]) -> (String, usize) {
     let mut content = String::new();
@@ -23,17 +70,15 @@ fn collect_strings(events: &[Event<'_>]) -> (String, usize) {
     (content, num_str_events)
 }
 
-fn extract_frontmatter(
-    metadata: &mut MetadataContainer,
-    slug: &ContentSlug,
-    events: &mut Vec<Event<'_>>,
-) -> anyhow::Result<()> {
+/// Find the JSON raw block at the start of the events, returning its text
+/// along with the number of events the block spans.
+fn frontmatter_block(events: &[Event<'_>]) -> Option<(String, usize)> {
     if !matches!(
-        &events[..],
+        events,
         [Event::Start(Container::RawBlock { format: "json" }, _), ..]
     ) {
         debug!("Missing json raw block start, skipping frontmatter");
-        return Ok(());
+        return None;
     }
 
     // We know at this point that we're in a raw json block, so we'll expect the
@@ -46,25 +91,119 @@ fn extract_frontmatter(
         Event::End(Container::RawBlock { format: "json" })
     ) {
         debug!("Missing raw block ending, skipping frontmatter");
-        return Ok(());
+        return None;
     }
 
+    Some((frontmatter, 1 + num_str_events + 1))
+}
+
+/// Parse the frontmatter from the JSON raw block at the start of the events,
+/// returning it along with the number of events the block spans.
+fn parse_frontmatter(events: &[Event<'_>]) -> anyhow::Result<Option<(Frontmatter, usize)>> {
+    let Some((frontmatter, num_events)) = frontmatter_block(events) else {
+        return Ok(None);
+    };
+
     let frontmatter: Frontmatter =
         serde_json::from_str(&frontmatter).context("failed to parse frontmatter")?;
 
     debug!(?frontmatter, "Parsed frontmatter from djot file");
 
-    if let Some(map) = frontmatter.0.as_object()
-        && let Some(Value::String(bibliography_field)) = map.get("bibliography")
-    {
-        metadata[slug].bibliography_file = Some(bibliography_field.clone());
+    Ok(Some((frontmatter, num_events)))
+}
+
+/// Point a frontmatter syntax error at its position in the source file, since
+/// the position reported by the JSON parser is relative to the raw block.
+fn frontmatter_diagnostic(
+    path: &Path,
+    content: &str,
+    json_error: &serde_json::Error,
+) -> Option<Diagnostic> {
+    let block_start = content.lines().position(|line| {
+        let line = line.trim();
+        line.starts_with("```") && line.trim_start_matches('`').trim() == "=json"
+    });
+    let block_start = block_start.filter(|_| json_error.line() > 0)?;
+
+    let message = json_error.to_string();
+    let message = message
+        .rsplit_once(" at line ")
+        .map_or(message.as_str(), |(message, _)| message);
+    let diagnostic = Diagnostic::new(path, format!("failed to parse frontmatter: {message}"))
+        .at_line(
+            content,
+            block_start + 1 + json_error.line(),
+            json_error.column(),
+        );
+    Some(diagnostic)
+}
+
+/// Add the position of a frontmatter syntax error in the source file to the
+/// error, if it is one.
+fn locate_frontmatter_error(path: &Path, content: &str, error: anyhow::Error) -> anyhow::Error {
+    let diagnostic = error
+        .downcast_ref::<serde_json::Error>()
+        .and_then(|json_error| frontmatter_diagnostic(path, content, json_error));
+    match diagnostic {
+        Some(diagnostic) => error.context(diagnostic),
+        None => error,
     }
-    metadata[slug].frontmatter = Some(frontmatter);
+}
+
+/// A notice shown at the top of a page whose frontmatter couldn't be parsed,
+/// so the problem is visible while previewing the site.
+fn error_banner(diagnostic: &Diagnostic) -> String {
+    format!(
+        "<div role=\"alert\" style=\"border: 2px solid #c00; background: #fee; color: #600; \
+         padding: 0.5em 1em; margin: 1em 0\"><strong>This page was rendered without its \
+         frontmatter</strong><pre style=\"white-space: pre-wrap\">{}</pre></div>\n",
+        feed::escape_xml(&diagnostic.to_string())
+    )
+}
+
+/// Read just the frontmatter of a djot document, without rendering it.
+pub fn read_frontmatter(path: &Path, content: &str) -> anyhow::Result<Option<Frontmatter>> {
+    let events = jotdown::Parser::new(content).collect::<Vec<_>>();
+    let parsed = parse_frontmatter(&events)
+        .map_err(|error| locate_frontmatter_error(path, content, error))?;
+    Ok(parsed.map(|(frontmatter, _)| frontmatter))
+}
+
+/// Parse the frontmatter into the metadata of the page and remove it from the
+/// events.
+///
+/// Outside of release builds, frontmatter that isn't valid JSON is removed
+/// without failing, and the syntax error is returned so that the page can be
+/// rendered without it.
+fn extract_frontmatter(
+    config: &SiteConfig,
+    metadata: &mut MetadataContainer,
+    slug: &ContentSlug,
+    events: &mut Vec<Event<'_>>,
+    release: bool,
+) -> anyhow::Result<Option<serde_json::Error>> {
+    let Some((frontmatter, num_events)) = frontmatter_block(events) else {
+        return Ok(None);
+    };
+    let frontmatter: Frontmatter = match serde_json::from_str(&frontmatter) {
+        Ok(frontmatter) => frontmatter,
+        Err(error) if !release => {
+            events.drain(..num_events);
+            return Ok(Some(error));
+        },
+        Err(error) => return Err(anyhow::Error::new(error).context("failed to parse frontmatter")),
+    };
+    debug!(?frontmatter, "Parsed frontmatter from djot file");
+
+    metadata[slug]
+        .set_frontmatter(frontmatter)
+        .context(format!("in frontmatter of [{slug}]"))?;
+    render_frontmatter_markup(config, &mut metadata[slug]);
 
     // Remove events from the start
-    events.drain(..(1 + num_str_events + 1));
+    events.drain(..num_events);
 
-    Ok(())
+    Ok(None)
 }
 
 fn find_title(
@@ -101,21 +240,213 @@ fn find_title(
     Ok(())
 }
 
+/// The settings of the site config that apply to snippets of djot, copied out
+/// so that template functions can own them.
+#[derive(Debug, Clone)]
+pub struct SnippetConfig {
+    smart_punctuation: bool,
+    emoji_images: Option<BTreeMap<String, String>>,
+    components: BTreeMap<String, ComponentConfig>,
+}
+
+impl SnippetConfig {
+    pub fn new(config: &SiteConfig) -> Self {
+        Self {
+            smart_punctuation: config.smart_punctuation,
+            emoji_images: config.emoji.as_ref().map(|emoji| emoji.images.clone()),
+            components: config.components.clone(),
+        }
+    }
+}
+
+/// Render a snippet of djot markup to HTML, without the `<p>` around a single
+/// paragraph if `inline` is set.
+///
+/// Snippets get the punctuation, emoji, and components of pages, but not the
+/// steps of [`render_events`] that belong to a page, like titles, links, and
+/// references, or the ones that read other files or fetch over the network,
+/// like galleries and link previews.
+pub fn render_snippet(config: &SnippetConfig, text: &str, inline: bool) -> String {
+    let mut events = jotdown::Parser::new(text).collect::<Vec<_>>();
+    if !config.smart_punctuation {
+        straighten_punctuation(&mut events);
+    }
+    if let Some(images) = &config.emoji_images {
+        emoji::replace_shortcodes(&mut events, images);
+    }
+    components::apply_components(&mut events, &config.components);
+
+    let html = jotdown::html::render_to_string(events.into_iter());
+    let html = html.trim_end();
+    if inline {
+        let paragraph = html
+            .strip_prefix("<p>")
+            .and_then(|html| html.strip_suffix("</p>"))
+            .filter(|content| !content.contains("<p>"));
+        if let Some(content) = paragraph {
+            return content.to_owned();
+        }
+    }
+    html.to_owned()
+}
+
+/// Render the text of the frontmatter fields listed in `djot_fields` as inline
+/// djot, replacing it with the HTML.
+fn render_frontmatter_markup(config: &SiteConfig, metadata: &mut Metadata) {
+    let Some(Frontmatter(tera::Value::Object(fields))) = metadata.frontmatter.as_mut() else {
+        return;
+    };
+    let snippet_config = SnippetConfig::new(config);
+    for name in &config.djot_fields {
+        let Some(tera::Value::String(text)) = fields.get_mut(name) else {
+            continue;
+        };
+        *text = render_snippet(&snippet_config, text, true);
+        match name.as_str() {
+            "summary" => metadata.summary = Some(text.clone()),
+            "description" => metadata.description = Some(text.clone()),
+            _ => {},
+        }
+    }
+}
+
+/// Turn the typographic punctuation that the parser produces back into the
+/// straight characters from the source.
+fn straighten_punctuation(events: &mut [Event<'_>]) {
+    for event in events {
+        let straight = match event {
+            Event::LeftSingleQuote | Event::RightSingleQuote => "'",
+            Event::LeftDoubleQuote | Event::RightDoubleQuote => "\"",
+            Event::Ellipsis => "...",
+            Event::EnDash => "--",
+            Event::EmDash => "---",
+            _ => continue,
+        };
+        *event = Event::Str(straight.into());
+    }
+}
+
 #[tracing::instrument(skip_all)]
 pub fn render(
     input: &BuildFile,
+    config: &SiteConfig,
     metadata: &mut MetadataContainer,
     slug: &ContentSlug,
     content: &str,
+    remote: &Remote,
+    release: bool,
 ) -> anyhow::Result<String> {
-    let mut events = jotdown::Parser::new(content).collect::<Vec<_>>();
+    let (mut events, ranges) = jotdown::Parser::new(content)
+        .into_offset_iter()
+        .unzip::<_, _, Vec<_>, Vec<_>>();
+    warn_irregularities(&input.full_path, content, &events, &ranges);
+
+    let syntax_error = extract_frontmatter(config, metadata, slug, &mut events, release)
+        .map_err(|error| locate_frontmatter_error(&input.full_path, content, error))
+        .context("extracting frontmatter")?;
 
-    extract_frontmatter(metadata, slug, &mut events).context("extracting frontmatter")?;
+    let html = render_events(input, config, metadata, slug, events, remote)?;
+    let Some(syntax_error) = syntax_error else {
+        return Ok(html);
+    };
+    let diagnostic = frontmatter_diagnostic(&input.full_path, content, &syntax_error)
+        .unwrap_or_else(|| {
+            Diagnostic::new(
+                &input.full_path,
+                format!("failed to parse frontmatter: {syntax_error}"),
+            )
+        });
+    diagnostic.warn();
+    Ok(error_banner(&diagnostic) + &html)
+}
+
+/// Warn about constructs that parse, but are most likely mistakes, given the
+/// events of the source along with the range of the source each one spans:
+///
+/// - raw and code blocks without a closing fence, which swallow the rest of
+///   the file
+/// - headings without any text
+fn warn_irregularities(path: &Path, content: &str, events: &[Event<'_>], ranges: &[Range<usize>]) {
+    let mut block_start = None;
+    for (idx, (event, range)) in events.iter().zip(ranges).enumerate() {
+        match event {
+            Event::Start(Container::RawBlock { .. } | Container::CodeBlock { .. }, _) => {
+                block_start = Some(range.clone());
+            },
+            // A closing fence spans the fence, and a block closed by its
+            // enclosing container is empty, but only an unclosed block runs to
+            // the end of the file
+            Event::End(Container::RawBlock { .. } | Container::CodeBlock { .. })
+                if range.is_empty() && range.start == content.len() =>
+            {
+                let start = block_start.take().unwrap_or(range.clone());
+                Diagnostic::new(
+                    path,
+                    "Block is never closed, so it takes up the rest of the file",
+                )
+                .at_offset(content, start.start, start.len().saturating_sub(1))
+                .warn();
+            },
+            Event::Start(Container::Heading { .. }, _)
+                if matches!(
+                    events.get(idx + 1),
+                    Some(Event::End(Container::Heading { .. }))
+                ) =>
+            {
+                Diagnostic::new(path, "Heading is empty")
+                    .at_offset(content, range.start, range.len())
+                    .warn();
+            },
+            _ => {},
+        }
+    }
+}
 
+/// Render djot events without frontmatter to HTML, extracting the page
+/// metadata along the way.
+///
+/// Nothing is fetched over the network when `remote` is offline.
+pub fn render_events(
+    input: &BuildFile,
+    config: &SiteConfig,
+    metadata: &mut MetadataContainer,
+    slug: &ContentSlug,
+    mut events: Vec<Event<'_>>,
+    remote: &Remote,
+) -> anyhow::Result<String> {
     find_title(metadata, slug, &events).context("finding page title")?;
 
+    links::record_links(metadata, slug, &events);
+
+    // The parser always converts punctuation outside of code, so it is undone
+    // here if the site opts out
+    if !config.smart_punctuation {
+        straighten_punctuation(&mut events);
+    }
+
+    if let Some(emoji) = &config.emoji {
+        emoji::replace_shortcodes(&mut events, &emoji.images);
+    }
+
+    components::apply_components(&mut events, &config.components);
+
+    gallery::render_galleries(input, metadata, slug, &mut events).context("rendering galleries")?;
+
+    preview::render_previews(&mut events, remote).context("rendering link previews")?;
+    github::render_repositories(&mut events, remote)
+        .context("rendering GitHub repository cards")?;
+
+    audio::render_players(input, &mut events, &mut metadata[slug].dependencies)
+        .context("rendering audio players")?;
+
+    csv::render_tables(input, &mut events, &mut metadata[slug].dependencies)
+        .context("rendering CSV tables")?;
+
     biblatex::handle_references(input, metadata, slug, &mut events)
         .context("parsing out citations and inserting reference")?;
 
+    for event in &events {
+        trace!(?event, "Rendering djot event");
+    }
     Ok(jotdown::html::render_to_string(events.into_iter()))
 }
@@ -0,0 +1,172 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, bail};
+use jotdown::{Attributes, Container, Event};
+use tracing::{debug, warn};
+
+use crate::build::{BuildFile, feed::escape_xml, summary::format_size};
+
+/// The class of divs rendered as audio players
+const AUDIO: &str = "audio";
+
+/// The MIME type of an audio file, from its extension.
+fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "m4a" | "mp4" | "aac" => "audio/mp4",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "webm" => "audio/webm",
+        _ => "audio/mpeg",
+    }
+}
+
+/// The duration of an audio file in seconds, read with `ffprobe`.
+///
+/// The duration is left out of the player when it can't be read, like when
+/// `ffprobe` isn't installed, so this only warns.
+fn probe_duration(path: &Path) -> Option<f64> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "format=duration"])
+        .args(["-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(path)
+        .output();
+    let output = match output {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            warn!(path = %path.display(), stderr = stderr.trim(), "Failed to read audio duration");
+            return None;
+        },
+        Err(err) => {
+            warn!(path = %path.display(), %err, "Failed to execute [ffprobe] to read audio duration");
+            return None;
+        },
+    };
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Format a number of seconds as `M:SS`, or `H:MM:SS` for an hour or longer.
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+fn render_player(
+    src: &str,
+    path: &Path,
+    transcript: Option<&str>,
+    caption: Option<&str>,
+) -> anyhow::Result<String> {
+    let size = fs::metadata(path)
+        .context(format!("failed to read audio file [{}]", path.display()))?
+        .len();
+    let src = escape_xml(src);
+
+    let mut html = String::new();
+    writeln!(html, r#"<figure class="{AUDIO}">"#)?;
+    if let Some(caption) = caption {
+        writeln!(html, "<figcaption>{caption}</figcaption>")?;
+    }
+    writeln!(
+        html,
+        r#"<audio controls preload="metadata"><source src="{src}" type="{}"></audio>"#,
+        mime_type(path)
+    )?;
+
+    let mut details = vec![];
+    if let Some(duration) = probe_duration(path) {
+        details.push(format!(
+            r#"<span class="audio-duration">{}</span>"#,
+            format_duration(duration)
+        ));
+    }
+    details.push(format!(
+        r#"<a href="{src}" download>Download ({})</a>"#,
+        format_size(size)
+    ));
+    if let Some(transcript) = transcript {
+        details.push(format!(
+            r#"<a href="{}">Transcript</a>"#,
+            escape_xml(transcript)
+        ));
+    }
+    writeln!(
+        html,
+        r#"<p class="audio-details">{}</p>"#,
+        details.join(" · ")
+    )?;
+    writeln!(html, "</figure>")?;
+
+    Ok(html)
+}
+
+/// Replace audio divs with a native player, along with the duration of the
+/// file, a download link with its size, and a link to a transcript.
+///
+/// The file is given by the `src` attribute, relative to the page, and the
+/// optional transcript by the `transcript` attribute. The contents of the div
+/// are used as the caption. Every audio file is added to `dependencies`.
+pub fn render_players(
+    input: &BuildFile,
+    events: &mut Vec<Event<'_>>,
+    dependencies: &mut BTreeSet<PathBuf>,
+) -> anyhow::Result<()> {
+    let page_dir = input.full_path.parent().unwrap_or(Path::new(""));
+    let mut num_players = 0;
+    let mut idx = 0;
+    while idx < events.len() {
+        let Event::Start(Container::Div { class: AUDIO }, attrs) = &events[idx] else {
+            idx += 1;
+            continue;
+        };
+        let end = Event::End(Container::Div { class: AUDIO });
+        let Some(len) = events[idx..].iter().position(|event| event == &end) else {
+            bail!("Missing end of audio block");
+        };
+        let Some(src) = attrs.get_value("src").map(|src| src.to_string()) else {
+            bail!("audio div is missing the `src` attribute");
+        };
+        let transcript = attrs.get_value("transcript").map(|value| value.to_string());
+        let inner = &events[(idx + 1)..(idx + len)];
+        let caption = (!inner.is_empty()).then(|| {
+            jotdown::html::render_to_string(inner.iter().cloned())
+                .trim()
+                .to_owned()
+        });
+
+        let path = page_dir.join(&src);
+        let html = render_player(&src, &path, transcript.as_deref(), caption.as_deref())?;
+        dependencies.insert(path);
+        events.splice(
+            idx..=(idx + len),
+            [
+                Event::Start(Container::RawBlock { format: "html" }, Attributes::new()),
+                Event::Str(html.into()),
+                Event::End(Container::RawBlock { format: "html" }),
+            ],
+        );
+        num_players += 1;
+        idx += 3;
+    }
+
+    debug!(num_players, "Rendered audio players");
+
+    Ok(())
+}
@@ -1,6 +1,11 @@
-use std::{fs, path::Path, sync::LazyLock};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+};
 
-use anyhow::Context;
+use anyhow::{Context, bail};
 use hayagriva::{
     BibliographyDriver, BibliographyRequest, BufWriteFormat, CitationItem, CitationRequest,
     ElemChild, ElemMeta, Formatting, Library, RenderedCitation,
@@ -11,15 +16,76 @@ use hayagriva::{
     },
 };
 use jotdown::{Attributes, Container, Event};
+use sha2::{Digest, Sha256};
 use tracing::debug;
 
-use crate::build::{BuildFile, ContentSlug, MetadataContainer, djot::collect_strings};
+use crate::build::{
+    BuildFile, ContentSlug, MetadataContainer, diagnostic::Diagnostic, djot::collect_strings,
+};
+
+/// The SHA-256 hash of a library file, which identifies its parsed and
+/// rendered forms in the caches
+type LibraryHash = [u8; 32];
+
+/// A parsed library, along with the hash of the content it was parsed from
+type ParsedLibrary = (LibraryHash, Arc<Library>);
+
+/// The parsed library of each file, so that rebuilds while serving don't parse
+/// an unchanged library again. Only the latest content of a file is kept.
+static LIBRARIES: LazyLock<Mutex<BTreeMap<PathBuf, ParsedLibrary>>> = LazyLock::new(Mutex::default);
+
+/// A parsed bibliography file, which is loaded once per build and shared by
+/// every page that cites from it.
+#[derive(Debug, Clone)]
+pub struct Bibliography {
+    pub path: PathBuf,
+    pub hash: LibraryHash,
+    pub library: Arc<Library>,
+}
+
+/// The citations and bibliography rendered for a page.
+#[derive(Debug, Clone)]
+struct RenderedReferences {
+    /// The HTML of each citation, in the order they appear in the text
+    citations: Vec<String>,
+    /// The key and HTML of each bibliography item
+    bibliography: Option<Vec<(String, String)>>,
+}
+
+/// Identifies the rendered references of a page: the library, the citation
+/// style, and the keys of each citation in order.
+type RenderKey = (LibraryHash, Option<String>, Vec<Vec<String>>);
+
+/// The rendered references of each page, since running the CSL driver is the
+/// slow part of rendering a page with citations. Only the latest rendering of a
+/// page is kept, and it is reused while its key stays the same.
+static RENDERED: LazyLock<Mutex<BTreeMap<PathBuf, (RenderKey, RenderedReferences)>>> =
+    LazyLock::new(Mutex::default);
 
-fn read_library_from_file(path: &Path) -> anyhow::Result<Library> {
+/// Read and parse a library, or reuse the parsed library if the file had the
+/// same content when it was parsed before.
+fn read_library_from_file(path: &Path) -> anyhow::Result<Bibliography> {
     let library_content = fs::read_to_string(path).context(format!(
         "reading biblatex library from file [{}]",
         path.display()
     ))?;
+    let hash = Sha256::digest(library_content.as_bytes()).into();
+    let bibliography = |library| Bibliography {
+        path: path.to_owned(),
+        hash,
+        library,
+    };
+
+    let mut libraries = LIBRARIES
+        .lock()
+        .expect("library cache lock is not poisoned");
+    if let Some((_, library)) = libraries
+        .get(path)
+        .filter(|(parsed_hash, _)| *parsed_hash == hash)
+    {
+        debug!(path = %path.display(), "Reusing library parsed by an earlier build");
+        return Ok(bibliography(Arc::clone(library)));
+    }
 
     let library = hayagriva::io::from_biblatex_str(&library_content)
         .map_err(|errs| {
@@ -27,8 +93,10 @@ fn read_library_from_file(path: &Path) -> anyhow::Result<Library> {
             anyhow::anyhow!(errors[..].join(", "))
         })
         .context("reading library from biblatex source")?;
+    let library = Arc::new(library);
+    libraries.insert(path.to_owned(), (hash, Arc::clone(&library)));
 
-    Ok(library)
+    Ok(bibliography(library))
 }
 
 static STYLE: LazyLock<IndependentStyle> =
@@ -38,6 +106,19 @@ static STYLE: LazyLock<IndependentStyle> =
             Style::Dependent(style) => panic!("Unexpected dependent style for IEEE! {style:?}"),
         },
     );
+/// Load one of the CSL styles bundled with hayagriva by name, like `apa` or
+/// `chicago-author-date`
+fn load_style(name: &str) -> anyhow::Result<IndependentStyle> {
+    let Some(style) = ArchivedStyle::by_name(name) else {
+        bail!("unknown citation style [{name}]");
+    };
+
+    match style.get() {
+        Style::Independent(style) => Ok(style),
+        Style::Dependent(_) => bail!("citation style [{name}] is a dependent style"),
+    }
+}
+
 static LOCALES: LazyLock<Vec<Locale>> = LazyLock::new(hayagriva::archive::locales);
 
 fn render_citation_to_html(
@@ -149,6 +230,78 @@ fn render_citation_to_html(
     Ok(buf)
 }
 
+/// Run the CSL driver over the citations of a page, with every entry of the
+/// library in the bibliography, and render the result to HTML.
+fn render_references(
+    library: &Library,
+    style_name: Option<&str>,
+    citations_keys: &[Vec<String>],
+) -> anyhow::Result<RenderedReferences> {
+    let custom_style = style_name
+        .map(load_style)
+        .transpose()
+        .context("loading citation style")?;
+    let style = custom_style.as_ref().unwrap_or(&STYLE);
+
+    let mut driver = BibliographyDriver::new();
+    for keys in citations_keys {
+        let citation_items = keys
+            .iter()
+            .filter_map(|key| library.get(key))
+            .map(|entry| CitationItem::new(entry, None, None, false, None))
+            .collect();
+        driver.citation(CitationRequest::new(
+            citation_items,
+            style,
+            None,
+            &LOCALES,
+            None,
+        ));
+    }
+
+    // This loop through the library add all items as hidden so that the
+    // bibliography rendered at the end will contain all citations
+    for entry in library.iter() {
+        let items = vec![CitationItem::new(entry, None, None, true, None)];
+        driver.citation(CitationRequest::from_items(items, style, &LOCALES));
+    }
+
+    let rendered = driver.finish(BibliographyRequest {
+        style,
+        locale: None,
+        locale_files: &LOCALES,
+    });
+
+    let citations = citations_keys
+        .iter()
+        .zip(&rendered.citations)
+        .map(|(keys, citation)| {
+            render_citation_to_html(citation, keys).context("rendering citation to HTML")
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let bibliography = rendered
+        .bibliography
+        .map(|bib| {
+            bib.items
+                .into_iter()
+                .map(|item| {
+                    let mut rendered_bib_item = String::new();
+                    item.content
+                        .write_buf(&mut rendered_bib_item, BufWriteFormat::Html)
+                        .context("formatting reference item to HTML")?;
+                    Ok((item.key, rendered_bib_item))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        })
+        .transpose()?;
+
+    Ok(RenderedReferences {
+        citations,
+        bibliography,
+    })
+}
+
 #[tracing::instrument(skip_all)]
 pub fn handle_references(
     input: &BuildFile,
@@ -166,9 +319,23 @@ pub fn handle_references(
         .map(Path::to_owned)
         .unwrap_or_default()
         .join(bibliography_path);
-    let library = read_library_from_file(&bibliography_path).context("reading biblatex library")?;
-
-    let mut driver = BibliographyDriver::new();
+    // Another page of this build may have loaded the same file already
+    let loaded = metadata
+        .0
+        .values()
+        .filter_map(|md| md.bibliography.as_ref())
+        .find(|bibliography| bibliography.path == bibliography_path)
+        .cloned();
+    let bibliography = match loaded {
+        Some(bibliography) => bibliography,
+        None => read_library_from_file(&bibliography_path).context("reading biblatex library")?,
+    };
+    let library = Arc::clone(&bibliography.library);
+    let library_hash = bibliography.hash;
+    metadata[slug].bibliography = Some(bibliography);
+    metadata[slug]
+        .dependencies
+        .insert(bibliography_path.clone());
 
     let citation_offsets = events
         .iter()
@@ -186,6 +353,7 @@ pub fn handle_references(
     // in order
     let mut citation_spans = vec![];
     let mut citations_keys = vec![];
+    let mut source = None;
     for cite_start_offset in citation_offsets {
         let (raw_citations, num_str_events) = collect_strings(&events[(cite_start_offset + 1)..]);
 
@@ -193,45 +361,63 @@ pub fn handle_references(
             &events.get(cite_start_offset + num_str_events + 1),
             Some(Event::End(Container::RawInline { format: "cite" }))
         ) {
-            debug!(cite_start_offset, "Missing citation end, skipping");
-            return Ok(());
+            let source: &String = source
+                .get_or_insert_with(|| fs::read_to_string(&input.full_path).unwrap_or_default());
+            Diagnostic::new(
+                &input.full_path,
+                "Citation is not plain text, so it can't be resolved",
+            )
+            .at_first(source, raw_citations.trim())
+            .warn();
+            continue;
         }
         citation_spans.push(cite_start_offset..(cite_start_offset + num_str_events + 1 + 1));
 
         let mut keys = vec![];
-        let mut citation_items = vec![];
         for key in raw_citations.split(";").map(str::trim) {
-            let Some(entry) = library.get(key) else {
-                debug!(key, "Citation key not found in library");
+            if library.get(key).is_none() {
+                // The source is only needed to point at the unknown key
+                let source: &String = source.get_or_insert_with(|| {
+                    fs::read_to_string(&input.full_path).unwrap_or_default()
+                });
+                Diagnostic::new(
+                    &input.full_path,
+                    format!(
+                        "Citation key [{key}] not found in bibliography [{}]",
+                        bibliography_path.display()
+                    ),
+                )
+                .at_first(source, key)
+                .warn();
                 continue;
-            };
+            }
             keys.push(key.to_owned());
-
-            citation_items.push(CitationItem::new(entry, None, None, false, None));
         }
-
         citations_keys.push(keys);
-        driver.citation(CitationRequest::new(
-            citation_items,
-            &STYLE,
-            None,
-            &LOCALES,
-            None,
-        ));
     }
 
-    // This loop through the library add all items as hidden so that the
-    // bibliography rendered at the end will contain all citations
-    for entry in library.iter() {
-        let items = vec![CitationItem::new(entry, None, None, true, None)];
-        driver.citation(CitationRequest::from_items(items, &STYLE, &LOCALES));
-    }
-
-    let rendered = driver.finish(BibliographyRequest {
-        style: &STYLE,
-        locale: None,
-        locale_files: &LOCALES,
-    });
+    let style_name = metadata[slug].citation_style.clone();
+    let render_key = (library_hash, style_name, citations_keys);
+    let cached = RENDERED
+        .lock()
+        .expect("rendered references cache lock is not poisoned")
+        .get(&input.full_path)
+        .filter(|(key, _)| *key == render_key)
+        .map(|(_, references)| references.clone());
+    let references = match cached {
+        Some(references) => {
+            debug!("Reusing rendered references");
+            references
+        },
+        None => {
+            let references = render_references(&library, render_key.1.as_deref(), &render_key.2)?;
+            RENDERED
+                .lock()
+                .expect("rendered references cache lock is not poisoned")
+                .insert(input.full_path.clone(), (render_key, references.clone()));
+            references
+        },
+    };
 
     // Now we have to:
     //  1. Remove all the raw inline blocks and replace them with citations and
@@ -239,10 +425,7 @@ pub fn handle_references(
     //  2. Insert a bibliography at the end of the text
 
     let mut removed_offset = 0;
-    for (citation_idx, span) in citation_spans.into_iter().enumerate() {
-        let citation = &rendered.citations[citation_idx];
-        let rendered_citation = render_citation_to_html(citation, &citations_keys[citation_idx])
-            .context("rendering citation to HTML")?;
+    for (span, rendered_citation) in citation_spans.into_iter().zip(references.citations) {
         let updated_span = (removed_offset + span.start)..(removed_offset + span.end);
         let num_events_removed = events
             .splice(
@@ -258,18 +441,14 @@ pub fn handle_references(
         removed_offset += num_events_removed - 3;
     }
 
-    let Some(bib) = rendered.bibliography else {
+    let Some(bib) = references.bibliography else {
         debug!("No bibliography, skipping adding events");
         return Ok(());
     };
 
     let mut bibliography_events = vec![];
-    let num_bib_items = bib.items.len();
-    for (idx, item) in bib.items.into_iter().enumerate() {
-        let mut rendered_bib_item = String::new();
-        item.content
-            .write_buf(&mut rendered_bib_item, BufWriteFormat::Html)
-            .context("formatting reference item to HTML")?;
+    let num_bib_items = bib.len();
+    for (idx, (key, rendered_bib_item)) in bib.into_iter().enumerate() {
         bibliography_events.extend([
             Event::Start(
                 Container::Div {
@@ -278,7 +457,7 @@ pub fn handle_references(
                 Attributes::new(),
             ),
             Event::Start(Container::RawBlock { format: "html" }, Attributes::new()),
-            Event::Str(format!("<span id=\"ref-{}\">[{}]</span>", item.key, idx + 1).into()),
+            Event::Str(format!("<span id=\"ref-{key}\">[{}]</span>", idx + 1).into()),
             Event::End(Container::RawBlock { format: "html" }),
             Event::End(Container::Div {
                 class: "reference-key",
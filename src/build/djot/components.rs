@@ -0,0 +1,142 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use jotdown::{Attributes, Container, Event};
+use tracing::debug;
+
+use crate::build::{config::ComponentConfig, djot::div_end, feed::escape_xml};
+
+/// Build the opening tag of a component, keeping every attribute of the
+/// original element except the class that selected the component.
+fn open_tag(component: &ComponentConfig, matched_class: &str, attrs: &Attributes<'_>) -> String {
+    let mut classes = component.class.iter().cloned().collect::<Vec<_>>();
+    let mut tag = format!("<{}", component.element);
+    for (key, value) in attrs.unique_pairs() {
+        let value = value.to_string();
+        if key == "class" {
+            classes.extend(
+                value
+                    .split_whitespace()
+                    .filter(|class| *class != matched_class)
+                    .map(str::to_owned),
+            );
+        } else {
+            let _ = write!(tag, r#" {key}="{}""#, escape_xml(&value));
+        }
+    }
+    if !classes.is_empty() {
+        let _ = write!(tag, r#" class="{}""#, escape_xml(&classes.join(" ")));
+    }
+    tag.push('>');
+    tag
+}
+
+/// Find the configured component for the first matching class of a span.
+fn span_component<'c>(
+    components: &'c BTreeMap<String, ComponentConfig>,
+    attrs: &Attributes<'_>,
+) -> Option<(String, &'c ComponentConfig)> {
+    let classes = attrs.get_value("class")?.to_string();
+    classes
+        .split_whitespace()
+        .find_map(|class| Some((class.to_owned(), components.get(class)?)))
+}
+
+fn raw_inline(html: String) -> [Event<'static>; 3] {
+    [
+        Event::Start(Container::RawInline { format: "html" }, Attributes::new()),
+        Event::Str(html.into()),
+        Event::End(Container::RawInline { format: "html" }),
+    ]
+}
+
+fn raw_block(html: String) -> [Event<'static>; 3] {
+    [
+        Event::Start(Container::RawBlock { format: "html" }, Attributes::new()),
+        Event::Str(html.into()),
+        Event::End(Container::RawBlock { format: "html" }),
+    ]
+}
+
+/// Render spans and divs that have a configured class as the HTML element of
+/// that component instead.
+///
+/// A div that only contains a single paragraph is unwrapped, so that a div
+/// rendered as a `<p>` doesn't contain another paragraph.
+pub fn apply_components(
+    events: &mut Vec<Event<'_>>,
+    components: &BTreeMap<String, ComponentConfig>,
+) {
+    if components.is_empty() {
+        return;
+    }
+
+    // Closing tags for the events that end a replaced element, keyed by index
+    let mut closing = BTreeMap::new();
+    // Paragraph events that are dropped from unwrapped divs
+    let mut dropped = BTreeSet::new();
+    // The closing tag of each open span, if it was replaced
+    let mut span_stack: Vec<Option<String>> = vec![];
+    let mut num_replaced = 0;
+
+    let mut replaced = Vec::with_capacity(events.len());
+    for idx in 0..events.len() {
+        if dropped.contains(&idx) {
+            continue;
+        }
+        if let Some(html) = closing.remove(&idx) {
+            replaced.extend(raw_block(html));
+            continue;
+        }
+
+        match &events[idx] {
+            Event::Start(Container::Span, attrs) => match span_component(components, attrs) {
+                Some((class, component)) => {
+                    replaced.extend(raw_inline(open_tag(component, &class, attrs)));
+                    span_stack.push(Some(format!("</{}>", component.element)));
+                    num_replaced += 1;
+                },
+                None => {
+                    replaced.push(events[idx].clone());
+                    span_stack.push(None);
+                },
+            },
+            Event::End(Container::Span) => match span_stack.pop().flatten() {
+                Some(html) => replaced.extend(raw_inline(html)),
+                None => replaced.push(events[idx].clone()),
+            },
+            Event::Start(Container::Div { class }, attrs) if components.contains_key(*class) => {
+                let component = &components[*class];
+                let Some(end) = div_end(events, idx) else {
+                    replaced.push(events[idx].clone());
+                    continue;
+                };
+
+                let is_single_paragraph =
+                    matches!(
+                        events.get(idx + 1),
+                        Some(Event::Start(Container::Paragraph, _))
+                    ) && matches!(events[end - 1], Event::End(Container::Paragraph))
+                        && events[(idx + 1)..end]
+                            .iter()
+                            .filter(|event| matches!(event, Event::Start(Container::Paragraph, _)))
+                            .count()
+                            == 1;
+                if is_single_paragraph {
+                    dropped.insert(idx + 1);
+                    dropped.insert(end - 1);
+                }
+
+                replaced.extend(raw_block(open_tag(component, class, attrs)));
+                closing.insert(end, format!("</{}>", component.element));
+                num_replaced += 1;
+            },
+            event => replaced.push(event.clone()),
+        }
+    }
+    *events = replaced;
+
+    debug!(num_replaced, "Rendered djot classes as components");
+}
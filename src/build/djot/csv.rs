@@ -0,0 +1,175 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use jotdown::{Attributes, Container, Event};
+use tracing::debug;
+
+use crate::build::{BuildFile, feed::escape_xml};
+
+/// The language of code blocks, and the class of divs, rendered as tables
+const CSV: &str = "csv";
+
+/// Parse CSV text into rows of fields, following RFC 4180 quoting.
+fn parse_csv(text: &str) -> anyhow::Result<Vec<Vec<String>>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            ('"', true) => in_quotes = false,
+            ('"', false) if field.is_empty() => in_quotes = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) if chars.peek() == Some(&'\n') => {},
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            },
+            (c, _) => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        bail!("unterminated quoted field");
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+/// Render CSV text as a table, using the first row as the header.
+fn render_table(
+    text: &str,
+    caption: Option<&str>,
+    classes: Option<&str>,
+) -> anyhow::Result<String> {
+    let rows = parse_csv(text).context("failed to parse CSV")?;
+    let Some((header, body)) = rows.split_first() else {
+        bail!("CSV data is empty");
+    };
+
+    let mut html = String::new();
+    write!(html, r#"<table class="csv-table sortable"#)?;
+    if let Some(classes) = classes {
+        write!(html, " {}", escape_xml(classes))?;
+    }
+    writeln!(html, r#"">"#)?;
+    if let Some(caption) = caption {
+        writeln!(html, "<caption>{caption}</caption>")?;
+    }
+
+    writeln!(html, "<thead>\n<tr>")?;
+    for cell in header {
+        writeln!(html, r#"<th scope="col">{}</th>"#, escape_xml(cell))?;
+    }
+    writeln!(html, "</tr>\n</thead>")?;
+
+    writeln!(html, "<tbody>")?;
+    for row in body {
+        writeln!(html, "<tr>")?;
+        for cell in row {
+            writeln!(html, "<td>{}</td>", escape_xml(cell))?;
+        }
+        writeln!(html, "</tr>")?;
+    }
+    writeln!(html, "</tbody>\n</table>")?;
+
+    Ok(html)
+}
+
+fn attribute(attrs: &Attributes<'_>, key: &str) -> Option<String> {
+    attrs.get_value(key).map(|value| value.to_string())
+}
+
+/// Replace CSV data with HTML tables.
+///
+/// Data is either written inline in a code block with the `csv` language, or
+/// read from the file in the `src` attribute of a `csv` div, relative to the
+/// page. Both take an optional `caption` attribute, though the contents of a
+/// div are used as the caption when present.
+///
+/// Every CSV file that is read is added to `dependencies`.
+pub fn render_tables(
+    input: &BuildFile,
+    events: &mut Vec<Event<'_>>,
+    dependencies: &mut BTreeSet<PathBuf>,
+) -> anyhow::Result<()> {
+    let page_dir = input.full_path.parent().unwrap_or(Path::new(""));
+    let mut num_tables = 0;
+    let mut idx = 0;
+    while idx < events.len() {
+        let (attrs, end) = match &events[idx] {
+            Event::Start(Container::CodeBlock { language: CSV }, attrs) => {
+                (attrs, Event::End(Container::CodeBlock { language: CSV }))
+            },
+            Event::Start(Container::Div { class: CSV }, attrs) => {
+                (attrs, Event::End(Container::Div { class: CSV }))
+            },
+            _ => {
+                idx += 1;
+                continue;
+            },
+        };
+
+        let Some(len) = events[idx..].iter().position(|event| event == &end) else {
+            bail!("Missing end of CSV block");
+        };
+        let inner = &events[(idx + 1)..(idx + len)];
+        let classes = attribute(attrs, "class");
+        let mut caption = attribute(attrs, "caption").map(|caption| escape_xml(&caption));
+
+        let data = if matches!(end, Event::End(Container::CodeBlock { .. })) {
+            inner
+                .iter()
+                .filter_map(|event| match event {
+                    Event::Str(text) => Some(text.as_ref()),
+                    _ => None,
+                })
+                .collect::<String>()
+        } else {
+            let Some(src) = attribute(attrs, "src") else {
+                bail!("CSV div is missing the `src` attribute");
+            };
+            if !inner.is_empty() {
+                let inner_html = jotdown::html::render_to_string(inner.iter().cloned());
+                caption = Some(inner_html.trim().to_owned());
+            }
+
+            let path = page_dir.join(&src);
+            let data = fs::read_to_string(&path)
+                .context(format!("failed to read CSV file [{}]", path.display()))?;
+            dependencies.insert(path);
+            data
+        };
+
+        let table = render_table(&data, caption.as_deref(), classes.as_deref())?;
+        events.splice(
+            idx..=(idx + len),
+            [
+                Event::Start(Container::RawBlock { format: "html" }, Attributes::new()),
+                Event::Str(table.into()),
+                Event::End(Container::RawBlock { format: "html" }),
+            ],
+        );
+        num_tables += 1;
+        idx += 3;
+    }
+
+    debug!(num_tables, "Rendered CSV tables");
+
+    Ok(())
+}
@@ -0,0 +1,332 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, bail};
+use jotdown::{Attributes, Container, Event};
+use tracing::debug;
+
+use crate::build::{
+    BuildFile, ContentSlug, MetadataContainer,
+    config::GalleryConfig,
+    djot::{div_end, is_div_of},
+    errors::FailureClass,
+    feed::escape_xml,
+    links::resolve_internal,
+};
+
+/// The class of divs rendered as galleries
+const GALLERY: &str = "gallery";
+
+/// Extensions of the files in a folder that are shown in a gallery
+const IMAGE_EXTENSIONS: &[&str] = &["avif", "gif", "jpeg", "jpg", "png", "webp"];
+
+/// The part of a file name that marks a thumbnail, like `cat.thumb.jpg`
+const THUMBNAIL_MARKER: &str = "thumb";
+
+/// A thumbnail that a gallery links to, which is generated after the pages
+/// are written.
+#[derive(Debug, Clone)]
+pub struct Thumbnail {
+    /// The full-size image in the content directory
+    source: PathBuf,
+    /// URL path of the thumbnail in the output
+    url: String,
+}
+
+/// A single image of a gallery.
+struct GalleryImage {
+    /// Link to the full-size image, as written in the page
+    dest: String,
+    alt: String,
+}
+
+/// The path of the thumbnail of an image, next to it with a `.thumb` marker
+/// before the extension.
+fn thumbnail_path(dest: &str) -> Option<String> {
+    let (stem, extension) = dest.rsplit_once('.')?;
+    if stem.is_empty() || extension.contains('/') {
+        return None;
+    }
+    Some(format!("{stem}.{THUMBNAIL_MARKER}.{extension}"))
+}
+
+/// Alt text for an image without any, from its file name.
+fn alt_from_file_name(path: &Path) -> String {
+    path.file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .replace(['-', '_'], " ")
+}
+
+/// The images in a folder, relative to the page, sorted by file name.
+fn folder_images(page_dir: &Path, src: &str) -> anyhow::Result<Vec<GalleryImage>> {
+    let folder = page_dir.join(src);
+    let mut paths = fs::read_dir(&folder)
+        .context(format!(
+            "failed to read gallery folder [{}]",
+            folder.display()
+        ))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .context(format!(
+            "failed to read gallery folder [{}]",
+            folder.display()
+        ))?;
+    paths.sort();
+
+    let src = src.trim_end_matches('/');
+    Ok(paths
+        .into_iter()
+        .filter(|path| {
+            let is_image = path.extension().is_some_and(|extension| {
+                IMAGE_EXTENSIONS
+                    .contains(&extension.to_string_lossy().to_ascii_lowercase().as_str())
+            });
+            let is_thumbnail = path
+                .file_stem()
+                .and_then(|stem| Path::new(stem).extension())
+                .is_some_and(|marker| marker == THUMBNAIL_MARKER);
+            is_image && !is_thumbnail
+        })
+        .map(|path| GalleryImage {
+            dest: format!("{src}/{}", path.file_name().unwrap().to_string_lossy()),
+            alt: alt_from_file_name(&path),
+        })
+        .collect())
+}
+
+/// The images written inside a gallery div, with their alt text.
+fn inline_images(inner: &[Event<'_>]) -> Vec<GalleryImage> {
+    let mut images = vec![];
+    let mut current: Option<GalleryImage> = None;
+    for event in inner {
+        match event {
+            Event::Start(Container::Image(dest, _), _) => {
+                current = Some(GalleryImage {
+                    dest: dest.to_string(),
+                    alt: String::new(),
+                });
+            },
+            Event::End(Container::Image(..)) => images.extend(current.take()),
+            Event::Str(text) => {
+                if let Some(image) = &mut current {
+                    image.alt.push_str(text);
+                }
+            },
+            _ => {},
+        }
+    }
+    for image in &mut images {
+        if image.alt.is_empty() {
+            image.alt = alt_from_file_name(Path::new(&image.dest));
+        }
+    }
+    images
+}
+
+fn render_gallery(
+    group: &str,
+    images: &[GalleryImage],
+    thumbnails: &[Option<String>],
+    caption: Option<&str>,
+    classes: Option<&str>,
+) -> anyhow::Result<String> {
+    let mut html = String::new();
+    write!(html, r#"<div class="{GALLERY}"#)?;
+    if let Some(classes) = classes {
+        write!(html, " {}", escape_xml(classes))?;
+    }
+    writeln!(html, r#"">"#)?;
+    for (image, thumbnail) in images.iter().zip(thumbnails) {
+        let dest = escape_xml(&image.dest);
+        let thumbnail = thumbnail.as_deref().map_or(dest.clone(), escape_xml);
+        writeln!(
+            html,
+            r#"<figure class="gallery-item"><a href="{dest}" data-lightbox="{group}"><img src="{thumbnail}" alt="{}" loading="lazy"></a></figure>"#,
+            escape_xml(&image.alt)
+        )?;
+    }
+    if let Some(caption) = caption {
+        writeln!(html, r#"<div class="gallery-caption">{caption}</div>"#)?;
+    }
+    writeln!(html, "</div>")?;
+
+    Ok(html)
+}
+
+/// Replace gallery divs with a grid of thumbnails linking to the full-size
+/// images.
+///
+/// A gallery either contains the images to show, or shows every image in the
+/// folder in its `src` attribute, relative to the page. The contents of a
+/// folder gallery are used as its caption. The grid is laid out by the
+/// stylesheet of the site, with the `gallery` and `gallery-item` classes, and
+/// each link has a `data-lightbox` attribute naming its gallery, for a
+/// lightbox script to group the images by.
+///
+/// The thumbnails of local images are recorded on the page, to be generated
+/// by [`write_thumbnails`], and every image is added to `dependencies`.
+pub fn render_galleries(
+    input: &BuildFile,
+    metadata: &mut MetadataContainer,
+    slug: &ContentSlug,
+    events: &mut Vec<Event<'_>>,
+) -> anyhow::Result<()> {
+    let page_dir = input.full_path.parent().unwrap_or(Path::new(""));
+    let page_url = metadata[slug].url_path.clone();
+    let mut num_galleries = 0;
+    let mut idx = 0;
+    while idx < events.len() {
+        let Event::Start(Container::Div { class }, attrs) = &events[idx] else {
+            idx += 1;
+            continue;
+        };
+        if !is_div_of(GALLERY, class, attrs) {
+            idx += 1;
+            continue;
+        }
+        let Some(len) = div_end(events, idx).map(|end| end - idx) else {
+            bail!("Missing end of gallery block");
+        };
+        let inner = &events[(idx + 1)..(idx + len)];
+        // The gallery class itself is always added
+        let classes = attrs
+            .get_value("class")
+            .map(|value| {
+                value
+                    .to_string()
+                    .split_whitespace()
+                    .filter(|class| *class != GALLERY)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .filter(|classes| !classes.is_empty());
+
+        let (images, caption) = match attrs.get_value("src") {
+            Some(src) => {
+                let caption = (!inner.is_empty()).then(|| {
+                    jotdown::html::render_to_string(inner.iter().cloned())
+                        .trim()
+                        .to_owned()
+                });
+                (folder_images(page_dir, &src.to_string())?, caption)
+            },
+            None => (inline_images(inner), None),
+        };
+        if images.is_empty() {
+            bail!("gallery has no images");
+        }
+
+        let md = &mut metadata[slug];
+        let mut thumbnails = vec![];
+        for image in &images {
+            // Only local images relative to the page have a thumbnail, others are
+            // shown at full size
+            let is_relative = !image.dest.starts_with('/');
+            let thumbnail = thumbnail_path(&image.dest)
+                .filter(|_| is_relative)
+                .and_then(|thumbnail| {
+                    let url = resolve_internal(&page_url, &thumbnail)?;
+                    Some((thumbnail, url))
+                });
+            match thumbnail {
+                Some((thumbnail, url)) => {
+                    let source = page_dir.join(&image.dest);
+                    md.dependencies.insert(source.clone());
+                    md.thumbnails.push(Thumbnail { source, url });
+                    thumbnails.push(Some(thumbnail));
+                },
+                None => {
+                    debug!(dest = image.dest, "Gallery image has no thumbnail");
+                    thumbnails.push(None);
+                },
+            }
+        }
+
+        let group = format!("{GALLERY}-{}", num_galleries + 1);
+        let html = render_gallery(
+            &group,
+            &images,
+            &thumbnails,
+            caption.as_deref(),
+            classes.as_deref(),
+        )?;
+        events.splice(
+            idx..=(idx + len),
+            [
+                Event::Start(Container::RawBlock { format: "html" }, Attributes::new()),
+                Event::Str(html.into()),
+                Event::End(Container::RawBlock { format: "html" }),
+            ],
+        );
+        num_galleries += 1;
+        idx += 3;
+    }
+
+    debug!(num_galleries, "Rendered galleries");
+
+    Ok(())
+}
+
+/// Whether the output file is missing or older than the source it is made
+/// from.
+fn is_stale(source: &Path, output: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(source), modified(output)) {
+        (Ok(source), Ok(output)) => source > output,
+        _ => true,
+    }
+}
+
+/// Generate the thumbnails of every gallery with ImageMagick, skipping ones
+/// that are newer than their image.
+#[tracing::instrument(skip_all)]
+pub fn write_thumbnails(
+    config: &GalleryConfig,
+    metadata: &MetadataContainer,
+    output_root: &Path,
+) -> anyhow::Result<()> {
+    let thumbnails = metadata
+        .0
+        .values()
+        .flat_map(|md| &md.thumbnails)
+        .map(|thumbnail| (&thumbnail.url, &thumbnail.source))
+        .collect::<BTreeSet<_>>();
+
+    let size = format!("{0}x{0}>", config.thumbnail_size);
+    let mut num_written = 0;
+    for (url, source) in thumbnails {
+        let output_path = output_root.join(url.trim_start_matches('/'));
+        if !is_stale(source, &output_path) {
+            continue;
+        }
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent).context("failed to create parent directory for output")?;
+        }
+
+        let output = Command::new("magick")
+            .arg(source)
+            .args(["-auto-orient", "-thumbnail", &size])
+            .arg(&output_path)
+            .output()
+            .map_err(|err| FailureClass::Tool.wrap("failed to execute [magick]", err))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(FailureClass::Tool.error(format!(
+                "failed to generate thumbnail of [{}]: {}",
+                source.display(),
+                stderr.trim()
+            )));
+        }
+        num_written += 1;
+    }
+
+    debug!(num_written, "Written gallery thumbnails");
+
+    Ok(())
+}
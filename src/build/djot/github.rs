@@ -0,0 +1,137 @@
+use std::fmt::Write;
+
+use anyhow::{Context, bail};
+use jotdown::{Attributes, Container, Event};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::build::{
+    djot::{div_end, is_div_of},
+    feed::escape_xml,
+    remote::{self, Remote},
+};
+
+/// The class of divs rendered as repository cards
+const GITHUB: &str = "github";
+
+/// The fields of a repository from the GitHub API that a card shows.
+#[derive(Debug, Deserialize)]
+struct Repository {
+    full_name: String,
+    html_url: String,
+    description: Option<String>,
+    language: Option<String>,
+    stargazers_count: u64,
+    forks_count: u64,
+}
+
+/// Fetch a repository, given as `owner/name`, from the GitHub API.
+fn fetch_repository(remote: &Remote, repo: &str) -> anyhow::Result<Repository> {
+    let body = remote::fetch(remote, &format!("https://api.github.com/repos/{repo}"))?;
+    serde_json::from_str(&body).context(format!("invalid GitHub API response for [{repo}]"))
+}
+
+fn render_card(repo: &Repository) -> anyhow::Result<String> {
+    let mut html = String::new();
+    writeln!(
+        html,
+        r#"<a class="github-card" href="{}">"#,
+        escape_xml(&repo.html_url)
+    )?;
+    writeln!(
+        html,
+        r#"<strong class="github-card-name">{}</strong>"#,
+        escape_xml(&repo.full_name)
+    )?;
+    if let Some(description) = &repo.description {
+        writeln!(
+            html,
+            r#"<span class="github-card-description">{}</span>"#,
+            escape_xml(description)
+        )?;
+    }
+    write!(html, r#"<span class="github-card-stats">"#)?;
+    if let Some(language) = &repo.language {
+        write!(
+            html,
+            r#"<span class="github-card-language">{}</span> "#,
+            escape_xml(language)
+        )?;
+    }
+    let forks = if repo.forks_count == 1 {
+        "fork"
+    } else {
+        "forks"
+    };
+    writeln!(
+        html,
+        r#"<span class="github-card-stars">★ {}</span> <span class="github-card-forks">{} {forks}</span></span>"#,
+        repo.stargazers_count, repo.forks_count
+    )?;
+    writeln!(html, "</a>")?;
+
+    Ok(html)
+}
+
+/// The card of a repository, or a plain link to it when it can't be fetched.
+fn render_repository(remote: &Remote, repo: &str) -> anyhow::Result<String> {
+    match fetch_repository(remote, repo) {
+        Ok(repository) => render_card(&repository),
+        Err(err) => {
+            warn!(
+                repo,
+                err = format!("{err:#}"),
+                "Failed to fetch GitHub repository, rendering a plain link"
+            );
+            let repo = escape_xml(repo);
+            Ok(format!(
+                r#"<p><a href="https://github.com/{repo}">{repo}</a></p>"#
+            ))
+        },
+    }
+}
+
+/// Replace GitHub divs with a card showing the description, stars, and
+/// language of the repository in their `repo` attribute, like `owner/name`.
+///
+/// Repositories are fetched from the GitHub API at build time and cached like
+/// any other remote data, and are linked to as is when they can't be fetched.
+pub fn render_repositories(events: &mut Vec<Event<'_>>, remote: &Remote) -> anyhow::Result<()> {
+    let mut num_cards = 0;
+    let mut idx = 0;
+    while idx < events.len() {
+        let Event::Start(Container::Div { class }, attrs) = &events[idx] else {
+            idx += 1;
+            continue;
+        };
+        if !is_div_of(GITHUB, class, attrs) {
+            idx += 1;
+            continue;
+        }
+        let Some(len) = div_end(events, idx).map(|end| end - idx) else {
+            bail!("Missing end of GitHub block");
+        };
+        let Some(repo) = attrs.get_value("repo").map(|repo| repo.to_string()) else {
+            bail!("GitHub div is missing the `repo` attribute");
+        };
+        if repo.split('/').count() != 2 || repo.split('/').any(str::is_empty) {
+            bail!("GitHub repository [{repo}] must be written as `owner/name`");
+        }
+
+        let html = render_repository(remote, &repo)?;
+        events.splice(
+            idx..=(idx + len),
+            [
+                Event::Start(Container::RawBlock { format: "html" }, Attributes::new()),
+                Event::Str(html.into()),
+                Event::End(Container::RawBlock { format: "html" }),
+            ],
+        );
+        num_cards += 1;
+        idx += 3;
+    }
+
+    debug!(num_cards, "Rendered GitHub repository cards");
+
+    Ok(())
+}
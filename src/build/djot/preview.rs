@@ -0,0 +1,197 @@
+use std::{fmt::Write, sync::LazyLock};
+
+use anyhow::bail;
+use jotdown::{Attributes, Container, Event, LinkType};
+use regex::Regex;
+use tracing::{debug, warn};
+
+use crate::build::{
+    check::decode_entities,
+    feed::escape_xml,
+    inline::attribute,
+    remote::{self, Remote},
+};
+
+/// The class of divs whose links are rendered as preview cards
+const PREVIEW: &str = "preview";
+
+/// Matches a `<meta>` tag
+static META_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<meta\b[^>]*>").unwrap());
+
+/// Matches the `<title>` element of a page
+static TITLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<title\b[^>]*>(.*?)</title>").unwrap());
+
+/// What a card shows about the page it links to.
+#[derive(Debug, Default)]
+struct Preview {
+    title: Option<String>,
+    description: Option<String>,
+    image: Option<String>,
+    site_name: Option<String>,
+}
+
+/// The value of the first `<meta>` tag with one of the names, in either the
+/// `property` or the `name` attribute.
+fn meta_content(html: &str, names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| {
+        META_TAG.find_iter(html).find_map(|tag| {
+            let tag = tag.as_str();
+            let key = attribute(tag, "property").or_else(|| attribute(tag, "name"))?;
+            if !key.eq_ignore_ascii_case(name) {
+                return None;
+            }
+            let content = attribute(tag, "content")?.trim().to_owned();
+            (!content.is_empty()).then_some(content)
+        })
+    })
+}
+
+/// Resolve the URL of an image against the URL of the page it is on.
+fn absolute_url(page_url: &str, src: &str) -> Option<String> {
+    if src.starts_with("http://") || src.starts_with("https://") {
+        return Some(src.to_owned());
+    }
+    let scheme_end = page_url.find("://")? + 3;
+    let origin_end = page_url[scheme_end..]
+        .find('/')
+        .map_or(page_url.len(), |idx| scheme_end + idx);
+    if let Some(src) = src.strip_prefix("//") {
+        return Some(format!("{}{src}", &page_url[..scheme_end]));
+    }
+    if src.starts_with('/') {
+        return Some(format!("{}{src}", &page_url[..origin_end]));
+    }
+    let dir_end = page_url[origin_end..]
+        .rfind('/')
+        .map_or(page_url.len(), |idx| origin_end + idx);
+    Some(format!("{}/{src}", &page_url[..dir_end]))
+}
+
+/// Read the title, description, and image of a page from its OpenGraph tags,
+/// falling back to the standard ones.
+fn extract_preview(url: &str, html: &str) -> Preview {
+    let title = meta_content(html, &["og:title", "twitter:title"]).or_else(|| {
+        TITLE
+            .captures(html)
+            .map(|captures| captures[1].trim().to_owned())
+    });
+    Preview {
+        title: title.map(|title| decode_entities(&title).into_owned()),
+        description: meta_content(html, &["og:description", "description"])
+            .map(|description| decode_entities(&description).into_owned()),
+        image: meta_content(html, &["og:image", "twitter:image"])
+            .and_then(|image| absolute_url(url, &decode_entities(&image))),
+        site_name: meta_content(html, &["og:site_name"])
+            .map(|site_name| decode_entities(&site_name).into_owned()),
+    }
+}
+
+/// The host of a URL, shown on cards of sites without a name.
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split(['/', '?', '#']).next().unwrap_or(rest)
+}
+
+fn render_card(url: &str, preview: &Preview) -> anyhow::Result<String> {
+    let href = escape_xml(url);
+    let mut html = String::new();
+    writeln!(html, r#"<a class="preview-card" href="{href}">"#)?;
+    if let Some(image) = &preview.image {
+        writeln!(
+            html,
+            r#"<img class="preview-card-image" src="{}" alt="" loading="lazy">"#,
+            escape_xml(image)
+        )?;
+    }
+    writeln!(html, r#"<span class="preview-card-body">"#)?;
+    writeln!(
+        html,
+        r#"<strong class="preview-card-title">{}</strong>"#,
+        escape_xml(preview.title.as_deref().unwrap_or(url))
+    )?;
+    if let Some(description) = &preview.description {
+        writeln!(
+            html,
+            r#"<span class="preview-card-description">{}</span>"#,
+            escape_xml(description)
+        )?;
+    }
+    writeln!(
+        html,
+        r#"<span class="preview-card-site">{}</span>"#,
+        escape_xml(preview.site_name.as_deref().unwrap_or(host(url)))
+    )?;
+    writeln!(html, "</span>\n</a>")?;
+
+    Ok(html)
+}
+
+/// The card of a URL, or a plain link when the page can't be fetched.
+fn render_preview(remote: &Remote, url: &str) -> anyhow::Result<String> {
+    match remote::fetch(remote, url) {
+        Ok(html) => render_card(url, &extract_preview(url, &html)),
+        Err(err) => {
+            warn!(
+                url,
+                err = format!("{err:#}"),
+                "Failed to fetch link preview, rendering a plain link"
+            );
+            let href = escape_xml(url);
+            Ok(format!(r#"<p><a href="{href}">{href}</a></p>"#))
+        },
+    }
+}
+
+/// Replace each link in a preview div with a card showing the title,
+/// description, and image of the page it links to, fetched at build time.
+///
+/// The div is meant to hold bare URLs, like `<https://example.com>`, though
+/// the text of other links is ignored too. Pages that can't be fetched, like
+/// when building offline without a cached copy, are linked to as is.
+pub fn render_previews(events: &mut Vec<Event<'_>>, remote: &Remote) -> anyhow::Result<()> {
+    let mut num_cards = 0;
+    let mut idx = 0;
+    while idx < events.len() {
+        let Event::Start(Container::Div { class: PREVIEW }, _) = &events[idx] else {
+            idx += 1;
+            continue;
+        };
+        let end = Event::End(Container::Div { class: PREVIEW });
+        let Some(len) = events[idx..].iter().position(|event| event == &end) else {
+            bail!("Missing end of preview block");
+        };
+
+        let urls = events[(idx + 1)..(idx + len)]
+            .iter()
+            .filter_map(|event| match event {
+                Event::Start(Container::Link(dest, LinkType::AutoLink | LinkType::Span(_)), _) => {
+                    Some(dest.to_string())
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if urls.is_empty() {
+            bail!("preview div has no links");
+        }
+
+        let mut html = String::new();
+        for url in &urls {
+            html.push_str(&render_preview(remote, url)?);
+        }
+        events.splice(
+            idx..=(idx + len),
+            [
+                Event::Start(Container::RawBlock { format: "html" }, Attributes::new()),
+                Event::Str(html.into()),
+                Event::End(Container::RawBlock { format: "html" }),
+            ],
+        );
+        num_cards += urls.len();
+        idx += 3;
+    }
+
+    debug!(num_cards, "Rendered link previews");
+
+    Ok(())
+}
@@ -0,0 +1,186 @@
+use std::{borrow::Cow, fmt::Write, fs, path::Path, sync::LazyLock};
+
+use anyhow::Context;
+use regex::{Captures, Regex};
+use tracing::debug;
+
+use crate::build::{
+    BuildDirFiles,
+    config::EmailObfuscation,
+    typography::{Token, tag_name, tokenize},
+};
+
+/// Matches an email address in text
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}").unwrap()
+});
+
+/// Matches a `mailto:` link in the `href` attribute of a tag
+static MAILTO_HREF: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\bhref\s*=\s*(?:"mailto:([^"]*)"|'mailto:([^']*)')"#).unwrap()
+});
+
+/// Elements whose text is left alone, since it isn't shown as text
+const SKIPPED_ELEMENTS: &[&str] = &["script", "style"];
+
+/// Elements that can't contain other elements, where addresses are always
+/// encoded as entities
+const TEXT_ONLY_ELEMENTS: &[&str] = &["title", "textarea", "option"];
+
+/// Decodes the addresses hidden by the `javascript` method, filling in the
+/// text of each placeholder and the `href` of each link
+const DECODE_SCRIPT: &str = r#"<script>
+for (const element of document.querySelectorAll("[data-email]")) {
+  const email = element.dataset.email.replace(/[a-z]/gi, (c) => {
+    const base = c <= "Z" ? 65 : 97;
+    return String.fromCharCode(((c.charCodeAt(0) - base + 13) % 26) + base);
+  });
+  if (element.tagName === "A") {
+    element.href = "mailto:" + email;
+  } else {
+    element.textContent = email;
+  }
+}
+</script>
+"#;
+
+/// Encode every character of the text as a numeric character reference,
+/// alternating between decimal and hexadecimal so the pattern is less regular.
+pub fn encode_entities(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len() * 6);
+    for (idx, c) in text.chars().enumerate() {
+        if idx % 2 == 0 {
+            write!(encoded, "&#{};", c as u32).unwrap();
+        } else {
+            write!(encoded, "&#x{:x};", c as u32).unwrap();
+        }
+    }
+    encoded
+}
+
+/// Rotate the letters of the text by 13 places.
+fn rot13(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'a'..='z' => (((c as u8 - b'a') + 13) % 26 + b'a') as char,
+            'A'..='Z' => (((c as u8 - b'A') + 13) % 26 + b'A') as char,
+            _ => c,
+        })
+        .collect()
+}
+
+/// Hide the `mailto:` link of a tag, if it has one.
+fn obfuscate_tag(tag: &str, method: EmailObfuscation) -> Cow<'_, str> {
+    MAILTO_HREF.replace(tag, |captures: &Captures| {
+        let address = captures
+            .get(1)
+            .or_else(|| captures.get(2))
+            .map_or("", |address| address.as_str());
+        match method {
+            EmailObfuscation::Entities => {
+                format!(
+                    r#"href="{}""#,
+                    encode_entities(&format!("mailto:{address}"))
+                )
+            },
+            EmailObfuscation::Javascript => {
+                format!(r##"href="#" data-email="{}""##, rot13(address))
+            },
+        }
+    })
+}
+
+/// Hide the email addresses in the text between tags.
+fn obfuscate_text(text: &str, method: EmailObfuscation) -> Cow<'_, str> {
+    EMAIL.replace_all(text, |captures: &Captures| match method {
+        EmailObfuscation::Entities => encode_entities(&captures[0]),
+        EmailObfuscation::Javascript => {
+            format!(r#"<span data-email="{}"></span>"#, rot13(&captures[0]))
+        },
+    })
+}
+
+/// Hide the email addresses and `mailto:` links of a page from scrapers.
+///
+/// With the `javascript` method, a script that decodes them is added to the
+/// end of the body.
+pub fn obfuscate(html: &str, method: EmailObfuscation) -> String {
+    let mut skipped_depth = 0usize;
+    let mut text_only_depth = 0usize;
+    let mut changed = false;
+    let mut obfuscated = String::with_capacity(html.len());
+    for token in tokenize(html) {
+        match token {
+            Token::Tag(tag) => {
+                let (name, is_closing) = tag_name(tag);
+                let name = name.to_ascii_lowercase();
+                for (elements, depth) in [
+                    (SKIPPED_ELEMENTS, &mut skipped_depth),
+                    (TEXT_ONLY_ELEMENTS, &mut text_only_depth),
+                ] {
+                    if elements.contains(&name.as_str()) {
+                        *depth = if is_closing {
+                            depth.saturating_sub(1)
+                        } else {
+                            *depth + 1
+                        };
+                    }
+                }
+
+                let tag = obfuscate_tag(tag, method);
+                changed |= matches!(tag, Cow::Owned(_));
+                obfuscated.push_str(&tag);
+            },
+            Token::Text(text) if skipped_depth > 0 => obfuscated.push_str(&text),
+            Token::Text(text) => {
+                let method = if text_only_depth > 0 {
+                    EmailObfuscation::Entities
+                } else {
+                    method
+                };
+                let text = obfuscate_text(&text, method);
+                changed |= matches!(text, Cow::Owned(_));
+                obfuscated.push_str(&text);
+            },
+        }
+    }
+
+    if changed && matches!(method, EmailObfuscation::Javascript) {
+        match obfuscated.rfind("</body>") {
+            Some(idx) => obfuscated.insert_str(idx, DECODE_SCRIPT),
+            None => obfuscated.push_str(DECODE_SCRIPT),
+        }
+    }
+    obfuscated
+}
+
+/// Hide the email addresses in every HTML page of the output.
+pub fn obfuscate_emails(method: EmailObfuscation, output_root: &Path) -> anyhow::Result<()> {
+    let output_files =
+        BuildDirFiles::gather(output_root).context("failed to collect output files")?;
+
+    let mut num_pages = 0;
+    for (path, file) in output_files.files {
+        if path.extension().is_none_or(|ext| ext != "html") {
+            continue;
+        }
+
+        let html = fs::read_to_string(&file.full_path).context(format!(
+            "failed to read output file [{}]",
+            file.full_path.display()
+        ))?;
+        let obfuscated = obfuscate(&html, method);
+
+        if obfuscated != html {
+            fs::write(&file.full_path, obfuscated).context(format!(
+                "failed to write output file [{}]",
+                file.full_path.display()
+            ))?;
+            num_pages += 1;
+        }
+    }
+
+    debug!(num_pages, "Obfuscated email addresses");
+
+    Ok(())
+}
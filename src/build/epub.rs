@@ -0,0 +1,454 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use anyhow::{Context, bail};
+use chrono::{DateTime, Utc};
+use regex::{Captures, Regex};
+use tracing::{debug, info};
+
+use crate::build::{
+    BuildCmd, ContentSlugStem, Metadata, Site, check::decode_entities, export::EpubCmd,
+    feed::escape_xml, links::resolve_internal, protect, remote::Remote, site_cache::site_cache_dir,
+};
+
+mod zip;
+
+/// Matches void elements, which must be self-closing in XHTML
+static VOID_ELEMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"<(area|base|br|col|embed|hr|img|input|link|meta|source|track|wbr)\b([^>]*?)\s*/?>")
+        .unwrap()
+});
+
+/// Matches the destination of `href` and `src` attributes, capturing the
+/// attribute name and the destination
+static LINK_ATTR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\b(href|src)="([^"]*)""#).unwrap());
+
+/// A file included in the book, other than the package document itself.
+struct BookItem {
+    id: String,
+    /// Path relative to the package document
+    href: String,
+    media_type: &'static str,
+    properties: Option<&'static str>,
+}
+
+fn image_media_type(path: &Path) -> Option<&'static str> {
+    match path.extension()?.to_str()? {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "svg" => Some("image/svg+xml"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Collects the images referenced by chapters, so that each one is embedded
+/// once.
+struct Images<'a> {
+    content_root: &'a Path,
+    /// The embedded path of every image, keyed by URL path
+    by_url: BTreeMap<String, BookItem>,
+}
+
+impl Images<'_> {
+    /// Embed the image at the URL path, returning its path relative to the
+    /// package document, or `None` if it isn't a local image.
+    fn embed(&mut self, url_path: &str) -> Option<String> {
+        if let Some(item) = self.by_url.get(url_path) {
+            return Some(item.href.clone());
+        }
+
+        let source = self.content_root.join(url_path.trim_start_matches('/'));
+        let media_type = image_media_type(&source)?;
+        if !source.is_file() {
+            return None;
+        }
+
+        let extension = source.extension()?.to_str()?;
+        let id = format!("image-{}", self.by_url.len() + 1);
+        let href = format!("images/{id}.{extension}");
+        self.by_url.insert(
+            url_path.to_owned(),
+            BookItem {
+                id,
+                href: href.clone(),
+                media_type,
+                properties: None,
+            },
+        );
+
+        Some(href)
+    }
+}
+
+/// Wrap a fragment of XHTML in a complete document.
+fn xhtml_document(title: &str, language: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{language}" lang="{language}">
+<head><title>{title}</title></head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        title = escape_xml(title),
+        language = escape_xml(language),
+    )
+}
+
+/// Convert a rendered page into XHTML for the book, embedding its images and
+/// pointing links between chapters at the chapter files.
+fn chapter_body(
+    md: &Metadata,
+    content: &str,
+    chapter_files: &BTreeMap<String, String>,
+    images: &mut Images,
+) -> String {
+    let page_url = md.url_path.as_path();
+    let content = VOID_ELEMENT.replace_all(content, "<$1$2 />");
+    let content = LINK_ATTR.replace_all(&content, |captures: &Captures| {
+        let attr = &captures[1];
+        let dest = decode_entities(&captures[2]);
+        let resolved = resolve_internal(page_url, &dest);
+        let replacement = match (attr, resolved) {
+            ("src", Some(url)) => images.embed(&url).map(|href| format!("../{href}")),
+            ("href", Some(url)) => chapter_files.get(&url).map(|file| {
+                let fragment = dest.split_once('#').map(|(_, f)| f);
+                match fragment {
+                    Some(fragment) => format!("{file}#{fragment}"),
+                    None => file.clone(),
+                }
+            }),
+            _ => None,
+        };
+
+        match replacement {
+            Some(dest) => format!(r#"{attr}="{}""#, escape_xml(&dest)),
+            None => captures[0].to_owned(),
+        }
+    });
+
+    content.into_owned()
+}
+
+/// The weight of a page for ordering, from the `weight` frontmatter field.
+fn weight(md: &Metadata) -> i64 {
+    md.frontmatter_field("weight")
+        .and_then(tera::Value::as_i64)
+        .unwrap_or(0)
+}
+
+/// Bundle the articles directly inside a section into a single EPUB, ordered
+/// by weight and then date, with a generated cover and table of contents.
+///
+/// The book takes its title and language from the section index page, when
+/// it has one, and its cover image from the `cover` frontmatter field.
+#[tracing::instrument(skip_all)]
+pub fn export_epub(cmd: &EpubCmd) -> anyhow::Result<()> {
+    let args = BuildCmd {
+        input_path: cmd.input_path.clone(),
+        output_path: PathBuf::new(),
+        release: true,
+        link_graph: None,
+        keep_going: false,
+        deny_warnings: false,
+        output_format: None,
+        offline: false,
+    };
+    let mut site = Site::load(&args)?;
+    let remote = Remote::new(&site_cache_dir(&args.input_path), args.offline);
+    site.content
+        .apply_cascades(args.release)
+        .context("failed to apply cascaded frontmatter")?;
+
+    for (slug, file) in &site.content.files {
+        if slug.parent != cmd.section {
+            continue;
+        }
+        let ctx = format!("Failed to render file [{}]", file.input.full_path.display());
+        file.render(
+            &args,
+            &site.config,
+            &remote,
+            &mut site.content.metadata,
+            slug,
+        )
+        .context(ctx)?;
+    }
+    site.content.remove_drafts();
+
+    let section_pages = site
+        .content
+        .metadata
+        .0
+        .iter()
+        .filter(|(slug, _)| slug.parent == cmd.section)
+        .collect::<Vec<_>>();
+    let index = section_pages
+        .iter()
+        .find(|(slug, _)| matches!(slug.stem, ContentSlugStem::Index))
+        .map(|(_, md)| *md);
+    let mut chapters = section_pages
+        .iter()
+        .filter(|(slug, md)| {
+            // Protected pages would be readable by anyone with the book
+            !matches!(slug.stem, ContentSlugStem::Index)
+                && md.is_article
+                && md.rendered_content.is_some()
+                && !protect::is_protected(&site.config, md)
+        })
+        .map(|(_, md)| *md)
+        .collect::<Vec<_>>();
+    if chapters.is_empty() {
+        bail!(
+            "Section [{}] has no articles to export",
+            cmd.section.display()
+        );
+    }
+    chapters.sort_by_key(|md| (weight(md), md.date));
+
+    let title = index
+        .and_then(|md| md.title.clone())
+        .or_else(|| site.config.title.clone())
+        .unwrap_or_else(|| cmd.section.display().to_string());
+    let language = index
+        .and_then(|md| md.frontmatter_field("language"))
+        .and_then(tera::Value::as_str)
+        .unwrap_or("en")
+        .to_owned();
+    let identifier = match &site.config.base_url {
+        Some(base_url) => format!(
+            "{}/{}/",
+            base_url.trim_end_matches('/'),
+            cmd.section.display()
+        ),
+        None => format!("urn:www:{}", cmd.section.display()),
+    };
+    let modified = chapters
+        .iter()
+        .filter_map(|md| md.date)
+        .max()
+        .map(|date| date.with_timezone(&Utc))
+        .unwrap_or_else(Utc::now);
+
+    let content_root = cmd.input_path.join("content");
+    let mut images = Images {
+        content_root: &content_root,
+        by_url: BTreeMap::new(),
+    };
+
+    let chapter_files = chapters
+        .iter()
+        .enumerate()
+        .map(|(num, md)| {
+            (
+                md.url_path.to_string_lossy().into_owned(),
+                format!("chapter-{:03}.xhtml", num + 1),
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let mut zip = zip::ZipWriter::default();
+    zip.add_stored("mimetype", b"application/epub+zip")?;
+    zip.add_deflated(
+        "META-INF/container.xml",
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#,
+    )?;
+
+    let mut items = vec![];
+    let mut spine = vec![];
+    let mut toc = String::new();
+
+    // Cover
+    let cover_image = index
+        .and_then(|md| {
+            let cover = md.frontmatter_field("cover")?.as_str()?;
+            resolve_internal(&md.url_path, cover)
+        })
+        .and_then(|url| images.embed(&url));
+    let mut cover_body = String::new();
+    if let Some(href) = &cover_image {
+        writeln!(
+            cover_body,
+            r#"<img src="{}" alt="{}" />"#,
+            escape_xml(href),
+            escape_xml(&title)
+        )?;
+    }
+    writeln!(cover_body, "<h1>{}</h1>", escape_xml(&title))?;
+    if let Some(author) = &site.config.author {
+        writeln!(cover_body, "<p>{}</p>", escape_xml(author))?;
+    }
+    zip.add_deflated(
+        "OEBPS/cover.xhtml",
+        xhtml_document(&title, &language, &cover_body).as_bytes(),
+    )?;
+    items.push(BookItem {
+        id: "cover".into(),
+        href: "cover.xhtml".into(),
+        media_type: "application/xhtml+xml",
+        properties: None,
+    });
+    spine.push("cover".to_owned());
+
+    // Chapters
+    for md in &chapters {
+        let url = md.url_path.to_string_lossy();
+        let file = &chapter_files[url.as_ref()];
+        let chapter_title = md.title.clone().unwrap_or_else(|| md.slug.to_string());
+        let content = md.rendered_content.as_deref().unwrap_or_default();
+        let body = chapter_body(md, content, &chapter_files, &mut images);
+        zip.add_deflated(
+            &format!("OEBPS/chapters/{file}"),
+            xhtml_document(&chapter_title, &language, &body).as_bytes(),
+        )?;
+
+        let id = file.trim_end_matches(".xhtml").to_owned();
+        writeln!(
+            toc,
+            r#"<li><a href="chapters/{file}">{}</a></li>"#,
+            escape_xml(&chapter_title)
+        )?;
+        items.push(BookItem {
+            id: id.clone(),
+            href: format!("chapters/{file}"),
+            media_type: "application/xhtml+xml",
+            properties: None,
+        });
+        spine.push(id);
+    }
+
+    // Table of contents
+    let nav_body =
+        format!("<nav epub:type=\"toc\" id=\"toc\">\n<h1>Contents</h1>\n<ol>\n{toc}</ol>\n</nav>");
+    zip.add_deflated(
+        "OEBPS/nav.xhtml",
+        xhtml_document(&title, &language, &nav_body).as_bytes(),
+    )?;
+    items.push(BookItem {
+        id: "nav".into(),
+        href: "nav.xhtml".into(),
+        media_type: "application/xhtml+xml",
+        properties: Some("nav"),
+    });
+
+    // Images
+    for (url, mut item) in images.by_url {
+        let source = content_root.join(url.trim_start_matches('/'));
+        let data =
+            fs::read(&source).context(format!("failed to read image [{}]", source.display()))?;
+        zip.add_deflated(&format!("OEBPS/{}", item.href), &data)?;
+        if cover_image.as_deref() == Some(item.href.as_str()) {
+            item.properties = Some("cover-image");
+        }
+        items.push(item);
+    }
+
+    let package = package_document(
+        &identifier,
+        &title,
+        &language,
+        site.config.author.as_deref(),
+        modified,
+        &items,
+        &spine,
+    )?;
+    zip.add_deflated("OEBPS/content.opf", package.as_bytes())?;
+
+    let output = cmd.output.clone().unwrap_or_else(|| {
+        let name = cmd
+            .section
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "site".into());
+        PathBuf::from(format!("{name}.epub"))
+    });
+    fs::write(&output, zip.finish()?)
+        .context(format!("failed to write EPUB to [{}]", output.display()))?;
+
+    debug!(num_items = items.len(), "Bundled EPUB contents");
+    info!(output = %output.display(), num_chapters = chapters.len(), "Exported EPUB");
+
+    Ok(())
+}
+
+fn package_document(
+    identifier: &str,
+    title: &str,
+    language: &str,
+    author: Option<&str>,
+    modified: DateTime<Utc>,
+    items: &[BookItem],
+    spine: &[String],
+) -> anyhow::Result<String> {
+    let mut opf = String::new();
+    writeln!(opf, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        opf,
+        r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id">"#
+    )?;
+    writeln!(
+        opf,
+        r#"  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">"#
+    )?;
+    writeln!(
+        opf,
+        r#"    <dc:identifier id="book-id">{}</dc:identifier>"#,
+        escape_xml(identifier)
+    )?;
+    writeln!(opf, "    <dc:title>{}</dc:title>", escape_xml(title))?;
+    writeln!(
+        opf,
+        "    <dc:language>{}</dc:language>",
+        escape_xml(language)
+    )?;
+    if let Some(author) = author {
+        writeln!(opf, "    <dc:creator>{}</dc:creator>", escape_xml(author))?;
+    }
+    writeln!(
+        opf,
+        r#"    <meta property="dcterms:modified">{}</meta>"#,
+        modified.format("%Y-%m-%dT%H:%M:%SZ")
+    )?;
+    writeln!(opf, "  </metadata>")?;
+
+    writeln!(opf, "  <manifest>")?;
+    for item in items {
+        write!(
+            opf,
+            r#"    <item id="{}" href="{}" media-type="{}""#,
+            item.id,
+            escape_xml(&item.href),
+            item.media_type
+        )?;
+        if let Some(properties) = item.properties {
+            write!(opf, r#" properties="{properties}""#)?;
+        }
+        writeln!(opf, "/>")?;
+    }
+    writeln!(opf, "  </manifest>")?;
+
+    writeln!(opf, "  <spine>")?;
+    for id in spine {
+        writeln!(opf, r#"    <itemref idref="{id}"/>"#)?;
+    }
+    writeln!(opf, "  </spine>")?;
+    writeln!(opf, "</package>")?;
+
+    Ok(opf)
+}
@@ -0,0 +1,132 @@
+use std::io::Write;
+
+use anyhow::Context;
+use flate2::{Compression, write::DeflateEncoder};
+
+const LOCAL_FILE_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+
+/// Version 2.0, the minimum that supports deflate
+const VERSION: u16 = 20;
+/// File names are UTF-8
+const FLAG_UTF8: u16 = 1 << 11;
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+/// 1980-01-01 in MS-DOS format, so that archives are reproducible
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+struct CentralEntry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// A minimal writer for ZIP archives held in memory, without ZIP64 support.
+#[derive(Default)]
+pub struct ZipWriter {
+    buf: Vec<u8>,
+    entries: Vec<CentralEntry>,
+}
+
+impl ZipWriter {
+    /// Add a file without compression, which EPUB requires for the `mimetype`
+    /// file.
+    pub fn add_stored(&mut self, name: &str, data: &[u8]) -> anyhow::Result<()> {
+        self.add_entry(name, METHOD_STORED, data, data.to_vec())
+    }
+
+    /// Add a file compressed with deflate.
+    pub fn add_deflated(&mut self, name: &str, data: &[u8]) -> anyhow::Result<()> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish().context("failed to compress file")?;
+        self.add_entry(name, METHOD_DEFLATE, data, compressed)
+    }
+
+    fn add_entry(
+        &mut self,
+        name: &str,
+        method: u16,
+        data: &[u8],
+        compressed: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let entry = CentralEntry {
+            name: name.to_owned(),
+            method,
+            crc: crc32fast::hash(data),
+            compressed_size: u32::try_from(compressed.len()).context("file is too large")?,
+            size: u32::try_from(data.len()).context("file is too large")?,
+            offset: u32::try_from(self.buf.len()).context("archive is too large")?,
+        };
+
+        self.put_u32(LOCAL_FILE_HEADER);
+        self.put_u16(VERSION);
+        self.put_u16(FLAG_UTF8);
+        self.put_u16(entry.method);
+        self.put_u16(0);
+        self.put_u16(DOS_DATE);
+        self.put_u32(entry.crc);
+        self.put_u32(entry.compressed_size);
+        self.put_u32(entry.size);
+        self.put_u16(name.len() as u16);
+        self.put_u16(0);
+        self.buf.extend_from_slice(name.as_bytes());
+        self.buf.extend_from_slice(&compressed);
+
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Write the central directory and return the complete archive.
+    pub fn finish(mut self) -> anyhow::Result<Vec<u8>> {
+        let directory_offset = u32::try_from(self.buf.len()).context("archive is too large")?;
+        let entries = std::mem::take(&mut self.entries);
+        for entry in &entries {
+            self.put_u32(CENTRAL_DIRECTORY_HEADER);
+            self.put_u16(VERSION);
+            self.put_u16(VERSION);
+            self.put_u16(FLAG_UTF8);
+            self.put_u16(entry.method);
+            self.put_u16(0);
+            self.put_u16(DOS_DATE);
+            self.put_u32(entry.crc);
+            self.put_u32(entry.compressed_size);
+            self.put_u32(entry.size);
+            self.put_u16(entry.name.len() as u16);
+            // Extra field, comment, disk number, and internal and external attributes
+            self.put_u16(0);
+            self.put_u16(0);
+            self.put_u16(0);
+            self.put_u16(0);
+            self.put_u32(0);
+            self.put_u32(entry.offset);
+            self.buf.extend_from_slice(entry.name.as_bytes());
+        }
+        let directory_size =
+            u32::try_from(self.buf.len()).context("archive is too large")? - directory_offset;
+
+        let num_entries = u16::try_from(entries.len()).context("too many files in archive")?;
+        self.put_u32(END_OF_CENTRAL_DIRECTORY);
+        self.put_u16(0);
+        self.put_u16(0);
+        self.put_u16(num_entries);
+        self.put_u16(num_entries);
+        self.put_u32(directory_size);
+        self.put_u32(directory_offset);
+        self.put_u16(0);
+
+        Ok(self.buf)
+    }
+
+    fn put_u16(&mut self, value: u16) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+}
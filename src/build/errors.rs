@@ -0,0 +1,212 @@
+use std::{error::Error, fmt};
+
+/// The errors of a build that kept going after a page failed, so that they can
+/// be reported together at the end.
+#[derive(Debug, Default)]
+pub struct BuildErrors {
+    keep_going: bool,
+    errors: Vec<anyhow::Error>,
+}
+
+impl BuildErrors {
+    pub fn new(keep_going: bool) -> Self {
+        Self {
+            keep_going,
+            errors: vec![],
+        }
+    }
+
+    /// Return the value of a successful step, or record the error of a failed
+    /// one and return `None`.
+    ///
+    /// The error is returned instead when the build stops at the first error.
+    pub fn record<T>(&mut self, result: anyhow::Result<T>) -> anyhow::Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if self.keep_going => {
+                self.errors.push(err);
+                Ok(None)
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The errors recorded so far.
+    pub fn errors(&self) -> &[anyhow::Error] {
+        &self.errors
+    }
+
+    /// Fail with every recorded error, if there were any.
+    pub fn finish(self) -> anyhow::Result<()> {
+        match self.errors.len() {
+            0 => Ok(()),
+            // A single error is reported as is, keeping its chain of causes
+            1 => Err(self.errors.into_iter().next().unwrap()),
+            _ => Err(self.into()),
+        }
+    }
+}
+
+impl fmt::Display for BuildErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} errors while building the site", self.errors.len())?;
+        for err in &self.errors {
+            write!(f, "\n\nerror: {err:?}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for BuildErrors {}
+
+/// The kind of problem that made a command fail, which decides the exit code
+/// of the process so that scripts can tell them apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureClass {
+    /// The site config couldn't be loaded
+    Config,
+    /// A content file couldn't be read or rendered
+    Content,
+    /// A template couldn't be parsed or rendered
+    Template,
+    /// A check of the output failed, like a size budget or other denied
+    /// warnings
+    Check,
+    /// An external program failed, like a formatter or a pipeline command
+    Tool,
+    /// Links to missing fragments were found when warnings are denied
+    BrokenLinks,
+}
+
+/// The exit code of the process when its arguments are invalid.
+pub const USAGE_EXIT_CODE: u8 = 2;
+
+impl FailureClass {
+    /// Every class of failure, in the order of their exit codes.
+    pub const ALL: [FailureClass; 6] = [
+        FailureClass::Config,
+        FailureClass::Content,
+        FailureClass::Template,
+        FailureClass::Check,
+        FailureClass::Tool,
+        FailureClass::BrokenLinks,
+    ];
+
+    /// The exit code of the process, after 1 for any other failure and
+    /// [`USAGE_EXIT_CODE`] for invalid arguments.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            FailureClass::Config => 3,
+            FailureClass::Content => 4,
+            FailureClass::Template => 5,
+            FailureClass::Check => 6,
+            FailureClass::Tool => 7,
+            FailureClass::BrokenLinks => 8,
+        }
+    }
+
+    /// What the exit code of the class means, for the documentation of the
+    /// command.
+    pub fn description(self) -> &'static str {
+        match self {
+            FailureClass::Config => "The site config is invalid.",
+            FailureClass::Content => "A content file failed to render.",
+            FailureClass::Template => "A template failed to parse or render.",
+            FailureClass::Check => "A check of the output failed, like denied warnings.",
+            FailureClass::Tool => "An external program failed.",
+            FailureClass::BrokenLinks => {
+                "A link points at a missing fragment, and warnings are denied."
+            },
+        }
+    }
+
+    /// A new error of this class.
+    pub fn error(self, message: impl Into<String>) -> anyhow::Error {
+        Failure {
+            class: self,
+            message: message.into(),
+            source: None,
+        }
+        .into()
+    }
+
+    /// Wrap an error with a message, like `context`, marking it as a failure of
+    /// this class.
+    pub fn wrap(
+        self,
+        message: impl Into<String>,
+        source: impl Into<anyhow::Error>,
+    ) -> anyhow::Error {
+        Failure {
+            class: self,
+            message: message.into(),
+            source: Some(source.into()),
+        }
+        .into()
+    }
+}
+
+/// An error marked with the class of the failure, which is kept in the chain
+/// of causes so that it can be found below later context.
+#[derive(Debug)]
+struct Failure {
+    class: FailureClass,
+    message: String,
+    source: Option<anyhow::Error>,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for Failure {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_ref().map(|source| source.as_ref())
+    }
+}
+
+/// The class of an error, from the innermost failure in its chain of causes
+/// since it is the most specific one. Errors from Tera are template failures
+/// wherever they come from.
+///
+/// When a build kept going, the class of its first error is used.
+fn failure_class(err: &anyhow::Error) -> Option<FailureClass> {
+    if let Some(build_errors) = err.downcast_ref::<BuildErrors>() {
+        return build_errors.errors.first().and_then(failure_class);
+    }
+
+    err.chain()
+        .filter_map(|cause| {
+            if let Some(failure) = cause.downcast_ref::<Failure>() {
+                Some(failure.class)
+            } else if cause.is::<tera::Error>() {
+                Some(FailureClass::Template)
+            } else {
+                None
+            }
+        })
+        .last()
+}
+
+/// Every exit code of the process, along with what it means.
+pub fn exit_statuses() -> Vec<(u8, &'static str)> {
+    let mut statuses = vec![
+        (0, "Success."),
+        (1, "An unclassified error."),
+        (USAGE_EXIT_CODE, "The arguments are invalid."),
+    ];
+    statuses.extend(
+        FailureClass::ALL
+            .iter()
+            .map(|class| (class.exit_code(), class.description())),
+    );
+    statuses
+}
+
+/// The exit code of the process for an error, which is 1 for errors without a
+/// class.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    failure_class(err).map_or(1, FailureClass::exit_code)
+}
@@ -0,0 +1,82 @@
+use std::{path::PathBuf, str::FromStr};
+
+use argh::{ArgsInfo, FromArgs};
+
+use crate::build::{epub, metadata_export};
+
+/// Export part of the site in another format.
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(subcommand, name = "export")]
+pub struct ExportCmd {
+    #[argh(subcommand)]
+    format: ExportFormat,
+}
+
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(subcommand)]
+enum ExportFormat {
+    Epub(EpubCmd),
+    Metadata(MetadataCmd),
+}
+
+/// Bundle the articles of a section into an EPUB book.
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(subcommand, name = "epub")]
+pub struct EpubCmd {
+    /// path to the input directory
+    #[argh(positional)]
+    pub input_path: PathBuf,
+
+    /// the content directory to export, e.g. `blog`
+    #[argh(positional)]
+    pub section: PathBuf,
+
+    /// path to write the book to, defaults to `<section>.epub`
+    #[argh(option, short = 'o')]
+    pub output: Option<PathBuf>,
+}
+
+/// Write the slug, URL, title, date, tags, and frontmatter of every page.
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(subcommand, name = "metadata")]
+pub struct MetadataCmd {
+    /// path to the input directory
+    #[argh(positional)]
+    pub input_path: PathBuf,
+
+    /// the format to write, only `json` is supported
+    #[argh(option, default = "MetadataFormat::Json")]
+    pub format: MetadataFormat,
+
+    /// include draft pages
+    #[argh(switch)]
+    pub drafts: bool,
+
+    /// path to write the metadata to, defaults to stdout
+    #[argh(option, short = 'o')]
+    pub output: Option<PathBuf>,
+}
+
+/// The formats the site metadata can be exported in.
+#[derive(Debug, Clone, Copy)]
+pub enum MetadataFormat {
+    Json,
+}
+
+impl FromStr for MetadataFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(MetadataFormat::Json),
+            _ => Err(format!("unknown metadata format [{s}], expected `json`")),
+        }
+    }
+}
+
+pub fn export(cmd: ExportCmd) -> anyhow::Result<()> {
+    match cmd.format {
+        ExportFormat::Epub(cmd) => epub::export_epub(&cmd),
+        ExportFormat::Metadata(cmd) => metadata_export::export_metadata(&cmd),
+    }
+}
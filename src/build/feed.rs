@@ -0,0 +1,448 @@
+use std::{
+    cmp,
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use crate::build::{
+    ContentSlugStem, Metadata, MetadataContainer,
+    config::{FeedContent, SiteConfig},
+    parse_date, protect,
+    taxonomy::term_dir,
+    virtual_page::VirtualPages,
+};
+
+/// The file name used for every generated feed
+const FEED_FILENAME: &str = "feed.xml";
+
+/// The file name of the built-in feed stylesheet, at the output root
+const STYLESHEET_FILENAME: &str = "feed.xsl";
+
+/// A stylesheet that renders Atom and RSS feeds as a readable page when they
+/// are opened in a browser
+const STYLESHEET: &str = include_str!("feed/feed.xsl");
+
+#[derive(Debug)]
+struct FeedEntry<'a> {
+    metadata: &'a Metadata,
+    updated: DateTime<FixedOffset>,
+}
+
+/// Which pages a feed is relevant to.
+#[derive(Debug, Clone)]
+enum FeedScope {
+    /// Every page on the site
+    Site,
+    /// The pages in the section at the directory
+    Section(PathBuf),
+    /// The pages listing the term of the taxonomy
+    Term { taxonomy: String, term: String },
+}
+
+/// A single feed to be written, along with the entries it contains.
+#[derive(Debug)]
+struct Feed<'a> {
+    title: String,
+    /// Path of the feed file relative to the output root
+    path: PathBuf,
+    scope: FeedScope,
+    /// URL path of the HTML page that the feed is an alternate for
+    alternate: String,
+    entries: Vec<&'a FeedEntry<'a>>,
+    content: FeedContent,
+}
+
+/// The articles in feeds, leaving out protected pages so that their content
+/// isn't published in the clear. Only warns about articles without a date when
+/// `warn_undated` is set, so that the warnings are only logged once per build.
+fn collect_entries<'a>(
+    config: &SiteConfig,
+    metadata: &'a MetadataContainer,
+    warn_undated: bool,
+) -> Vec<FeedEntry<'a>> {
+    let mut entries = vec![];
+    for (slug, md) in &metadata.0 {
+        if !md.is_article
+            || matches!(slug.stem, ContentSlugStem::Index)
+            || md.is_not_found()
+            || protect::is_protected(config, md)
+        {
+            continue;
+        }
+
+        let Some(updated) = md.date else {
+            if warn_undated {
+                warn!(%slug, "Article has no date, excluding it from feeds");
+            }
+            continue;
+        };
+
+        entries.push(FeedEntry {
+            metadata: md,
+            updated,
+        });
+    }
+
+    // Newest entries first
+    entries.sort_by_key(|entry| cmp::Reverse(entry.updated));
+
+    entries
+}
+
+fn collect_feeds<'a>(
+    config: &SiteConfig,
+    metadata: &'a MetadataContainer,
+    entries: &'a [FeedEntry<'a>],
+) -> Vec<Feed<'a>> {
+    let site_title = config.title.clone().unwrap_or_default();
+    let mut feeds = vec![Feed {
+        title: site_title.clone(),
+        path: PathBuf::from(FEED_FILENAME),
+        scope: FeedScope::Site,
+        alternate: "/".into(),
+        entries: entries.iter().collect(),
+        content: FeedContent::default(),
+    }];
+
+    // Every directory with an index page is a section with its own feed
+    for (slug, md) in &metadata.0 {
+        if !matches!(slug.stem, ContentSlugStem::Index) || slug.parent.as_os_str().is_empty() {
+            continue;
+        }
+
+        let section_entries = entries
+            .iter()
+            .filter(|entry| entry.metadata.slug.parent.starts_with(&slug.parent))
+            .collect::<Vec<_>>();
+        if section_entries.is_empty() {
+            continue;
+        }
+
+        let section_title = md
+            .title
+            .clone()
+            .unwrap_or_else(|| slug.parent.display().to_string());
+        feeds.push(Feed {
+            title: format!("{site_title} - {section_title}"),
+            path: slug.parent.join(FEED_FILENAME),
+            scope: FeedScope::Section(slug.parent.clone()),
+            alternate: md.url_path.to_string_lossy().into_owned(),
+            entries: section_entries,
+            content: FeedContent::default(),
+        });
+    }
+
+    for taxonomy in config.taxonomies.iter().filter(|taxonomy| taxonomy.feed) {
+        let mut terms: BTreeMap<PathBuf, (&str, Vec<&FeedEntry>)> = BTreeMap::new();
+        for entry in entries {
+            let entry_terms = entry
+                .metadata
+                .frontmatter_str_list(&taxonomy.name)
+                .map(|term| (term_dir(&taxonomy.name, term), term))
+                .collect::<BTreeMap<_, _>>();
+            for (term_dir, term) in entry_terms {
+                terms
+                    .entry(term_dir)
+                    .or_insert((term, vec![]))
+                    .1
+                    .push(entry);
+            }
+        }
+
+        for (term_dir, (term, term_entries)) in terms {
+            feeds.push(Feed {
+                title: format!("{site_title} - {term}"),
+                alternate: format!("/{}/", term_dir.display()),
+                path: term_dir.join(FEED_FILENAME),
+                scope: FeedScope::Term {
+                    taxonomy: taxonomy.name.clone(),
+                    term: term.to_owned(),
+                },
+                entries: term_entries,
+                content: FeedContent::default(),
+            });
+        }
+    }
+
+    feeds
+}
+
+/// A feed that pages can link to, so that feed readers can discover it.
+#[derive(Debug, Serialize)]
+pub struct FeedLink {
+    pub title: String,
+    /// URL path of the feed
+    pub url: String,
+    #[serde(skip)]
+    scope: FeedScope,
+}
+
+/// Every feed that a build writes, for finding the feeds of each page.
+pub fn feed_links(config: &SiteConfig, metadata: &MetadataContainer) -> Vec<FeedLink> {
+    if config.base_url.is_none() {
+        return vec![];
+    }
+
+    let entries = collect_entries(config, metadata, false);
+    collect_feeds(config, metadata, &entries)
+        .into_iter()
+        .map(|feed| FeedLink {
+            title: feed.title,
+            url: format!("/{}", feed.path.display()),
+            scope: feed.scope,
+        })
+        .collect()
+}
+
+/// The feeds relevant to a page: the feed of the whole site, the feeds of the
+/// sections that contain the page, and the feeds of its taxonomy terms.
+pub fn page_feeds<'a>(links: &'a [FeedLink], metadata: &Metadata) -> Vec<&'a FeedLink> {
+    links
+        .iter()
+        .filter(|link| match &link.scope {
+            FeedScope::Site => true,
+            FeedScope::Section(dir) => metadata.slug.parent.starts_with(dir),
+            // Terms that only differ in case or punctuation share a feed
+            FeedScope::Term { taxonomy, term } => {
+                let dir = term_dir(taxonomy, term);
+                metadata
+                    .frontmatter_str_list(taxonomy)
+                    .any(|page_term| term_dir(taxonomy, page_term) == dir)
+            },
+        })
+        .collect()
+}
+
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A stable ID for a feed entry, so that feed readers don't show an entry
+/// again when its URL changes.
+///
+/// This is the `id` frontmatter field if set, otherwise a tag URI built from
+/// the host of the base URL, the publish date, and the URL path of the page.
+pub fn entry_id(base_url: &str, metadata: &Metadata, published: DateTime<FixedOffset>) -> String {
+    if let Some(id) = metadata
+        .frontmatter_field("id")
+        .and_then(tera::Value::as_str)
+    {
+        return id.to_owned();
+    }
+
+    let authority = base_url
+        .split_once("://")
+        .map_or(base_url, |(_, rest)| rest);
+    let host = authority.split(['/', ':']).next().unwrap_or(authority);
+    format!(
+        "tag:{host},{}:{}",
+        published.format("%Y-%m-%d"),
+        metadata.url_path.display()
+    )
+}
+
+/// The processing instruction that links a feed to its XSL stylesheet, which is
+/// either the configured one or the built-in one.
+pub fn stylesheet_instruction(config: &SiteConfig) -> String {
+    let href = config
+        .feed_stylesheet
+        .clone()
+        .unwrap_or_else(|| format!("/{STYLESHEET_FILENAME}"));
+    format!(
+        r#"<?xml-stylesheet href="{}" type="text/xsl"?>"#,
+        escape_xml(&href)
+    )
+}
+
+/// Register the built-in feed stylesheet, unless a stylesheet is configured or
+/// one was already copied from the content directory.
+fn add_stylesheet(config: &SiteConfig, output_root: &Path, pages: &mut VirtualPages) {
+    if config.feed_stylesheet.is_some() {
+        debug!("Custom feed stylesheet configured, skipping built-in stylesheet");
+        return;
+    }
+
+    if output_root.join(STYLESHEET_FILENAME).exists() {
+        debug!("Feed stylesheet found in content, skipping built-in stylesheet");
+        return;
+    }
+
+    pages.add(STYLESHEET_FILENAME, "feed stylesheet", || {
+        Ok(STYLESHEET.to_owned())
+    });
+}
+
+fn render_feed(config: &SiteConfig, base_url: &str, feed: &Feed) -> anyhow::Result<String> {
+    let feed_url = format!("{base_url}/{}", feed.path.display());
+    let alternate_url = format!("{base_url}{}", feed.alternate);
+    let updated = feed
+        .entries
+        .iter()
+        .map(|entry| entry.updated)
+        .max()
+        .unwrap_or_default();
+
+    let mut buf = String::new();
+    writeln!(buf, r#"<?xml version="1.0" encoding="utf-8"?>"#)?;
+    writeln!(buf, "{}", stylesheet_instruction(config))?;
+    writeln!(buf, r#"<feed xmlns="http://www.w3.org/2005/Atom">"#)?;
+    writeln!(buf, "  <title>{}</title>", escape_xml(&feed.title))?;
+    writeln!(
+        buf,
+        r#"  <link href="{}" rel="self" type="application/atom+xml"/>"#,
+        escape_xml(&feed_url)
+    )?;
+    writeln!(
+        buf,
+        r#"  <link href="{}" rel="alternate" type="text/html"/>"#,
+        escape_xml(&alternate_url)
+    )?;
+    writeln!(buf, "  <id>{}</id>", escape_xml(&feed_url))?;
+    writeln!(buf, "  <updated>{}</updated>", updated.to_rfc3339())?;
+    if let Some(author) = &config.author {
+        writeln!(
+            buf,
+            "  <author><name>{}</name></author>",
+            escape_xml(author)
+        )?;
+    }
+
+    for entry in &feed.entries {
+        let md = entry.metadata;
+        let entry_url = format!("{base_url}{}", md.url_path.display());
+        let title = md.title.clone().unwrap_or_else(|| md.slug.to_string());
+
+        writeln!(buf, "  <entry>")?;
+        writeln!(buf, "    <title>{}</title>", escape_xml(&title))?;
+        writeln!(
+            buf,
+            r#"    <link href="{}" rel="alternate" type="text/html"/>"#,
+            escape_xml(&entry_url)
+        )?;
+        writeln!(
+            buf,
+            "    <id>{}</id>",
+            escape_xml(&entry_id(base_url, md, entry.updated))
+        )?;
+        writeln!(buf, "    <updated>{}</updated>", entry.updated.to_rfc3339())?;
+        for author in &md.byline {
+            writeln!(
+                buf,
+                "    <author><name>{}</name></author>",
+                escape_xml(&author.name)
+            )?;
+        }
+        for taxonomy in &config.taxonomies {
+            for term in md.frontmatter_str_list(&taxonomy.name) {
+                writeln!(
+                    buf,
+                    r#"    <category term="{}" scheme="{}"/>"#,
+                    escape_xml(term),
+                    escape_xml(&format!("{base_url}/{}/", taxonomy.name))
+                )?;
+            }
+        }
+        match feed.content {
+            FeedContent::Full => {
+                if let Some(content) = &md.rendered_content {
+                    writeln!(
+                        buf,
+                        r#"    <content type="html">{}</content>"#,
+                        escape_xml(content)
+                    )?;
+                }
+            },
+            FeedContent::Summary | FeedContent::Description => {
+                let (field, summary) = match feed.content {
+                    FeedContent::Summary => ("summary", md.summary.as_deref()),
+                    _ => (
+                        "description",
+                        md.frontmatter_field("description")
+                            .and_then(tera::Value::as_str),
+                    ),
+                };
+                // Fields rendered from djot are HTML, which feed readers are told
+                let summary_type = if config.djot_fields.iter().any(|name| name == field) {
+                    r#" type="html""#
+                } else {
+                    ""
+                };
+                if let Some(summary) = summary {
+                    writeln!(
+                        buf,
+                        "    <summary{summary_type}>{}</summary>",
+                        escape_xml(summary)
+                    )?;
+                }
+            },
+        }
+        writeln!(buf, "  </entry>")?;
+    }
+
+    writeln!(buf, "</feed>")?;
+
+    Ok(buf)
+}
+
+/// Register the global feed, one feed per section, and one feed per taxonomy
+/// term.
+///
+/// Feeds are rendered as they are registered, since they borrow the entries
+/// collected here.
+#[tracing::instrument(skip_all)]
+pub fn add_feeds(
+    config: &SiteConfig,
+    metadata: &MetadataContainer,
+    output_root: &Path,
+    pages: &mut VirtualPages,
+) -> anyhow::Result<()> {
+    let Some(base_url) = config.base_url.as_deref() else {
+        debug!("No base URL configured, skipping feed generation");
+        return Ok(());
+    };
+    let base_url = base_url.trim_end_matches('/');
+
+    add_stylesheet(config, output_root, pages);
+
+    let entries = collect_entries(config, metadata, true);
+    for mut feed in collect_feeds(config, metadata, &entries) {
+        let settings = config.feeds.settings_for(&feed.path);
+        if let Some(since) = &settings.since {
+            let since = parse_date(since).context(format!(
+                "invalid `since` date for feed [{}]",
+                feed.path.display()
+            ))?;
+            feed.entries.retain(|entry| entry.updated >= since);
+        }
+        if let Some(limit) = settings.limit {
+            feed.entries.truncate(limit);
+        }
+        feed.content = settings.content.unwrap_or_default();
+
+        let feed_content = render_feed(config, base_url, &feed)
+            .context(format!("failed to render feed [{}]", feed.path.display()))?;
+        debug!(feed_path = %feed.path.display(), num_entries = feed.entries.len(), "Rendered feed");
+
+        let description = format!("feed [{}]", feed.path.display());
+        pages.add(feed.path, description, move || Ok(feed_content));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+<?xml version="1.0" encoding="utf-8"?>
+<xsl:stylesheet version="1.0"
+  xmlns:xsl="http://www.w3.org/1999/XSL/Transform"
+  xmlns:atom="http://www.w3.org/2005/Atom">
+  <xsl:output method="html" encoding="utf-8" doctype-system="about:legacy-compat"/>
+
+  <xsl:template match="/">
+    <html lang="en">
+      <head>
+        <meta charset="utf-8"/>
+        <meta name="viewport" content="width=device-width, initial-scale=1"/>
+        <title><xsl:value-of select="atom:feed/atom:title | rss/channel/title"/> (feed)</title>
+        <style>
+          body { font-family: system-ui, sans-serif; line-height: 1.5; max-width: 40rem; margin: 2rem auto; padding: 0 1rem; }
+          .notice { background: #f4f4f4; border-radius: 0.25rem; padding: 0.5rem 1rem; }
+          ol { padding: 0; list-style: none; }
+          li { margin-bottom: 1rem; }
+          time { color: #666; font-size: 0.9rem; display: block; }
+        </style>
+      </head>
+      <body>
+        <p class="notice">
+          This is a web feed. Copy the URL from the address bar into a feed
+          reader to subscribe.
+        </p>
+        <xsl:apply-templates select="atom:feed | rss/channel"/>
+      </body>
+    </html>
+  </xsl:template>
+
+  <xsl:template match="atom:feed">
+    <h1><xsl:value-of select="atom:title"/></h1>
+    <p><a href="{atom:link[@rel='alternate']/@href}">Visit the website</a></p>
+    <ol>
+      <xsl:for-each select="atom:entry">
+        <li>
+          <a href="{atom:link[@rel='alternate']/@href}"><xsl:value-of select="atom:title"/></a>
+          <time><xsl:value-of select="substring(atom:updated, 1, 10)"/></time>
+        </li>
+      </xsl:for-each>
+    </ol>
+  </xsl:template>
+
+  <xsl:template match="rss/channel">
+    <h1><xsl:value-of select="title"/></h1>
+    <p><xsl:value-of select="description"/></p>
+    <p><a href="{link}">Visit the website</a></p>
+    <ol>
+      <xsl:for-each select="item">
+        <li>
+          <a href="{link}"><xsl:value-of select="title"/></a>
+          <time><xsl:value-of select="pubDate"/></time>
+        </li>
+      </xsl:for-each>
+    </ol>
+  </xsl:template>
+</xsl:stylesheet>
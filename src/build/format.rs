@@ -0,0 +1,214 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::build::{BuildDirFiles, config::SiteConfig, errors::FailureClass};
+
+/// The most files passed to a single run of a formatter, which keeps the
+/// command line under the length limit of the platform
+const FILES_PER_RUN: usize = 200;
+
+/// How long a formatted file stays cached after a build last used it
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// The directory in the cache of a site that formatted files are cached in,
+/// named by the hash of the formatter command, its version, and the file before
+/// it was formatted.
+fn cache_root(site_cache: &Path) -> PathBuf {
+    site_cache.join("formatted")
+}
+
+/// The output of `--version` for the program of a formatter command, so that
+/// upgrading the formatter invalidates the files it formatted before. A
+/// formatter without a version flag gets an empty version.
+fn formatter_version(command: &[String]) -> Vec<u8> {
+    let Some(program) = command.first() else {
+        return vec![];
+    };
+    match Command::new(program).arg("--version").output() {
+        Ok(output) if output.status.success() => output.stdout,
+        _ => vec![],
+    }
+}
+
+/// Remove the cached files that no build has used for longer than `MAX_AGE`.
+/// Failures are only logged, since they leave nothing but stale files behind.
+fn evict_expired(cache_root: &Path) {
+    let Ok(entries) = fs::read_dir(cache_root) else {
+        return;
+    };
+    let now = SystemTime::now();
+    let mut num_evicted = 0;
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > MAX_AGE);
+        if !expired {
+            continue;
+        }
+        match fs::remove_file(entry.path()) {
+            Ok(()) => num_evicted += 1,
+            Err(err) => {
+                debug!(path = %entry.path().display(), %err, "Failed to evict formatted file");
+            },
+        }
+    }
+    debug!(num_evicted, "Evicted expired formatted files");
+}
+
+/// An output file that needs to be formatted.
+struct Pending {
+    path: PathBuf,
+    /// Where the formatted file is cached once it is formatted
+    cached_path: PathBuf,
+}
+
+/// Format the files in the output directory in place, running the formatter
+/// configured for each extension on the files with it. Files without a
+/// formatter, like images, are left alone.
+///
+/// Since every build writes the output directory from scratch, files are only
+/// formatted if they differ from the files that earlier builds formatted with
+/// the same version of the formatter, and otherwise replaced with the cached
+/// result. Cached files that no build used for a month are removed. The files
+/// that are left are formatted by several runs of the formatter in parallel.
+pub fn format_output(
+    config: &SiteConfig,
+    site_cache: &Path,
+    output_root: &Path,
+) -> anyhow::Result<()> {
+    let output_files =
+        BuildDirFiles::gather(output_root).context("failed to collect output files")?;
+    let cache_root = cache_root(site_cache);
+    evict_expired(&cache_root);
+
+    let mut versions = BTreeMap::<Vec<String>, Vec<u8>>::new();
+    let mut pending = BTreeMap::<Vec<String>, Vec<Pending>>::new();
+    let mut num_cached = 0;
+    for (path, file) in output_files.files {
+        let Some(command) = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| config.formatter(ext))
+        else {
+            continue;
+        };
+
+        let content = fs::read(&file.full_path).context(format!(
+            "failed to read output file [{}]",
+            file.full_path.display()
+        ))?;
+        let mut hasher = Sha256::new();
+        for part in &command {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        let version = versions
+            .entry(command.clone())
+            .or_insert_with_key(|command| formatter_version(command));
+        hasher.update(&*version);
+        hasher.update([0]);
+        hasher.update(&content);
+        let cached_path = cache_root.join(format!("{:x}", hasher.finalize()));
+
+        if cached_path.is_file() {
+            fs::copy(&cached_path, &file.full_path).context(format!(
+                "failed to restore formatted file [{}]",
+                file.full_path.display()
+            ))?;
+            // Using a cached file keeps it from being evicted
+            if let Err(err) = File::options()
+                .write(true)
+                .open(&cached_path)
+                .and_then(|cached| cached.set_modified(SystemTime::now()))
+            {
+                debug!(path = %cached_path.display(), %err, "Failed to refresh formatted file");
+            }
+            num_cached += 1;
+            continue;
+        }
+        pending.entry(command).or_default().push(Pending {
+            path: file.full_path,
+            cached_path,
+        });
+    }
+
+    if !pending.is_empty() {
+        fs::create_dir_all(&cache_root).context("failed to create formatter cache directory")?;
+    }
+    let parallelism = thread::available_parallelism().map_or(1, |n| n.get());
+    let num_formatted = pending.values().map(Vec::len).sum::<usize>();
+    let runs = pending
+        .iter()
+        .flat_map(|(command, files)| {
+            let per_run = files.len().div_ceil(parallelism).clamp(1, FILES_PER_RUN);
+            files.chunks(per_run).map(move |chunk| (command, chunk))
+        })
+        .collect::<Vec<_>>();
+    // Each thread takes every n-th run, so that no more formatters than cores
+    // run at once
+    thread::scope(|scope| {
+        let handles = (0..parallelism.min(runs.len()))
+            .map(|idx| {
+                let runs = &runs;
+                scope.spawn(move || {
+                    runs.iter()
+                        .skip(idx)
+                        .step_by(parallelism)
+                        .try_for_each(|(command, files)| run_formatter(command, files))
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().expect("formatter thread does not panic"))
+    })?;
+
+    debug!(num_formatted, num_cached, "Formatted output files");
+    Ok(())
+}
+
+/// Format some files with a single run of a formatter, and cache the results.
+fn run_formatter(command: &[String], files: &[Pending]) -> anyhow::Result<()> {
+    let (program, args) = command
+        .split_first()
+        .expect("formatter commands are not empty");
+    let output = Command::new(program)
+        .args(args)
+        .args(files.iter().map(|file| &file.path))
+        .output()
+        .map_err(|err| {
+            FailureClass::Tool.wrap(format!("failed to execute formatter [{program}]"), err)
+        })?;
+
+    if !output.status.success() {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        debug!(%stdout, %stderr, "Failed formatter output");
+        return Err(FailureClass::Tool.error(format!(
+            "Execution of formatter [{program}] returned an unsuccessful status code: {}",
+            stderr.trim()
+        )));
+    }
+
+    for file in files {
+        // A failure to cache only means the file is formatted again next time
+        if let Err(err) = fs::copy(&file.path, &file.cached_path) {
+            debug!(path = %file.path.display(), %err, "Failed to cache formatted file");
+        }
+    }
+    debug!(?command, num_files = files.len(), "Ran formatter");
+    Ok(())
+}
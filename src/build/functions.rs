@@ -0,0 +1,356 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context;
+use chrono::{DateTime, FixedOffset};
+use tera::{Filter, Function, Tera, Value};
+
+use crate::build::{
+    MetadataContainer,
+    config::SiteConfig,
+    djot,
+    djot::SnippetConfig,
+    email, parse_date,
+    remote::{self, Remote},
+};
+
+/// Register the custom functions available to every template, where functions
+/// that fetch over the network only use cached responses when `remote` is
+/// offline.
+pub fn register_functions(tera: &mut Tera, config: &SiteConfig, remote: &Remote) {
+    let djot = Djot(SnippetConfig::new(config));
+    tera.register_function("env", env_function);
+    tera.register_function("qr", QrFunction);
+    tera.register_function("djot", djot.clone());
+    tera.register_function(
+        "load_remote",
+        LoadRemote {
+            remote: remote.clone(),
+        },
+    );
+    tera.register_filter("djot", djot);
+    tera.register_filter("obfuscate_email", ObfuscateEmail);
+}
+
+/// `env(name, default)` returns the value of an environment variable at build
+/// time, or `default` if it is not set.
+fn env_function(args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let Some(name) = args.get("name").and_then(Value::as_str) else {
+        return Err("`env` requires a string `name` argument".into());
+    };
+
+    match (env::var(name), args.get("default")) {
+        (Ok(value), _) => Ok(Value::String(value)),
+        (Err(env::VarError::NotPresent), Some(default)) => Ok(default.clone()),
+        (Err(err), _) => Err(format!("failed to read environment variable `{name}`: {err}").into()),
+    }
+}
+
+/// `qr(url, margin=1, level="M")` returns an inline SVG QR code of the text,
+/// generated with `qrencode`. `level` is the error correction level, one of `L`,
+/// `M`, `Q`, or `H`.
+struct QrFunction;
+
+impl Function for QrFunction {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let Some(url) = args.get("url").and_then(Value::as_str) else {
+            return Err("`qr` requires a string `url` argument".into());
+        };
+        let margin = args.get("margin").and_then(Value::as_u64).unwrap_or(1);
+        let level = args.get("level").and_then(Value::as_str).unwrap_or("M");
+        if !matches!(level, "L" | "M" | "Q" | "H") {
+            return Err(format!("`qr` level must be one of L, M, Q, or H, found `{level}`").into());
+        }
+
+        let output = Command::new("qrencode")
+            .arg("--type=SVG")
+            .arg("--inline")
+            .arg("--output=-")
+            .arg(format!("--margin={margin}"))
+            .arg(format!("--level={level}"))
+            .arg("--")
+            .arg(url)
+            .output()
+            .map_err(|err| format!("failed to execute 'qrencode': {err}"))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("Execution of 'qrencode' failed: {stderr}").into());
+        }
+
+        let svg = String::from_utf8(output.stdout)
+            .map_err(|err| format!("'qrencode' returned invalid UTF-8: {err}"))?;
+        Ok(Value::String(svg.trim().to_owned()))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// Whether the `inline` argument is set, which defaults to `false`.
+fn inline_arg(args: &HashMap<String, Value>) -> bool {
+    args.get("inline").and_then(Value::as_bool).unwrap_or(false)
+}
+
+/// Render a string of djot to HTML, either as the `djot(text, inline=false)`
+/// function or as the `djot(inline=false)` filter, for rich text that is kept
+/// in data files or the site config.
+///
+/// With `inline=true`, a single paragraph is rendered without the `<p>` around
+/// it, for use inside other elements. The text gets the punctuation, emoji, and
+/// components configured for pages, but none of the steps that need a page,
+/// like galleries or citations, see [`djot::render_snippet`].
+#[derive(Clone)]
+struct Djot(SnippetConfig);
+
+impl Function for Djot {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let Some(text) = args.get("text").and_then(Value::as_str) else {
+            return Err("`djot` requires a string `text` argument".into());
+        };
+        Ok(Value::String(djot::render_snippet(
+            &self.0,
+            text,
+            inline_arg(args),
+        )))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+impl Filter for Djot {
+    fn filter(&self, value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let Some(text) = value.as_str() else {
+            return Err("`djot` filter can only be applied to strings".into());
+        };
+        Ok(Value::String(djot::render_snippet(
+            &self.0,
+            text,
+            inline_arg(args),
+        )))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// `obfuscate_email` writes every character of an email address as a numeric
+/// character reference, which browsers show as normal but naive scrapers miss.
+/// It can be used in text and in `href="mailto:..."` attributes.
+struct ObfuscateEmail;
+
+impl Filter for ObfuscateEmail {
+    fn filter(&self, value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let Some(text) = value.as_str() else {
+            return Err("`obfuscate_email` filter can only be applied to strings".into());
+        };
+        Ok(Value::String(email::encode_entities(text)))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// `load_remote(url, format="text")` fetches a URL at build time, returning
+/// its body as a string, or parsed when `format` is `json`. Responses are
+/// cached between builds, and only the cache is used when building offline.
+struct LoadRemote {
+    remote: Remote,
+}
+
+impl Function for LoadRemote {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let Some(url) = args.get("url").and_then(Value::as_str) else {
+            return Err("`load_remote` requires a string `url` argument".into());
+        };
+        let format = args.get("format").and_then(Value::as_str).unwrap_or("text");
+        if !matches!(format, "text" | "json") {
+            return Err(
+                format!("`load_remote` format must be `text` or `json`, found `{format}`").into(),
+            );
+        }
+
+        let body = remote::fetch(&self.remote, url)
+            .map_err(|err| tera::Error::msg(format!("`load_remote` failed: {err:#}")))?;
+        match format {
+            "json" => serde_json::from_str(&body).map_err(|err| {
+                tera::Error::msg(format!(
+                    "`load_remote` response of `{url}` is not JSON: {err}"
+                ))
+            }),
+            _ => Ok(Value::String(body)),
+        }
+    }
+}
+
+/// Register the `pages` function, which needs the metadata of every page and
+/// so can only be added once all content is rendered. The 404 page is never
+/// listed.
+pub fn register_pages(tera: &mut Tera, metadata: &MetadataContainer) -> anyhow::Result<()> {
+    let pages = metadata
+        .0
+        .values()
+        .filter(|md| !md.is_not_found())
+        .map(|md| {
+            Ok(QueriedPage {
+                value: serde_json::to_value(md).context("failed to serialize page metadata")?,
+                date: md.date,
+                tags: md.tags.clone(),
+                section: md.slug.parent.clone(),
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+    tera.register_function("pages", Pages { pages });
+    Ok(())
+}
+
+/// A page as seen by the `pages` function.
+struct QueriedPage {
+    value: Value,
+    date: Option<DateTime<FixedOffset>>,
+    tags: Vec<String>,
+    /// The content directory of the page
+    section: PathBuf,
+}
+
+/// `pages(where={}, section, tag, after, before, sort, reverse=false, limit)`
+/// returns the pages of the site that match every filter that is given, for
+/// listings like all posts tagged `rust` from 2024:
+///
+/// - `where` is an object of fields that pages must have, like `{"series":
+///   "intro"}`, where a field that is a list only needs to contain the value.
+///   Since Tera has no object literals, fields can also be given as any other
+///   argument, like `pages(series="intro")`
+/// - `section` is a content directory, like `blog`, that pages must be in,
+///   directly or in a subdirectory
+/// - `tag` is a tag that pages must have
+/// - `after` and `before` are dates that the date of pages must be on or after,
+///   and before, which leaves out pages without a date
+/// - `sort` is the field that pages are sorted by, like `date` or `title`, with
+///   pages that don't have it last, and `reverse` sorts them the other way
+///   around
+/// - `limit` is the most pages that are returned
+struct Pages {
+    pages: Vec<QueriedPage>,
+}
+
+impl Pages {
+    /// The arguments that aren't field filters
+    const ARGS: &[&str] = &[
+        "where", "section", "tag", "after", "before", "sort", "reverse", "limit",
+    ];
+
+    fn date_arg(
+        args: &HashMap<String, Value>,
+        name: &str,
+    ) -> tera::Result<Option<DateTime<FixedOffset>>> {
+        let Some(value) = args.get(name) else {
+            return Ok(None);
+        };
+        let Some(date) = value.as_str() else {
+            return Err(format!("`pages` argument `{name}` must be a date string").into());
+        };
+        parse_date(date)
+            .map(Some)
+            .map_err(|err| format!("`pages` argument `{name}` is invalid: {err}").into())
+    }
+}
+
+impl Function for Pages {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let mut fields = match args.get("where") {
+            None => serde_json::Map::new(),
+            Some(Value::Object(fields)) => fields.clone(),
+            Some(_) => return Err("`pages` argument `where` must be an object".into()),
+        };
+        fields.extend(
+            args.iter()
+                .filter(|(name, _)| !Self::ARGS.contains(&name.as_str()))
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+        let section = args.get("section").and_then(Value::as_str).map(Path::new);
+        let tag = args.get("tag").and_then(Value::as_str);
+        let after = Self::date_arg(args, "after")?;
+        let before = Self::date_arg(args, "before")?;
+
+        let mut pages = self
+            .pages
+            .iter()
+            .filter(|page| section.is_none_or(|section| page.section.starts_with(section)))
+            .filter(|page| tag.is_none_or(|tag| page.tags.iter().any(|t| t == tag)))
+            .filter(|page| after.is_none_or(|after| page.date.is_some_and(|date| date >= after)))
+            .filter(|page| before.is_none_or(|before| page.date.is_some_and(|date| date < before)))
+            .filter(|page| {
+                fields
+                    .iter()
+                    .all(|(name, expected)| match page.value.get(name) {
+                        Some(Value::Array(values)) if !expected.is_array() => {
+                            values.contains(expected)
+                        },
+                        Some(value) => value == expected,
+                        None => false,
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(sort) = args.get("sort") {
+            let Some(sort) = sort.as_str() else {
+                return Err("`pages` argument `sort` must be a field name".into());
+            };
+            let reverse = args
+                .get("reverse")
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+            pages.sort_by(|a, b| match (a.value.get(sort), b.value.get(sort)) {
+                (Some(a_value), Some(b_value)) => {
+                    // Dates are compared as dates, since they can be written with
+                    // or without a time
+                    let ordering = match (sort, a.date, b.date) {
+                        ("date", Some(a), Some(b)) => a.cmp(&b),
+                        _ => compare_values(a_value, b_value),
+                    };
+                    if reverse {
+                        ordering.reverse()
+                    } else {
+                        ordering
+                    }
+                },
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            });
+        }
+        if let Some(limit) = args.get("limit") {
+            let Some(limit) = limit.as_u64() else {
+                return Err("`pages` argument `limit` must be a positive number".into());
+            };
+            pages.truncate(limit as usize);
+        }
+
+        Ok(Value::Array(
+            pages.into_iter().map(|page| page.value.clone()).collect(),
+        ))
+    }
+}
+
+/// Order two field values, comparing numbers by value and everything else,
+/// like dates in frontmatter, by its text.
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        _ => match (a.as_str(), b.as_str()) {
+            (Some(a), Some(b)) => a.cmp(b),
+            _ => a.to_string().cmp(&b.to_string()),
+        },
+    }
+}
@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::bail;
+use serde::Serialize;
+
+use crate::build::{SiteTemplateContext, TemplateRenderer, virtual_page::VirtualPages};
+
+#[derive(Debug, Serialize)]
+struct GeneratedPageContext<'a> {
+    title: Option<&'a str>,
+    url_path: &'a str,
+    /// The `extra` values configured for this page
+    page: &'a serde_json::Map<String, serde_json::Value>,
+    release: bool,
+    #[serde(flatten)]
+    site: &'a SiteTemplateContext<'a>,
+}
+
+/// Register every page declared in the `generated_pages` config, which have no
+/// content file and are produced by their template from the site metadata
+/// alone.
+pub fn add_generated_pages<'a>(
+    renderer: &'a TemplateRenderer<'a>,
+    pages: &mut VirtualPages<'a>,
+) -> anyhow::Result<()> {
+    let args = renderer.args;
+
+    for (url_path, page) in &renderer.config.generated_pages {
+        let Some(template) = renderer.templates.find_named_template(&[&page.template]) else {
+            bail!(
+                "template [{}] for generated page [{url_path}] does not exist",
+                page.template.display()
+            );
+        };
+
+        let relative_path = url_path.trim_start_matches('/');
+        let path = if relative_path.is_empty() || relative_path.ends_with('/') {
+            Path::new(relative_path).join("index.html")
+        } else {
+            PathBuf::from(relative_path)
+        };
+        let output_path = args.output_path.join(&path);
+        if output_path.exists() {
+            bail!(
+                "generated page [{url_path}] would overwrite [{}], which was already written",
+                output_path.display()
+            );
+        }
+
+        let context = GeneratedPageContext {
+            title: page.title.as_deref(),
+            url_path,
+            page: &page.extra,
+            release: args.release,
+            site: renderer.site,
+        };
+        pages.add_template(
+            renderer,
+            template,
+            context,
+            path,
+            format!("generated page [{url_path}]"),
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::{
+    collections::BTreeSet,
+    ffi::OsString,
+    path::{self, Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context;
+use tracing::debug;
+
+use crate::build::{BuildCmd, errors::FailureClass};
+
+/// The environment variables passed to hook commands, which describe the
+/// build they run in.
+///
+/// Paths are made absolute, since the commands run from the input directory.
+#[derive(Debug, Clone)]
+pub struct HookEnv {
+    input_path: PathBuf,
+    vars: Vec<(&'static str, OsString)>,
+}
+
+impl HookEnv {
+    /// The variables of a build, with the content files whose pages are
+    /// written if only the pages affected by a change are built, like by
+    /// `serve`.
+    pub fn new(args: &BuildCmd, changed_files: Option<&BTreeSet<PathBuf>>) -> Self {
+        let mut vars = vec![
+            ("WWW_INPUT_PATH", absolute(&args.input_path)),
+            ("WWW_OUTPUT_PATH", absolute(&args.output_path)),
+            ("WWW_RELEASE", args.release.to_string().into()),
+        ];
+        // One path per line, which is left unset when the whole site is built
+        if let Some(changed_files) = changed_files {
+            let mut joined = OsString::new();
+            for path in changed_files {
+                joined.push(absolute(path));
+                joined.push("\n");
+            }
+            vars.push(("WWW_CHANGED_FILES", joined));
+        }
+        Self {
+            input_path: args.input_path.clone(),
+            vars,
+        }
+    }
+
+    /// The variables of a build along with those of a page that was just
+    /// written.
+    pub fn with_page(&self, input_path: &Path, output_path: &Path, url_path: &Path) -> Self {
+        let mut env = self.clone();
+        env.vars.extend([
+            ("WWW_PAGE_INPUT_PATH", absolute(input_path)),
+            ("WWW_PAGE_OUTPUT_PATH", absolute(output_path)),
+            ("WWW_PAGE_URL", url_path.into()),
+        ]);
+        env
+    }
+}
+
+/// The path made absolute, or as is if the working directory is unknown.
+fn absolute(path: &Path) -> OsString {
+    path::absolute(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .into_os_string()
+}
+
+/// Run the commands of a hook in order from the input directory, stopping at
+/// the first one that fails.
+pub fn run_hook(hook: &str, commands: &[Vec<String>], env: &HookEnv) -> anyhow::Result<()> {
+    for command in commands {
+        let Some((program, args)) = command.split_first() else {
+            continue;
+        };
+        debug!(hook, ?command, "Running hook command");
+
+        let output = Command::new(program)
+            .args(args)
+            .current_dir(&env.input_path)
+            .env("WWW_HOOK", hook)
+            .envs(env.vars.iter().map(|(name, value)| (name, value)))
+            .output()
+            .map_err(|err| FailureClass::Tool.wrap(format!("failed to execute [{program}]"), err))
+            .context(format!("failed to run {hook} hook"))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        debug!(hook, %stdout, "Hook command output");
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let mut message = format!("{hook} hook [{program}] failed with {}", output.status);
+            if !stderr.trim().is_empty() {
+                message.push_str(&format!(": {}", stderr.trim()));
+            }
+            return Err(FailureClass::Tool.error(message));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use std::path::Path;
+
+use anyhow::bail;
+
+use crate::build::{config::SiteConfig, virtual_page::VirtualPages};
+
+/// The file that GitHub Pages reads the custom domain of a site from
+const CNAME_FILENAME: &str = "CNAME";
+
+/// Register the host configuration files for the configured custom domain.
+pub fn add_host_files(
+    config: &SiteConfig,
+    output_root: &Path,
+    pages: &mut VirtualPages,
+) -> anyhow::Result<()> {
+    let Some(domain) = &config.custom_domain else {
+        return Ok(());
+    };
+    if domain.contains(['/', ':']) || domain.trim().is_empty() {
+        bail!("custom domain [{domain}] must be a bare host name, like `example.com`");
+    }
+    if output_root.join(CNAME_FILENAME).exists() {
+        bail!(
+            "a custom domain is configured, so the `{CNAME_FILENAME}` file in the content \
+             directory should be removed"
+        );
+    }
+
+    let content = format!("{}\n", domain.trim());
+    pages.add(CNAME_FILENAME, "CNAME file", move || Ok(content));
+
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, bail};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::build::{Metadata, MetadataContainer, config::SiteConfig};
+
+/// The file name of the calendar containing every event on the site
+const SITE_CALENDAR_FILENAME: &str = "events.ics";
+
+/// The `event` frontmatter field of a page.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EventFrontmatter {
+    start: String,
+    end: Option<String>,
+    location: Option<String>,
+}
+
+/// A point in time for an event, which is either a whole day or a specific
+/// instant.
+#[derive(Debug, Clone, Copy)]
+enum EventTime {
+    Date(NaiveDate),
+    DateTime(DateTime<Utc>),
+}
+
+impl EventTime {
+    fn parse(time: &str) -> anyhow::Result<Self> {
+        if let Ok(timestamp) = DateTime::parse_from_rfc3339(time) {
+            return Ok(Self::DateTime(timestamp.to_utc()));
+        }
+
+        let Ok(date) = NaiveDate::parse_from_str(time, "%Y-%m-%d") else {
+            bail!("event time [{time}] is not an RFC 3339 timestamp or YYYY-MM-DD date")
+        };
+
+        Ok(Self::Date(date))
+    }
+
+    fn write_property(&self, buf: &mut String, name: &str) {
+        match self {
+            Self::Date(date) => {
+                write_line(buf, &format!("{name};VALUE=DATE:{}", date.format("%Y%m%d")))
+            },
+            Self::DateTime(timestamp) => write_line(
+                buf,
+                &format!("{name}:{}", timestamp.format("%Y%m%dT%H%M%SZ")),
+            ),
+        }
+    }
+
+    fn as_utc(&self) -> DateTime<Utc> {
+        match self {
+            Self::Date(date) => date.and_time(Default::default()).and_utc(),
+            Self::DateTime(timestamp) => *timestamp,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Event<'a> {
+    metadata: &'a Metadata,
+    start: EventTime,
+    end: Option<EventTime>,
+    location: Option<String>,
+}
+
+fn collect_events(metadata: &MetadataContainer) -> anyhow::Result<Vec<Event<'_>>> {
+    let mut events = vec![];
+    for (slug, md) in &metadata.0 {
+        let Some(event) = md.frontmatter_field("event") else {
+            continue;
+        };
+        let event: EventFrontmatter = serde_json::from_value(event.clone())
+            .context(format!("invalid 'event' frontmatter in [{slug}]"))?;
+
+        let start =
+            EventTime::parse(&event.start).context(format!("invalid event start for [{slug}]"))?;
+        let end = event
+            .end
+            .as_deref()
+            .map(EventTime::parse)
+            .transpose()
+            .context(format!("invalid event end for [{slug}]"))?;
+
+        events.push(Event {
+            metadata: md,
+            start,
+            end,
+            location: event.location,
+        });
+    }
+
+    events.sort_by_key(|event| event.start.as_utc());
+
+    Ok(events)
+}
+
+/// Escape a text value according to RFC 5545 section 3.3.11
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {},
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Write a single content line, folding it so that no line is longer than 75
+/// octets as required by RFC 5545 section 3.1
+fn write_line(buf: &mut String, line: &str) {
+    let mut line_len = 0;
+    for c in line.chars() {
+        if line_len + c.len_utf8() > 75 {
+            buf.push_str("\r\n ");
+            line_len = 1;
+        }
+        buf.push(c);
+        line_len += c.len_utf8();
+    }
+    buf.push_str("\r\n");
+}
+
+fn write_event(buf: &mut String, config: &SiteConfig, host: &str, event: &Event) {
+    let md = event.metadata;
+    let title = md.title.clone().unwrap_or_else(|| md.slug.to_string());
+
+    write_line(buf, "BEGIN:VEVENT");
+    write_line(buf, &format!("UID:{}@{host}", md.url_path.display()));
+    // The stamp has to be stable between builds, otherwise calendar apps would
+    // see every event as modified on each deploy
+    write_line(
+        buf,
+        &format!("DTSTAMP:{}", event.start.as_utc().format("%Y%m%dT%H%M%SZ")),
+    );
+    event.start.write_property(buf, "DTSTART");
+    if let Some(end) = &event.end {
+        end.write_property(buf, "DTEND");
+    }
+    write_line(buf, &format!("SUMMARY:{}", escape_text(&title)));
+    if let Some(location) = &event.location {
+        write_line(buf, &format!("LOCATION:{}", escape_text(location)));
+    }
+    if let Some(base_url) = &config.base_url {
+        let url = format!(
+            "{}{}",
+            base_url.trim_end_matches('/'),
+            md.url_path.display()
+        );
+        write_line(buf, &format!("URL:{url}"));
+    }
+    write_line(buf, "END:VEVENT");
+}
+
+fn render_calendar(
+    config: &SiteConfig,
+    host: &str,
+    name: Option<&str>,
+    events: &[&Event],
+) -> String {
+    let mut buf = String::new();
+    write_line(&mut buf, "BEGIN:VCALENDAR");
+    write_line(&mut buf, "VERSION:2.0");
+    write_line(&mut buf, &format!("PRODID:-//{host}//www//EN"));
+    write_line(&mut buf, "CALSCALE:GREGORIAN");
+    if let Some(name) = name {
+        write_line(&mut buf, &format!("X-WR-CALNAME:{}", escape_text(name)));
+    }
+    for event in events {
+        write_event(&mut buf, config, host, event);
+    }
+    write_line(&mut buf, "END:VCALENDAR");
+
+    buf
+}
+
+/// Write a calendar file for each page with `event` frontmatter next to the
+/// page, plus a site-wide calendar containing all of them.
+#[tracing::instrument(skip_all)]
+pub fn write_calendars(
+    config: &SiteConfig,
+    metadata: &MetadataContainer,
+    output_root: &Path,
+) -> anyhow::Result<()> {
+    let events = collect_events(metadata).context("failed to collect events")?;
+    if events.is_empty() {
+        debug!("No event pages found, skipping calendar generation");
+        return Ok(());
+    }
+
+    // The host is only used to make event UIDs globally unique, so fall back to
+    // something reasonable when no base URL is configured
+    let host = config
+        .base_url
+        .as_deref()
+        .map(|base_url| {
+            base_url
+                .trim_start_matches("https://")
+                .trim_start_matches("http://")
+                .trim_end_matches('/')
+        })
+        .unwrap_or("localhost");
+
+    for event in &events {
+        let calendar = render_calendar(config, host, None, &[event]);
+
+        let mut calendar_path = output_root.join(event.metadata.slug.as_path());
+        calendar_path.set_extension("ics");
+        // The event page itself isn't written by a partial build
+        if let Some(parent) = calendar_path.parent() {
+            fs::create_dir_all(parent).context("failed to create parent directory for calendar")?;
+        }
+        fs::write(&calendar_path, calendar).context(format!(
+            "failed to write event calendar [{}]",
+            calendar_path.display()
+        ))?;
+        debug!(calendar_path = %calendar_path.display(), "Written event calendar");
+    }
+
+    let all_events = events.iter().collect::<Vec<_>>();
+    let calendar = render_calendar(config, host, config.title.as_deref(), &all_events);
+    let calendar_path = output_root.join(SITE_CALENDAR_FILENAME);
+    fs::write(&calendar_path, calendar).context(format!(
+        "failed to write site calendar [{}]",
+        calendar_path.display()
+    ))?;
+    debug!(calendar_path = %calendar_path.display(), num_events = events.len(), "Written site calendar");
+
+    Ok(())
+}
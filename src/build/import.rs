@@ -0,0 +1,590 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, bail};
+use argh::{ArgsInfo, FromArgs};
+use serde_json::{Map, Value};
+use tracing::{debug, info, warn};
+
+use crate::build::import::obsidian::Vault;
+
+mod frontmatter;
+mod markdown;
+mod obsidian;
+mod toml;
+mod yaml;
+
+/// Convert the content of a site made with another static site generator into
+/// a new site for this one.
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(subcommand, name = "import")]
+pub struct ImportCmd {
+    /// the generator the site was made with, one of `zola`, `hugo`, or
+    /// `jekyll`, or `obsidian` for an Obsidian vault
+    #[argh(positional)]
+    flavor: ImportFlavor,
+
+    /// path to the site or vault to import
+    #[argh(positional)]
+    source_path: PathBuf,
+
+    /// path to the directory to write the new site to, which must not contain
+    /// any of the converted files yet
+    #[argh(positional)]
+    output_path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFlavor {
+    Zola,
+    Hugo,
+    Jekyll,
+    Obsidian,
+}
+
+impl FromStr for ImportFlavor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zola" => Ok(ImportFlavor::Zola),
+            "hugo" => Ok(ImportFlavor::Hugo),
+            "jekyll" => Ok(ImportFlavor::Jekyll),
+            "obsidian" => Ok(ImportFlavor::Obsidian),
+            _ => Err(format!(
+                "unknown generator [{s}], expected `zola`, `hugo`, `jekyll`, or `obsidian`"
+            )),
+        }
+    }
+}
+
+/// What to do with a single file of the imported site.
+#[derive(Debug)]
+enum Action {
+    /// Convert a Markdown page to Djot
+    Convert {
+        /// The directory the assets of a page bundle were moved to
+        bundle: Option<String>,
+        /// Frontmatter fields that come from the file layout, like the date of
+        /// a Jekyll post
+        fields: Map<String, Value>,
+    },
+    Copy,
+    /// Write generated content, like the site config
+    Write(String),
+}
+
+/// The files of the new site, keyed by their path relative to the output
+/// directory, along with the file each one comes from.
+type Plan = BTreeMap<PathBuf, (PathBuf, Action)>;
+
+/// Resolve the backslash escapes of a double quoted string in front matter.
+///
+/// This covers the escapes of both TOML and YAML, since neither uses an escape
+/// the other reads differently: `\0` is only valid in YAML, and a TOML parser
+/// rejects it rather than reading it as anything else.
+fn unescape(content: &str) -> String {
+    let mut unescaped = String::with_capacity(content.len());
+    let mut chars = content.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('0') => unescaped.push('\0'),
+            Some('u') => {
+                let hex = chars.by_ref().take(4).collect::<String>();
+                if let Some(c) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    unescaped.push(c);
+                }
+            },
+            Some(other) => unescaped.push(other),
+            None => {},
+        }
+    }
+    unescaped
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "md" || ext == "markdown")
+}
+
+/// Every file below a directory, relative to it and in a stable order.
+fn list_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    fn visit(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+        let mut entries = fs::read_dir(dir)
+            .context(format!("failed to read [{}] directory", dir.display()))?
+            .collect::<Result<Vec<_>, _>>()
+            .context(format!(
+                "failed to read directory entry in [{}]",
+                dir.display()
+            ))?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let path = entry.path();
+            if path.is_dir() {
+                visit(root, &path, files)?;
+            } else {
+                files.push(path.strip_prefix(root)?.to_path_buf());
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = vec![];
+    if dir.is_dir() {
+        visit(dir, dir, &mut files)?;
+    }
+    Ok(files)
+}
+
+fn add(plan: &mut Plan, dest: PathBuf, source: PathBuf, action: Action) -> anyhow::Result<()> {
+    if let Some((existing, _)) = plan.get(&dest) {
+        bail!(
+            "both [{}] and [{}] would be written to [{}]",
+            existing.display(),
+            source.display(),
+            dest.display()
+        );
+    }
+    plan.insert(dest, (source, action));
+    Ok(())
+}
+
+/// Plan the content of a Zola or Hugo site, which both keep pages in
+/// `content/` and static files in `static/`.
+///
+/// Section pages (`_index.md`) become index pages. A page bundle, which is a
+/// directory with an `index.md` and the page's assets, becomes a page named
+/// after the directory with the assets in a directory of the same name.
+fn plan_content_dir(source: &Path, plan: &mut Plan) -> anyhow::Result<()> {
+    let content_dir = source.join("content");
+    let files = list_files(&content_dir)?;
+    for file in &files {
+        let source_file = content_dir.join(file);
+        let parent = file.parent().unwrap_or(Path::new(""));
+        let name = file.file_name().unwrap_or_default();
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let is_bundle = name == "index.md"
+            && !parent.as_os_str().is_empty()
+            && !files.contains(&parent.join("_index.md"));
+
+        let (dest, action) = if !is_markdown(file) {
+            (file.clone(), Action::Copy)
+        } else if name == "_index.md" || name == "index.md" && !is_bundle {
+            (
+                parent.join("index.dj"),
+                Action::Convert {
+                    bundle: None,
+                    fields: Map::new(),
+                },
+            )
+        } else if is_bundle {
+            let bundle = parent
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            (
+                parent.with_extension("dj"),
+                Action::Convert {
+                    bundle: Some(bundle),
+                    fields: Map::new(),
+                },
+            )
+        } else {
+            (
+                file.with_extension("dj"),
+                Action::Convert {
+                    bundle: None,
+                    fields: Map::new(),
+                },
+            )
+        };
+        add(plan, Path::new("content").join(dest), source_file, action)?;
+    }
+
+    for file in list_files(&source.join("static"))? {
+        add(
+            plan,
+            Path::new("content").join(&file),
+            source.join("static").join(&file),
+            Action::Copy,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Plan the content of a Jekyll site, where posts live in `_posts` with the
+/// date in their file name and every other page sits where it is served from.
+///
+/// Posts and drafts are moved to `blog/`.
+fn plan_jekyll(source: &Path, plan: &mut Plan) -> anyhow::Result<()> {
+    const IGNORED: [&str; 5] = ["Gemfile", "Gemfile.lock", "vendor", "node_modules", "_site"];
+
+    for file in list_files(source)? {
+        let source_file = source.join(&file);
+        let mut components = file.components().map(|c| c.as_os_str().to_string_lossy());
+        let first = components.next().unwrap_or_default();
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        if first.starts_with('.') || IGNORED.contains(&first.as_ref()) || name.starts_with('.') {
+            continue;
+        }
+
+        let (dest, action) = match first.as_ref() {
+            "_posts" | "_drafts" => {
+                if !is_markdown(&file) {
+                    warn!(path = %file.display(), "Skipping post that is not Markdown");
+                    continue;
+                }
+                let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+                let mut fields = Map::new();
+                let slug = match markdown::jekyll_post_slug(&stem) {
+                    Some(slug) => {
+                        fields.insert("date".to_owned(), Value::String(stem[..10].to_owned()));
+                        slug
+                    },
+                    None => &stem,
+                };
+                if first == "_drafts" {
+                    fields.insert("draft".to_owned(), Value::Bool(true));
+                }
+                (
+                    Path::new("blog").join(format!("{slug}.dj")),
+                    Action::Convert {
+                        bundle: None,
+                        fields,
+                    },
+                )
+            },
+            first if first.starts_with('_') => {
+                if file.components().count() == 1 {
+                    // Like `_config.yml`, which is read for the site config
+                    debug!(path = %file.display(), "Skipping Jekyll file");
+                } else {
+                    debug!(path = %file.display(), "Skipping file in Jekyll directory");
+                }
+                continue;
+            },
+            _ if is_markdown(&file) => (
+                file.with_extension("dj"),
+                Action::Convert {
+                    bundle: None,
+                    fields: Map::new(),
+                },
+            ),
+            _ => {
+                let content = fs::read(&source_file)
+                    .context(format!("failed to read [{}]", source_file.display()))?;
+                if content.starts_with(b"---\n") || content.starts_with(b"---\r\n") {
+                    warn!(
+                        path = %file.display(),
+                        "Skipping file with front matter, which is a Liquid template that can't be converted"
+                    );
+                    continue;
+                }
+                (file.clone(), Action::Copy)
+            },
+        };
+        add(plan, Path::new("content").join(dest), source_file, action)?;
+    }
+
+    Ok(())
+}
+
+/// Plan the notes of an Obsidian vault, which keep their folders. Every path is
+/// turned into a slug, and the notes and attachments are added to the vault
+/// index that wiki links are resolved against.
+fn plan_obsidian(source: &Path, plan: &mut Plan, vault: &mut Vault) -> anyhow::Result<()> {
+    for file in list_files(source)? {
+        // Like the `.obsidian` settings and the `.trash` folder
+        if file
+            .components()
+            .any(|component| component.as_os_str().to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+
+        let source_file = source.join(&file);
+        let dest = obsidian::dest_path(&file);
+        if is_markdown(&file) {
+            let dest = dest.with_extension("dj");
+            let content = fs::read_to_string(&source_file)
+                .context(format!("failed to read [{}]", source_file.display()))?;
+            let (fields, _) = frontmatter::split(&content).context(format!(
+                "failed to read frontmatter of [{}]",
+                file.display()
+            ))?;
+            let aliases = match fields.get("aliases") {
+                Some(Value::String(alias)) => vec![alias.clone()],
+                Some(Value::Array(aliases)) => aliases
+                    .iter()
+                    .filter_map(|alias| alias.as_str().map(str::to_owned))
+                    .collect(),
+                _ => vec![],
+            };
+            vault.add_note(&file, &aliases, &dest);
+            add(
+                plan,
+                Path::new("content").join(dest),
+                source_file,
+                Action::Convert {
+                    bundle: None,
+                    fields: Map::new(),
+                },
+            )?;
+        } else {
+            vault.add_attachment(&file, &dest);
+            add(
+                plan,
+                Path::new("content").join(dest),
+                source_file,
+                Action::Copy,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Warn about the directories of the imported site that are not converted,
+/// like templates, which need to be rewritten by hand.
+fn warn_skipped_dirs(flavor: ImportFlavor, source: &Path) {
+    let dirs: &[&str] = match flavor {
+        ImportFlavor::Zola => &["templates", "sass", "themes"],
+        ImportFlavor::Hugo => &["layouts", "assets", "data", "i18n", "themes"],
+        ImportFlavor::Jekyll => &["_layouts", "_includes", "_sass", "_data"],
+        ImportFlavor::Obsidian => &[],
+    };
+    for dir in dirs {
+        if source.join(dir).is_dir() {
+            warn!(dir, "Skipping directory, which has to be converted by hand");
+        }
+    }
+}
+
+/// Read a config file as TOML or YAML, depending on its extension.
+fn read_config(path: &Path) -> anyhow::Result<Map<String, Value>> {
+    let content =
+        fs::read_to_string(path).context(format!("failed to read [{}]", path.display()))?;
+    let value = if path.extension().is_some_and(|ext| ext == "toml") {
+        toml::parse(&content)?
+    } else {
+        yaml::parse(&content)?
+    };
+    match value {
+        Value::Object(config) => Ok(config),
+        _ => bail!("config must be a table of fields"),
+    }
+}
+
+/// Build the config of the new site from the title, base URL, and author of
+/// the imported site, or `None` if the imported site has no config.
+fn site_config(flavor: ImportFlavor, source: &Path) -> anyhow::Result<Option<String>> {
+    let candidates: &[&str] = match flavor {
+        ImportFlavor::Zola => &["config.toml", "zola.toml"],
+        ImportFlavor::Hugo => &["hugo.toml", "hugo.yaml", "config.toml", "config.yaml"],
+        ImportFlavor::Jekyll => &["_config.yml", "_config.yaml"],
+        ImportFlavor::Obsidian => return Ok(None),
+    };
+    let Some(path) = candidates
+        .iter()
+        .map(|name| source.join(name))
+        .find(|path| path.is_file())
+    else {
+        return Ok(None);
+    };
+    let config = read_config(&path).context(format!("failed to parse [{}]", path.display()))?;
+
+    let string = |key: &str| config.get(key).and_then(Value::as_str).map(str::to_owned);
+    let base_url = match flavor {
+        ImportFlavor::Zola => string("base_url"),
+        ImportFlavor::Hugo => string("baseURL").or_else(|| string("baseurl")),
+        ImportFlavor::Jekyll => {
+            string("url").map(|url| format!("{url}{}", string("baseurl").unwrap_or_default()))
+        },
+        ImportFlavor::Obsidian => None,
+    };
+    let author = match config.get("author") {
+        Some(Value::String(author)) => Some(author.clone()),
+        Some(Value::Object(author)) => author
+            .get("name")
+            .and_then(Value::as_str)
+            .map(str::to_owned),
+        _ => None,
+    };
+
+    let mut site = Map::new();
+    for (key, value) in [
+        ("base_url", base_url),
+        ("title", string("title")),
+        ("author", author),
+    ] {
+        if let Some(value) = value {
+            site.insert(key.to_owned(), Value::String(value));
+        }
+    }
+    Ok(Some(serde_json::to_string_pretty(&site)? + "\n"))
+}
+
+/// Convert a single Markdown page, returning the converted document.
+fn convert(
+    flavor: ImportFlavor,
+    source_file: &Path,
+    bundle: Option<&str>,
+    vault: Option<&Vault>,
+    layout_fields: &Map<String, Value>,
+) -> anyhow::Result<markdown::Converted> {
+    let content = fs::read_to_string(source_file)
+        .context(format!("failed to read [{}]", source_file.display()))?;
+    let (mut fields, mut body) = frontmatter::split(&content)?;
+    for (key, value) in layout_fields {
+        if !fields.contains_key(key) {
+            fields.insert(key.clone(), value.clone());
+        }
+    }
+    let (mut fields, mut title) = frontmatter::normalize(fields);
+
+    // Obsidian shows the name of a note as its title, unless it starts with a
+    // heading
+    if flavor == ImportFlavor::Obsidian && title.is_none() {
+        let trimmed = body.trim_start();
+        match trimmed.strip_prefix("# ") {
+            Some(heading) => {
+                let (heading, rest) = heading.split_once('\n').unwrap_or((heading, ""));
+                title = Some(heading.trim().to_owned());
+                body = rest;
+            },
+            None => {
+                let stem = source_file.file_stem().unwrap_or_default();
+                title = Some(stem.to_string_lossy().into_owned());
+            },
+        }
+    }
+
+    let mut converted = markdown::to_djot(body, flavor, bundle, vault, title.is_some());
+    if !converted.tags.is_empty() {
+        let tags = fields.entry("tags").or_insert_with(|| Value::Array(vec![]));
+        if let Value::Array(tags) = tags {
+            for tag in &converted.tags {
+                if !tags.iter().any(|existing| existing.as_str() == Some(tag)) {
+                    tags.push(Value::String(tag.clone()));
+                }
+            }
+        }
+    }
+    converted.content = frontmatter::write(&fields, title.as_deref(), &converted.content);
+
+    Ok(converted)
+}
+
+/// Convert another generator's site or an Obsidian vault into the layout of
+/// this one: pages become Djot files with JSON frontmatter, shortcodes become
+/// stubs that can be mapped to components, wiki links become links to the
+/// converted notes, and static files are copied into the content.
+///
+/// Nothing is written if any of the converted files already exist in the
+/// output directory.
+pub fn import(cmd: ImportCmd) -> anyhow::Result<()> {
+    let source = &cmd.source_path;
+    if !source.is_dir() {
+        bail!("[{}] is not a directory", source.display());
+    }
+
+    let mut plan = Plan::new();
+    let mut vault = Vault::default();
+    match cmd.flavor {
+        ImportFlavor::Zola | ImportFlavor::Hugo => plan_content_dir(source, &mut plan)?,
+        ImportFlavor::Jekyll => plan_jekyll(source, &mut plan)?,
+        ImportFlavor::Obsidian => plan_obsidian(source, &mut plan, &mut vault)?,
+    }
+    let vault = (cmd.flavor == ImportFlavor::Obsidian).then_some(&vault);
+    match site_config(cmd.flavor, source) {
+        Ok(Some(config)) => add(
+            &mut plan,
+            "site.json".into(),
+            source.clone(),
+            Action::Write(config),
+        )?,
+        Ok(None) => debug!("No config found in imported site"),
+        Err(err) => warn!("Skipping the config of the imported site: {err:#}"),
+    }
+    warn_skipped_dirs(cmd.flavor, source);
+
+    let existing = plan
+        .keys()
+        .filter(|dest| cmd.output_path.join(dest).exists())
+        .map(|dest| dest.display().to_string())
+        .collect::<Vec<_>>();
+    if !existing.is_empty() {
+        bail!(
+            "refusing to overwrite existing files in [{}]: {}",
+            cmd.output_path.display(),
+            existing.join(", ")
+        );
+    }
+
+    let mut num_converted = 0;
+    let mut num_stubs = 0;
+    for (dest, (source_file, action)) in &plan {
+        let dest_path = cmd.output_path.join(dest);
+        if let Some(parent) = dest_path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("failed to create [{}] directory", parent.display()))?;
+        }
+        match action {
+            Action::Convert { bundle, fields } => {
+                let converted = convert(cmd.flavor, source_file, bundle.as_deref(), vault, fields)
+                    .context(format!("failed to convert [{}]", source_file.display()))?;
+                fs::write(&dest_path, &converted.content)
+                    .context(format!("failed to write [{}]", dest_path.display()))?;
+                if converted.num_stubs > 0 {
+                    info!(
+                        page = %dest.display(),
+                        num_stubs = converted.num_stubs,
+                        "Replaced shortcodes with stubs"
+                    );
+                }
+                for target in &converted.unresolved {
+                    warn!(page = %dest.display(), target, "Wiki link does not match any note");
+                }
+                num_converted += 1;
+                num_stubs += converted.num_stubs;
+            },
+            Action::Copy => {
+                fs::copy(source_file, &dest_path).context(format!(
+                    "failed to copy [{}] to [{}]",
+                    source_file.display(),
+                    dest_path.display()
+                ))?;
+            },
+            Action::Write(content) => {
+                fs::write(&dest_path, content)
+                    .context(format!("failed to write [{}]", dest_path.display()))?;
+            },
+        }
+        debug!(source = %source_file.display(), dest = %dest.display(), "Imported file");
+    }
+
+    info!(
+        num_files = plan.len(),
+        num_converted,
+        num_stubs,
+        output = %cmd.output_path.display(),
+        "Imported site"
+    );
+
+    Ok(())
+}
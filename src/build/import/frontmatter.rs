@@ -0,0 +1,185 @@
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde_json::{Map, Value};
+
+use crate::build::import::{toml, yaml};
+
+/// Split a document into its frontmatter, parsed as TOML between `+++` lines or
+/// as YAML between `---` lines, and the rest of the document.
+pub fn split(content: &str) -> anyhow::Result<(Map<String, Value>, &str)> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let first_line = content.lines().next().unwrap_or_default().trim_end();
+    let delimiter = match first_line {
+        "+++" | "---" => first_line,
+        _ => return Ok((Map::new(), content)),
+    };
+
+    let after_open = &content[content.find('\n').map_or(content.len(), |idx| idx + 1)..];
+    let mut offset = 0;
+    let (frontmatter, body) = loop {
+        let line_end = after_open[offset..]
+            .find('\n')
+            .map_or(after_open.len(), |idx| offset + idx + 1);
+        if offset == line_end {
+            anyhow::bail!("frontmatter is missing its closing `{delimiter}` line");
+        }
+        if after_open[offset..line_end].trim_end() == delimiter {
+            break (&after_open[..offset], &after_open[line_end..]);
+        }
+        offset = line_end;
+    };
+
+    let value = if delimiter == "+++" {
+        toml::parse(frontmatter).context("failed to parse TOML frontmatter")?
+    } else if frontmatter.trim().is_empty() {
+        Value::Object(Map::new())
+    } else {
+        yaml::parse(frontmatter).context("failed to parse YAML frontmatter")?
+    };
+    let Value::Object(fields) = value else {
+        anyhow::bail!("frontmatter must be a table of fields");
+    };
+
+    Ok((fields, body))
+}
+
+/// Convert a date in any of the formats the other generators accept to an
+/// RFC 3339 timestamp or a `YYYY-MM-DD` date.
+fn normalize_date(date: &str) -> Option<String> {
+    let date = date.trim();
+    if DateTime::parse_from_rfc3339(date).is_ok() {
+        return Some(date.to_owned());
+    }
+    // Jekyll writes dates like `2024-01-02 10:00:00 +0100`
+    if let Ok(timestamp) = DateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S %z") {
+        return Some(timestamp.to_rfc3339());
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(timestamp) = NaiveDateTime::parse_from_str(date, format) {
+            return Some(timestamp.and_utc().to_rfc3339());
+        }
+    }
+    let day = date.get(..10)?;
+    NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .is_ok()
+        .then(|| day.to_owned())
+}
+
+/// Turn a field into a list of strings, splitting a plain string on whitespace
+/// and commas the way Jekyll and Obsidian do for `tags` and `categories`.
+///
+/// Obsidian tags may also start with a `#`, which is dropped.
+fn string_list(value: Value) -> Value {
+    let term = |term: &str| Value::from(term.trim_start_matches('#'));
+    match value {
+        Value::String(terms) => terms
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|term| !term.is_empty())
+            .map(term)
+            .collect(),
+        Value::Array(terms) => terms
+            .into_iter()
+            .map(|value| match value {
+                Value::String(value) => term(&value),
+                other => Value::String(other.to_string()),
+            })
+            .collect(),
+        other => other,
+    }
+}
+
+/// Rename and reshape the frontmatter fields of another generator to the ones
+/// this one reads, returning the page title separately since it is written as
+/// the first heading.
+///
+/// Fields without an equivalent are kept as they are, so templates can still
+/// use them.
+pub fn normalize(mut fields: Map<String, Value>) -> (Map<String, Value>, Option<String>) {
+    let title = match fields.remove("title") {
+        Some(Value::String(title)) => Some(title),
+        Some(other) => Some(other.to_string()),
+        None => None,
+    };
+
+    // Zola and Hugo nest taxonomies, which are top-level lists here
+    if let Some(Value::Object(taxonomies)) = fields.remove("taxonomies") {
+        for (name, terms) in taxonomies {
+            fields.insert(name, string_list(terms));
+        }
+    }
+    for name in ["tags", "categories"] {
+        if let Some(terms) = fields.remove(name) {
+            fields.insert(name.to_owned(), string_list(terms));
+        }
+    }
+    // Jekyll's singular forms
+    for (singular, plural) in [("tag", "tags"), ("category", "categories")] {
+        if let Some(Value::String(term)) = fields.remove(singular) {
+            let terms = fields.entry(plural).or_insert_with(|| Value::Array(vec![]));
+            if let Value::Array(terms) = terms {
+                terms.push(Value::String(term));
+            }
+        }
+    }
+
+    if fields.get("published") == Some(&Value::Bool(false)) {
+        fields.remove("published");
+        fields.insert("draft".to_owned(), Value::Bool(true));
+    }
+    if !fields.contains_key("summary")
+        && let Some(Value::String(excerpt)) = fields.remove("excerpt")
+    {
+        fields.insert("summary".to_owned(), Value::String(excerpt));
+    }
+
+    // Hugo calls the publish date `publishDate`, and Zola calls the last
+    // modification `updated`, which this generator has no use for
+    if !fields.contains_key("date")
+        && let Some(date) = fields.remove("publishDate")
+    {
+        fields.insert("date".to_owned(), date);
+    }
+    if let Some(Value::String(date)) = fields.get("date") {
+        match normalize_date(date) {
+            Some(date) => {
+                fields.insert("date".to_owned(), Value::String(date));
+            },
+            None => {
+                let date = fields.remove("date").unwrap_or_default();
+                fields.insert("original_date".to_owned(), date);
+            },
+        }
+    }
+
+    // Layouts belong to the other generator's templates, and permalinks are
+    // replaced by the file layout
+    for name in ["layout", "permalink", "slug", "url"] {
+        if let Some(value) = fields.remove(name) {
+            fields.insert(format!("original_{name}"), value);
+        }
+    }
+
+    (fields, title)
+}
+
+/// Write a converted document, with the frontmatter as a JSON raw block and
+/// the title as the first heading.
+pub fn write(fields: &Map<String, Value>, title: Option<&str>, body: &str) -> String {
+    let mut document = String::new();
+    if !fields.is_empty() {
+        let json = serde_json::to_string_pretty(fields).expect("JSON values always serialize");
+        document.push_str("```=json\n");
+        document.push_str(&json);
+        document.push_str("\n```\n\n");
+    }
+    if let Some(title) = title {
+        document.push_str("# ");
+        document.push_str(title);
+        document.push_str("\n\n");
+    }
+    document.push_str(body.trim_start_matches('\n'));
+    if !document.ends_with('\n') {
+        document.push('\n');
+    }
+    document
+}
@@ -0,0 +1,958 @@
+//! A best-effort conversion of Markdown to Djot, which also turns the
+//! shortcodes and template tags of the other generators into stubs.
+//!
+//! An inline shortcode becomes an empty span whose class is the shortcode
+//! name, like `[]{.youtube arg0="xyz"}`, and a shortcode with a body becomes a
+//! div of the same class. Configuring a component for the class brings the
+//! shortcode back.
+//!
+//! Obsidian notes have no shortcodes, but link to each other with wiki links,
+//! which are resolved against the [`Vault`] instead.
+
+use std::{collections::BTreeSet, fmt::Write, sync::LazyLock};
+
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+
+use crate::build::import::{
+    ImportFlavor,
+    obsidian::{self, Vault},
+};
+
+/// Matches the marker of a list item, along with its indentation
+static LIST_ITEM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\s*)([-*+]|\d{1,9}[.)])(\s+|$)").unwrap());
+
+/// Matches the opening of a fenced code block
+static FENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\s*)(`{3,}|~{3,})\s*(.*)$").unwrap());
+
+/// Matches the start of an HTML block
+static HTML_BLOCK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^<(/?(address|article|aside|audio|blockquote|details|div|dl|figure|figcaption|footer|form|h[1-6]|header|hr|iframe|nav|ol|p|picture|pre|section|script|style|table|ul|video)\b|!--)",
+    )
+    .unwrap()
+});
+
+/// Matches a Jekyll `highlight` tag or a Hugo `highlight` shortcode, which
+/// open a code block
+static HIGHLIGHT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"^\s*(?:\{%-?\s*highlight\s+([^\s%]+).*?%\}|\{\{[<%]\s*highlight\s+"?([^\s">%]+).*?[>%]\}\})\s*$"#,
+    )
+    .unwrap()
+});
+
+/// The result of converting a document.
+#[derive(Debug, Default)]
+pub struct Converted {
+    pub content: String,
+    /// The number of shortcodes and template tags that were replaced by stubs
+    pub num_stubs: usize,
+    /// The tags written inline as `#tag`, in Obsidian notes
+    pub tags: BTreeSet<String>,
+    /// The targets of wiki links that don't match any note
+    pub unresolved: Vec<String>,
+}
+
+/// A shortcode or template tag found in the content.
+#[derive(Debug)]
+enum Tag {
+    /// A shortcode, with its name and arguments
+    Shortcode {
+        name: String,
+        args: Vec<(String, String)>,
+    },
+    /// The end of a shortcode with a body
+    End,
+    /// Text that replaces the tag, like the URL of a page reference
+    Text(String),
+}
+
+/// Split shortcode arguments on `separator`, outside of quotes, and name the
+/// positional ones `arg0`, `arg1`, and so on.
+fn parse_args(args: &str, separator: char) -> Vec<(String, String)> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut quote = None;
+    for c in args.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'' | '`') => quote = Some(c),
+            (None, c) if c == separator || (separator == ' ' && c.is_whitespace()) => {
+                if !current.trim().is_empty() {
+                    tokens.push(current.trim().to_owned());
+                }
+                current.clear();
+            },
+            (None, c) => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() {
+        tokens.push(current.trim().to_owned());
+    }
+
+    let mut positional = 0;
+    tokens
+        .into_iter()
+        .map(|token| match token.split_once('=') {
+            Some((key, value))
+                if !key.is_empty()
+                    && key
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') =>
+            {
+                (key.to_owned(), value.trim_matches(['"', '\'']).to_owned())
+            },
+            _ => {
+                positional += 1;
+                (format!("arg{}", positional - 1), token)
+            },
+        })
+        .collect()
+}
+
+/// A name that can be used as a Djot class.
+fn class_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_owned()
+}
+
+fn attributes(args: &[(String, String)]) -> String {
+    let mut attrs = String::new();
+    for (key, value) in args {
+        let _ = write!(
+            attrs,
+            r#" {key}="{}""#,
+            value.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+    }
+    attrs
+}
+
+/// Convert a link to a content file of another generator to the URL path of
+/// the converted page.
+fn page_path(path: &str) -> String {
+    let (path, fragment) = path
+        .split_once('#')
+        .map_or((path, None), |(p, f)| (p, Some(f)));
+    let path = path.trim_start_matches('/');
+    let path = ["/_index.md", "/index.md"]
+        .iter()
+        .find_map(|suffix| path.strip_suffix(suffix))
+        .map(|dir| format!("{dir}/index.html"))
+        .or_else(|| (path == "_index.md").then(|| "index.html".to_owned()))
+        .or_else(|| {
+            path.strip_suffix(".md")
+                .or_else(|| path.strip_suffix(".markdown"))
+                .map(|stem| format!("{stem}.html"))
+        })
+        .unwrap_or_else(|| path.to_owned());
+    match fragment {
+        Some(fragment) => format!("/{path}#{fragment}"),
+        None => format!("/{path}"),
+    }
+}
+
+/// The slug of a Jekyll post, without the date in front of it.
+pub fn jekyll_post_slug(name: &str) -> Option<&str> {
+    let bytes = name.as_bytes();
+    let is_date = bytes.len() > 11
+        && bytes[..10].iter().enumerate().all(|(idx, b)| {
+            if idx == 4 || idx == 7 {
+                *b == b'-'
+            } else {
+                b.is_ascii_digit()
+            }
+        })
+        && bytes[10] == b'-';
+    is_date.then(|| &name[11..])
+}
+
+struct Converter<'a> {
+    flavor: ImportFlavor,
+    /// The directory that relative links of a page bundle are rewritten to
+    bundle: Option<&'a str>,
+    /// The number of levels headings are moved down by, so that only the
+    /// title is a level 1 heading
+    heading_shift: usize,
+    /// The notes and attachments wiki links are resolved against
+    vault: Option<&'a Vault>,
+    out: String,
+    num_stubs: usize,
+    tags: BTreeSet<String>,
+    unresolved: Vec<String>,
+}
+
+impl Converter<'_> {
+    /// Parse the inside of a shortcode or template tag, returning `None` if it
+    /// is not one the flavor recognizes.
+    fn parse_tag(&self, open: &str, inner: &str) -> Option<Tag> {
+        let inner = inner.trim().trim_matches('-').trim();
+        match (self.flavor, open) {
+            (ImportFlavor::Hugo, "{{<" | "{{%") => {
+                let inner = inner.trim_start_matches("/*").trim_end_matches("*/").trim();
+                if inner.starts_with('/') {
+                    return Some(Tag::End);
+                }
+                let (name, args) = inner.split_once(char::is_whitespace).unwrap_or((inner, ""));
+                let args = parse_args(args, ' ');
+                if matches!(name, "ref" | "relref") {
+                    let target = args
+                        .first()
+                        .map(|(_, value)| value.as_str())
+                        .unwrap_or_default();
+                    return Some(Tag::Text(page_path(target)));
+                }
+                Some(Tag::Shortcode {
+                    name: name.to_owned(),
+                    args,
+                })
+            },
+            (ImportFlavor::Zola, "{{" | "{%") => {
+                if open == "{%" && inner == "end" {
+                    return Some(Tag::End);
+                }
+                let (name, args) = inner.strip_suffix(')')?.split_once('(')?;
+                if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    return None;
+                }
+                Some(Tag::Shortcode {
+                    name: name.to_owned(),
+                    args: parse_args(args, ','),
+                })
+            },
+            (ImportFlavor::Jekyll, "{{") => {
+                if matches!(inner, "site.baseurl" | "site.url") {
+                    return Some(Tag::Text(String::new()));
+                }
+                Some(Tag::Shortcode {
+                    name: "liquid".to_owned(),
+                    args: vec![("expr".to_owned(), inner.to_owned())],
+                })
+            },
+            (ImportFlavor::Jekyll, "{%") => {
+                let (name, args) = inner.split_once(char::is_whitespace).unwrap_or((inner, ""));
+                match name {
+                    "raw" | "endraw" => Some(Tag::Text(String::new())),
+                    "post_url" => {
+                        let post = args.trim();
+                        let slug = jekyll_post_slug(post).unwrap_or(post);
+                        Some(Tag::Text(format!("/blog/{slug}.html")))
+                    },
+                    "link" => Some(Tag::Text(page_path(args.trim()))),
+                    name if name.starts_with("end") => Some(Tag::End),
+                    name => Some(Tag::Shortcode {
+                        name: name.to_owned(),
+                        args: parse_args(args, ' '),
+                    }),
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// Find a shortcode or template tag at the start of `text`, returning it
+    /// along with its length.
+    fn find_tag(&self, text: &str) -> Option<(Tag, usize)> {
+        for (open, close) in [("{{<", ">}}"), ("{{%", "%}}"), ("{{", "}}"), ("{%", "%}")] {
+            if let Some(rest) = text.strip_prefix(open) {
+                let end = rest.find(close)?;
+                let tag = self.parse_tag(open, &rest[..end])?;
+                return Some((tag, open.len() + end + close.len()));
+            }
+        }
+        None
+    }
+
+    /// Replace the template tags in a link destination, leaving the rest of it
+    /// as it is.
+    fn replace_tags(&mut self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            rest = &rest[start..];
+            match self.find_tag(rest) {
+                Some((Tag::Text(text), len)) => {
+                    out.push_str(&text);
+                    rest = &rest[len..];
+                },
+                Some((Tag::Shortcode { name, args }, len)) => {
+                    let stub = self.stub(&name, &args);
+                    out.push_str(&stub);
+                    rest = &rest[len..];
+                },
+                Some((Tag::End, len)) => rest = &rest[len..],
+                None => {
+                    out.push('{');
+                    rest = &rest[1..];
+                },
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// Rewrite the destination of a link.
+    fn link(&mut self, destination: &str) -> String {
+        let (url, title) = destination
+            .split_once(char::is_whitespace)
+            .map_or((destination, ""), |(url, title)| (url, title));
+
+        if let Some((Tag::Text(text), len)) = self.find_tag(destination) {
+            return format!("{text}{}", self.replace_tags(&destination[len..]));
+        }
+        let is_relative = !(url.contains("://")
+            || url.starts_with(['/', '#'])
+            || url.starts_with("mailto:")
+            || url.starts_with("{{")
+            || url.starts_with("{%"));
+        let url = match self.flavor {
+            ImportFlavor::Zola if url.starts_with("@/") => page_path(&url[2..]),
+            _ if !is_relative => self.replace_tags(url),
+            // Obsidian links to notes and attachments by their path in the
+            // vault, which moves
+            ImportFlavor::Obsidian => {
+                let (path, fragment) = url
+                    .split_once('#')
+                    .map_or((url, None), |(p, f)| (p, Some(f)));
+                let path = percent_decode_str(path).decode_utf8_lossy();
+                let vault = self
+                    .vault
+                    .expect("Obsidian notes are converted with a vault");
+                match vault
+                    .resolve_note(&path)
+                    .or_else(|| vault.resolve_attachment(&path))
+                {
+                    Some(resolved) => match fragment {
+                        Some(fragment) => format!(
+                            "{resolved}#{}",
+                            obsidian::heading_id(&percent_decode_str(fragment).decode_utf8_lossy())
+                        ),
+                        None => resolved.to_owned(),
+                    },
+                    None => url.to_owned(),
+                }
+            },
+            _ => match self.bundle {
+                Some(bundle) => format!("{bundle}/{url}"),
+                None => url.to_owned(),
+            },
+        };
+        if title.is_empty() {
+            url
+        } else {
+            format!("{url} {title}")
+        }
+    }
+
+    fn stub(&mut self, name: &str, args: &[(String, String)]) -> String {
+        self.num_stubs += 1;
+        format!("[]{{.{}{}}}", class_name(name), attributes(args))
+    }
+
+    /// Convert a wiki link or embed at the start of `text`, like
+    /// `[[Note#Heading|label]]` or `![[image.png|300]]`, returning it along
+    /// with its length.
+    fn wiki_link(&mut self, text: &str) -> Option<(String, usize)> {
+        let vault = self.vault?;
+        let embed = text.starts_with("![[");
+        let start = if embed {
+            3
+        } else if text.starts_with("[[") {
+            2
+        } else {
+            return None;
+        };
+        let end = text[start..].find("]]")?;
+        let inner = &text[start..start + end];
+        let len = start + end + 2;
+        let (target, label) = inner
+            .split_once('|')
+            .map_or((inner, None), |(target, label)| (target, Some(label)));
+        let (target, heading) = target
+            .split_once('#')
+            .map_or((target, None), |(target, heading)| (target, Some(heading)));
+
+        if embed && let Some(url) = vault.resolve_attachment(target) {
+            // The label of an embedded image is its size, like `300x200`
+            let size = label.filter(|label| {
+                label
+                    .split('x')
+                    .all(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+            });
+            let alt = if size.is_some() {
+                ""
+            } else {
+                label.unwrap_or_default()
+            };
+            let mut image = format!("![{}]({url})", self.inline(alt));
+            if let Some(size) = size {
+                let (width, height) = size
+                    .split_once('x')
+                    .map_or((size, None), |(w, h)| (w, Some(h)));
+                let _ = write!(image, r#"{{width="{width}""#);
+                if let Some(height) = height {
+                    let _ = write!(image, r#" height="{height}""#);
+                }
+                image.push('}');
+            }
+            return Some((image, len));
+        }
+
+        // Block references, like `#^abc123`, have no equivalent
+        let heading = heading.filter(|heading| !heading.starts_with('^'));
+        let label = match (label, heading) {
+            (Some(label), _) => label.to_owned(),
+            (None, Some(heading)) if target.is_empty() => heading.to_owned(),
+            (None, Some(heading)) => format!("{target} > {heading}"),
+            (None, None) => target.to_owned(),
+        };
+        let label = self.inline(&label);
+        let url = if target.is_empty() {
+            Some("")
+        } else {
+            vault.resolve_note(target)
+        };
+        let Some(url) = url else {
+            self.unresolved.push(target.to_owned());
+            return Some((label, len));
+        };
+        let fragment = heading
+            .map(|heading| format!("#{}", obsidian::heading_id(heading)))
+            .unwrap_or_default();
+        // Embedded notes can't be transcluded, so they become links
+        let class = if embed { "{.embed}" } else { "" };
+        Some((format!("[{label}]({url}{fragment}){class}"), len))
+    }
+
+    /// Convert the inline syntax of a line.
+    fn inline(&mut self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let chars = text.char_indices().collect::<Vec<_>>();
+        let mut idx = 0;
+        while idx < chars.len() {
+            let (pos, c) = chars[idx];
+            let rest = &text[pos..];
+            let prev = idx.checked_sub(1).map(|prev| chars[prev].1);
+
+            if let Some((link, len)) = self.wiki_link(rest) {
+                out.push_str(&link);
+                idx += rest[..len].chars().count();
+                continue;
+            }
+            if let Some((tag, len)) = self.find_tag(rest) {
+                match tag {
+                    Tag::Shortcode { name, args } => {
+                        let stub = self.stub(&name, &args);
+                        out.push_str(&stub);
+                    },
+                    Tag::Text(text) => out.push_str(&text),
+                    Tag::End => {},
+                }
+                idx += rest[..len].chars().count();
+                continue;
+            }
+
+            match c {
+                '\\' => {
+                    out.push_str(&rest[..rest.chars().take(2).map(char::len_utf8).sum()]);
+                    idx += 2;
+                },
+                '`' => {
+                    let ticks = rest.len() - rest.trim_start_matches('`').len();
+                    let fence = &rest[..ticks];
+                    let len = rest[ticks..]
+                        .find(fence)
+                        .map_or(ticks, |end| ticks * 2 + end);
+                    out.push_str(&rest[..len]);
+                    idx += rest[..len].chars().count();
+                },
+                '<' => {
+                    let tag_len = rest.find('>').map(|end| end + 1);
+                    match tag_len {
+                        // Autolinks are the same in Djot
+                        Some(len)
+                            if rest[1..len].contains("://")
+                                || (rest[1..len].contains('@') && !rest[1..len].contains(' ')) =>
+                        {
+                            out.push_str(&rest[..len]);
+                            idx += rest[..len].chars().count();
+                        },
+                        Some(len)
+                            if rest[1..].starts_with(|c: char| {
+                                c.is_ascii_alphabetic() || c == '/' || c == '!'
+                            }) =>
+                        {
+                            let _ = write!(out, "`{}`{{=html}}", &rest[..len]);
+                            idx += rest[..len].chars().count();
+                        },
+                        _ => {
+                            out.push('<');
+                            idx += 1;
+                        },
+                    }
+                },
+                ']' if rest.starts_with("](") => {
+                    let destination = &rest[2..];
+                    // A page reference shortcode can contain parentheses
+                    let search_from = if destination.starts_with('{') {
+                        destination.find("}}").map_or(0, |end| end + 2)
+                    } else {
+                        0
+                    };
+                    match destination[search_from..].find(')') {
+                        Some(end) => {
+                            let end = search_from + end;
+                            let link = self.link(destination[..end].trim());
+                            let _ = write!(out, "]({link})");
+                            idx += rest[..end + 3].chars().count();
+                        },
+                        None => {
+                            out.push(']');
+                            idx += 1;
+                        },
+                    }
+                },
+                '*' | '_' | '~' => {
+                    let run = chars[idx..]
+                        .iter()
+                        .take_while(|(_, other)| *other == c)
+                        .count();
+                    let next = chars.get(idx + run).map(|(_, next)| *next);
+                    let prev_space = prev.is_none_or(char::is_whitespace);
+                    let next_space = next.is_none_or(char::is_whitespace);
+                    let intraword = prev.is_some_and(char::is_alphanumeric)
+                        && next.is_some_and(char::is_alphanumeric);
+                    let opening =
+                        prev_space || !next_space && prev.is_some_and(|p| p.is_ascii_punctuation());
+
+                    if c == '~' {
+                        if run == 2 && !(prev_space && next_space) {
+                            out.push_str(if opening { "{-" } else { "-}" });
+                        } else {
+                            out.push_str(&"\\~".repeat(run));
+                        }
+                    } else if (prev_space && next_space) || (c == '_' && intraword) {
+                        // Not emphasis, so escape underscores, which Djot
+                        // treats as emphasis even inside words
+                        if c == '_' {
+                            out.push_str(&"\\_".repeat(run));
+                        } else {
+                            out.push_str(&rest[..run]);
+                        }
+                    } else {
+                        out.push_str(match (run, opening) {
+                            (1, _) => "_",
+                            (2, _) => "*",
+                            (_, true) => "*_",
+                            (_, false) => "_*",
+                        });
+                    }
+                    idx += run;
+                },
+                '^' => {
+                    out.push_str("\\^");
+                    idx += 1;
+                },
+                // Highlights
+                '=' if self.vault.is_some()
+                    && rest.starts_with("==")
+                    && !rest.starts_with("===") =>
+                {
+                    let next = chars.get(idx + 2).map(|(_, next)| *next);
+                    let prev_space = prev.is_none_or(char::is_whitespace);
+                    let next_space = next.is_none_or(char::is_whitespace);
+                    out.push_str(match (prev_space, next_space) {
+                        (true, true) => "==",
+                        (true, false) => "{=",
+                        _ => "=}",
+                    });
+                    idx += 2;
+                },
+                // Math, which Djot writes as a verbatim span after the `$`
+                '$' if self.vault.is_some() => {
+                    let delimiter = if rest.starts_with("$$") { "$$" } else { "$" };
+                    let body = &rest[delimiter.len()..];
+                    let end = body.find(delimiter).filter(|end| {
+                        delimiter == "$$"
+                            || (*end > 0
+                                && !body.starts_with(char::is_whitespace)
+                                && !body[..*end].ends_with(char::is_whitespace)
+                                && !body[end + 1..].starts_with(|c: char| c.is_ascii_digit()))
+                    });
+                    match end {
+                        Some(end) => {
+                            let _ = write!(out, "{delimiter}`{}`", &body[..end]);
+                            idx += rest[..delimiter.len() * 2 + end].chars().count();
+                        },
+                        None => {
+                            out.push('$');
+                            idx += 1;
+                        },
+                    }
+                },
+                // Tags, which are kept in the text and added to the frontmatter
+                '#' if self.vault.is_some() && prev.is_none_or(char::is_whitespace) => {
+                    let tag = rest[1..]
+                        .split(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '/')))
+                        .next()
+                        .unwrap_or_default();
+                    if !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit()) {
+                        self.tags.insert(tag.to_owned());
+                    }
+                    out.push('#');
+                    idx += 1;
+                },
+                c => {
+                    out.push(c);
+                    idx += 1;
+                },
+            }
+        }
+        out
+    }
+
+    fn push_line(&mut self, line: &str) {
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn last_line(&self) -> &str {
+        self.out
+            .trim_end_matches('\n')
+            .rsplit('\n')
+            .next()
+            .unwrap_or_default()
+    }
+
+    /// Start a new block, which must be separated from a paragraph by a blank
+    /// line in Djot.
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    /// Convert a line that consists only of a shortcode into a block, returning
+    /// `false` if the line is not a lone shortcode.
+    fn block_tag(&mut self, line: &str, lines: &[&str]) -> bool {
+        let trimmed = line.trim();
+        let Some((tag, len)) = self.find_tag(trimmed) else {
+            return false;
+        };
+        if len != trimmed.len() {
+            return false;
+        }
+
+        match tag {
+            Tag::Shortcode { name, args } => {
+                let has_body = match self.flavor {
+                    ImportFlavor::Zola => trimmed.starts_with("{%"),
+                    ImportFlavor::Hugo => {
+                        let closing = format!("/{name}");
+                        lines.iter().any(|line| {
+                            let line = line.replace(' ', "");
+                            line.contains(&format!("{{{{<{closing}>}}}}"))
+                                || line.contains(&format!("{{{{%{closing}%}}}}"))
+                        })
+                    },
+                    ImportFlavor::Jekyll => {
+                        let closing = format!("end{name}");
+                        lines.iter().any(|line| line.contains(&closing))
+                    },
+                    ImportFlavor::Obsidian => false,
+                };
+                self.blank_line();
+                if has_body {
+                    self.num_stubs += 1;
+                    if !args.is_empty() {
+                        self.push_line(&format!("{{{}}}", attributes(&args).trim_start()));
+                    }
+                    self.push_line(&format!("::: {}", class_name(&name)));
+                } else {
+                    let stub = self.stub(&name, &args);
+                    self.push_line(&stub);
+                }
+                self.out.push('\n');
+            },
+            Tag::End => {
+                self.blank_line();
+                self.push_line(":::");
+                self.out.push('\n');
+            },
+            Tag::Text(text) => {
+                if !text.is_empty() {
+                    self.push_line(&text);
+                }
+            },
+        }
+        true
+    }
+
+    fn convert(&mut self, markdown: &str) {
+        let lines = markdown.lines().collect::<Vec<_>>();
+        let mut idx = 0;
+        // Whether the lines are inside a list, where indented lines continue
+        // list items instead of being code
+        let mut in_list = false;
+
+        while idx < lines.len() {
+            let line = lines[idx].trim_end_matches('\r');
+            let next = lines.get(idx + 1).map(|line| line.trim());
+            idx += 1;
+
+            if line.trim().is_empty() {
+                if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+                    self.out.push('\n');
+                }
+                continue;
+            }
+            let indent = line.len() - line.trim_start().len();
+            if indent == 0 && !LIST_ITEM.is_match(line) && self.out.ends_with("\n\n") {
+                in_list = false;
+            }
+
+            if let Some(captures) = FENCE.captures(line) {
+                let fence = &captures[2];
+                let closing_char = fence.chars().next().unwrap_or('`');
+                let ticks = "`".repeat(fence.len());
+                self.blank_line();
+                self.push_line(&format!("{}{ticks}{}", &captures[1], &captures[3]));
+                while idx < lines.len() {
+                    let code = lines[idx];
+                    idx += 1;
+                    let trimmed = code.trim();
+                    if trimmed.len() >= fence.len() && trimmed.chars().all(|c| c == closing_char) {
+                        break;
+                    }
+                    self.push_line(code);
+                }
+                self.push_line(&format!("{}{ticks}", &captures[1]));
+                continue;
+            }
+
+            if self.flavor != ImportFlavor::Zola
+                && let Some(captures) = HIGHLIGHT.captures(line)
+            {
+                let language = captures
+                    .get(1)
+                    .or(captures.get(2))
+                    .map_or("", |m| m.as_str());
+                self.blank_line();
+                self.push_line(&format!("``` {language}"));
+                while idx < lines.len() {
+                    let code = lines[idx];
+                    idx += 1;
+                    if code.contains("endhighlight") || code.contains("/highlight") {
+                        break;
+                    }
+                    self.push_line(code);
+                }
+                self.push_line("```");
+                continue;
+            }
+
+            // Display math on lines of its own
+            if self.vault.is_some() && line.trim() == "$$" {
+                let mut math = vec![];
+                while idx < lines.len() && lines[idx].trim() != "$$" {
+                    math.push(lines[idx]);
+                    idx += 1;
+                }
+                idx += 1;
+                self.blank_line();
+                self.push_line(&format!("$$`{}`", math.join("\n")));
+                self.out.push('\n');
+                continue;
+            }
+
+            if self.block_tag(line, &lines[idx..]) {
+                continue;
+            }
+
+            if indent == 0 && HTML_BLOCK.is_match(line) {
+                self.blank_line();
+                self.push_line("```=html");
+                self.push_line(line);
+                while idx < lines.len() && !lines[idx].trim().is_empty() {
+                    self.push_line(lines[idx]);
+                    idx += 1;
+                }
+                self.push_line("```");
+                continue;
+            }
+
+            // Indented code blocks don't exist in Djot
+            if !in_list && indent >= 4 && self.out.ends_with("\n\n") {
+                self.push_line("```");
+                self.push_line(strip_code_indent(line));
+                while idx < lines.len() {
+                    let code = lines[idx];
+                    let code_indent = code.len() - code.trim_start().len();
+                    if !code.trim().is_empty() && code_indent < 4 {
+                        break;
+                    }
+                    self.push_line(strip_code_indent(code));
+                    idx += 1;
+                }
+                while self.out.ends_with("\n\n") {
+                    self.out.pop();
+                }
+                self.push_line("```");
+                self.out.push('\n');
+                continue;
+            }
+
+            // Setext headings
+            if let Some(next) = next
+                && !next.is_empty()
+                && (next.chars().all(|c| c == '=')
+                    || (next.chars().all(|c| c == '-') && next.len() >= 2))
+                && !LIST_ITEM.is_match(line)
+                && indent < 4
+            {
+                let level = if next.starts_with('=') { 1 } else { 2 };
+                let level = "#".repeat((level + self.heading_shift).min(6));
+                let heading = self.inline(line.trim());
+                self.blank_line();
+                self.push_line(&format!("{level} {heading}"));
+                self.out.push('\n');
+                idx += 1;
+                continue;
+            }
+
+            // ATX headings, which need a space after the `#` in Djot
+            let trimmed = line.trim_start();
+            let hashes = trimmed.len() - trimmed.trim_start_matches('#').len();
+            // Obsidian reads `#tag` at the start of a line as a tag
+            let is_tag = self.vault.is_some()
+                && !trimmed[hashes..].starts_with([' ', '\t'])
+                && trimmed.len() > hashes;
+            if indent < 4 && (1..=6).contains(&hashes) && !is_tag {
+                let text = trimmed[hashes..].trim();
+                let text = text.trim_end_matches('#').trim_end();
+                let heading = self.inline(text);
+                self.blank_line();
+                let level = "#".repeat((hashes + self.heading_shift).min(6));
+                self.push_line(&format!("{level} {heading}"));
+                self.out.push('\n');
+                continue;
+            }
+
+            // Kramdown attribute lists are close to Djot attributes, except
+            // that they follow the block they apply to instead of preceding it
+            if let Some(attrs) = trimmed
+                .strip_prefix("{:")
+                .and_then(|attrs| attrs.strip_suffix('}'))
+            {
+                let attrs = format!("{}{{{}}}\n", &line[..indent], attrs.trim());
+                if self.out.ends_with("\n\n") {
+                    self.out.push_str(&attrs);
+                } else {
+                    let block_start = self.out.trim_end().rfind("\n\n").map_or(0, |idx| idx + 2);
+                    self.out.insert_str(block_start, &attrs);
+                }
+                continue;
+            }
+
+            if let Some(captures) = LIST_ITEM.captures(line) {
+                let item_indent = captures[1].len();
+                let last = self.last_line().to_owned();
+                let continues_list = LIST_ITEM
+                    .captures(&last)
+                    .is_some_and(|last| last[1].len() == item_indent);
+                if !continues_list {
+                    self.blank_line();
+                }
+                in_list = true;
+            }
+
+            let mut converted = self.inline(line);
+            // Two trailing spaces are a hard line break
+            if line.ends_with("  ") && next.is_some_and(|next| !next.is_empty()) {
+                converted = format!("{}\\", converted.trim_end());
+            }
+            self.push_line(&converted);
+        }
+    }
+}
+
+/// A line of an indented code block without its indentation, which is up to
+/// four spaces or a tab.
+fn strip_code_indent(line: &str) -> &str {
+    if let Some(rest) = line.strip_prefix('\t') {
+        return rest;
+    }
+    let len = line.bytes().take(4).take_while(|&b| b == b' ').count();
+    &line[len..]
+}
+
+/// Whether a document has a level 1 heading, outside of code blocks.
+fn has_level_one_heading(markdown: &str) -> bool {
+    let mut in_code = false;
+    let mut prev_line = "";
+    for line in markdown.lines() {
+        if FENCE.is_match(line) {
+            in_code = !in_code;
+        } else if !in_code
+            && (line.starts_with("# ")
+                || (!prev_line.trim().is_empty()
+                    && !line.is_empty()
+                    && line.trim_end().chars().all(|c| c == '=')))
+        {
+            return true;
+        }
+        prev_line = line;
+    }
+    false
+}
+
+/// Convert a Markdown document to Djot.
+///
+/// `bundle` is the directory that the assets of a page bundle were moved to,
+/// which relative links are rewritten to point into. When the title is written
+/// as a heading before a document that has level 1 headings of its own, the
+/// headings of the document are moved down a level, since a page has a single
+/// level 1 heading.
+pub fn to_djot(
+    markdown: &str,
+    flavor: ImportFlavor,
+    bundle: Option<&str>,
+    vault: Option<&Vault>,
+    has_title: bool,
+) -> Converted {
+    let mut converter = Converter {
+        flavor,
+        bundle,
+        heading_shift: usize::from(has_title && has_level_one_heading(markdown)),
+        vault,
+        out: String::new(),
+        num_stubs: 0,
+        tags: BTreeSet::new(),
+        unresolved: vec![],
+    };
+    match vault {
+        Some(_) => converter.convert(&obsidian::preprocess(markdown)),
+        None => converter.convert(markdown),
+    }
+
+    Converted {
+        content: converter.out.trim_end().to_owned() + "\n",
+        num_stubs: converter.num_stubs,
+        tags: converter.tags,
+        unresolved: converter.unresolved,
+    }
+}
@@ -0,0 +1,169 @@
+//! Support for Obsidian vaults, where notes link to each other by name with
+//! `[[wiki links]]` instead of by path.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use regex::Regex;
+
+/// Matches the first line of a callout, like `> [!note] Title`
+static CALLOUT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^>\s*\[!([\w-]+)\][+-]?\s*(.*)$").unwrap());
+
+/// Matches a comment, which Obsidian leaves out of the rendered note
+static COMMENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)%%.*?%%").unwrap());
+
+/// The URL paths of the notes and attachments of a vault, looked up the way
+/// Obsidian resolves links: by vault path or by name, ignoring case.
+///
+/// Each URL is stored along with the depth of its file in the vault, since
+/// the file closest to the vault root wins when several share a name.
+#[derive(Debug, Default)]
+pub struct Vault {
+    notes: BTreeMap<String, (usize, String)>,
+    attachments: BTreeMap<String, (usize, String)>,
+}
+
+fn lookup_key(target: &str) -> String {
+    target.trim().trim_start_matches('/').to_lowercase()
+}
+
+/// Insert the URL under the key, unless a file closer to the vault root, or
+/// an earlier one at the same depth, already has it.
+fn insert_closest(
+    entries: &mut BTreeMap<String, (usize, String)>,
+    key: &str,
+    depth: usize,
+    url: &str,
+) {
+    let key = lookup_key(key);
+    match entries.get(&key) {
+        Some((existing, _)) if *existing <= depth => {},
+        _ => {
+            entries.insert(key, (depth, url.to_owned()));
+        },
+    }
+}
+
+impl Vault {
+    /// Add a note, given its path in the vault and its aliases, along with the
+    /// path it is written to in the new site.
+    pub fn add_note(&mut self, path: &Path, aliases: &[String], dest: &Path) {
+        let url = format!("/{}", dest.with_extension("html").display());
+        let stem = path.with_extension("");
+        let name = stem.file_name().unwrap_or_default().to_string_lossy();
+        let keys = [stem.to_string_lossy().into_owned(), name.into_owned()]
+            .into_iter()
+            .chain(aliases.iter().cloned());
+        let depth = path.components().count();
+        for key in keys {
+            insert_closest(&mut self.notes, &key, depth, &url);
+        }
+    }
+
+    /// Add an attachment, like an image, given its path in the vault and the
+    /// path it is copied to in the new site.
+    pub fn add_attachment(&mut self, path: &Path, dest: &Path) {
+        let url = format!("/{}", dest.display());
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let depth = path.components().count();
+        for key in [path.to_string_lossy(), name] {
+            insert_closest(&mut self.attachments, &key, depth, &url);
+        }
+    }
+
+    pub fn resolve_note(&self, target: &str) -> Option<&str> {
+        let key = lookup_key(target);
+        let key = key.strip_suffix(".md").unwrap_or(&key);
+        self.notes.get(key).map(|(_, url)| url.as_str())
+    }
+
+    pub fn resolve_attachment(&self, target: &str) -> Option<&str> {
+        self.attachments
+            .get(&lookup_key(target))
+            .map(|(_, url)| url.as_str())
+    }
+}
+
+/// The path a file of the vault is written to, with every component turned
+/// into a slug so that URLs don't contain spaces.
+pub fn dest_path(path: &Path) -> PathBuf {
+    let slugified = |part: &str| {
+        let slug = slug::slugify(part);
+        if slug.is_empty() {
+            part.to_owned()
+        } else {
+            slug
+        }
+    };
+
+    let mut dest = PathBuf::new();
+    if let Some(parent) = path.parent() {
+        for component in parent.components() {
+            dest.push(slugified(&component.as_os_str().to_string_lossy()));
+        }
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = slugified(&stem);
+    if let Some(ext) = path.extension() {
+        name.push('.');
+        name.push_str(&ext.to_string_lossy().to_lowercase());
+    }
+    dest.push(name);
+    dest
+}
+
+/// The ID Djot gives to a heading with the given text, which is the text with
+/// punctuation removed and whitespace replaced by `-`.
+pub fn heading_id(text: &str) -> String {
+    let mut id = String::new();
+    for word in text.split_ascii_whitespace() {
+        if !id.is_empty() {
+            id.push('-');
+        }
+        id.extend(
+            word.chars()
+                .filter(|c| !c.is_ascii_punctuation() || matches!(c, '-' | '_')),
+        );
+    }
+    id.trim_end_matches('-').to_owned()
+}
+
+/// Remove comments and turn callouts into divs whose class is the callout type,
+/// with the callout title as the first paragraph.
+pub fn preprocess(markdown: &str) -> String {
+    let markdown = COMMENT.replace_all(markdown, "");
+    let mut out = String::with_capacity(markdown.len());
+    let mut in_callout = false;
+    for line in markdown.lines() {
+        if let Some(captures) = CALLOUT.captures(line) {
+            if in_callout {
+                out.push_str("\n:::\n");
+            }
+            out.push_str(&format!("\n::: {}\n\n", captures[1].to_lowercase()));
+            if !captures[2].trim().is_empty() {
+                out.push_str(&format!("**{}**\n\n", captures[2].trim()));
+            }
+            in_callout = true;
+            continue;
+        }
+        if in_callout {
+            if let Some(content) = line.strip_prefix('>') {
+                out.push_str(content.strip_prefix(' ').unwrap_or(content));
+                out.push('\n');
+                continue;
+            }
+            out.push_str("\n:::\n\n");
+            in_callout = false;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    if in_callout {
+        out.push_str("\n:::\n");
+    }
+    out
+}
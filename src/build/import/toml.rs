@@ -0,0 +1,298 @@
+//! A parser for the subset of TOML used in frontmatter and site configs:
+//! tables, arrays of tables, dotted keys, strings, numbers, booleans, dates,
+//! arrays, and inline tables.
+//!
+//! Dates are kept as strings, since frontmatter dates are strings too.
+
+use anyhow::{Context, bail};
+use serde_json::{Map, Value};
+
+use crate::build::import::unescape;
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn line(&self) -> usize {
+        self.text[..self.pos].matches('\n').count() + 1
+    }
+
+    /// Skip spaces and tabs, and also newlines and comments if `newlines` is
+    /// set.
+    fn skip_whitespace(&mut self, newlines: bool) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\r') => self.pos += 1,
+                Some('\n') if newlines => self.pos += 1,
+                Some('#') => {
+                    self.pos += self.rest().find('\n').unwrap_or(self.rest().len());
+                },
+                _ => return,
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> anyhow::Result<()> {
+        if self.peek() != Some(expected) {
+            bail!("expected `{expected}` on line {}", self.line());
+        }
+        self.pos += expected.len_utf8();
+        Ok(())
+    }
+
+    fn key_part(&mut self) -> anyhow::Result<String> {
+        match self.peek() {
+            Some('"' | '\'') => self.string(),
+            _ => {
+                let len = self
+                    .rest()
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+                    .unwrap_or(self.rest().len());
+                if len == 0 {
+                    bail!("expected a key on line {}", self.line());
+                }
+                let key = self.rest()[..len].to_owned();
+                self.pos += len;
+                Ok(key)
+            },
+        }
+    }
+
+    /// A key, which may be dotted like `taxonomies.tags`.
+    fn key(&mut self) -> anyhow::Result<Vec<String>> {
+        let mut parts = vec![self.key_part()?];
+        loop {
+            self.skip_whitespace(false);
+            if self.peek() != Some('.') {
+                return Ok(parts);
+            }
+            self.pos += 1;
+            self.skip_whitespace(false);
+            parts.push(self.key_part()?);
+        }
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let quote = self.peek().unwrap_or('"');
+        let triple = if quote == '"' { "\"\"\"" } else { "'''" };
+        if let Some(rest) = self.rest().strip_prefix(triple) {
+            let Some(end) = rest.find(triple) else {
+                bail!("unterminated multi-line string on line {}", self.line());
+            };
+            // A newline right after the opening quotes is trimmed
+            let content = rest[..end].strip_prefix('\n').unwrap_or(&rest[..end]);
+            let content = if quote == '"' {
+                unescape(content)
+            } else {
+                content.to_owned()
+            };
+            self.pos += triple.len() * 2 + end;
+            return Ok(content);
+        }
+
+        self.pos += 1;
+        let mut content = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((idx, c)) = chars.next() {
+            match c {
+                '\n' => break,
+                c if c == quote => {
+                    self.pos += idx + 1;
+                    return Ok(if quote == '"' {
+                        unescape(&content)
+                    } else {
+                        content
+                    });
+                },
+                '\\' if quote == '"' => {
+                    content.push('\\');
+                    if let Some((_, escaped)) = chars.next() {
+                        content.push(escaped);
+                    }
+                },
+                c => content.push(c),
+            }
+        }
+        bail!("unterminated string on line {}", self.line())
+    }
+
+    fn value(&mut self) -> anyhow::Result<Value> {
+        match self.peek() {
+            Some('"' | '\'') => Ok(Value::String(self.string()?)),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = vec![];
+                loop {
+                    self.skip_whitespace(true);
+                    if self.peek() == Some(']') {
+                        self.pos += 1;
+                        return Ok(Value::Array(items));
+                    }
+                    items.push(self.value()?);
+                    self.skip_whitespace(true);
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some(']') => {},
+                        _ => bail!("expected `,` or `]` in array on line {}", self.line()),
+                    }
+                }
+            },
+            Some('{') => {
+                self.pos += 1;
+                let mut table = Map::new();
+                loop {
+                    self.skip_whitespace(false);
+                    if self.peek() == Some('}') {
+                        self.pos += 1;
+                        return Ok(Value::Object(table));
+                    }
+                    let key = self.key()?;
+                    self.skip_whitespace(false);
+                    self.expect('=')?;
+                    self.skip_whitespace(false);
+                    let value = self.value()?;
+                    insert(&mut table, &key, value)?;
+                    self.skip_whitespace(false);
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some('}') => {},
+                        _ => bail!(
+                            "expected `,` or `}}` in inline table on line {}",
+                            self.line()
+                        ),
+                    }
+                }
+            },
+            _ => {
+                // Numbers, booleans, and dates, which may contain a space
+                // between the date and the time
+                let len = self
+                    .rest()
+                    .find([',', ']', '}', '\n', '#'])
+                    .unwrap_or(self.rest().len());
+                let value = scalar(self.rest()[..len].trim())
+                    .context(format!("invalid value on line {}", self.line()));
+                self.pos += len;
+                value
+            },
+        }
+    }
+}
+
+fn scalar(token: &str) -> anyhow::Result<Value> {
+    match token {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {},
+    }
+    let number = token.replace('_', "");
+    if let Ok(int) = number.parse::<i64>() {
+        return Ok(Value::from(int));
+    }
+    if let Ok(float) = number.parse::<f64>() {
+        return Ok(Value::from(float));
+    }
+    // Dates and times, like `2024-01-02` or `2024-01-02T10:00:00Z`
+    if token.len() >= 8 && token.starts_with(|c: char| c.is_ascii_digit()) {
+        return Ok(Value::String(token.to_owned()));
+    }
+    bail!("unsupported value `{token}`")
+}
+
+/// The table at a dotted key, creating the tables along the way. The key of an
+/// array of tables refers to its last table.
+fn table_mut<'a>(
+    table: &'a mut Map<String, Value>,
+    key: &[String],
+) -> anyhow::Result<&'a mut Map<String, Value>> {
+    let mut current = table;
+    for part in key {
+        let entry = current
+            .entry(part.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        let entry = if entry.as_array().is_some_and(|tables| !tables.is_empty()) {
+            entry
+                .as_array_mut()
+                .and_then(|tables| tables.last_mut())
+                .expect("array of tables is not empty")
+        } else {
+            entry
+        };
+        let Value::Object(next) = entry else {
+            bail!("key [{}] is not a table", key.join("."));
+        };
+        current = next;
+    }
+    Ok(current)
+}
+
+/// Insert a value at a dotted key, creating the tables along the way.
+fn insert(table: &mut Map<String, Value>, key: &[String], value: Value) -> anyhow::Result<()> {
+    let (last, parents) = key.split_last().expect("keys are never empty");
+    let current = table_mut(table, parents)?;
+    if current.insert(last.clone(), value).is_some() {
+        bail!("key [{}] is defined more than once", key.join("."));
+    }
+    Ok(())
+}
+
+/// Parse a TOML document into a JSON object.
+pub fn parse(text: &str) -> anyhow::Result<Value> {
+    let mut parser = Parser { text, pos: 0 };
+    let mut root = Map::new();
+    // The key of the current `[table]`, empty for the root table
+    let mut table_key: Vec<String> = vec![];
+
+    loop {
+        parser.skip_whitespace(true);
+        match parser.peek() {
+            None => break,
+            Some('[') => {
+                let is_array = parser.rest().starts_with("[[");
+                parser.pos += if is_array { 2 } else { 1 };
+                parser.skip_whitespace(false);
+                table_key = parser.key()?;
+                parser.expect(']')?;
+                if is_array {
+                    parser.expect(']')?;
+                    let (last, parents) = table_key.split_last().expect("keys are never empty");
+                    let tables = table_mut(&mut root, parents)?
+                        .entry(last.clone())
+                        .or_insert_with(|| Value::Array(vec![]));
+                    let Value::Array(tables) = tables else {
+                        bail!("key [{}] is not an array of tables", table_key.join("."));
+                    };
+                    tables.push(Value::Object(Map::new()));
+                } else {
+                    table_mut(&mut root, &table_key)?;
+                }
+            },
+            Some(_) => {
+                let key = parser.key()?;
+                parser.skip_whitespace(false);
+                parser.expect('=')?;
+                parser.skip_whitespace(false);
+                let value = parser.value()?;
+                let full_key = table_key.iter().cloned().chain(key).collect::<Vec<_>>();
+                insert(&mut root, &full_key, value)?;
+            },
+        }
+        parser.skip_whitespace(false);
+        match parser.peek() {
+            None | Some('\n') => {},
+            _ => bail!("unexpected text after value on line {}", parser.line()),
+        }
+    }
+
+    Ok(Value::Object(root))
+}
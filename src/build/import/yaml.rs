@@ -0,0 +1,439 @@
+//! A parser for the subset of YAML used in frontmatter and site configs:
+//! block mappings and sequences, flow sequences and mappings, plain and quoted
+//! scalars, and literal and folded block scalars.
+//!
+//! Anchors, aliases, tags, and multiple documents aren't supported. Dates are
+//! kept as strings, like in the TOML parser.
+
+use anyhow::bail;
+use serde_json::{Map, Value};
+
+use crate::build::import::unescape;
+
+struct Parser {
+    lines: Vec<String>,
+    idx: usize,
+}
+
+/// The number of spaces at the start of a line.
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Whether a line has nothing but whitespace and a comment.
+fn is_blank(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.is_empty() || trimmed.starts_with('#')
+}
+
+/// A line without its trailing comment, which starts with a `#` after
+/// whitespace, outside of quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    let mut chars = line.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match quote {
+            None if c == '#' && prev.is_whitespace() => return line[..idx].trim_end(),
+            // Quotes only start a string at the start of a value
+            None if matches!(c, '"' | '\'') && (prev.is_whitespace() || "[{,:-".contains(prev)) => {
+                quote = Some(c);
+            },
+            Some('"') if c == '\\' => {
+                chars.next();
+            },
+            Some('\'') if c == '\'' && line[idx + 1..].starts_with('\'') => {
+                chars.next();
+            },
+            Some(open) if c == open => quote = None,
+            _ => {},
+        }
+        prev = c;
+    }
+    line.trim_end()
+}
+
+/// Whether a line is an item of a block sequence.
+fn is_item(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed == "-" || trimmed.starts_with("- ")
+}
+
+/// Split a line of a block mapping into its key and the text after the colon,
+/// or `None` if it isn't one.
+fn split_key(line: &str) -> anyhow::Result<Option<(String, &str)>> {
+    let trimmed = line.trim_start();
+    if let Some(quote @ ('"' | '\'')) = trimmed.chars().next() {
+        let Some(end) = trimmed[1..].find(quote) else {
+            return Ok(None);
+        };
+        let rest = trimmed[end + 2..].trim_start();
+        return Ok(
+            match (rest.strip_prefix(':'), scalar(&trimmed[..end + 2])?) {
+                (Some(value), Value::String(key)) if value.is_empty() || value.starts_with(' ') => {
+                    Some((key, value.trim_start()))
+                },
+                _ => None,
+            },
+        );
+    }
+    if trimmed.starts_with(['[', '{']) {
+        return Ok(None);
+    }
+    let end = trimmed
+        .match_indices(':')
+        .map(|(idx, _)| idx)
+        .find(|&idx| trimmed[idx + 1..].is_empty() || trimmed[idx + 1..].starts_with(' '));
+    Ok(end.map(|end| {
+        (
+            trimmed[..end].trim_end().to_owned(),
+            trimmed[end + 1..].trim_start(),
+        )
+    }))
+}
+
+impl Parser {
+    fn skip_blank(&mut self) {
+        while self.lines.get(self.idx).is_some_and(|line| is_blank(line)) {
+            self.idx += 1;
+        }
+    }
+
+    /// The next line that isn't blank, if it is indented by at least
+    /// `min_indent`.
+    fn next_line(&mut self, min_indent: usize) -> Option<String> {
+        self.skip_blank();
+        self.lines
+            .get(self.idx)
+            .filter(|line| indent(line) >= min_indent)
+            .cloned()
+    }
+
+    /// The node whose lines are indented by at least `min_indent`, which is
+    /// null if there are none.
+    fn node(&mut self, min_indent: usize) -> anyhow::Result<Value> {
+        let Some(line) = self.next_line(min_indent) else {
+            return Ok(Value::Null);
+        };
+        let line_indent = indent(&line);
+        if is_item(&line) {
+            self.sequence(line_indent)
+        } else if split_key(strip_comment(&line))?.is_some() {
+            self.mapping(line_indent)
+        } else {
+            let line_idx = self.idx;
+            let text = self.continued(strip_comment(&line).trim_start().to_owned(), line_indent);
+            inline_value(&text, line_idx)
+        }
+    }
+
+    /// A scalar or flow collection that starts with `text`, along with the
+    /// lines after it that are indented by more than `indent`, which continue
+    /// it.
+    fn continued(&mut self, mut text: String, min_indent: usize) -> String {
+        self.idx += 1;
+        while let Some(line) = self.lines.get(self.idx) {
+            if is_blank(line) || indent(line) <= min_indent {
+                break;
+            }
+            text.push(' ');
+            text.push_str(strip_comment(line).trim());
+            self.idx += 1;
+        }
+        text
+    }
+
+    fn sequence(&mut self, seq_indent: usize) -> anyhow::Result<Value> {
+        let mut items = vec![];
+        while let Some(line) = self.next_line(seq_indent) {
+            if indent(&line) != seq_indent || !is_item(&line) {
+                break;
+            }
+            // The item is parsed as if the dash were a space, so that a mapping
+            // that starts on the line of the dash continues on the lines after
+            let line = &mut self.lines[self.idx];
+            line.replace_range(seq_indent..seq_indent + 1, " ");
+            if line.trim().is_empty() {
+                self.idx += 1;
+            }
+            items.push(self.node(seq_indent + 1)?);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn mapping(&mut self, map_indent: usize) -> anyhow::Result<Value> {
+        let mut map = Map::new();
+        while let Some(line) = self.next_line(map_indent) {
+            if indent(&line) != map_indent {
+                bail!("unexpected indentation on line {}", self.idx + 1);
+            }
+            let Some((key, rest)) = split_key(strip_comment(&line))? else {
+                bail!("expected a key on line {}", self.idx + 1);
+            };
+            let value = if rest.is_empty() {
+                self.idx += 1;
+                // A sequence under a key may be at the same indentation as it
+                match self.next_line(map_indent) {
+                    Some(next) if indent(&next) == map_indent && is_item(&next) => {
+                        self.sequence(map_indent)?
+                    },
+                    _ => self.node(map_indent + 1)?,
+                }
+            } else if let Some(header) = rest
+                .strip_prefix(['|', '>'])
+                .filter(|header| header.chars().all(|c| "+-123456789".contains(c)))
+            {
+                let folded = rest.starts_with('>');
+                self.idx += 1;
+                self.block_scalar(map_indent, folded, header)
+            } else {
+                let line_idx = self.idx;
+                let text = self.continued(rest.to_owned(), map_indent);
+                inline_value(&text, line_idx)?
+            };
+            if map.insert(key.clone(), value).is_some() {
+                bail!("key [{key}] is defined more than once");
+            }
+        }
+        Ok(Value::Object(map))
+    }
+
+    /// The lines of a literal (`|`) or folded (`>`) block scalar, which are
+    /// indented by more than the key it belongs to.
+    fn block_scalar(&mut self, key_indent: usize, folded: bool, header: &str) -> Value {
+        let start = self.idx;
+        while let Some(line) = self.lines.get(self.idx) {
+            if !line.trim().is_empty() && indent(line) <= key_indent {
+                break;
+            }
+            self.idx += 1;
+        }
+        let lines = &self.lines[start..self.idx];
+        let content_indent = lines
+            .iter()
+            .find(|line| !line.trim().is_empty())
+            .map_or(0, |line| indent(line));
+        let lines = lines
+            .iter()
+            .map(|line| line.get(content_indent..).unwrap_or_default())
+            .collect::<Vec<_>>();
+
+        let mut text = String::new();
+        for (idx, line) in lines.iter().enumerate() {
+            if idx > 0 {
+                let prev = lines[idx - 1];
+                // Folded lines are joined with a space, and the line break
+                // before blank lines is dropped, but more indented lines are
+                // kept as they are
+                if !folded || prev.starts_with(' ') || line.starts_with(' ') || prev.is_empty() {
+                    text.push('\n');
+                } else if !line.is_empty() {
+                    text.push(' ');
+                }
+            }
+            text.push_str(line);
+        }
+        let text = text.trim_end_matches('\n');
+        let mut text = if header.contains('-') || text.is_empty() {
+            text.to_owned()
+        } else {
+            format!("{text}\n")
+        };
+        if header.contains('+') {
+            let trailing = lines
+                .iter()
+                .rev()
+                .take_while(|line| line.is_empty())
+                .count();
+            text.extend(std::iter::repeat_n('\n', trailing.saturating_sub(1)));
+        }
+        Value::String(text)
+    }
+}
+
+/// Parse a scalar or flow collection written after a key or dash.
+fn inline_value(text: &str, line_idx: usize) -> anyhow::Result<Value> {
+    let mut flow = Flow { text, pos: 0 };
+    let value = flow.value(false)?;
+    flow.skip_whitespace();
+    if flow.pos != text.len() {
+        bail!("unexpected text after value on line {}", line_idx + 1);
+    }
+    Ok(value)
+}
+
+/// A parser for a single value, which may be a flow collection like
+/// `[a, b]` or `{a: 1}`.
+struct Flow<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Flow<'_> {
+    fn rest(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.pos = self.text.len() - self.rest().trim_start().len();
+    }
+
+    fn expect_separator(&mut self, close: char) -> anyhow::Result<bool> {
+        self.skip_whitespace();
+        match self.rest().chars().next() {
+            Some(',') => {
+                self.pos += 1;
+                Ok(false)
+            },
+            Some(c) if c == close => {
+                self.pos += 1;
+                Ok(true)
+            },
+            _ => bail!("expected `,` or `{close}` in `{}`", self.text),
+        }
+    }
+
+    /// Parse a value, stopping at the separators of flow collections when it
+    /// is inside one.
+    fn value(&mut self, in_flow: bool) -> anyhow::Result<Value> {
+        self.skip_whitespace();
+        match self.rest().chars().next() {
+            Some('[') => {
+                self.pos += 1;
+                let mut items = vec![];
+                self.skip_whitespace();
+                if self.rest().starts_with(']') {
+                    self.pos += 1;
+                    return Ok(Value::Array(items));
+                }
+                loop {
+                    items.push(self.value(true)?);
+                    if self.expect_separator(']')? {
+                        return Ok(Value::Array(items));
+                    }
+                }
+            },
+            Some('{') => {
+                self.pos += 1;
+                let mut map = Map::new();
+                self.skip_whitespace();
+                if self.rest().starts_with('}') {
+                    self.pos += 1;
+                    return Ok(Value::Object(map));
+                }
+                loop {
+                    let key = match self.value(true)? {
+                        Value::String(key) => key,
+                        key => key.to_string(),
+                    };
+                    self.skip_whitespace();
+                    let value = match self.rest().strip_prefix(':') {
+                        Some(_) => {
+                            self.pos += 1;
+                            self.value(true)?
+                        },
+                        None => Value::Null,
+                    };
+                    map.insert(key, value);
+                    if self.expect_separator('}')? {
+                        return Ok(Value::Object(map));
+                    }
+                }
+            },
+            Some(quote @ ('"' | '\'')) => {
+                let rest = self.rest();
+                let mut chars = rest.char_indices().skip(1);
+                let mut end = None;
+                while let Some((idx, c)) = chars.next() {
+                    match c {
+                        '\\' if quote == '"' => {
+                            chars.next();
+                        },
+                        // Two single quotes are an escaped single quote
+                        '\'' if quote == '\'' && rest[idx + 1..].starts_with('\'') => {
+                            chars.next();
+                        },
+                        c if c == quote => {
+                            end = Some(idx);
+                            break;
+                        },
+                        _ => {},
+                    }
+                }
+                let Some(end) = end else {
+                    bail!("unterminated string in `{}`", self.text);
+                };
+                let token = &rest[..=end];
+                let value = scalar(token)?;
+                self.pos += token.len();
+                Ok(value)
+            },
+            _ => {
+                let rest = self.rest();
+                let len = if in_flow {
+                    rest.find([',', ']', '}'])
+                        .into_iter()
+                        .chain(rest.find(": "))
+                        .min()
+                        .unwrap_or(rest.len())
+                } else {
+                    rest.len()
+                };
+                let value = scalar(rest[..len].trim())?;
+                self.pos += len;
+                Ok(value)
+            },
+        }
+    }
+}
+
+fn scalar(token: &str) -> anyhow::Result<Value> {
+    if let Some(content) = token
+        .strip_prefix('"')
+        .and_then(|token| token.strip_suffix('"'))
+    {
+        return Ok(Value::String(unescape(content)));
+    }
+    if let Some(content) = token
+        .strip_prefix('\'')
+        .and_then(|token| token.strip_suffix('\''))
+    {
+        return Ok(Value::String(content.replace("''", "'")));
+    }
+    if token.starts_with(['&', '*', '!']) {
+        bail!("anchors, aliases, and tags are not supported: `{token}`");
+    }
+    Ok(match token {
+        "" | "~" | "null" | "Null" | "NULL" => Value::Null,
+        "true" | "True" | "TRUE" => Value::Bool(true),
+        "false" | "False" | "FALSE" => Value::Bool(false),
+        _ => {
+            if let Ok(int) = token.parse::<i64>() {
+                Value::from(int)
+            } else if let Some(float) = token.parse::<f64>().ok().filter(|float| float.is_finite())
+            {
+                Value::from(float)
+            } else {
+                Value::String(token.to_owned())
+            }
+        },
+    })
+}
+
+/// Parse a YAML document into a JSON value.
+pub fn parse(text: &str) -> anyhow::Result<Value> {
+    // Document markers are left as blank lines, to keep the line numbers
+    let lines = text
+        .lines()
+        .map(|line| match line.trim_end() {
+            "---" | "..." => String::new(),
+            line => line.to_owned(),
+        })
+        .collect();
+    let mut parser = Parser { lines, idx: 0 };
+    let value = parser.node(0)?;
+    if parser.next_line(0).is_some() {
+        bail!("unexpected indentation on line {}", parser.idx + 1);
+    }
+    Ok(value)
+}
@@ -0,0 +1,50 @@
+use std::{path::Path, process::Command};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::debug;
+
+/// Details about the current build, exposed to templates as `build`.
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    /// Full hash of the commit checked out in the input directory
+    pub commit: Option<String>,
+    /// Abbreviated hash of the same commit
+    pub short_commit: Option<String>,
+    pub branch: Option<String>,
+    /// Whether the input directory has uncommitted changes
+    pub dirty: Option<bool>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl BuildInfo {
+    /// Collect build details, leaving the git fields empty if the input
+    /// directory is not in a git repository or git is not installed.
+    pub fn collect(input_root: &Path) -> Self {
+        let git = |args: &[&str]| -> Option<String> {
+            let output = Command::new("git")
+                .arg("-C")
+                .arg(input_root)
+                .args(args)
+                .output()
+                .ok()?;
+            if !output.status.success() {
+                debug!(?args, stderr = %String::from_utf8_lossy(&output.stderr), "Git command failed");
+                return None;
+            }
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        };
+
+        let info = Self {
+            commit: git(&["rev-parse", "HEAD"]),
+            short_commit: git(&["rev-parse", "--short", "HEAD"]),
+            branch: git(&["rev-parse", "--abbrev-ref", "HEAD"]),
+            dirty: git(&["status", "--porcelain"]).map(|status| !status.is_empty()),
+            timestamp: Utc::now(),
+        };
+
+        debug!(?info, "Collected build info");
+
+        info
+    }
+}
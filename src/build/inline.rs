@@ -0,0 +1,150 @@
+use std::{borrow::Cow, fs, path::Path, sync::LazyLock};
+
+use anyhow::Context;
+use regex::{Captures, Regex};
+use tracing::debug;
+
+use crate::build::{
+    BuildDirFiles, check::decode_entities, config::InlineAssetsConfig, links::resolve_internal,
+    manifest::url_path,
+};
+
+/// Matches a `<link>` tag
+pub static LINK_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<link\b[^>]*>").unwrap());
+
+/// Matches an empty `<script>` element, which loads its source from a file
+static SCRIPT_TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)<script\b([^>]*)>\s*</script>").unwrap());
+
+/// Matches a single attribute and its optional value
+static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"([a-zA-Z_:][-a-zA-Z0-9_:.]*)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+)))?"#)
+        .unwrap()
+});
+
+/// The decoded value of an attribute of an HTML tag, or an empty string for an
+/// attribute without a value.
+pub fn attribute<'a>(tag: &'a str, name: &str) -> Option<Cow<'a, str>> {
+    ATTRIBUTE
+        .captures_iter(tag.trim_start_matches('<'))
+        .skip(1)
+        .find(|captures| captures[1].eq_ignore_ascii_case(name))
+        .map(|captures| {
+            let value = captures
+                .get(2)
+                .or_else(|| captures.get(3))
+                .or_else(|| captures.get(4))
+                .map_or("", |value| value.as_str());
+            decode_entities(value)
+        })
+}
+
+/// Read the local asset linked from a page, if it is small enough to inline.
+fn read_small_asset(
+    output_root: &Path,
+    page_url: &str,
+    dest: &str,
+    max_size: u64,
+) -> Option<String> {
+    let asset_url = resolve_internal(Path::new(page_url), dest)?;
+    let asset_path = output_root.join(asset_url.trim_start_matches('/'));
+    let size = fs::metadata(&asset_path).ok()?.len();
+    if size >= max_size {
+        return None;
+    }
+    fs::read_to_string(&asset_path).ok()
+}
+
+fn inline_stylesheets(html: &str, output_root: &Path, page_url: &str, max_size: u64) -> String {
+    LINK_TAG
+        .replace_all(html, |captures: &Captures| {
+            let tag = &captures[0];
+            let is_stylesheet = attribute(tag, "rel").is_some_and(|rel| {
+                rel.split_whitespace()
+                    .any(|rel| rel.eq_ignore_ascii_case("stylesheet"))
+            });
+            let Some(href) = attribute(tag, "href").filter(|_| is_stylesheet) else {
+                return tag.to_owned();
+            };
+            let Some(css) = read_small_asset(output_root, page_url, &href, max_size) else {
+                return tag.to_owned();
+            };
+            // Relative URLs in the stylesheet would resolve against the page instead
+            if css.contains("url(") || css.contains("@import") || css.contains("</style") {
+                debug!(page_url, %href, "Stylesheet references other files, not inlining");
+                return tag.to_owned();
+            }
+
+            debug!(page_url, %href, "Inlining stylesheet");
+            match attribute(tag, "media") {
+                Some(media) => format!(r#"<style media="{media}">{css}</style>"#),
+                None => format!("<style>{css}</style>"),
+            }
+        })
+        .into_owned()
+}
+
+fn inline_scripts(html: &str, output_root: &Path, page_url: &str, max_size: u64) -> String {
+    SCRIPT_TAG
+        .replace_all(html, |captures: &Captures| {
+            let tag = &captures[0];
+            // Inline scripts can't be deferred, so inlining would change when they run
+            let Some(src) = attribute(tag, "src")
+                .filter(|_| attribute(tag, "defer").is_none() && attribute(tag, "async").is_none())
+            else {
+                return tag.to_owned();
+            };
+            let Some(js) = read_small_asset(output_root, page_url, &src, max_size) else {
+                return tag.to_owned();
+            };
+            if js.contains("</script") {
+                return tag.to_owned();
+            }
+
+            debug!(page_url, %src, "Inlining script");
+            match attribute(tag, "type") {
+                Some(script_type) => format!(r#"<script type="{script_type}">{js}</script>"#),
+                None => format!("<script>{js}</script>"),
+            }
+        })
+        .into_owned()
+}
+
+/// Replace links to small local stylesheets and scripts in every HTML page with
+/// the contents of the file, saving a request for each.
+///
+/// The assets themselves are still written, for any page that links to them
+/// normally. Stylesheets that reference other files and deferred scripts are
+/// never inlined, since they would behave differently.
+#[tracing::instrument(skip_all)]
+pub fn inline_small_assets(config: &InlineAssetsConfig, output_root: &Path) -> anyhow::Result<()> {
+    let output_files =
+        BuildDirFiles::gather(output_root).context("failed to collect output files")?;
+
+    let mut num_pages = 0;
+    for (path, file) in output_files.files {
+        if path.extension().is_none_or(|ext| ext != "html") {
+            continue;
+        }
+
+        let html = fs::read_to_string(&file.full_path).context(format!(
+            "failed to read output file [{}]",
+            file.full_path.display()
+        ))?;
+        let page_url = url_path(&path);
+        let inlined = inline_stylesheets(&html, output_root, &page_url, config.max_size);
+        let inlined = inline_scripts(&inlined, output_root, &page_url, config.max_size);
+
+        if inlined != html {
+            fs::write(&file.full_path, inlined).context(format!(
+                "failed to write output file [{}]",
+                file.full_path.display()
+            ))?;
+            num_pages += 1;
+        }
+    }
+
+    debug!(num_pages, "Inlined small assets");
+
+    Ok(())
+}
@@ -0,0 +1,228 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+use jotdown::{Container, Event};
+use serde::Serialize;
+use tracing::debug;
+
+use crate::build::{ContentSlug, Metadata, MetadataContainer};
+
+/// A link from one page on the site to another.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PageLink {
+    pub url_path: String,
+    pub title: Option<String>,
+}
+
+/// Resolve a link destination found on the page at `page_url` into the URL
+/// path of the page it points to, without any query or fragment.
+///
+/// Returns `None` for external links, like `https://` or `mailto:` URLs, and
+/// for links to a fragment of the same page.
+pub fn resolve_internal(page_url: &Path, dest: &str) -> Option<String> {
+    let dest = dest.split(['#', '?']).next().unwrap_or_default();
+    if dest.is_empty() || dest.contains(':') || dest.starts_with("//") {
+        return None;
+    }
+
+    let joined = if dest.starts_with('/') {
+        PathBuf::from(dest)
+    } else {
+        page_url.parent().unwrap_or(Path::new("/")).join(dest)
+    };
+
+    // Normalize `.` and `..` without touching the filesystem
+    let mut normalized = PathBuf::from("/");
+    for component in joined.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            },
+            Component::Normal(part) => normalized.push(part),
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {},
+        }
+    }
+
+    let mut url_path = normalized.to_string_lossy().into_owned();
+    if dest.ends_with('/') {
+        if !url_path.ends_with('/') {
+            url_path.push('/');
+        }
+        url_path.push_str("index.html");
+    }
+
+    Some(url_path)
+}
+
+/// Record the URL paths of every internal page linked from the djot events.
+pub fn record_links(metadata: &mut MetadataContainer, slug: &ContentSlug, events: &[Event<'_>]) {
+    let page_url = metadata[slug].url_path.clone();
+    let outgoing_links = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Start(Container::Link(dest, _), _) => resolve_internal(&page_url, dest),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+
+    debug!(num_links = outgoing_links.len(), "Recorded internal links");
+
+    metadata[slug].outgoing_links = outgoing_links;
+}
+
+/// Fill in the backlinks of every page from the outgoing links of all other
+/// pages.
+pub fn resolve_backlinks(metadata: &mut MetadataContainer) {
+    let slugs_by_url = metadata
+        .0
+        .iter()
+        .map(|(slug, md)| (md.url_path.to_string_lossy().into_owned(), slug.clone()))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut backlinks: BTreeMap<ContentSlug, BTreeSet<PageLink>> = BTreeMap::new();
+    for (source_slug, md) in &metadata.0 {
+        for target_url in &md.outgoing_links {
+            let Some(target_slug) = slugs_by_url.get(target_url) else {
+                continue;
+            };
+            if target_slug == source_slug {
+                continue;
+            }
+
+            backlinks
+                .entry(target_slug.clone())
+                .or_default()
+                .insert(PageLink {
+                    url_path: md.url_path.to_string_lossy().into_owned(),
+                    title: md.title.clone(),
+                });
+        }
+    }
+
+    for (slug, links) in backlinks {
+        metadata[&slug].backlinks = links.into_iter().collect();
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct GraphNode<'a> {
+    id: &'a str,
+    title: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct GraphEdge<'a> {
+    source: &'a str,
+    target: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct LinkGraph<'a> {
+    nodes: Vec<GraphNode<'a>>,
+    edges: Vec<GraphEdge<'a>>,
+}
+
+impl<'a> LinkGraph<'a> {
+    fn from_pages(pages: &'a [(&'a Metadata, String)]) -> Self {
+        let nodes = pages
+            .iter()
+            .map(|(md, url_path)| GraphNode {
+                id: url_path,
+                title: md.title.as_deref(),
+            })
+            .collect::<Vec<_>>();
+
+        // Only keep edges between pages, links to other assets are not part of the
+        // graph
+        let known_urls = pages
+            .iter()
+            .map(|(_, url_path)| url_path.as_str())
+            .collect::<BTreeSet<_>>();
+        let edges = pages
+            .iter()
+            .flat_map(|(md, source)| {
+                md.outgoing_links
+                    .iter()
+                    .filter(|target| known_urls.contains(target.as_str()))
+                    .map(move |target| GraphEdge { source, target })
+            })
+            .collect();
+
+        Self { nodes, edges }
+    }
+
+    fn to_dot(&self) -> anyhow::Result<String> {
+        let mut buf = String::new();
+        writeln!(buf, "digraph links {{")?;
+        for node in &self.nodes {
+            let label = node.title.unwrap_or(node.id);
+            writeln!(
+                buf,
+                "  {} [label={}];",
+                dot_string(node.id),
+                dot_string(label)
+            )?;
+        }
+        for edge in &self.edges {
+            writeln!(
+                buf,
+                "  {} -> {};",
+                dot_string(edge.source),
+                dot_string(edge.target)
+            )?;
+        }
+        writeln!(buf, "}}")?;
+
+        Ok(buf)
+    }
+}
+
+/// Quote a string for DOT, where only quotes, backslashes, and line breaks
+/// need escaping. Every other character is written as is.
+fn dot_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => {},
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Write the graph of links between pages to the given path, as GraphViz DOT if
+/// the file has a `.dot` or `.gv` extension and as JSON otherwise.
+pub fn write_link_graph(metadata: &MetadataContainer, graph_path: &Path) -> anyhow::Result<()> {
+    let pages = metadata
+        .0
+        .values()
+        .filter(|md| md.url_path.extension().is_some_and(|ext| ext == "html"))
+        .map(|md| (md, md.url_path.to_string_lossy().into_owned()))
+        .collect::<Vec<_>>();
+    let graph = LinkGraph::from_pages(&pages);
+
+    let content = match graph_path.extension().and_then(|ext| ext.to_str()) {
+        Some("dot" | "gv") => graph
+            .to_dot()
+            .context("failed to format link graph as DOT")?,
+        _ => serde_json::to_string_pretty(&graph).context("failed to serialize link graph")?,
+    };
+
+    fs::write(graph_path, content).context(format!(
+        "failed to write link graph to [{}]",
+        graph_path.display()
+    ))?;
+    debug!(graph_path = %graph_path.display(), num_nodes = graph.nodes.len(), num_edges = graph.edges.len(), "Written link graph");
+
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::Context;
+use argh::{ArgsInfo, CommandInfoWithArgs, FlagInfo, FlagInfoKind, FromArgs, Optionality};
+use tracing::info;
+
+use crate::build::errors::exit_statuses;
+
+/// Print a man page for `www` and all of its subcommands, in roff.
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(subcommand, name = "man")]
+pub struct ManCmd {
+    /// write the man page to this file instead of stdout, like `www.1`
+    #[argh(option, short = 'o')]
+    output: Option<PathBuf>,
+}
+
+/// Escape text for roff, so that backslashes, dashes and leading dots or
+/// quotes are shown as is. Text in backticks is shown in bold.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut in_code = false;
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\e"),
+            '-' => escaped.push_str("\\-"),
+            '`' => {
+                escaped.push_str(if in_code { "\\fR" } else { "\\fB" });
+                in_code = !in_code;
+            },
+            _ => escaped.push(c),
+        }
+    }
+    if in_code {
+        escaped.push_str("\\fR");
+    }
+
+    if escaped.starts_with(['.', '\'']) {
+        escaped.insert_str(0, "\\&");
+    }
+    escaped
+}
+
+/// A description as a sentence, since argh descriptions are usually lowercase
+/// fragments.
+fn sentence(description: &str) -> String {
+    let mut chars = description.trim().chars();
+    let Some(first) = chars.next() else {
+        return String::new();
+    };
+    let mut sentence = first.to_uppercase().chain(chars).collect::<String>();
+    if !sentence.ends_with(['.', '!', '?']) {
+        sentence.push('.');
+    }
+    escape(&sentence)
+}
+
+/// The flag as it is written on the command line, like `-o, --output <output>`.
+fn flag_usage(flag: &FlagInfo) -> String {
+    let mut usage = String::new();
+    if let Some(short) = flag.short {
+        usage.push_str(&format!("\\fB\\-{short}\\fR, "));
+    }
+    usage.push_str(&format!("\\fB{}\\fR", escape(flag.long)));
+    if let FlagInfoKind::Option { arg_name } = flag.kind {
+        usage.push_str(&format!(" \\fI<{}>\\fR", escape(arg_name)));
+    }
+    usage
+}
+
+/// The synopsis of a command, with optional arguments in brackets.
+fn synopsis(path: &str, command: &CommandInfoWithArgs) -> String {
+    let mut synopsis = format!(".B {}\n", escape(path));
+    for flag in command.flags.iter().filter(|flag| !flag.hidden) {
+        if flag.long == "--help" {
+            continue;
+        }
+        let usage = flag_usage(flag);
+        match flag.optionality {
+            Optionality::Required => synopsis.push_str(&usage),
+            Optionality::Repeating => synopsis.push_str(&format!("[{usage}]...")),
+            Optionality::Optional | Optionality::Greedy => synopsis.push_str(&format!("[{usage}]")),
+        }
+        synopsis.push('\n');
+    }
+    for positional in command.positionals.iter().filter(|arg| !arg.hidden) {
+        let name = format!("\\fI<{}>\\fR", escape(positional.name));
+        match positional.optionality {
+            Optionality::Required => synopsis.push_str(&name),
+            Optionality::Optional => synopsis.push_str(&format!("[{name}]")),
+            Optionality::Repeating | Optionality::Greedy => {
+                synopsis.push_str(&format!("[{name}...]"))
+            },
+        }
+        synopsis.push('\n');
+    }
+    if !command.commands.is_empty() {
+        synopsis.push_str("\\fI<command>\\fR [\\fI<args>\\fR]\n");
+    }
+    synopsis
+}
+
+/// The positional arguments and flags of a command, as a list of tagged
+/// paragraphs.
+fn arguments(command: &CommandInfoWithArgs) -> String {
+    let mut arguments = String::new();
+    for positional in command.positionals.iter().filter(|arg| !arg.hidden) {
+        arguments.push_str(&format!(
+            ".TP\n\\fI<{}>\\fR\n{}\n",
+            escape(positional.name),
+            sentence(positional.description)
+        ));
+    }
+    for flag in command.flags.iter().filter(|flag| !flag.hidden) {
+        arguments.push_str(&format!(
+            ".TP\n{}\n{}\n",
+            flag_usage(flag),
+            sentence(flag.description)
+        ));
+    }
+    arguments
+}
+
+/// Write a section for each subcommand, nested subcommands included, named by
+/// the full command line that runs it.
+fn write_commands(page: &mut String, path: &str, command: &CommandInfoWithArgs) {
+    for subcommand in &command.commands {
+        let path = format!("{path} {}", subcommand.name);
+        let command = &subcommand.command;
+        page.push_str(&format!(".SS \"{}\"\n", escape(&path)));
+        page.push_str(&synopsis(&path, command));
+        page.push_str(&format!(".PP\n{}\n", sentence(command.description)));
+        page.push_str(".RS\n");
+        page.push_str(&arguments(command));
+        page.push_str(".RE\n");
+        write_commands(page, &path, command);
+    }
+}
+
+/// Render the man page of a command line tool from its argh definitions.
+pub fn render_man_page(name: &str, command: &CommandInfoWithArgs) -> String {
+    let mut page = format!(".TH {} 1\n", escape(&name.to_uppercase()));
+    page.push_str(&format!(
+        ".SH NAME\n{} \\- {}\n",
+        escape(name),
+        sentence(command.description)
+    ));
+    page.push_str(&format!(".SH SYNOPSIS\n{}", synopsis(name, command)));
+
+    page.push_str(".SH OPTIONS\n");
+    page.push_str(&arguments(command));
+
+    page.push_str(".SH COMMANDS\n");
+    write_commands(&mut page, name, command);
+
+    if !command.examples.is_empty() {
+        page.push_str(".SH EXAMPLES\n");
+        for example in command.examples {
+            page.push_str(&format!(".PP\n{}\n", escape(example)));
+        }
+    }
+
+    // The exit codes come from the failure classes, rather than the error codes
+    // listed for `--help`, so that they can't fall out of date
+    page.push_str(".SH EXIT STATUS\n");
+    for (code, description) in exit_statuses() {
+        page.push_str(&format!(".TP\n{code}\n{}\n", sentence(description)));
+    }
+    page
+}
+
+/// Print the man page, or write it to the output file.
+pub fn man(cmd: ManCmd, name: &str, command: &CommandInfoWithArgs) -> anyhow::Result<()> {
+    let page = render_man_page(name, command);
+    match cmd.output {
+        Some(output) => {
+            fs::write(&output, page).context("failed to write man page")?;
+            info!(path = %output.display(), "Wrote man page");
+        },
+        None => print!("{page}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::Context;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::build::BuildDirFiles;
+
+/// The file name of the precache manifest written into the root of the output
+/// directory.
+const PRECACHE_MANIFEST_FILENAME: &str = "precache-manifest.json";
+
+#[derive(Debug, Serialize)]
+pub struct OutputEntry {
+    pub hash: String,
+    pub size: u64,
+}
+
+/// Every file written to the output directory, keyed by the URL path it is
+/// served at.
+#[derive(Debug)]
+pub struct OutputManifest {
+    pub files: BTreeMap<String, OutputEntry>,
+}
+
+/// A single entry of the precache manifest, in the same shape that `workbox`
+/// and similar service worker libraries expect.
+#[derive(Debug, Serialize)]
+struct PrecacheEntry<'a> {
+    url: &'a str,
+    revision: &'a str,
+}
+
+impl OutputManifest {
+    pub fn gather(output_root: &Path) -> anyhow::Result<Self> {
+        let output_files =
+            BuildDirFiles::gather(output_root).context("failed to collect output files")?;
+
+        let mut files = BTreeMap::new();
+        for (path, file) in output_files.files {
+            let contents = fs::read(&file.full_path).context(format!(
+                "failed to read output file [{}]",
+                file.full_path.display()
+            ))?;
+
+            let url = url_path(&path);
+            let entry = OutputEntry {
+                hash: format!("{:x}", Sha256::digest(&contents)),
+                size: contents.len() as u64,
+            };
+            files.insert(url, entry);
+        }
+
+        debug!(num_files = files.len(), "Gathered output manifest");
+
+        Ok(Self { files })
+    }
+
+    pub fn write_precache(&self, output_root: &Path) -> anyhow::Result<()> {
+        let entries = self
+            .files
+            .iter()
+            .map(|(url, entry)| PrecacheEntry {
+                url,
+                revision: &entry.hash,
+            })
+            .collect::<Vec<_>>();
+
+        let manifest_path = output_root.join(PRECACHE_MANIFEST_FILENAME);
+        let manifest =
+            serde_json::to_string_pretty(&entries).context("failed to serialize manifest")?;
+        fs::write(&manifest_path, manifest).context(format!(
+            "failed to write precache manifest to [{}]",
+            manifest_path.display()
+        ))?;
+
+        debug!(manifest_path = %manifest_path.display(), "Written precache manifest");
+
+        Ok(())
+    }
+}
+
+/// Convert a path relative to the output root into the absolute URL path it is
+/// served at, always using `/` as the separator.
+pub fn url_path(relative: &Path) -> String {
+    let mut url = String::new();
+    for component in relative.components() {
+        url.push('/');
+        url.push_str(&component.as_os_str().to_string_lossy());
+    }
+
+    if url.is_empty() {
+        url.push('/');
+    }
+
+    url
+}
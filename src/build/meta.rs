@@ -0,0 +1,157 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::build::{
+    Metadata,
+    check::decode_entities,
+    config::SiteConfig,
+    feed::escape_xml,
+    inline::attribute,
+    protect,
+    typography::{Token, tag_name, tokenize},
+};
+
+/// The longest meta description in characters, which is about what search
+/// engines show
+const MAX_DESCRIPTION_LEN: usize = 160;
+
+/// Matches a `<meta>` tag
+static META_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<meta\b[^>]*>").unwrap());
+
+/// Matches the first paragraph of rendered content
+static FIRST_PARAGRAPH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<p\b[^>]*>(.*?)</p>").unwrap());
+
+/// The `keywords` frontmatter field, either a list or a comma-separated
+/// string.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Keywords {
+    List(Vec<String>),
+    Text(String),
+}
+
+impl Keywords {
+    pub fn into_vec(self) -> Vec<String> {
+        let keywords = match self {
+            Keywords::List(keywords) => keywords,
+            Keywords::Text(text) => text.split(',').map(str::to_owned).collect(),
+        };
+        keywords
+            .into_iter()
+            .map(|keyword| keyword.trim().to_owned())
+            .filter(|keyword| !keyword.is_empty())
+            .collect()
+    }
+}
+
+/// The text of an HTML fragment without its tags, with runs of whitespace
+/// collapsed.
+fn plain_text(html: &str) -> String {
+    let mut skipped_depth = 0usize;
+    let mut text = String::new();
+    for token in tokenize(html) {
+        match token {
+            Token::Tag(tag) => {
+                let (name, is_closing) = tag_name(tag);
+                if matches!(name.to_ascii_lowercase().as_str(), "script" | "style") {
+                    skipped_depth = if is_closing {
+                        skipped_depth.saturating_sub(1)
+                    } else {
+                        skipped_depth + 1
+                    };
+                }
+            },
+            Token::Text(fragment) if skipped_depth == 0 => text.push_str(&fragment),
+            Token::Text(_) => {},
+        }
+    }
+    decode_entities(&text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Cut text to at most `max_len` characters at a word boundary, ending it
+/// with an ellipsis when anything was cut.
+fn truncate_at_word(text: &str, max_len: usize) -> String {
+    if text.chars().count() <= max_len {
+        return text.to_owned();
+    }
+    let end = text
+        .char_indices()
+        .nth(max_len)
+        .map_or(text.len(), |(idx, _)| idx);
+    let cut = text[..end]
+        .rfind(' ')
+        .map_or(&text[..end], |space| &text[..space]);
+    format!("{}…", cut.trim_end_matches([',', '.', ';', ':']))
+}
+
+/// Set the meta description of a page, from its `description` field, then its
+/// `summary`, and then the first paragraph of its content.
+///
+/// Fields rendered from djot are reduced to their text. The content of
+/// protected pages is never used, since the description is shown to anyone.
+pub fn describe(config: &SiteConfig, metadata: &mut Metadata) {
+    let is_protected = protect::is_protected(config, metadata);
+    let source = metadata
+        .description
+        .as_deref()
+        .or(metadata.summary.as_deref())
+        .map(plain_text)
+        .or_else(|| {
+            if is_protected {
+                return None;
+            }
+            let content = metadata.rendered_content.as_deref()?;
+            let paragraph = FIRST_PARAGRAPH.captures(content)?;
+            Some(truncate_at_word(
+                &plain_text(&paragraph[1]),
+                MAX_DESCRIPTION_LEN,
+            ))
+        });
+    metadata.meta_description = source.filter(|description| !description.is_empty());
+}
+
+/// The `<meta>` tags for the description and keywords of a page, for templates
+/// to include in the `<head>` as `{{ meta_tags | safe }}`.
+pub fn meta_tags(metadata: &Metadata) -> String {
+    let mut tags = String::new();
+    if let Some(description) = &metadata.meta_description {
+        tags.push_str(&format!(
+            r#"<meta name="description" content="{}">"#,
+            escape_xml(description)
+        ));
+    }
+    if !metadata.keywords.is_empty() {
+        tags.push_str(&format!(
+            r#"<meta name="keywords" content="{}">"#,
+            escape_xml(&metadata.keywords.join(", "))
+        ));
+    }
+    tags
+}
+
+/// Add the `<meta>` tags of a page to the end of its `<head>`, leaving out
+/// any that the template already wrote.
+pub fn inject_meta_tags(metadata: &Metadata, html: &str) -> String {
+    let Some(head_end) = html.find("</head>") else {
+        return html.to_owned();
+    };
+    let head = &html[..head_end];
+    let mut tags = String::new();
+    for tag in META_TAG.find_iter(&meta_tags(metadata)) {
+        let name = attribute(tag.as_str(), "name").unwrap_or_default();
+        let exists = META_TAG.find_iter(head).any(|existing| {
+            attribute(existing.as_str(), "name")
+                .is_some_and(|existing| existing.eq_ignore_ascii_case(&name))
+        });
+        if !exists {
+            tags.push_str(tag.as_str());
+        }
+    }
+    format!("{head}{tags}{}", &html[head_end..])
+}
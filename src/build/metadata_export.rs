@@ -0,0 +1,100 @@
+use std::{fs, io::Write, path::PathBuf};
+
+use anyhow::Context;
+use serde::Serialize;
+use tracing::info;
+
+use crate::build::{
+    BuildCmd, Site,
+    export::{MetadataCmd, MetadataFormat},
+    remote::Remote,
+    site_cache::site_cache_dir,
+};
+
+/// The structure of a single page, as exported for other tools.
+#[derive(Debug, Serialize)]
+struct PageEntry<'a> {
+    slug: String,
+    url: String,
+    title: Option<&'a str>,
+    date: Option<String>,
+    tags: &'a [String],
+    is_article: bool,
+    /// The full frontmatter of the page, after cascaded defaults are applied
+    frontmatter: Option<&'a tera::Value>,
+}
+
+/// Write the metadata of every HTML page on the site, without building it.
+///
+/// Drafts are left out unless `--drafts` is given.
+pub fn export_metadata(cmd: &MetadataCmd) -> anyhow::Result<()> {
+    let args = BuildCmd {
+        input_path: cmd.input_path.clone(),
+        output_path: PathBuf::new(),
+        release: !cmd.drafts,
+        link_graph: None,
+        keep_going: false,
+        deny_warnings: false,
+        output_format: None,
+        offline: false,
+    };
+    let mut site = Site::load(&args)?;
+    let remote = Remote::new(&site_cache_dir(&args.input_path), args.offline);
+    site.content
+        .apply_cascades(args.release)
+        .context("failed to apply cascaded frontmatter")?;
+
+    for (slug, file) in &site.content.files {
+        let ctx = format!("Failed to render file [{}]", file.input.full_path.display());
+        file.render(
+            &args,
+            &site.config,
+            &remote,
+            &mut site.content.metadata,
+            slug,
+        )
+        .context(ctx)?;
+    }
+    if !cmd.drafts {
+        site.content.remove_drafts();
+    }
+
+    let pages = site
+        .content
+        .metadata
+        .0
+        .values()
+        // Assets like images and stylesheets also have metadata, but aren't pages
+        .filter(|md| md.url_path.extension().is_some_and(|ext| ext == "html"))
+        .map(|md| PageEntry {
+            slug: md.slug.to_string(),
+            url: md.url_path.to_string_lossy().into_owned(),
+            title: md.title.as_deref(),
+            date: md.date.map(|date| date.to_rfc3339()),
+            tags: &md.tags,
+            is_article: md.is_article,
+            frontmatter: md.frontmatter.as_ref().map(|frontmatter| &frontmatter.0),
+        })
+        .collect::<Vec<_>>();
+
+    let content = match cmd.format {
+        MetadataFormat::Json => {
+            serde_json::to_string_pretty(&pages).context("failed to serialize site metadata")?
+        },
+    };
+    match &cmd.output {
+        Some(output) => {
+            fs::write(output, content).context(format!(
+                "failed to write site metadata to [{}]",
+                output.display()
+            ))?;
+            info!(output = %output.display(), num_pages = pages.len(), "Exported site metadata");
+        },
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            writeln!(stdout, "{content}").context("failed to write site metadata")?;
+        },
+    }
+
+    Ok(())
+}
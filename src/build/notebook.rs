@@ -0,0 +1,259 @@
+use std::{collections::BTreeMap, sync::LazyLock};
+
+use anyhow::Context;
+use jotdown::{Attributes, Container, Event};
+use regex::Regex;
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::build::{
+    BuildFile, ContentSlug, Frontmatter, MetadataContainer, config::SiteConfig, djot,
+    feed::escape_xml, remote::Remote,
+};
+
+/// Matches the ANSI color codes that kernels put in tracebacks
+static ANSI_ESCAPE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\x1b\[[0-9;]*[A-Za-z]").unwrap());
+
+/// Output MIME types in the order they are preferred, when an output has more
+/// than one representation
+const OUTPUT_MIME_TYPES: &[&str] = &[
+    "image/svg+xml",
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "text/html",
+    "text/plain",
+];
+
+/// A string that notebooks store either whole or split into lines.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MultilineString {
+    Whole(String),
+    Lines(Vec<String>),
+}
+
+impl MultilineString {
+    fn joined(&self) -> String {
+        match self {
+            MultilineString::Whole(text) => text.clone(),
+            MultilineString::Lines(lines) => lines.concat(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Notebook {
+    cells: Vec<Cell>,
+    #[serde(default)]
+    metadata: NotebookMetadata,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct NotebookMetadata {
+    language_info: Option<LanguageInfo>,
+    /// Page frontmatter, stored in the notebook metadata since notebooks have
+    /// nowhere else to put it
+    frontmatter: Option<Frontmatter>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LanguageInfo {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cell_type", rename_all = "lowercase")]
+enum Cell {
+    Markdown {
+        source: MultilineString,
+    },
+    Code {
+        source: MultilineString,
+        #[serde(default)]
+        outputs: Vec<Output>,
+    },
+    Raw {},
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "output_type", rename_all = "snake_case")]
+enum Output {
+    Stream {
+        name: String,
+        text: MultilineString,
+    },
+    ExecuteResult {
+        data: BTreeMap<String, MultilineString>,
+    },
+    DisplayData {
+        data: BTreeMap<String, MultilineString>,
+    },
+    Error {
+        traceback: Vec<String>,
+    },
+}
+
+impl Output {
+    fn to_html(&self) -> Option<String> {
+        match self {
+            Output::Stream { name, text } => Some(format!(
+                r#"<pre class="nb-stream nb-{}">{}</pre>"#,
+                escape_xml(name),
+                escape_xml(&text.joined())
+            )),
+            Output::ExecuteResult { data } | Output::DisplayData { data } => {
+                let (mime_type, value) = OUTPUT_MIME_TYPES
+                    .iter()
+                    .find_map(|mime_type| Some((*mime_type, data.get(*mime_type)?)))?;
+                let value = value.joined();
+                Some(match mime_type {
+                    "image/svg+xml" | "text/html" => value,
+                    "text/plain" => format!("<pre>{}</pre>", escape_xml(&value)),
+                    // Binary data is already base64 encoded
+                    image => format!(
+                        r#"<img src="data:{image};base64,{}" alt="">"#,
+                        value.split_whitespace().collect::<String>()
+                    ),
+                })
+            },
+            Output::Error { traceback } => {
+                let traceback = ANSI_ESCAPE
+                    .replace_all(&traceback.join("\n"), "")
+                    .into_owned();
+                Some(format!(
+                    r#"<pre class="nb-error">{}</pre>"#,
+                    escape_xml(&traceback)
+                ))
+            },
+        }
+    }
+}
+
+/// Add the blank lines that djot needs and Markdown doesn't, after headings and
+/// before lists that directly follow a paragraph.
+fn markdown_to_djot(source: &str) -> String {
+    let is_list_item = |line: &str| {
+        let line = line.trim_start();
+        line.starts_with("- ")
+            || line.starts_with("* ")
+            || line.starts_with("+ ")
+            || line
+                .split_once(". ")
+                .is_some_and(|(num, _)| !num.is_empty() && num.chars().all(|c| c.is_ascii_digit()))
+    };
+
+    let mut djot = String::with_capacity(source.len());
+    let mut previous: Option<&str> = None;
+    let mut in_code = false;
+    for line in source.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        }
+        if !in_code && let Some(previous) = previous {
+            let previous_is_heading = previous.starts_with('#');
+            let starts_list = is_list_item(line) && !is_list_item(previous);
+            if !previous.trim().is_empty() && (previous_is_heading || starts_list) {
+                djot.push('\n');
+            }
+        }
+        djot.push_str(line);
+        djot.push('\n');
+        previous = Some(line);
+    }
+
+    djot
+}
+
+fn raw_html(html: String) -> [Event<'static>; 3] {
+    [
+        Event::Start(Container::RawBlock { format: "html" }, Attributes::new()),
+        Event::Str(html.into()),
+        Event::End(Container::RawBlock { format: "html" }),
+    ]
+}
+
+/// Render a Jupyter notebook to HTML.
+///
+/// Markdown cells are parsed as djot, which covers the common subset of
+/// Markdown, and code cells become code blocks followed by their saved
+/// outputs. The page frontmatter is read from the `frontmatter` key of the
+/// notebook metadata. Raw cells are skipped.
+#[tracing::instrument(skip_all)]
+pub fn render(
+    input: &BuildFile,
+    config: &SiteConfig,
+    metadata: &mut MetadataContainer,
+    slug: &ContentSlug,
+    content: &str,
+    remote: &Remote,
+) -> anyhow::Result<String> {
+    let notebook: Notebook = serde_json::from_str(content).context("failed to parse notebook")?;
+
+    if let Some(frontmatter) = notebook.metadata.frontmatter.clone() {
+        metadata[slug]
+            .set_frontmatter(frontmatter)
+            .context(format!("in frontmatter of [{slug}]"))?;
+    }
+
+    let language = notebook
+        .metadata
+        .language_info
+        .as_ref()
+        .map(|info| info.name.as_str())
+        .unwrap_or_default();
+
+    // Sources are collected first so that the parsed events can borrow from them
+    let sources = notebook
+        .cells
+        .iter()
+        .map(|cell| match cell {
+            Cell::Markdown { source } => markdown_to_djot(&source.joined()),
+            Cell::Code { source, .. } => source.joined(),
+            Cell::Raw {} => String::new(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut events = vec![];
+    for (cell, source) in notebook.cells.iter().zip(&sources) {
+        match cell {
+            Cell::Markdown { .. } => events.extend(jotdown::Parser::new(source)),
+            Cell::Code { outputs, .. } => {
+                events.push(Event::Start(
+                    Container::Div { class: "nb-cell" },
+                    Attributes::new(),
+                ));
+                events.push(Event::Start(
+                    Container::CodeBlock { language },
+                    Attributes::new(),
+                ));
+                events.push(Event::Str(format!("{source}\n").into()));
+                events.push(Event::End(Container::CodeBlock { language }));
+
+                let outputs = outputs
+                    .iter()
+                    .filter_map(Output::to_html)
+                    .collect::<Vec<_>>();
+                if !outputs.is_empty() {
+                    events.push(Event::Start(
+                        Container::Div {
+                            class: "nb-outputs",
+                        },
+                        Attributes::new(),
+                    ));
+                    for output in outputs {
+                        events.extend(raw_html(output));
+                    }
+                    events.push(Event::End(Container::Div {
+                        class: "nb-outputs",
+                    }));
+                }
+                events.push(Event::End(Container::Div { class: "nb-cell" }));
+            },
+            Cell::Raw {} => debug!("Skipping raw notebook cell"),
+        }
+    }
+
+    djot::render_events(input, config, metadata, slug, events, remote)
+}
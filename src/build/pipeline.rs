@@ -0,0 +1,56 @@
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+};
+
+use anyhow::{Context, bail};
+use tracing::debug;
+
+use crate::build::errors::FailureClass;
+
+/// Run a pipeline command with the content on stdin, returning what it wrote
+/// to stdout.
+///
+/// The command runs from the directory of the content file, so that it can
+/// resolve paths relative to the page.
+pub fn run_command(args: &[String], input_path: &Path, content: String) -> anyhow::Result<String> {
+    let Some((program, args)) = args.split_first() else {
+        bail!("pipeline command is empty");
+    };
+
+    let mut child = Command::new(program)
+        .args(args)
+        .current_dir(input_path.parent().unwrap_or(Path::new(".")))
+        .env("WWW_INPUT_PATH", input_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| FailureClass::Tool.wrap(format!("failed to execute [{program}]"), err))?;
+
+    // Write from another thread so that a command that fills its stdout before
+    // reading all of stdin doesn't deadlock
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer = thread::spawn(move || stdin.write_all(content.as_bytes()));
+
+    let output = child
+        .wait_with_output()
+        .context(format!("failed to wait for [{program}]"))?;
+    writer
+        .join()
+        .expect("stdin writer thread panicked")
+        .context(format!("failed to write content to [{program}]"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        debug!(%stderr, "Failed pipeline command output");
+        return Err(FailureClass::Tool.error(format!(
+            "Execution of [{program}] returned an unsuccessful status code: {}",
+            stderr.trim()
+        )));
+    }
+
+    String::from_utf8(output.stdout).context(format!("output of [{program}] is not UTF-8"))
+}
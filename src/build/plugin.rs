@@ -0,0 +1,213 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::build::{
+    ContentSlug, Frontmatter, Metadata,
+    config::{PluginConfig, PluginEvent, SiteConfig},
+    errors::FailureClass,
+    meta,
+};
+
+/// A message sent to a plugin about a page, as a single line of JSON.
+#[derive(Debug, Serialize)]
+struct Request<'a> {
+    event: PluginEvent,
+    slug: &'a ContentSlug,
+    url_path: &'a Path,
+    source_path: &'a Path,
+    /// The frontmatter of the page, or `null` if it has none
+    frontmatter: Option<&'a serde_json::Value>,
+    html: &'a str,
+}
+
+/// The answer of a plugin to a request, as a single line of JSON. Fields that
+/// are left out keep the page as it is.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Response {
+    /// The new HTML of the page
+    html: Option<String>,
+    /// Fields merged over the frontmatter of the page, which is only possible
+    /// for `render` events
+    frontmatter: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// A running plugin process.
+struct Plugin {
+    name: String,
+    events: Vec<PluginEvent>,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Plugin {
+    /// Send a request and wait for the response.
+    fn call(&mut self, request: &Request) -> anyhow::Result<Response> {
+        let mut line = serde_json::to_string(request).context("failed to serialize request")?;
+        line.push('\n');
+        let stdin = self
+            .stdin
+            .as_mut()
+            .expect("stdin is open until the plugin is dropped");
+        stdin
+            .write_all(line.as_bytes())
+            .and_then(|()| stdin.flush())
+            .context("failed to write request, the plugin may have exited")?;
+
+        let mut line = String::new();
+        let num_read = self
+            .stdout
+            .read_line(&mut line)
+            .context("failed to read response")?;
+        if num_read == 0 {
+            bail!("plugin exited without responding");
+        }
+        serde_json::from_str(&line).context("invalid response")
+    }
+}
+
+impl Drop for Plugin {
+    /// Close stdin, which tells the plugin that the build is done, and wait for
+    /// it to exit.
+    fn drop(&mut self) {
+        drop(self.stdin.take());
+        match self.child.wait() {
+            Ok(status) if status.success() => {},
+            Ok(status) => warn!(plugin = self.name, %status, "Plugin exited unsuccessfully"),
+            Err(err) => warn!(plugin = self.name, %err, "Failed to wait for plugin to exit"),
+        }
+    }
+}
+
+/// The plugins of a site, which run for the whole build and are sent an event
+/// for each page.
+///
+/// Plugins are external programs that read requests from stdin and write a
+/// response to stdout for each of them, one JSON object per line. Whatever
+/// they write to stderr is shown with the output of the build.
+pub struct Plugins {
+    plugins: RefCell<Vec<Plugin>>,
+}
+
+impl Plugins {
+    /// Start every plugin, from the input directory.
+    pub fn start(
+        configs: &BTreeMap<String, PluginConfig>,
+        input_path: &Path,
+    ) -> anyhow::Result<Self> {
+        let mut plugins = vec![];
+        for (name, config) in configs {
+            let Some((program, args)) = config.command.split_first() else {
+                bail!("command of plugin [{name}] is empty");
+            };
+            let mut child = Command::new(program)
+                .args(args)
+                .current_dir(input_path)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .map_err(|err| {
+                    FailureClass::Tool.wrap(format!("failed to execute plugin [{name}]"), err)
+                })?;
+            debug!(plugin = name, "Started plugin");
+            plugins.push(Plugin {
+                name: name.clone(),
+                events: config.events.clone(),
+                stdin: child.stdin.take(),
+                stdout: BufReader::new(child.stdout.take().expect("stdout is piped")),
+                child,
+            });
+        }
+        Ok(Self {
+            plugins: RefCell::new(plugins),
+        })
+    }
+
+    /// Send the content of a page after it is rendered, before any template is
+    /// applied, merging the frontmatter fields the plugins return into its
+    /// metadata.
+    pub fn render(
+        &self,
+        config: &SiteConfig,
+        metadata: &mut Metadata,
+        html: String,
+    ) -> anyhow::Result<String> {
+        let (html, frontmatter) = self.send(PluginEvent::Render, metadata, html)?;
+        if !frontmatter.is_empty() {
+            metadata
+                .set_frontmatter(Frontmatter(serde_json::Value::Object(frontmatter)))
+                .context("invalid frontmatter returned by plugin")?;
+            meta::describe(config, metadata);
+        }
+        Ok(html)
+    }
+
+    /// Send the HTML of a page after its template is applied, right before it
+    /// is written.
+    pub fn page(&self, metadata: &Metadata, html: String) -> anyhow::Result<String> {
+        let (html, frontmatter) = self.send(PluginEvent::Page, metadata, html)?;
+        if !frontmatter.is_empty() {
+            warn!(
+                slug = %metadata.slug,
+                "Plugin returned frontmatter for a page that is already rendered, ignoring it"
+            );
+        }
+        Ok(html)
+    }
+
+    /// Send an event about a page to every plugin that listens to it in turn,
+    /// each one getting the HTML left by the one before.
+    ///
+    /// Returns the new HTML, along with the frontmatter fields that the plugins
+    /// returned, later plugins taking precedence.
+    fn send(
+        &self,
+        event: PluginEvent,
+        metadata: &Metadata,
+        mut html: String,
+    ) -> anyhow::Result<(String, serde_json::Map<String, serde_json::Value>)> {
+        let mut frontmatter = serde_json::Map::new();
+        for plugin in self.plugins.borrow_mut().iter_mut() {
+            if !plugin.events.contains(&event) {
+                continue;
+            }
+            let request = Request {
+                event,
+                slug: &metadata.slug,
+                url_path: &metadata.url_path,
+                source_path: &metadata.source_path,
+                frontmatter: metadata
+                    .frontmatter
+                    .as_ref()
+                    .map(|frontmatter| &frontmatter.0),
+                html: &html,
+            };
+            let response = plugin.call(&request).map_err(|err| {
+                FailureClass::Tool.wrap(format!("plugin [{}] failed", plugin.name), err)
+            })?;
+            debug!(
+                plugin = plugin.name,
+                ?event,
+                slug = %metadata.slug,
+                changed_html = response.html.is_some(),
+                "Plugin handled event"
+            );
+
+            if let Some(new_html) = response.html {
+                html = new_html;
+            }
+            frontmatter.extend(response.frontmatter.unwrap_or_default());
+        }
+        Ok((html, frontmatter))
+    }
+}
@@ -0,0 +1,263 @@
+use std::{cmp, fmt::Write, fs, path::Path};
+
+use anyhow::Context;
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::build::{
+    Metadata, MetadataContainer,
+    config::{PodcastConfig, SiteConfig},
+    feed::{entry_id, escape_xml, stylesheet_instruction},
+    protect,
+};
+
+/// The file name of the podcast feed, written into the podcast section
+const PODCAST_FEED_FILENAME: &str = "podcast.xml";
+
+/// The `enclosure` frontmatter field of a podcast episode.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Enclosure {
+    /// Path of the audio file, relative to the episode page
+    file: String,
+    /// Size of the audio file in bytes, read from the file when missing
+    length: Option<u64>,
+    #[serde(default = "default_mime_type")]
+    mime_type: String,
+    /// Episode duration as `HH:MM:SS` or a number of seconds
+    duration: Option<String>,
+}
+
+fn default_mime_type() -> String {
+    "audio/mpeg".into()
+}
+
+#[derive(Debug)]
+struct Episode<'a> {
+    metadata: &'a Metadata,
+    published: DateTime<FixedOffset>,
+    enclosure: Enclosure,
+    /// URL path of the audio file
+    url_path: String,
+    length: u64,
+}
+
+fn collect_episodes<'a>(
+    config: &SiteConfig,
+    podcast: &PodcastConfig,
+    metadata: &'a MetadataContainer,
+    output_root: &Path,
+) -> anyhow::Result<Vec<Episode<'a>>> {
+    let mut episodes = vec![];
+    for (slug, md) in &metadata.0 {
+        if !md.is_article
+            || !slug.parent.starts_with(&podcast.section)
+            || protect::is_protected(config, md)
+        {
+            continue;
+        }
+
+        let Some(enclosure) = md.frontmatter_field("enclosure") else {
+            debug!(%slug, "Page in podcast section has no enclosure, skipping");
+            continue;
+        };
+        let enclosure: Enclosure = serde_json::from_value(enclosure.clone())
+            .context(format!("invalid 'enclosure' frontmatter in [{slug}]"))?;
+
+        let Some(published) = md.date else {
+            debug!(%slug, "Podcast episode has no date, skipping");
+            continue;
+        };
+
+        let relative_file = slug.parent.join(&enclosure.file);
+        let length = match enclosure.length {
+            Some(length) => length,
+            None => {
+                let output_file = output_root.join(&relative_file);
+                fs::metadata(&output_file)
+                    .context(format!(
+                        "failed to read size of enclosure [{}] for [{slug}]",
+                        output_file.display()
+                    ))?
+                    .len()
+            },
+        };
+
+        episodes.push(Episode {
+            metadata: md,
+            published,
+            url_path: format!("/{}", relative_file.display()),
+            enclosure,
+            length,
+        });
+    }
+
+    episodes.sort_by_key(|episode| cmp::Reverse(episode.published));
+
+    Ok(episodes)
+}
+
+fn render_podcast(
+    config: &SiteConfig,
+    podcast: &PodcastConfig,
+    base_url: &str,
+    episodes: &[Episode],
+) -> anyhow::Result<String> {
+    let title = podcast
+        .title
+        .as_ref()
+        .or(config.title.as_ref())
+        .cloned()
+        .unwrap_or_default();
+    let section_url = format!("{base_url}/{}/", podcast.section.display());
+    let author = podcast.owner_name.as_ref().or(config.author.as_ref());
+
+    let mut buf = String::new();
+    writeln!(buf, r#"<?xml version="1.0" encoding="utf-8"?>"#)?;
+    writeln!(buf, "{}", stylesheet_instruction(config))?;
+    writeln!(
+        buf,
+        r#"<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd">"#
+    )?;
+    writeln!(buf, "  <channel>")?;
+    writeln!(buf, "    <title>{}</title>", escape_xml(&title))?;
+    writeln!(buf, "    <link>{}</link>", escape_xml(&section_url))?;
+    if let Some(description) = &podcast.description {
+        writeln!(
+            buf,
+            "    <description>{}</description>",
+            escape_xml(description)
+        )?;
+    }
+    if let Some(language) = &podcast.language {
+        writeln!(buf, "    <language>{}</language>", escape_xml(language))?;
+    }
+    if let Some(author) = author {
+        writeln!(
+            buf,
+            "    <itunes:author>{}</itunes:author>",
+            escape_xml(author)
+        )?;
+    }
+    if let Some(image) = &podcast.image {
+        let image_url = if image.starts_with('/') {
+            format!("{base_url}{image}")
+        } else {
+            image.clone()
+        };
+        writeln!(
+            buf,
+            r#"    <itunes:image href="{}"/>"#,
+            escape_xml(&image_url)
+        )?;
+    }
+    if let Some(category) = &podcast.category {
+        writeln!(
+            buf,
+            r#"    <itunes:category text="{}"/>"#,
+            escape_xml(category)
+        )?;
+    }
+    writeln!(
+        buf,
+        "    <itunes:explicit>{}</itunes:explicit>",
+        podcast.explicit
+    )?;
+    if podcast.owner_name.is_some() || podcast.owner_email.is_some() {
+        writeln!(buf, "    <itunes:owner>")?;
+        if let Some(name) = &podcast.owner_name {
+            writeln!(buf, "      <itunes:name>{}</itunes:name>", escape_xml(name))?;
+        }
+        if let Some(email) = &podcast.owner_email {
+            writeln!(
+                buf,
+                "      <itunes:email>{}</itunes:email>",
+                escape_xml(email)
+            )?;
+        }
+        writeln!(buf, "    </itunes:owner>")?;
+    }
+
+    for episode in episodes {
+        let md = episode.metadata;
+        let episode_url = format!("{base_url}{}", md.url_path.display());
+        let title = md.title.clone().unwrap_or_else(|| md.slug.to_string());
+
+        writeln!(buf, "    <item>")?;
+        writeln!(buf, "      <title>{}</title>", escape_xml(&title))?;
+        writeln!(buf, "      <link>{}</link>", escape_xml(&episode_url))?;
+        writeln!(
+            buf,
+            r#"      <guid isPermaLink="false">{}</guid>"#,
+            escape_xml(&entry_id(base_url, md, episode.published))
+        )?;
+        writeln!(
+            buf,
+            "      <pubDate>{}</pubDate>",
+            episode.published.to_rfc2822()
+        )?;
+        writeln!(
+            buf,
+            r#"      <enclosure url="{}" length="{}" type="{}"/>"#,
+            escape_xml(&format!("{base_url}{}", episode.url_path)),
+            episode.length,
+            escape_xml(&episode.enclosure.mime_type)
+        )?;
+        if let Some(duration) = &episode.enclosure.duration {
+            writeln!(
+                buf,
+                "      <itunes:duration>{}</itunes:duration>",
+                escape_xml(duration)
+            )?;
+        }
+        if let Some(content) = &md.rendered_content {
+            writeln!(
+                buf,
+                "      <description>{}</description>",
+                escape_xml(content)
+            )?;
+        }
+        writeln!(buf, "    </item>")?;
+    }
+
+    writeln!(buf, "  </channel>")?;
+    writeln!(buf, "</rss>")?;
+
+    Ok(buf)
+}
+
+/// Write an RSS feed with iTunes tags for the configured podcast section.
+#[tracing::instrument(skip_all)]
+pub fn write_podcast_feed(
+    config: &SiteConfig,
+    metadata: &MetadataContainer,
+    output_root: &Path,
+) -> anyhow::Result<()> {
+    let Some(podcast) = &config.podcast else {
+        debug!("No podcast configured, skipping podcast feed");
+        return Ok(());
+    };
+    let Some(base_url) = config.base_url.as_deref() else {
+        debug!("No base URL configured, skipping podcast feed");
+        return Ok(());
+    };
+    let base_url = base_url.trim_end_matches('/');
+
+    let episodes = collect_episodes(config, podcast, metadata, output_root)
+        .context("failed to collect podcast episodes")?;
+    let feed = render_podcast(config, podcast, base_url, &episodes)
+        .context("failed to render podcast feed")?;
+
+    let feed_dir = output_root.join(&podcast.section);
+    fs::create_dir_all(&feed_dir).context("failed to create podcast section directory")?;
+    let feed_path = feed_dir.join(PODCAST_FEED_FILENAME);
+    fs::write(&feed_path, feed).context(format!(
+        "failed to write podcast feed [{}]",
+        feed_path.display()
+    ))?;
+
+    debug!(feed_path = %feed_path.display(), num_episodes = episodes.len(), "Written podcast feed");
+
+    Ok(())
+}
@@ -0,0 +1,325 @@
+//! Encryption of pages with a passphrase, so that they can be shared on a
+//! public host without being readable by anyone who doesn't know it.
+//!
+//! The rendered page is encrypted with AES-256-GCM, using a key derived from
+//! the passphrase with PBKDF2-HMAC-SHA256 and a salt unique to the page. It is
+//! replaced by a small page that asks for the passphrase and decrypts the
+//! original page in the browser with the Web Crypto API.
+//!
+//! The salt and nonce are derived rather than random, so that building the
+//! same page twice gives the same output. The salt comes from the URL of the
+//! page, and the nonce from a keyed hash of the page, which only repeats when
+//! the page does.
+//!
+//! The cipher is implemented here following FIPS 197 and NIST SP 800-38D,
+//! rather than adding a dependency for a single use.
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::build::{
+    Metadata,
+    config::{ProtectedPagesConfig, SiteConfig},
+    errors::FailureClass,
+    feed::escape_xml,
+};
+
+/// Bytes of salt for deriving the key
+const SALT_LEN: usize = 16;
+
+/// Bytes of the GCM nonce, the length recommended for GCM
+pub const IV_LEN: usize = 12;
+
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+/// Whether a page is encrypted, either from `protected: true` in its
+/// frontmatter or from being listed in the config.
+pub(super) fn is_protected(config: &SiteConfig, metadata: &Metadata) -> bool {
+    metadata
+        .frontmatter_field("protected")
+        .and_then(tera::Value::as_bool)
+        .unwrap_or_else(|| {
+            config
+                .protected_pages
+                .as_ref()
+                .is_some_and(|protected| protected.lists(&metadata.slug.as_path()))
+        })
+}
+
+/// Encrypt a rendered page and return the page that decrypts it.
+pub(super) fn protect_page(
+    config: &SiteConfig,
+    metadata: &Metadata,
+    html: &str,
+) -> anyhow::Result<String> {
+    let Some(protected) = &config.protected_pages else {
+        return Err(FailureClass::Config
+            .error("page is marked as protected, but `protected_pages` is not configured"));
+    };
+    if protected.password.is_empty() {
+        return Err(FailureClass::Config.error("the password of protected pages is empty"));
+    }
+
+    // The salt doesn't depend on the password, which would let anyone check a
+    // guess without deriving the key
+    let site = config.base_url.as_deref().unwrap_or_default();
+    let salt: [u8; SALT_LEN] = Sha256::new()
+        .chain_update(site)
+        .chain_update([0])
+        .chain_update(metadata.url_path.to_string_lossy().as_bytes())
+        .finalize()[..SALT_LEN]
+        .try_into()
+        .expect("salt is shorter than the hash");
+    let key = pbkdf2_sha256(protected.password.as_bytes(), &salt, protected.iterations);
+    let iv: [u8; IV_LEN] = hmac_sha256(&key, html.as_bytes())[..IV_LEN]
+        .try_into()
+        .expect("nonce is shorter than the hash");
+    let data = Aes256::new(&key).encrypt_gcm(&iv, html.as_bytes());
+
+    Ok(render_shell(protected, metadata, &salt, &iv, &data))
+}
+
+/// The page shown in place of a protected page, which asks for the password.
+fn render_shell(
+    protected: &ProtectedPagesConfig,
+    metadata: &Metadata,
+    salt: &[u8],
+    iv: &[u8],
+    data: &[u8],
+) -> String {
+    let payload = json!({
+        "salt": BASE64_STANDARD.encode(salt),
+        "iv": BASE64_STANDARD.encode(iv),
+        "data": BASE64_STANDARD.encode(data),
+        "iterations": protected.iterations,
+    });
+    let title = escape_xml(metadata.title.as_deref().unwrap_or("Protected page"));
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="robots" content="noindex">
+<title>{title}</title>
+</head>
+<body>
+<form id="unlock">
+<p><label for="password">This page is protected, enter the password to read it.</label></p>
+<input id="password" type="password" autocomplete="current-password" autofocus required>
+<button type="submit">Unlock</button>
+<p id="error" role="alert" hidden>The password is incorrect.</p>
+</form>
+<script>
+const payload = {payload};
+const decode = (text) => Uint8Array.from(atob(text), (c) => c.charCodeAt(0));
+document.getElementById("unlock").addEventListener("submit", async (event) => {{
+  event.preventDefault();
+  const password = new TextEncoder().encode(document.getElementById("password").value);
+  const material = await crypto.subtle.importKey("raw", password, "PBKDF2", false, ["deriveKey"]);
+  const key = await crypto.subtle.deriveKey(
+    {{ name: "PBKDF2", salt: decode(payload.salt), iterations: payload.iterations, hash: "SHA-256" }},
+    material,
+    {{ name: "AES-GCM", length: 256 }},
+    false,
+    ["decrypt"],
+  );
+  try {{
+    const page = await crypto.subtle.decrypt({{ name: "AES-GCM", iv: decode(payload.iv) }}, key, decode(payload.data));
+    document.open();
+    document.write(new TextDecoder().decode(page));
+    document.close();
+  }} catch {{
+    document.getElementById("error").hidden = false;
+  }}
+}});
+</script>
+</body>
+</html>
+"#
+    )
+}
+
+/// PBKDF2 with HMAC-SHA256, producing a single block of 32 bytes.
+pub fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    let mut block = salt.to_vec();
+    block.extend_from_slice(&1u32.to_be_bytes());
+    let mut u = hmac_sha256(password, &block);
+    let mut key = u;
+    for _ in 1..iterations {
+        u = hmac_sha256(password, &u);
+        for (k, b) in key.iter_mut().zip(u) {
+            *k ^= b;
+        }
+    }
+    key
+}
+
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_LEN: usize = 64;
+    let mut padded = [0u8; BLOCK_LEN];
+    if key.len() > BLOCK_LEN {
+        padded[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        padded[..key.len()].copy_from_slice(key);
+    }
+
+    let inner = Sha256::new()
+        .chain_update(padded.map(|b| b ^ 0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(padded.map(|b| b ^ 0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+/// The AES block cipher with a 256-bit key, which only encrypts since GCM
+/// never needs the inverse cipher.
+pub struct Aes256 {
+    round_keys: [[u8; 16]; 15],
+}
+
+impl Aes256 {
+    pub fn new(key: &[u8; 32]) -> Self {
+        let mut words = [[0u8; 4]; 60];
+        for (word, chunk) in words.iter_mut().zip(key.chunks_exact(4)) {
+            word.copy_from_slice(chunk);
+        }
+        let mut rcon = 1u8;
+        for i in 8..60 {
+            let mut temp = words[i - 1];
+            if i % 8 == 0 {
+                temp.rotate_left(1);
+                temp = temp.map(|b| SBOX[b as usize]);
+                temp[0] ^= rcon;
+                rcon = xtime(rcon);
+            } else if i % 8 == 4 {
+                temp = temp.map(|b| SBOX[b as usize]);
+            }
+            for j in 0..4 {
+                words[i][j] = words[i - 8][j] ^ temp[j];
+            }
+        }
+
+        let mut round_keys = [[0u8; 16]; 15];
+        for (round_key, round_words) in round_keys.iter_mut().zip(words.chunks_exact(4)) {
+            for (bytes, word) in round_key.chunks_exact_mut(4).zip(round_words) {
+                bytes.copy_from_slice(word);
+            }
+        }
+        Self { round_keys }
+    }
+
+    pub fn encrypt_block(&self, block: &[u8; 16]) -> [u8; 16] {
+        let mut state = *block;
+        add_round_key(&mut state, &self.round_keys[0]);
+        for round in 1..15 {
+            state = state.map(|b| SBOX[b as usize]);
+            shift_rows(&mut state);
+            if round != 14 {
+                mix_columns(&mut state);
+            }
+            add_round_key(&mut state, &self.round_keys[round]);
+        }
+        state
+    }
+
+    /// Encrypt with GCM and no additional data, returning the ciphertext with
+    /// the 16 byte tag appended, as the Web Crypto API expects.
+    pub fn encrypt_gcm(&self, iv: &[u8; IV_LEN], plaintext: &[u8]) -> Vec<u8> {
+        let hash_key = u128::from_be_bytes(self.encrypt_block(&[0; 16]));
+        let mut counter = [0u8; 16];
+        counter[..IV_LEN].copy_from_slice(iv);
+        counter[15] = 1;
+        let tag_mask = self.encrypt_block(&counter);
+
+        let mut ciphertext = Vec::with_capacity(plaintext.len() + 16);
+        for chunk in plaintext.chunks(16) {
+            let count = u32::from_be_bytes(counter[12..].try_into().unwrap()).wrapping_add(1);
+            counter[12..].copy_from_slice(&count.to_be_bytes());
+            let keystream = self.encrypt_block(&counter);
+            ciphertext.extend(chunk.iter().zip(keystream).map(|(b, k)| b ^ k));
+        }
+
+        let mut hash = 0u128;
+        for chunk in ciphertext.chunks(16) {
+            let mut block = [0u8; 16];
+            block[..chunk.len()].copy_from_slice(chunk);
+            hash = gf_mul(hash ^ u128::from_be_bytes(block), hash_key);
+        }
+        let lengths = (ciphertext.len() as u128) * 8;
+        hash = gf_mul(hash ^ lengths, hash_key);
+
+        let tag = (hash ^ u128::from_be_bytes(tag_mask)).to_be_bytes();
+        ciphertext.extend_from_slice(&tag);
+        ciphertext
+    }
+}
+
+fn xtime(b: u8) -> u8 {
+    (b << 1) ^ if b & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+fn add_round_key(state: &mut [u8; 16], round_key: &[u8; 16]) {
+    for (b, k) in state.iter_mut().zip(round_key) {
+        *b ^= k;
+    }
+}
+
+/// The state is stored column by column, so row `r` is every fourth byte
+/// starting at `r`.
+fn shift_rows(state: &mut [u8; 16]) {
+    let old = *state;
+    for column in 0..4 {
+        for row in 0..4 {
+            state[column * 4 + row] = old[((column + row) % 4) * 4 + row];
+        }
+    }
+}
+
+fn mix_columns(state: &mut [u8; 16]) {
+    for column in state.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+        let all = a0 ^ a1 ^ a2 ^ a3;
+        column[0] ^= all ^ xtime(a0 ^ a1);
+        column[1] ^= all ^ xtime(a1 ^ a2);
+        column[2] ^= all ^ xtime(a2 ^ a3);
+        column[3] ^= all ^ xtime(a3 ^ a0);
+    }
+}
+
+/// Multiplication in the field of GHASH, where the bits of a block are
+/// reflected so the first bit is the lowest power.
+fn gf_mul(x: u128, y: u128) -> u128 {
+    const R: u128 = 0xe1 << 120;
+    let mut product = 0;
+    let mut v = y;
+    for i in 0..128 {
+        if x & (1 << (127 - i)) != 0 {
+            product ^= v;
+        }
+        v = if v & 1 != 0 { (v >> 1) ^ R } else { v >> 1 };
+    }
+    product
+}
@@ -0,0 +1,90 @@
+use std::{fmt::Write, fs, path::Path};
+
+use anyhow::Context;
+use tracing::{debug, info};
+
+use crate::build::{BuildDirFiles, config::PruneCssConfig, css, manifest::url_path};
+
+/// The suffix added to the file stem of each pruned stylesheet
+const PRUNED_SUFFIX: &str = "pruned";
+
+/// Write a pruned copy of each stylesheet next to it, containing only the
+/// selectors that match an element, class, or ID in the rendered HTML.
+///
+/// The original stylesheets are left as they are, so that the pruned ones can
+/// be checked before switching templates over to them.
+#[tracing::instrument(skip_all)]
+pub fn prune_stylesheets(config: &PruneCssConfig, output_root: &Path) -> anyhow::Result<()> {
+    let output_files =
+        BuildDirFiles::gather(output_root).context("failed to collect output files")?;
+
+    let mut used = css::UsedNames::default();
+    for class in &config.keep_classes {
+        used.add_class(class);
+    }
+    for (path, file) in &output_files.files {
+        if path.extension().is_some_and(|ext| ext == "html") {
+            let html = fs::read_to_string(&file.full_path).context(format!(
+                "failed to read output file [{}]",
+                file.full_path.display()
+            ))?;
+            used.add_document(&html);
+        }
+    }
+
+    let mut report = String::new();
+    for (path, file) in &output_files.files {
+        let url = url_path(path);
+        let is_selected = if config.stylesheets.is_empty() {
+            path.extension().is_some_and(|ext| ext == "css")
+        } else {
+            config.stylesheets.contains(&url)
+        };
+        let is_pruned_output = path
+            .file_stem()
+            .and_then(|stem| Path::new(stem).extension())
+            .is_some_and(|ext| ext == PRUNED_SUFFIX);
+        if !is_selected || is_pruned_output {
+            continue;
+        }
+
+        let stylesheet = fs::read_to_string(&file.full_path).context(format!(
+            "failed to read stylesheet [{}]",
+            file.full_path.display()
+        ))?;
+        let stylesheet = css::strip_comments(&stylesheet);
+        let rules = css::parse(&stylesheet);
+        let mut removed = vec![];
+        let pruned =
+            css::retain_selectors(&rules, &|selector| used.matches(selector), &mut removed);
+
+        let mut pruned_path = file.full_path.clone();
+        pruned_path.set_extension(format!("{PRUNED_SUFFIX}.css"));
+        fs::write(&pruned_path, &pruned).context(format!(
+            "failed to write pruned stylesheet [{}]",
+            pruned_path.display()
+        ))?;
+
+        info!(
+            stylesheet = url,
+            num_removed = removed.len(),
+            original_len = stylesheet.len(),
+            pruned_len = pruned.len(),
+            "Pruned unused CSS"
+        );
+        writeln!(report, "{url}: {} unused selectors", removed.len())?;
+        for selector in &removed {
+            writeln!(report, "  {selector}")?;
+        }
+    }
+
+    if let Some(report_path) = &config.report {
+        fs::write(report_path, report).context(format!(
+            "failed to write CSS pruning report [{}]",
+            report_path.display()
+        ))?;
+        debug!(report_path = %report_path.display(), "Written CSS pruning report");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::build::errors::FailureClass;
+
+/// How long a cached response is used before the URL is fetched again
+const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The longest a single request may take, in seconds
+const TIMEOUT_SECS: &str = "10";
+
+/// The directory in the cache of a site that responses are cached in between
+/// builds.
+fn cache_root(site_cache: &Path) -> PathBuf {
+    site_cache.join("remote")
+}
+
+/// Where a build caches the responses it fetches, and whether it may use the
+/// network to fetch them.
+#[derive(Debug, Clone)]
+pub struct Remote {
+    cache_root: PathBuf,
+    offline: bool,
+}
+
+impl Remote {
+    pub fn new(site_cache: &Path, offline: bool) -> Self {
+        Self {
+            cache_root: cache_root(site_cache),
+            offline,
+        }
+    }
+
+    /// The cache file of a URL, keyed by its hash.
+    fn cache_path(&self, url: &str) -> PathBuf {
+        let hash = Sha256::digest(url.as_bytes());
+        self.cache_root
+            .join(format!("{:x}", hash).get(..16).unwrap_or_default())
+    }
+}
+
+/// Fetch the body of a URL with `curl`.
+fn download(url: &str) -> anyhow::Result<String> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--max-time", TIMEOUT_SECS])
+        .args(["--user-agent", concat!("www/", env!("CARGO_PKG_VERSION"))])
+        .arg("--")
+        .arg(url)
+        .output()
+        .map_err(|err| FailureClass::Tool.wrap("failed to execute 'curl'", err))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(FailureClass::Tool.error(format!("failed to fetch [{url}]: {}", stderr.trim())));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Fetch the body of a URL at build time, reusing a cached response for a day.
+///
+/// When the URL can't be fetched, an expired response is used instead if
+/// there is one, so that a build without a network connection still works
+/// after the first. When `offline` is set, the cached response is always used
+/// and nothing is fetched.
+pub fn fetch(remote: &Remote, url: &str) -> anyhow::Result<String> {
+    let path = remote.cache_path(url);
+    let cached = fs::read_to_string(&path).ok();
+    if remote.offline {
+        debug!(url, "Offline, using cached response");
+        return cached.with_context(|| {
+            format!("[{url}] has not been fetched before, so it can't be used offline")
+        });
+    }
+    let age = fs::metadata(&path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+    if let (Some(cached), Some(age)) = (&cached, age)
+        && age < MAX_AGE
+    {
+        debug!(url, "Using cached response");
+        return Ok(cached.clone());
+    }
+
+    match (download(url), cached) {
+        (Ok(body), _) => {
+            fs::create_dir_all(&remote.cache_root)
+                .context("failed to create remote cache directory")?;
+            fs::write(&path, &body).context(format!("failed to cache response of [{url}]"))?;
+            debug!(url, "Fetched and cached response");
+            Ok(body)
+        },
+        (Err(err), Some(cached)) => {
+            warn!(url, %err, "Failed to fetch URL, using an expired cached response");
+            Ok(cached)
+        },
+        (Err(err), None) => Err(err),
+    }
+}
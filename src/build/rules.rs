@@ -0,0 +1,139 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use tracing::debug;
+
+use crate::build::{BuildDirFiles, config::DirectoryRules};
+
+/// The name of the file that declares rules for the content directory it is
+/// in, using the same fields as the `directories` site config.
+pub const RULES_FILENAME: &str = ".build.json";
+
+/// Where the templates for a page are looked up, when its directory sets a
+/// template root.
+#[derive(Debug, Clone)]
+pub struct TemplateRoot {
+    /// Directory relative to the template directory
+    pub dir: PathBuf,
+    /// Path of the page relative to `dir`, which mirrors its path below the
+    /// directory that set the template root
+    pub page_path: PathBuf,
+}
+
+/// The rules in effect for a single content file.
+#[derive(Debug)]
+pub struct FileRules {
+    pub exclude: bool,
+    pub copy_only: bool,
+    pub template_root: Option<TemplateRoot>,
+    /// Path of the file relative to the content directory, after moving it
+    /// under any output prefix
+    pub output_path: PathBuf,
+}
+
+/// The directory rules from the site config and from every rules file, keyed
+/// by content directory.
+#[derive(Debug, Default)]
+pub struct DirectoryRulesSet(BTreeMap<PathBuf, DirectoryRules>);
+
+impl DirectoryRulesSet {
+    pub fn load(
+        config: &BTreeMap<PathBuf, DirectoryRules>,
+        build_files: &BuildDirFiles,
+    ) -> anyhow::Result<Self> {
+        let mut rules = config.clone();
+
+        for (path, file) in &build_files.files {
+            let Ok(sub_path) = path.strip_prefix("content") else {
+                continue;
+            };
+            if sub_path
+                .file_name()
+                .is_none_or(|name| name != RULES_FILENAME)
+            {
+                continue;
+            }
+
+            let dir = sub_path.parent().unwrap_or(Path::new("")).to_path_buf();
+            let content = fs::read_to_string(&file.full_path).context(format!(
+                "failed to read directory rules [{}]",
+                file.full_path.display()
+            ))?;
+            let dir_rules: DirectoryRules = serde_json::from_str(&content).context(format!(
+                "failed to parse directory rules [{}]",
+                file.full_path.display()
+            ))?;
+
+            if rules.contains_key(&dir) {
+                bail!(
+                    "rules for content directory [{}] are declared in both the site config and \
+                     [{}]",
+                    dir.display(),
+                    file.full_path.display()
+                );
+            }
+            debug!(dir = %dir.display(), ?dir_rules, "Loaded directory rules file");
+            rules.insert(dir, dir_rules);
+        }
+
+        Ok(Self(rules))
+    }
+
+    /// The rules for the content file at the given path, relative to the
+    /// content directory.
+    pub fn resolve(&self, content_path: &Path) -> FileRules {
+        let mut rules = FileRules {
+            exclude: false,
+            copy_only: false,
+            template_root: None,
+            output_path: content_path.to_path_buf(),
+        };
+        if self.0.is_empty() {
+            return rules;
+        }
+
+        // Apply directories from the outermost inwards, so inner directories win
+        let mut output_prefix = None;
+        let ancestors = content_path.ancestors().skip(1).collect::<Vec<_>>();
+        for dir in ancestors.into_iter().rev() {
+            let Some(dir_rules) = self.0.get(dir) else {
+                continue;
+            };
+            let relative_path = content_path
+                .strip_prefix(dir)
+                .expect("directory is an ancestor of the path");
+
+            if let Some(exclude) = dir_rules.exclude {
+                rules.exclude = exclude;
+            }
+            if let Some(copy_only) = dir_rules.copy_only {
+                rules.copy_only = copy_only;
+            }
+            if let Some(template_root) = &dir_rules.template_root {
+                rules.template_root = Some(TemplateRoot {
+                    dir: template_root.clone(),
+                    page_path: relative_path.to_path_buf(),
+                });
+            }
+            if let Some(prefix) = &dir_rules.output_prefix {
+                output_prefix = Some(relative_path_under(prefix, relative_path));
+            }
+        }
+
+        if let Some(output_path) = output_prefix {
+            rules.output_path = output_path;
+        }
+
+        rules
+    }
+}
+
+/// Join a path under a prefix, ignoring any leading `/` in the prefix since
+/// it always refers to the root of the output.
+fn relative_path_under(prefix: &Path, path: &Path) -> PathBuf {
+    prefix.strip_prefix("/").unwrap_or(prefix).join(path)
+}
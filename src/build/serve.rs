@@ -0,0 +1,481 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, fs,
+    io::{BufRead, BufReader, Write},
+    iter,
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    str::FromStr,
+    sync::{Arc, RwLock},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Context;
+use argh::{ArgsInfo, FromArgs};
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use tracing::{debug, error, info, warn};
+
+use crate::build::{
+    BuildCmd, BuildDirFiles, BuildReport, NOT_FOUND_URL_PATH, build_pages,
+    config::CONFIG_FILENAME,
+    deploy::content_type,
+    manifest::url_path,
+    warnings::{self, Warning},
+};
+
+/// How often the input directory is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Build the site and serve it locally, rebuilding whenever the input changes.
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(subcommand, name = "serve")]
+pub struct ServeCmd {
+    /// path to the input directory
+    #[argh(positional)]
+    input_path: PathBuf,
+
+    /// port to listen on, defaults to 8000
+    #[argh(option, default = "8000")]
+    port: u16,
+
+    /// print an event to stdout after every build, `json` prints one JSON
+    /// object per line
+    #[argh(option)]
+    events: Option<EventFormat>,
+
+    /// open the site in the browser once it is served, at the page of the most
+    /// recently edited content file
+    #[argh(switch)]
+    open: bool,
+}
+
+/// How build events are printed.
+#[derive(Debug, Clone, Copy)]
+enum EventFormat {
+    Json,
+}
+
+impl FromStr for EventFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(EventFormat::Json),
+            _ => Err(format!("unknown event format [{s}], expected `json`")),
+        }
+    }
+}
+
+/// The outcome of a single build, for editors and other tools to react to.
+#[derive(Debug, Serialize)]
+struct BuildEvent<'a> {
+    /// The input files that changed since the last build, relative to the
+    /// input directory, empty for the first build
+    changed: Vec<&'a Path>,
+    /// Whether the whole site was rebuilt, rather than only affected pages
+    full: bool,
+    /// The URL paths of the output files that were written
+    outputs: Vec<&'a str>,
+    duration_ms: u128,
+    warnings: &'a [Warning],
+    /// The error that failed the build, in which case the last good build is
+    /// still being served
+    error: Option<String>,
+}
+
+impl BuildEvent<'_> {
+    fn print(&self, format: EventFormat) {
+        match format {
+            EventFormat::Json => match serde_json::to_string(self) {
+                Ok(line) => println!("{line}"),
+                Err(err) => debug!(%err, "Failed to serialize build event"),
+            },
+        }
+    }
+}
+
+/// Every output file of the last successful build, keyed by URL path.
+type Pages = Arc<RwLock<BTreeMap<String, Vec<u8>>>>;
+
+/// The size and modification time of every input file, keyed by full path.
+type Snapshot = BTreeMap<PathBuf, (u64, SystemTime)>;
+
+/// The size and modification time of every file in the input directory and
+/// the other directories the last build read from, to detect changes.
+fn snapshot(input_path: &Path, report: &BuildReport) -> anyhow::Result<Snapshot> {
+    let mut snapshot = Snapshot::new();
+    for dir in iter::once(input_path).chain(report.external_dirs.iter().map(PathBuf::as_path)) {
+        let files = BuildDirFiles::gather(dir).context(format!(
+            "failed to collect input files from [{}]",
+            dir.display()
+        ))?;
+        for file in files.files.into_values() {
+            let metadata = fs::metadata(&file.full_path).context(format!(
+                "failed to read metadata of [{}]",
+                file.full_path.display()
+            ))?;
+            snapshot.insert(file.full_path, (metadata.len(), metadata.modified()?));
+        }
+    }
+    Ok(snapshot)
+}
+
+/// The prefix of the name of each scratch directory, followed by the process
+/// ID of the server
+pub const SCRATCH_PREFIX: &str = "www-serve-";
+
+/// The directory that scratch directories are created in, which is `/dev/shm`
+/// where the platform has it, so that rebuilds are written to a filesystem
+/// backed by memory rather than to disk, and the temporary directory
+/// otherwise.
+pub fn scratch_root() -> PathBuf {
+    let shm = Path::new("/dev/shm");
+    if shm.is_dir() {
+        shm.to_path_buf()
+    } else {
+        env::temp_dir()
+    }
+}
+
+/// A directory for the build output of this server.
+fn scratch_dir() -> PathBuf {
+    scratch_root().join(format!("{SCRATCH_PREFIX}{}", process::id()))
+}
+
+/// The input files that were added, removed, or modified between two
+/// snapshots.
+fn changed_files(previous: &Snapshot, current: &Snapshot) -> Vec<PathBuf> {
+    previous
+        .keys()
+        .chain(current.keys())
+        .filter(|path| previous.get(*path) != current.get(*path))
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Build the site into the scratch directory like `www build` would, then read
+/// every output file back so it can be served, removing the directory
+/// afterwards.
+///
+/// Only the pages of the `selected` content files are written if given, along
+/// with the generated pages.
+fn build_and_load(
+    input_path: &Path,
+    selected: Option<&BTreeSet<PathBuf>>,
+) -> anyhow::Result<(BTreeMap<String, Vec<u8>>, BuildReport)> {
+    let output_path = scratch_dir();
+    let args = BuildCmd {
+        input_path: input_path.to_path_buf(),
+        output_path: output_path.clone(),
+        release: false,
+        link_graph: None,
+        keep_going: false,
+        deny_warnings: false,
+        output_format: None,
+        offline: false,
+    };
+    let result = build_pages(&args, selected).and_then(|report| {
+        let output_files =
+            BuildDirFiles::gather(&output_path).context("failed to collect output files")?;
+        let pages = output_files
+            .files
+            .into_iter()
+            .map(|(path, file)| {
+                let content = fs::read(&file.full_path).context(format!(
+                    "failed to read output file [{}]",
+                    file.full_path.display()
+                ))?;
+                Ok((url_path(&path), content))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok((pages, report))
+    });
+
+    if let Err(err) = fs::remove_dir_all(&output_path) {
+        debug!(%err, "Failed to remove scratch output directory");
+    }
+
+    result
+}
+
+fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+    include_body: bool,
+) -> std::io::Result<()> {
+    let mut response = format!("HTTP/1.1 {status}\r\n");
+    for (name, value) in headers {
+        response.push_str(&format!("{name}: {value}\r\n"));
+    }
+    response.push_str(&format!(
+        "Content-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        body.len()
+    ));
+    stream.write_all(response.as_bytes())?;
+    if include_body {
+        stream.write_all(body)?;
+    }
+    stream.flush()
+}
+
+fn handle_connection(mut stream: TcpStream, pages: &Pages) -> anyhow::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers aren't needed, but are read so the client sees a clean close
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(write_response(
+            &mut stream,
+            "400 Bad Request",
+            &[],
+            b"",
+            true,
+        )?);
+    };
+    if method != "GET" && method != "HEAD" {
+        return Ok(write_response(
+            &mut stream,
+            "405 Method Not Allowed",
+            &[("Allow", "GET, HEAD")],
+            b"",
+            true,
+        )?);
+    }
+    let include_body = method == "GET";
+
+    let path = percent_decode_str(target.split(['?', '#']).next().unwrap_or("/"))
+        .decode_utf8_lossy()
+        .into_owned();
+    let pages = pages.read().expect("pages lock is not poisoned");
+    let lookup = if path.ends_with('/') {
+        format!("{path}index.html")
+    } else {
+        path.clone()
+    };
+
+    if let Some(body) = pages.get(&lookup) {
+        debug!(path, "Serving page");
+        return Ok(write_response(
+            &mut stream,
+            "200 OK",
+            &[("Content-Type", content_type(&lookup))],
+            body,
+            include_body,
+        )?);
+    }
+
+    // Directories without a trailing slash would break relative links
+    if pages.contains_key(&format!("{path}/index.html")) {
+        let location = format!("{path}/");
+        return Ok(write_response(
+            &mut stream,
+            "301 Moved Permanently",
+            &[("Location", &location)],
+            b"",
+            include_body,
+        )?);
+    }
+
+    debug!(path, "Page not found");
+    if let Some(body) = pages.get(NOT_FOUND_URL_PATH) {
+        return Ok(write_response(
+            &mut stream,
+            "404 Not Found",
+            &[("Content-Type", content_type(NOT_FOUND_URL_PATH))],
+            body,
+            include_body,
+        )?);
+    }
+    Ok(write_response(
+        &mut stream,
+        "404 Not Found",
+        &[("Content-Type", "text/plain; charset=utf-8")],
+        format!("No page at {path}").as_bytes(),
+        include_body,
+    )?)
+}
+
+/// Build and load the output like [`build_and_load`], and print a build event
+/// for the result if requested.
+fn build_with_event(
+    cmd: &ServeCmd,
+    changed: &[PathBuf],
+    selected: Option<&BTreeSet<PathBuf>>,
+) -> anyhow::Result<(BTreeMap<String, Vec<u8>>, BuildReport)> {
+    let start = Instant::now();
+    let (result, warnings) = warnings::collect(|| build_and_load(&cmd.input_path, selected));
+
+    if let Some(format) = cmd.events {
+        let event = BuildEvent {
+            changed: changed
+                .iter()
+                .map(|path| path.strip_prefix(&cmd.input_path).unwrap_or(path))
+                .collect(),
+            full: selected.is_none(),
+            outputs: result
+                .as_ref()
+                .map(|(pages, _)| pages.keys().map(String::as_str).collect())
+                .unwrap_or_default(),
+            duration_ms: start.elapsed().as_millis(),
+            warnings: &warnings,
+            error: result.as_ref().err().map(|err| format!("{err:#}")),
+        };
+        event.print(format);
+    }
+
+    result
+}
+
+/// The URL path of the page of the content file that was modified last, or the
+/// root of the site if none can be found.
+fn last_edited_page(report: &BuildReport) -> String {
+    let last_edited = report
+        .page_urls
+        .iter()
+        .filter_map(|(path, url)| {
+            let modified = fs::metadata(path).and_then(|md| md.modified()).ok()?;
+            Some((modified, url))
+        })
+        .max();
+    match last_edited {
+        Some((_, url)) => url.strip_suffix("index.html").unwrap_or(url).to_owned(),
+        None => "/".into(),
+    }
+}
+
+/// Open a URL with the default browser of the system.
+fn open_browser(url: &str) -> anyhow::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    debug!(url, "Opening browser");
+    command
+        .arg(url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("failed to start the browser")?;
+    Ok(())
+}
+
+/// Build the site into a scratch directory, serve the output from memory, and
+/// rebuild when any input file changes. A failed rebuild is logged and the last
+/// good build keeps being served.
+///
+/// The site config, templates, and theme are loaded again by every build, so
+/// changes to them take effect without restarting.
+///
+/// With `--events json`, a JSON object describing each build is printed as a
+/// line on stdout, while logs are written to stderr.
+///
+/// A rebuild only writes the pages that depend on the changed files, as found
+/// by the last build, which includes every page whose templates list other
+/// pages. Adding or removing a file, or changing one that no page
+/// is known to depend on, like the site configuration, rebuilds everything.
+pub fn serve(cmd: ServeCmd) -> anyhow::Result<()> {
+    let pages: Pages = Arc::default();
+    let (built, mut report) = build_with_event(&cmd, &[], None)?;
+    *pages.write().expect("pages lock is not poisoned") = built;
+
+    let listener = TcpListener::bind(("127.0.0.1", cmd.port))
+        .context(format!("failed to listen on port {}", cmd.port))?;
+    info!("Serving site at http://127.0.0.1:{}/", cmd.port);
+    if cmd.open {
+        let url = format!("http://127.0.0.1:{}{}", cmd.port, last_edited_page(&report));
+        if let Err(err) = open_browser(&url) {
+            warn!(%err, url, "Failed to open the browser");
+        }
+    }
+
+    let server_pages = Arc::clone(&pages);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let pages = Arc::clone(&server_pages);
+            thread::spawn(move || {
+                if let Err(err) = handle_connection(stream, &pages) {
+                    debug!(%err, "Failed to handle request");
+                }
+            });
+        }
+    });
+
+    let mut last_snapshot = snapshot(&cmd.input_path, &report)?;
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let current = match snapshot(&cmd.input_path, &report) {
+            Ok(current) => current,
+            Err(err) => {
+                debug!(%err, "Failed to check input for changes");
+                continue;
+            },
+        };
+        if current == last_snapshot {
+            continue;
+        }
+        let changed = changed_files(&last_snapshot, &current);
+        let selected = if last_snapshot.keys().eq(current.keys()) {
+            report.affected_by(&changed)
+        } else {
+            None
+        };
+        last_snapshot = current;
+
+        if changed.contains(&cmd.input_path.join(CONFIG_FILENAME)) {
+            info!("Site config changed, reloading it");
+        }
+        let start = Instant::now();
+        match &selected {
+            Some(selected) => info!(
+                num_pages = selected.len(),
+                "Input changed, rebuilding pages"
+            ),
+            None => info!("Input changed, rebuilding site"),
+        }
+        match build_with_event(&cmd, &changed, selected.as_ref()) {
+            Ok((built, rebuilt_report)) => {
+                let mut pages = pages.write().expect("pages lock is not poisoned");
+                let dirs_changed = report.external_dirs != rebuilt_report.external_dirs;
+                if selected.is_some() {
+                    pages.extend(built);
+                    report.merge(rebuilt_report);
+                } else {
+                    *pages = built;
+                    report = rebuilt_report;
+                }
+                drop(pages);
+                info!(duration = ?start.elapsed(), "Rebuilt site");
+
+                // A different theme is watched from now on
+                if dirs_changed {
+                    match snapshot(&cmd.input_path, &report) {
+                        Ok(current) => last_snapshot = current,
+                        Err(err) => debug!(%err, "Failed to check input for changes"),
+                    }
+                }
+            },
+            Err(err) => error!("Rebuild failed, still serving the last build: {err:?}"),
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+/// The directory that the caches of a site are kept in between builds, like
+/// formatted files and fetched responses, named by the hash of the canonical
+/// input path so that every site on the machine has its own.
+pub fn site_cache_dir(input_path: &Path) -> PathBuf {
+    let input_path = input_path
+        .canonicalize()
+        .unwrap_or_else(|_| input_path.to_path_buf());
+    let hash = Sha256::digest(input_path.as_os_str().as_encoded_bytes());
+    env::temp_dir()
+        .join("www-cache")
+        .join(format!("{:x}", hash).get(..16).unwrap_or_default())
+}
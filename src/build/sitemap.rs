@@ -0,0 +1,340 @@
+use std::{collections::BTreeMap, fmt::Write, fs, path::Path};
+
+use anyhow::{Context, bail};
+use chrono::{DateTime, FixedOffset};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::build::{
+    Frontmatter, Metadata, MetadataContainer,
+    config::SiteConfig,
+    feed::escape_xml,
+    protect,
+    translation::{self, Translation},
+};
+
+/// The file name of the sitemap, at the output root
+const SITEMAP_FILENAME: &str = "sitemap.xml";
+
+/// How often a page is expected to change, as a hint to crawlers.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeFrequency {
+    Always,
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+    Never,
+}
+
+impl ChangeFrequency {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ChangeFrequency::Always => "always",
+            ChangeFrequency::Hourly => "hourly",
+            ChangeFrequency::Daily => "daily",
+            ChangeFrequency::Weekly => "weekly",
+            ChangeFrequency::Monthly => "monthly",
+            ChangeFrequency::Yearly => "yearly",
+            ChangeFrequency::Never => "never",
+        }
+    }
+}
+
+/// The sitemap settings of a single page, from its frontmatter.
+#[derive(Debug, Clone)]
+pub struct SitemapHints {
+    /// Whether the page is listed at all, set with `sitemap: false`
+    pub include: bool,
+    /// From `sitemap_priority`, between 0.0 and 1.0
+    pub priority: Option<f64>,
+    /// From `sitemap_changefreq`
+    pub changefreq: Option<ChangeFrequency>,
+}
+
+impl Default for SitemapHints {
+    fn default() -> Self {
+        Self {
+            include: true,
+            priority: None,
+            changefreq: None,
+        }
+    }
+}
+
+impl SitemapHints {
+    pub fn from_frontmatter(frontmatter: &Frontmatter) -> anyhow::Result<Self> {
+        let priority = frontmatter.typed_field::<f64>("sitemap_priority")?;
+        if let Some(priority) = priority
+            && !(0.0..=1.0).contains(&priority)
+        {
+            bail!("invalid frontmatter field 'sitemap_priority', must be between 0.0 and 1.0");
+        }
+
+        Ok(Self {
+            include: frontmatter.typed_field("sitemap")?.unwrap_or(true),
+            priority,
+            changefreq: frontmatter.typed_field("sitemap_changefreq")?,
+        })
+    }
+}
+
+/// The most URLs a single sitemap may list, by the sitemap protocol
+const MAX_URLS: usize = 50_000;
+
+/// The largest a single sitemap may be in bytes, by the sitemap protocol
+const MAX_BYTES: usize = 50 * 1024 * 1024;
+
+const URLSET_START: &str = concat!(
+    r#"<?xml version="1.0" encoding="utf-8"?>"#,
+    "\n",
+    r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9" xmlns:xhtml="http://www.w3.org/1999/xhtml">"#,
+    "\n"
+);
+
+const URLSET_END: &str = "</urlset>\n";
+
+/// The bytes encoded in the name of a section's sitemap, which is everything
+/// but ASCII letters and digits and the unreserved `-_~`
+const SECTION_NAME: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'~');
+
+/// The bytes encoded in a URL path, which keeps `.` and `/` as well
+const URL_PATH: &AsciiSet = &SECTION_NAME.remove(b'.').remove(b'/');
+
+/// Percent-encode a URL path, leaving the characters that don't need it.
+fn encode_path(path: &str) -> String {
+    utf8_percent_encode(path, URL_PATH).to_string()
+}
+
+/// The `<url>` element of a page, along with what decides which sitemap it is
+/// listed in.
+struct UrlEntry {
+    /// The top-level content directory of the page, or `None` for pages at the
+    /// root
+    section: Option<String>,
+    date: Option<DateTime<FixedOffset>>,
+    xml: String,
+}
+
+/// A sitemap file with some of the URLs of the site.
+struct SitemapFile {
+    name: String,
+    lastmod: Option<DateTime<FixedOffset>>,
+    content: String,
+}
+
+/// Write the `<url>` element of a page, with the hints from its frontmatter and
+/// links to its translations.
+fn url_entry(
+    base_url: &str,
+    md: &Metadata,
+    translations: &BTreeMap<String, Vec<Translation>>,
+) -> anyhow::Result<String> {
+    let mut buf = String::new();
+    writeln!(buf, "  <url>")?;
+    writeln!(
+        buf,
+        "    <loc>{}</loc>",
+        escape_xml(&format!(
+            "{base_url}{}",
+            encode_path(&md.url_path.to_string_lossy())
+        ))
+    )?;
+    if let Some(date) = md.date {
+        writeln!(buf, "    <lastmod>{}</lastmod>", date.to_rfc3339())?;
+    }
+    if let Some(changefreq) = md.sitemap.changefreq {
+        writeln!(buf, "    <changefreq>{}</changefreq>", changefreq.as_str())?;
+    }
+    if let Some(priority) = md.sitemap.priority {
+        writeln!(buf, "    <priority>{priority}</priority>")?;
+    }
+    for translation in translation::page_translations(translations, md) {
+        writeln!(
+            buf,
+            r#"    <xhtml:link rel="alternate" hreflang="{}" href="{}"/>"#,
+            escape_xml(&translation.language),
+            escape_xml(&translation.url)
+        )?;
+    }
+    writeln!(buf, "  </url>")?;
+    Ok(buf)
+}
+
+/// The sitemaps that a group of entries is listed in.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Group<'a> {
+    /// Every page, when the sitemap isn't split by section
+    All,
+    /// The pages at the root of the site
+    Root,
+    /// The pages under a top-level content directory
+    Section(&'a str),
+}
+
+impl Group<'_> {
+    /// The file name of the sitemap of the group, or of one of its chunks when
+    /// there are several.
+    ///
+    /// Section names are percent-encoded, including any `.`, so that they can't
+    /// be mistaken for the chunk number or for the root pages.
+    fn file_name(&self, number: Option<usize>) -> String {
+        let stem = match self {
+            Group::All => "sitemap".to_owned(),
+            Group::Root => "sitemap-root".to_owned(),
+            Group::Section(section) => {
+                format!(
+                    "sitemap-section-{}",
+                    utf8_percent_encode(section, SECTION_NAME)
+                )
+            },
+        };
+        match (self, number) {
+            (Group::All, Some(number)) => format!("{stem}-{number}.xml"),
+            (_, Some(number)) => format!("{stem}.{number}.xml"),
+            (_, None) => format!("{stem}.xml"),
+        }
+    }
+}
+
+/// Group the entries into sitemaps that stay within the limits of the
+/// protocol, named after their section when `by_section` is set.
+fn split_entries(entries: Vec<UrlEntry>, by_section: bool) -> Vec<SitemapFile> {
+    let mut groups = BTreeMap::<Group, Vec<&UrlEntry>>::new();
+    // A site without pages still gets an empty sitemap
+    if !by_section {
+        groups.insert(Group::All, vec![]);
+    }
+    for entry in &entries {
+        let group = match &entry.section {
+            _ if !by_section => Group::All,
+            Some(section) => Group::Section(section),
+            None => Group::Root,
+        };
+        groups.entry(group).or_default().push(entry);
+    }
+
+    let mut files = vec![];
+    for (group, entries) in groups {
+        let mut chunks = vec![];
+        let mut chunk = Vec::<&UrlEntry>::new();
+        let mut chunk_bytes = URLSET_START.len() + URLSET_END.len();
+        for entry in entries {
+            if chunk.len() == MAX_URLS
+                || (!chunk.is_empty() && chunk_bytes + entry.xml.len() > MAX_BYTES)
+            {
+                chunks.push(std::mem::take(&mut chunk));
+                chunk_bytes = URLSET_START.len() + URLSET_END.len();
+            }
+            chunk_bytes += entry.xml.len();
+            chunk.push(entry);
+        }
+        chunks.push(chunk);
+
+        let num_chunks = chunks.len();
+        for (idx, chunk) in chunks.into_iter().enumerate() {
+            // The chunks of an unsplit sitemap are numbered even when there is one
+            let number = (group == Group::All || num_chunks > 1).then_some(idx + 1);
+            let name = group.file_name(number);
+            let mut content = URLSET_START.to_owned();
+            content.extend(chunk.iter().map(|entry| entry.xml.as_str()));
+            content.push_str(URLSET_END);
+            files.push(SitemapFile {
+                name,
+                lastmod: chunk.iter().filter_map(|entry| entry.date).max(),
+                content,
+            });
+        }
+    }
+    files
+}
+
+/// Write a sitemap listing every HTML page, with the hints from each page's
+/// frontmatter and links to its translations.
+///
+/// When the pages don't fit in a single sitemap by the limits of the protocol,
+/// or when `split_sections` is set, they are listed in several sitemaps, which
+/// `sitemap.xml` lists as a sitemap index instead.
+#[tracing::instrument(skip_all)]
+pub fn write_sitemap(
+    config: &SiteConfig,
+    metadata: &MetadataContainer,
+    translations: &BTreeMap<String, Vec<Translation>>,
+    output_root: &Path,
+) -> anyhow::Result<()> {
+    let Some(base_url) = config.base_url.as_deref() else {
+        debug!("No base URL configured, skipping sitemap");
+        return Ok(());
+    };
+    let base_url = base_url.trim_end_matches('/');
+
+    let mut entries = vec![];
+    for md in metadata.0.values() {
+        if !md.sitemap.include
+            || md.url_path.extension().is_none_or(|ext| ext != "html")
+            || md.is_not_found()
+            || protect::is_protected(config, md)
+        {
+            continue;
+        }
+
+        let section = md
+            .slug
+            .parent
+            .components()
+            .next()
+            .map(|section| section.as_os_str().to_string_lossy().into_owned());
+        entries.push(UrlEntry {
+            section,
+            date: md.date,
+            xml: url_entry(base_url, md, translations)?,
+        });
+    }
+    let num_urls = entries.len();
+
+    let mut files = split_entries(entries, config.sitemap.split_sections);
+    if let [file] = files.as_mut_slice()
+        && !config.sitemap.split_sections
+    {
+        file.name = SITEMAP_FILENAME.to_owned();
+    } else {
+        let mut index = String::new();
+        writeln!(index, r#"<?xml version="1.0" encoding="utf-8"?>"#)?;
+        writeln!(
+            index,
+            r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#
+        )?;
+        for file in &files {
+            writeln!(index, "  <sitemap>")?;
+            writeln!(
+                index,
+                "    <loc>{}</loc>",
+                escape_xml(&format!("{base_url}/{}", encode_path(&file.name)))
+            )?;
+            if let Some(lastmod) = file.lastmod {
+                writeln!(index, "    <lastmod>{}</lastmod>", lastmod.to_rfc3339())?;
+            }
+            writeln!(index, "  </sitemap>")?;
+        }
+        writeln!(index, "</sitemapindex>")?;
+        files.push(SitemapFile {
+            name: SITEMAP_FILENAME.to_owned(),
+            lastmod: None,
+            content: index,
+        });
+    }
+
+    for file in &files {
+        let sitemap_path = output_root.join(&file.name);
+        fs::write(&sitemap_path, &file.content).context(format!(
+            "failed to write sitemap [{}]",
+            sitemap_path.display()
+        ))?;
+    }
+    debug!(num_urls, num_files = files.len(), "Written sitemap");
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+use crate::build::{BuildDirFiles, config::ContentSource, errors::FailureClass};
+
+/// The directory in the cache of a site that content sources are checked out
+/// into.
+fn checkout_root(site_cache: &Path) -> PathBuf {
+    site_cache.join("sources")
+}
+
+/// Run git in a directory, failing with its stderr if it doesn't succeed.
+fn git(dir: &Path, args: &[&str]) -> anyhow::Result<()> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|err| FailureClass::Tool.wrap("failed to execute 'git'", err))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(FailureClass::Tool.error(format!(
+            "Execution of 'git {}' returned an unsuccessful status code: {}",
+            args.first().copied().unwrap_or_default(),
+            stderr.trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Check out a content source into the cache of the site, keyed by its URL and
+/// revision. Since the revision is pinned, an existing checkout is reused as
+/// is.
+///
+/// Only the files of the revision are kept, without the `.git` directory, so
+/// that they can be gathered like the files of the site.
+fn checkout(site_cache: &Path, source: &ContentSource) -> anyhow::Result<PathBuf> {
+    let key = format!("{}#{}", source.git, source.rev);
+    let hash = Sha256::digest(key.as_bytes());
+    let dir = checkout_root(site_cache).join(format!("{:x}", hash).get(..16).unwrap_or_default());
+    if dir.is_dir() {
+        debug!(url = source.git, source = %dir.display(), "Reusing checked out content source");
+        return Ok(dir);
+    }
+
+    info!(
+        url = source.git,
+        rev = source.rev,
+        "Fetching content source"
+    );
+    // The checkout is made next to its final place and moved there once it is
+    // complete, so that a failed fetch isn't mistaken for a checkout later
+    let partial_dir = dir.with_extension("partial");
+    let _ = fs::remove_dir_all(&partial_dir);
+    fs::create_dir_all(&partial_dir).context("failed to create content source directory")?;
+
+    // Fetching the revision directly works for commits as well as branches and
+    // tags, unlike `git clone --branch`
+    let fetched = git(&partial_dir, &["init", "--quiet"])
+        .and_then(|()| {
+            git(
+                &partial_dir,
+                &["fetch", "--quiet", "--depth", "1", &source.git, &source.rev],
+            )
+        })
+        .and_then(|()| git(&partial_dir, &["checkout", "--quiet", "FETCH_HEAD"]));
+    if let Err(err) = fetched {
+        let _ = fs::remove_dir_all(&partial_dir);
+        return Err(err.context(format!(
+            "failed to fetch content source [{}] at [{}]",
+            source.git, source.rev
+        )));
+    }
+
+    fs::remove_dir_all(partial_dir.join(".git"))
+        .context("failed to remove git directory of content source")?;
+    fs::rename(&partial_dir, &dir).context("failed to move content source into place")?;
+    Ok(dir)
+}
+
+/// Overlay the files of each content source into the content tree of the
+/// site, under the `content/` directory at the source's `mount` point.
+///
+/// The site's own files take precedence over the files of a source, and
+/// earlier sources over later ones.
+pub fn overlay_sources(
+    site_cache: &Path,
+    sources: &[ContentSource],
+    build_files: &mut BuildDirFiles,
+) -> anyhow::Result<()> {
+    for source in sources {
+        let checkout_dir = checkout(site_cache, source)?;
+        let source_dir = match &source.path {
+            Some(path) => checkout_dir.join(path),
+            None => checkout_dir,
+        };
+        if !source_dir.is_dir() {
+            return Err(FailureClass::Config.error(format!(
+                "content source [{}] has no directory [{}]",
+                source.git,
+                source.path.as_deref().unwrap_or(Path::new("")).display()
+            )));
+        }
+
+        let files = BuildDirFiles::gather(&source_dir).context(format!(
+            "failed to collect files of content source [{}]",
+            source.git
+        ))?;
+        let mut num_files = 0;
+        for (path, file) in files.files {
+            let path = Path::new("content").join(&source.mount).join(path);
+            if build_files.files.contains_key(&path) {
+                debug!(path = %path.display(), "Content source file is overridden by the site");
+                continue;
+            }
+            build_files.files.insert(path, file);
+            num_files += 1;
+        }
+        debug!(url = source.git, num_files, "Overlaid content source");
+    }
+    Ok(())
+}
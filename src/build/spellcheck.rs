@@ -0,0 +1,255 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::LazyLock,
+    thread,
+};
+
+use anyhow::Context;
+use regex::Regex;
+use tracing::debug;
+
+use crate::build::{
+    Metadata, MetadataContainer,
+    check::decode_entities,
+    config::SpellcheckConfig,
+    diagnostic::Diagnostic,
+    errors::FailureClass,
+    typography::{Token, tag_name, tokenize},
+};
+
+/// Elements whose text isn't prose, so it isn't spellchecked
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "code", "pre", "kbd", "samp", "var", "script", "style", "math", "svg",
+];
+
+/// Elements that start a new section of the page
+const HEADING_ELEMENTS: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
+
+/// Matches a word of letters, along with the apostrophes inside it
+static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\p{L}+(?:['’]\p{L}+)*").unwrap());
+
+/// The text of a page under a single heading.
+#[derive(Debug, Default)]
+struct Section {
+    /// The text of the heading, or `None` for the text before the first heading
+    heading: Option<String>,
+    text: String,
+}
+
+/// Split the text of a page into the sections under each heading, leaving out
+/// the text that isn't prose, like code.
+fn sections(html: &str) -> Vec<Section> {
+    let mut sections = vec![Section::default()];
+    let mut skipped_depth = 0usize;
+    let mut in_heading = false;
+    for token in tokenize(html) {
+        match token {
+            Token::Tag(tag) => {
+                let (name, is_closing) = tag_name(tag);
+                let name = name.to_ascii_lowercase();
+                if SKIPPED_ELEMENTS.contains(&name.as_str()) {
+                    skipped_depth = if is_closing {
+                        skipped_depth.saturating_sub(1)
+                    } else {
+                        skipped_depth + 1
+                    };
+                } else if HEADING_ELEMENTS.contains(&name.as_str()) {
+                    in_heading = !is_closing;
+                    if !is_closing {
+                        sections.push(Section {
+                            heading: Some(String::new()),
+                            text: String::new(),
+                        });
+                    }
+                }
+                // Tags separate words, like the cells of a table
+                if let Some(section) = sections.last_mut() {
+                    section.text.push(' ');
+                }
+            },
+            Token::Text(_) if skipped_depth > 0 => {},
+            Token::Text(text) => {
+                let text = decode_entities(&text);
+                let Some(section) = sections.last_mut() else {
+                    continue;
+                };
+                if in_heading && let Some(heading) = &mut section.heading {
+                    heading.push_str(&text);
+                }
+                section.text.push_str(&text);
+            },
+        }
+    }
+    sections
+}
+
+/// Read the project dictionary, with one correctly spelled word per line.
+fn read_dictionary(path: &Path) -> anyhow::Result<BTreeSet<String>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(FailureClass::Config.error(format!(
+                "spellcheck dictionary [{}] does not exist",
+                path.display()
+            )));
+        },
+        Err(err) => return Err(err).context("failed to read spellcheck dictionary"),
+    };
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|word| !word.is_empty() && !word.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect())
+}
+
+/// Run the spellchecker on the words, returning the ones it doesn't know.
+fn misspelled_words(
+    command: &[String],
+    words: &BTreeSet<&str>,
+) -> anyhow::Result<BTreeSet<String>> {
+    let Some((program, args)) = command.split_first() else {
+        return Err(FailureClass::Config.error("spellcheck command is empty"));
+    };
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            FailureClass::Tool.wrap(format!("failed to execute spellchecker [{program}]"), err)
+        })?;
+
+    let input = words
+        .iter()
+        .map(|word| format!("{word}\n"))
+        .collect::<String>();
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    // Written from another thread, so that a spellchecker that writes before it
+    // has read everything can't fill its stdout and block
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .context("failed to wait for spellchecker")?;
+    writer
+        .join()
+        .expect("spellchecker writer does not panic")
+        .context("failed to write words to spellchecker")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(FailureClass::Tool.error(format!(
+            "Execution of spellchecker [{program}] returned an unsuccessful status code: {}",
+            stderr.trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|word| !word.is_empty())
+        .map(str::to_owned)
+        .collect())
+}
+
+/// The words a page lists in its `spellcheck_ignore` frontmatter field, or
+/// `None` if it opts out with `spellcheck: false`.
+fn page_ignored_words(md: &Metadata) -> anyhow::Result<Option<BTreeSet<String>>> {
+    let Some(frontmatter) = &md.frontmatter else {
+        return Ok(Some(BTreeSet::new()));
+    };
+    if !frontmatter
+        .typed_field::<bool>("spellcheck")?
+        .unwrap_or(true)
+    {
+        return Ok(None);
+    }
+    let ignored = frontmatter
+        .typed_field::<Vec<String>>("spellcheck_ignore")?
+        .unwrap_or_default();
+    Ok(Some(
+        ignored.iter().map(|word| word.to_lowercase()).collect(),
+    ))
+}
+
+/// Warn about the words in the rendered content of each page that the
+/// spellchecker doesn't know, along with the heading they are under.
+///
+/// Words in the project dictionary, and in the `spellcheck_ignore` list in the
+/// frontmatter of a page, are never reported. Pages with `spellcheck: false`
+/// aren't checked at all. Only the pages in `selected` are checked, if given.
+#[tracing::instrument(skip_all)]
+pub fn check_spelling(
+    config: &SpellcheckConfig,
+    input_root: &Path,
+    metadata: &MetadataContainer,
+    selected: Option<&BTreeSet<PathBuf>>,
+) -> anyhow::Result<()> {
+    let dictionary = match &config.dictionary {
+        Some(path) => read_dictionary(&input_root.join(path))?,
+        None => BTreeSet::new(),
+    };
+
+    let mut num_misspelled = 0;
+    for md in metadata.0.values() {
+        if selected.is_some_and(|selected| !selected.contains(&md.source_path)) {
+            continue;
+        }
+        let Some(html) = &md.rendered_content else {
+            continue;
+        };
+        let Some(ignored) =
+            page_ignored_words(md).context(format!("in frontmatter of [{}]", md.slug))?
+        else {
+            continue;
+        };
+
+        let sections = sections(html);
+        let words = sections
+            .iter()
+            .flat_map(|section| WORD.find_iter(&section.text))
+            .map(|word| word.as_str())
+            .filter(|word| {
+                let word = word.to_lowercase();
+                !dictionary.contains(&word) && !ignored.contains(&word)
+            })
+            .collect::<BTreeSet<_>>();
+        if words.is_empty() {
+            continue;
+        }
+        let misspelled = misspelled_words(&config.command, &words)?;
+        if misspelled.is_empty() {
+            continue;
+        }
+
+        // The source is only needed to point at the misspelled words
+        let source = fs::read_to_string(&md.source_path).unwrap_or_default();
+        let mut reported = BTreeSet::new();
+        for section in &sections {
+            for word in WORD.find_iter(&section.text) {
+                let word = word.as_str();
+                if !misspelled.contains(word) || !reported.insert(word) {
+                    continue;
+                }
+                let message = match &section.heading {
+                    Some(heading) => format!(
+                        "Possibly misspelled word [{word}] under heading [{}]",
+                        heading.trim()
+                    ),
+                    None => format!("Possibly misspelled word [{word}]"),
+                };
+                Diagnostic::new(&md.source_path, message)
+                    .at_first(&source, word)
+                    .warn();
+                num_misspelled += 1;
+            }
+        }
+    }
+
+    debug!(num_misspelled, "Checked spelling");
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    path::Path,
+    time::Duration,
+};
+
+use tracing::debug;
+
+use crate::build::BuildDirFiles;
+
+/// The target of the logs reporting the progress of a build, which are only
+/// shown when asked for with `-v`.
+pub const PROGRESS_TARGET: &str = "www::progress";
+
+/// What a build wrote to the output, for the summary at the end of the build.
+#[derive(Debug, Default, Clone)]
+pub struct BuildStats {
+    /// Pages rendered from content files
+    pub pages: usize,
+    /// Pages generated by the site itself, like feeds and taxonomy pages
+    pub generated_pages: usize,
+    /// Content and static files copied to the output as is
+    pub assets: usize,
+}
+
+/// The ANSI escape codes used for the summary, which are left empty when the
+/// summary isn't shown on a terminal.
+struct Style {
+    bold: &'static str,
+    green: &'static str,
+    yellow: &'static str,
+    reset: &'static str,
+}
+
+impl Style {
+    /// Colors are used when stderr is a terminal and `NO_COLOR` isn't set.
+    fn detect() -> Self {
+        if io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none() {
+            Self {
+                bold: "\x1b[1m",
+                green: "\x1b[1;32m",
+                yellow: "\x1b[1;33m",
+                reset: "\x1b[0m",
+            }
+        } else {
+            Self {
+                bold: "",
+                green: "",
+                yellow: "",
+                reset: "",
+            }
+        }
+    }
+}
+
+/// The total size of the files in the output directory.
+fn output_size(output_root: &Path) -> anyhow::Result<u64> {
+    let files = BuildDirFiles::gather(output_root)?;
+    Ok(files
+        .files
+        .values()
+        .filter_map(|file| fs::metadata(&file.full_path).ok())
+        .map(|metadata| metadata.len())
+        .sum())
+}
+
+/// Format a number of bytes with a binary unit, like `1.5 MiB`.
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{size} B");
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next_unit;
+    }
+    format!("{value:.1} {unit}")
+}
+
+/// Print a short summary of a finished build to stderr, with the labels
+/// aligned in a column.
+pub fn print_summary(
+    stats: &BuildStats,
+    num_warnings: usize,
+    duration: Duration,
+    output_root: &Path,
+) {
+    let style = Style::detect();
+    let size = match output_size(output_root) {
+        Ok(size) => format_size(size),
+        Err(err) => {
+            debug!(%err, "Failed to measure output size");
+            "unknown".into()
+        },
+    };
+    let warning_color = if num_warnings > 0 { style.yellow } else { "" };
+
+    let rows = [
+        (
+            "Pages",
+            format!("{} ({} generated)", stats.pages, stats.generated_pages),
+        ),
+        ("Assets", stats.assets.to_string()),
+        (
+            "Warnings",
+            format!("{warning_color}{num_warnings}{}", style.reset),
+        ),
+        ("Output", size),
+    ];
+
+    eprintln!(
+        "{}{:>10}{} site in {:.2}s",
+        style.green,
+        "Finished",
+        style.reset,
+        duration.as_secs_f64()
+    );
+    for (label, value) in rows {
+        eprintln!("{}{label:>10}{} {value}", style.bold, style.reset);
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use tracing::debug;
+
+use crate::build::{
+    Metadata, MetadataContainer, SiteTemplateContext, TemplateRenderer, config::SiteConfig,
+    virtual_page::VirtualPages,
+};
+
+/// A single term of a taxonomy, along with every page classified under it.
+#[derive(Debug, Serialize)]
+pub struct Term<'a> {
+    pub name: &'a str,
+    pub slug: String,
+    pub url_path: String,
+    pub pages: Vec<&'a Metadata>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Taxonomy<'a> {
+    pub name: &'a str,
+    pub url_path: String,
+    pub terms: Vec<Term<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct TaxonomyListContext<'a> {
+    taxonomy: &'a Taxonomy<'a>,
+    release: bool,
+    #[serde(flatten)]
+    site: &'a SiteTemplateContext<'a>,
+}
+
+#[derive(Debug, Serialize)]
+struct TermContext<'a> {
+    taxonomy: &'a Taxonomy<'a>,
+    term: &'a Term<'a>,
+    release: bool,
+    #[serde(flatten)]
+    site: &'a SiteTemplateContext<'a>,
+}
+
+/// The output directory for a term, relative to the output root.
+pub fn term_dir(taxonomy: &str, term: &str) -> PathBuf {
+    Path::new(taxonomy).join(slug::slugify(term))
+}
+
+/// Group every page by the terms it lists for each configured taxonomy.
+pub fn collect_taxonomies<'a>(
+    config: &'a SiteConfig,
+    metadata: &'a MetadataContainer,
+) -> BTreeMap<String, Taxonomy<'a>> {
+    let mut taxonomies = BTreeMap::new();
+    for taxonomy_config in &config.taxonomies {
+        let name = taxonomy_config.name.as_str();
+
+        // Terms are keyed by their slug, so that differences in case or punctuation
+        // between pages still end up in the same term
+        let mut terms: BTreeMap<String, Term> = BTreeMap::new();
+        for md in metadata.0.values() {
+            for term_name in md.frontmatter_str_list(name) {
+                let slug = slug::slugify(term_name);
+                terms
+                    .entry(slug.clone())
+                    .or_insert_with(|| Term {
+                        name: term_name,
+                        url_path: format!("/{}/", term_dir(name, term_name).display()),
+                        slug,
+                        pages: vec![],
+                    })
+                    .pages
+                    .push(md);
+            }
+        }
+
+        debug!(
+            taxonomy = name,
+            num_terms = terms.len(),
+            "Collected taxonomy"
+        );
+
+        taxonomies.insert(
+            name.to_owned(),
+            Taxonomy {
+                name,
+                url_path: format!("/{name}/"),
+                terms: terms.into_values().collect(),
+            },
+        );
+    }
+
+    taxonomies
+}
+
+/// Register a page listing every term of each taxonomy, and a page listing
+/// every page for each term.
+///
+/// The list page uses the `<taxonomy>/list.html` template, falling back to
+/// `taxonomy_list.html`, and term pages use `<taxonomy>/term.html` falling back
+/// to `taxonomy_term.html`. If no template is found, the pages are skipped.
+pub fn add_taxonomy_pages<'a>(renderer: &'a TemplateRenderer<'a>, pages: &mut VirtualPages<'a>) {
+    let release = renderer.args.release;
+    for taxonomy in renderer.site.taxonomies.values() {
+        let list_template = Path::new(taxonomy.name).join("list.html");
+        if let Some(template) = renderer
+            .templates
+            .find_named_template(&[&list_template, Path::new("taxonomy_list.html")])
+        {
+            let context = TaxonomyListContext {
+                taxonomy,
+                release,
+                site: renderer.site,
+            };
+            pages.add_template(
+                renderer,
+                template,
+                context,
+                Path::new(taxonomy.name).join("index.html"),
+                format!("list page for [{}]", taxonomy.name),
+            );
+        } else {
+            debug!(taxonomy = taxonomy.name, "No list template found, skipping");
+        }
+
+        let term_template = Path::new(taxonomy.name).join("term.html");
+        let Some(template) = renderer
+            .templates
+            .find_named_template(&[&term_template, Path::new("taxonomy_term.html")])
+        else {
+            debug!(taxonomy = taxonomy.name, "No term template found, skipping");
+            continue;
+        };
+
+        for term in &taxonomy.terms {
+            let context = TermContext {
+                taxonomy,
+                term,
+                release,
+                site: renderer.site,
+            };
+            pages.add_template(
+                renderer,
+                template,
+                context,
+                term_dir(taxonomy.name, term.name).join("index.html"),
+                format!("term page for [{}] in [{}]", term.name, taxonomy.name),
+            );
+        }
+    }
+}
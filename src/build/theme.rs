@@ -0,0 +1,76 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::bail;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+use crate::build::{config::ThemeSource, errors::FailureClass, site_cache::site_cache_dir};
+
+/// The theme directory used when no theme is configured, if it exists
+const DEFAULT_THEME_DIR: &str = "theme";
+
+/// The directory in the cache of a site that theme repositories are cloned
+/// into.
+fn clone_root(site_cache: &Path) -> PathBuf {
+    site_cache.join("themes")
+}
+
+/// Find the directory of the theme, cloning it first if it is a git
+/// repository that hasn't been cloned yet.
+pub fn theme_dir(
+    theme: Option<&ThemeSource>,
+    input_path: &Path,
+) -> anyhow::Result<Option<PathBuf>> {
+    let dir = match theme {
+        None => {
+            let dir = input_path.join(DEFAULT_THEME_DIR);
+            return Ok(dir.is_dir().then_some(dir));
+        },
+        Some(ThemeSource::Path(path)) => input_path.join(path),
+        Some(ThemeSource::Git { git, rev }) => {
+            clone_theme(&site_cache_dir(input_path), git, rev.as_deref())?
+        },
+    };
+
+    if !dir.is_dir() {
+        bail!("theme directory [{}] does not exist", dir.display());
+    }
+    debug!(theme = %dir.display(), "Using theme");
+    Ok(Some(dir))
+}
+
+/// Clone a theme repository into the cache of the site, keyed by its URL and
+/// revision. An existing clone is reused as is, so a different `rev` is needed
+/// to pick up new commits of a branch.
+fn clone_theme(site_cache: &Path, url: &str, rev: Option<&str>) -> anyhow::Result<PathBuf> {
+    let key = format!("{url}#{}", rev.unwrap_or_default());
+    let hash = Sha256::digest(key.as_bytes());
+    let dir = clone_root(site_cache).join(format!("{:x}", hash).get(..16).unwrap_or_default());
+    if dir.is_dir() {
+        debug!(url, theme = %dir.display(), "Reusing cloned theme");
+        return Ok(dir);
+    }
+
+    info!(url, "Cloning theme");
+    let mut command = Command::new("git");
+    command.args(["clone", "--quiet", "--depth", "1"]);
+    if let Some(rev) = rev {
+        command.args(["--branch", rev]);
+    }
+    let output = command
+        .arg(url)
+        .arg(&dir)
+        .output()
+        .map_err(|err| FailureClass::Tool.wrap("failed to execute 'git'", err))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(
+            FailureClass::Tool.error(format!("failed to clone theme [{url}]: {}", stderr.trim()))
+        );
+    }
+
+    Ok(dir)
+}
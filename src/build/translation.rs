@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use tracing::warn;
+
+use crate::build::{Metadata, MetadataContainer, config::SiteConfig};
+
+/// The frontmatter field that groups the translations of a page, which every
+/// translation sets to the same value
+const TRANSLATION_KEY_FIELD: &str = "translation_key";
+
+/// The frontmatter field with the language of a page, like `en` or `fr-CA`
+const LANGUAGE_FIELD: &str = "language";
+
+/// One of the translations of a page.
+#[derive(Debug, Clone, Serialize)]
+pub struct Translation {
+    /// The language of the translation, for the `hreflang` attribute
+    pub language: String,
+    /// Absolute URL of the translation when the site has a `base_url`, as
+    /// `hreflang` links require, or its URL path otherwise
+    pub url: String,
+    pub title: Option<String>,
+}
+
+/// The language of a page, from its frontmatter or the site config.
+pub fn page_language<'a>(config: &'a SiteConfig, metadata: &'a Metadata) -> Option<&'a str> {
+    metadata
+        .frontmatter_field(LANGUAGE_FIELD)
+        .and_then(tera::Value::as_str)
+        .or(config.language.as_deref())
+}
+
+fn translation_key(metadata: &Metadata) -> Option<&str> {
+    metadata
+        .frontmatter_field(TRANSLATION_KEY_FIELD)
+        .and_then(tera::Value::as_str)
+}
+
+/// Every set of translations on the site, keyed by their translation key.
+///
+/// Only sets with more than one page are kept, since a page on its own has no
+/// alternates. Pages in a set without a language are left out with a warning.
+pub fn collect_translations(
+    config: &SiteConfig,
+    metadata: &MetadataContainer,
+) -> BTreeMap<String, Vec<Translation>> {
+    let base_url = config
+        .base_url
+        .as_deref()
+        .map(|base_url| base_url.trim_end_matches('/'));
+
+    let mut sets: BTreeMap<String, Vec<Translation>> = BTreeMap::new();
+    for (slug, md) in &metadata.0 {
+        let Some(key) = translation_key(md) else {
+            continue;
+        };
+        let Some(language) = page_language(config, md) else {
+            warn!(%slug, "Page has a translation key but no language, leaving it out of its translations");
+            continue;
+        };
+        let url_path = md.url_path.to_string_lossy();
+        sets.entry(key.to_owned()).or_default().push(Translation {
+            language: language.to_owned(),
+            url: format!("{}{url_path}", base_url.unwrap_or_default()),
+            title: md.title.clone(),
+        });
+    }
+    sets.retain(|_, translations| translations.len() > 1);
+
+    sets
+}
+
+/// The translations of a page, including the page itself, or nothing if it
+/// has none.
+pub fn page_translations<'a>(
+    sets: &'a BTreeMap<String, Vec<Translation>>,
+    metadata: &Metadata,
+) -> &'a [Translation] {
+    translation_key(metadata)
+        .and_then(|key| sets.get(key))
+        .map_or(&[], Vec::as_slice)
+}
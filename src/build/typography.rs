@@ -0,0 +1,238 @@
+use std::{borrow::Cow, collections::BTreeSet, sync::LazyLock};
+
+use regex::Regex;
+use tracing::debug;
+
+use crate::build::config::TypographyConfig;
+
+/// Matches a tag, so that the text between tags can be found
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+/// Matches runs of two or more capital letters or digits, starting with a
+/// capital
+static CAPS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b[A-Z][A-Z0-9]+\b").unwrap());
+
+/// Elements whose text is never changed
+const VERBATIM_ELEMENTS: &[&str] = &["code", "kbd", "pre", "samp", "script", "style"];
+
+/// Opening punctuation that is hung outside of the text block
+const HANGING_PUNCTUATION: &[&str] = &["“", "‘", "«", "\"", "&quot;", "'", "&#39;"];
+
+pub enum Token<'a> {
+    Tag(&'a str),
+    Text(Cow<'a, str>),
+}
+
+pub fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut last = 0;
+    for tag in TAG.find_iter(html) {
+        if tag.start() > last {
+            tokens.push(Token::Text(Cow::Borrowed(&html[last..tag.start()])));
+        }
+        tokens.push(Token::Tag(tag.as_str()));
+        last = tag.end();
+    }
+    if last < html.len() {
+        tokens.push(Token::Text(Cow::Borrowed(&html[last..])));
+    }
+    tokens
+}
+
+pub fn tag_name(tag: &str) -> (&str, bool) {
+    let tag = tag.trim_start_matches('<');
+    let (tag, is_closing) = match tag.strip_prefix('/') {
+        Some(tag) => (tag, true),
+        None => (tag, false),
+    };
+    let end = tag
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .unwrap_or(tag.len());
+    (&tag[..end], is_closing)
+}
+
+/// Mark every text token inside a verbatim element, so that it is skipped.
+fn verbatim_flags(tokens: &[Token]) -> Vec<bool> {
+    let mut depth = 0usize;
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Tag(tag) => {
+                let (name, is_closing) = tag_name(tag);
+                if VERBATIM_ELEMENTS.contains(&name.to_ascii_lowercase().as_str()) {
+                    if is_closing {
+                        depth = depth.saturating_sub(1);
+                    } else {
+                        depth += 1;
+                    }
+                }
+                true
+            },
+            Token::Text(_) => depth > 0,
+        })
+        .collect()
+}
+
+/// Replace the last space between words with a non-breaking space, so that the
+/// last word never wraps onto a line of its own.
+fn prevent_widow(tokens: &mut [Token], verbatim: &[bool]) {
+    let has_text_before = |tokens: &[Token], idx: usize| {
+        tokens[..idx].iter().any(|token| match token {
+            Token::Text(text) => !text.trim().is_empty(),
+            Token::Tag(_) => false,
+        })
+    };
+
+    let mut seen_word = false;
+    for idx in (0..tokens.len()).rev() {
+        let Token::Text(text) = &tokens[idx] else {
+            continue;
+        };
+        if verbatim[idx] {
+            seen_word |= !text.trim().is_empty();
+            continue;
+        }
+
+        for (pos, c) in text.char_indices().rev() {
+            if !c.is_whitespace() {
+                seen_word = true;
+                continue;
+            }
+            if !seen_word {
+                continue;
+            }
+
+            // Found the space before the last word, expand it to the whole run
+            let run_end = pos + c.len_utf8();
+            let run_start = text[..pos].trim_end_matches(char::is_whitespace).len();
+            if run_start == 0 && !has_text_before(tokens, idx) {
+                return;
+            }
+            let replaced = format!("{}&nbsp;{}", &text[..run_start], &text[run_end..]);
+            tokens[idx] = Token::Text(Cow::Owned(replaced));
+            return;
+        }
+    }
+}
+
+/// Wrap opening punctuation at the very start of the element in a span, so
+/// that it can be hung into the margin.
+fn hang_punctuation(tokens: &mut [Token], verbatim: &[bool]) {
+    for (token, verbatim) in tokens.iter_mut().zip(verbatim) {
+        let Token::Text(text) = token else {
+            continue;
+        };
+        if *verbatim {
+            return;
+        }
+        let trimmed = text.trim_start();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        if let Some(punctuation) = HANGING_PUNCTUATION
+            .iter()
+            .find(|punctuation| trimmed.starts_with(**punctuation))
+        {
+            let leading = &text[..(text.len() - trimmed.len())];
+            let rest = &trimmed[punctuation.len()..];
+            *text = Cow::Owned(format!(
+                r#"{leading}<span class="hang-punct">{punctuation}</span>{rest}"#
+            ));
+        }
+        return;
+    }
+}
+
+fn wrap_text(tokens: &mut [Token], verbatim: &[bool], wrap: impl Fn(&str) -> String) {
+    for (token, verbatim) in tokens.iter_mut().zip(verbatim) {
+        if let (Token::Text(text), false) = (token, verbatim) {
+            *text = Cow::Owned(wrap(text));
+        }
+    }
+}
+
+/// The typographic fixes enabled for a single element.
+#[derive(Default)]
+struct Fixes {
+    widows: bool,
+    ampersands: bool,
+    caps: bool,
+    hanging_punctuation: bool,
+}
+
+fn apply_fixes(inner: &str, fixes: &Fixes) -> String {
+    let mut tokens = tokenize(inner);
+    let verbatim = verbatim_flags(&tokens);
+
+    if fixes.widows {
+        prevent_widow(&mut tokens, &verbatim);
+    }
+    if fixes.caps {
+        wrap_text(&mut tokens, &verbatim, |text| {
+            CAPS.replace_all(text, r#"<span class="caps">$0</span>"#)
+                .into_owned()
+        });
+    }
+    if fixes.ampersands {
+        wrap_text(&mut tokens, &verbatim, |text| {
+            text.replace("&amp;", r#"<span class="amp">&amp;</span>"#)
+        });
+    }
+    if fixes.hanging_punctuation {
+        hang_punctuation(&mut tokens, &verbatim);
+    }
+
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Tag(tag) => *tag,
+            Token::Text(text) => text.as_ref(),
+        })
+        .collect()
+}
+
+/// Apply the typographic fixes enabled in the config to the elements they are
+/// enabled for.
+///
+/// Elements are matched without nesting, so an element type should only be
+/// configured if it never contains itself, which holds for headings and
+/// paragraphs.
+pub fn apply(config: &TypographyConfig, html: &str) -> String {
+    let elements = config
+        .widows
+        .iter()
+        .chain(&config.ampersands)
+        .chain(&config.caps)
+        .chain(&config.hanging_punctuation)
+        .collect::<BTreeSet<_>>();
+
+    let mut html = Cow::Borrowed(html);
+    for element in elements {
+        let fixes = Fixes {
+            widows: config.widows.contains(element),
+            ampersands: config.ampersands.contains(element),
+            caps: config.caps.contains(element),
+            hanging_punctuation: config.hanging_punctuation.contains(element),
+        };
+
+        let element = regex::escape(element);
+        let pattern = Regex::new(&format!(
+            r"(?s)(<{element}(?:\s[^>]*)?>)(.*?)(</{element}>)"
+        ))
+        .expect("escaped element name is a valid pattern");
+        let replaced = pattern.replace_all(&html, |captures: &regex::Captures| {
+            format!(
+                "{}{}{}",
+                &captures[1],
+                apply_fixes(&captures[2], &fixes),
+                &captures[3]
+            )
+        });
+        html = Cow::Owned(replaced.into_owned());
+    }
+
+    debug!("Applied typographic fixes");
+
+    html.into_owned()
+}
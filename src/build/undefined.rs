@@ -0,0 +1,100 @@
+use std::{error::Error, sync::LazyLock};
+
+use regex::Regex;
+use serde_json::Value;
+
+/// Matches Tera's error for a variable that isn't in the context, capturing the
+/// dotted path of the variable
+static UNDEFINED_VARIABLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^Variable `([A-Za-z_]\w*(?:\.[A-Za-z_]\w*)*)` not found in context").unwrap()
+});
+
+/// Matches the first name quoted in an error message, like a variable, filter,
+/// or function name, which Tera quotes with either backticks or single quotes
+static QUOTED_NAME: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[`']([^`'\n]+)[`']").unwrap());
+
+/// Matches the template that Tera says a render error happened in, which is
+/// a parent or included template rather than the one being rendered
+static FAILING_TEMPLATE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:error happened in|while rendering) '([^'\n]+)'").unwrap());
+
+/// The maximum number of missing variables filled in for a single render,
+/// which bounds the number of times a template is re-rendered
+pub const MAX_FILLED_VARIABLES: usize = 64;
+
+/// Find the variable that caused a render error because it is not in the
+/// context.
+pub fn undefined_variable(error: &tera::Error) -> Option<String> {
+    let mut current: Option<&dyn Error> = Some(error);
+    while let Some(error) = current {
+        if let Some(captures) = UNDEFINED_VARIABLE.captures(&error.to_string()) {
+            return Some(captures[1].to_owned());
+        }
+        current = error.source();
+    }
+
+    None
+}
+
+/// Find the name that a render error is about, like a missing variable or an
+/// unknown filter, from the most specific error in the chain that quotes one.
+pub fn error_subject(error: &tera::Error) -> Option<String> {
+    let mut subject = None;
+    let mut current: Option<&dyn Error> = Some(error);
+    while let Some(error) = current {
+        if let Some(captures) = QUOTED_NAME.captures(&error.to_string()) {
+            subject = Some(captures[1].to_owned());
+        }
+        current = error.source();
+    }
+
+    subject
+}
+
+/// Find the templates that a render error says it happened in, the most
+/// specific first. Inherited blocks are reported as happening in the child
+/// template, so the parent that Tera names separately comes before it.
+pub fn failing_templates(error: &tera::Error) -> Vec<String> {
+    let mut templates = vec![];
+    let mut current: Option<&dyn Error> = Some(error);
+    while let Some(error) = current {
+        let message = error.to_string();
+        for captures in FAILING_TEMPLATE.captures_iter(&message) {
+            if !templates.contains(&captures[1].to_owned()) {
+                templates.push(captures[1].to_owned());
+            }
+        }
+        current = error.source();
+    }
+
+    templates
+}
+
+/// Insert the placeholder at the dotted path of a missing variable, creating
+/// any missing objects along the way.
+///
+/// Returns `false` if part of the path already exists and isn't an object, so
+/// the variable can't be filled in.
+pub fn fill_variable(context: &mut Value, path: &str, placeholder: &str) -> bool {
+    let mut current = context;
+    let mut parts = path.split('.').peekable();
+    while let Some(part) = parts.next() {
+        let Value::Object(map) = current else {
+            return false;
+        };
+
+        if parts.peek().is_none() {
+            if map.contains_key(part) {
+                return false;
+            }
+            map.insert(part.to_owned(), Value::String(placeholder.to_owned()));
+            return true;
+        }
+
+        current = map
+            .entry(part)
+            .or_insert_with(|| Value::Object(Default::default()));
+    }
+
+    false
+}
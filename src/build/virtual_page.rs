@@ -0,0 +1,102 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use serde::Serialize;
+use tracing::debug;
+
+use crate::build::TemplateRenderer;
+
+/// A page of the output that isn't backed by a content file, like a taxonomy
+/// page or a feed.
+struct VirtualPage<'a> {
+    /// Path of the page relative to the output root
+    path: PathBuf,
+    /// What the page is, used in errors, like `list page for [tags]`
+    description: String,
+    render: Box<dyn FnOnce() -> anyhow::Result<String> + 'a>,
+}
+
+/// Every virtual page registered during a build, which are rendered and
+/// written together once all content pages are written.
+#[derive(Default)]
+pub struct VirtualPages<'a> {
+    pages: Vec<VirtualPage<'a>>,
+}
+
+impl<'a> VirtualPages<'a> {
+    /// Register a page whose content is produced by the callback.
+    pub fn add(
+        &mut self,
+        path: impl Into<PathBuf>,
+        description: impl Into<String>,
+        render: impl FnOnce() -> anyhow::Result<String> + 'a,
+    ) {
+        self.pages.push(VirtualPage {
+            path: path.into(),
+            description: description.into(),
+            render: Box::new(render),
+        });
+    }
+
+    /// Register a page rendered by a template, with a path relative to the
+    /// template directory.
+    pub fn add_template(
+        &mut self,
+        renderer: &'a TemplateRenderer<'a>,
+        template: impl Into<PathBuf>,
+        context: impl Serialize + 'a,
+        path: impl Into<PathBuf>,
+        description: impl Into<String>,
+    ) {
+        let template = template.into();
+        let path = path.into();
+        let page = path.clone();
+        self.add(path, description, move || {
+            renderer.render(&template, &page, &context)
+        });
+    }
+
+    /// The number of pages registered so far.
+    pub fn num_pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Render every registered page and write it under the output root.
+    ///
+    /// Fails if two pages are registered at the same path.
+    #[tracing::instrument(skip_all)]
+    pub fn write_all(self, output_root: &Path) -> anyhow::Result<()> {
+        let mut seen = BTreeSet::new();
+        for page in &self.pages {
+            if !seen.insert(&page.path) {
+                bail!(
+                    "more than one generated page is written to [{}], including the {}",
+                    page.path.display(),
+                    page.description
+                );
+            }
+        }
+
+        for page in self.pages {
+            let content =
+                (page.render)().context(format!("failed to render {}", page.description))?;
+
+            let output_path = output_root.join(&page.path);
+            if let Some(parent) = output_path.parent() {
+                fs::create_dir_all(parent)
+                    .context("failed to create parent directory for output")?;
+            }
+            fs::write(&output_path, content).context(format!(
+                "failed to write generated page [{}]",
+                output_path.display()
+            ))?;
+            debug!(output_path = %output_path.display(), "Written generated page");
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use std::{cell::RefCell, collections::BTreeMap, fmt};
+
+use serde::Serialize;
+use tracing::{
+    Event, Level, Subscriber,
+    field::{Field, Visit},
+    info,
+};
+use tracing_subscriber::{Layer, layer::Context};
+
+use crate::build::errors::FailureClass;
+
+/// The tracing target of the warnings about broken links, which fail a build
+/// with their own exit code when warnings are denied.
+pub const BROKEN_LINK_TARGET: &str = "www::broken_link";
+
+/// A warning that was logged during a build.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Warning {
+    pub message: String,
+    /// The other fields of the log event, like the page the warning is about
+    pub fields: BTreeMap<String, String>,
+    /// Whether the warning is about a broken link
+    #[serde(skip)]
+    pub broken_link: bool,
+}
+
+impl Visit for Warning {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_owned();
+        } else {
+            self.fields
+                .insert(field.name().to_owned(), value.to_owned());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record_str(field, &format!("{value:?}"));
+    }
+}
+
+thread_local! {
+    /// The warnings logged on this thread since collection started, or `None`
+    /// when no warnings are being collected. A build logs from the thread it
+    /// runs on, so builds on other threads don't see each other's warnings.
+    static COLLECTED: RefCell<Option<Vec<Warning>>> = const { RefCell::new(None) };
+}
+
+/// A tracing layer that records every warning logged on a thread while
+/// [`collect`] is running on it.
+pub struct WarningLayer;
+
+impl<S: Subscriber> Layer<S> for WarningLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if *event.metadata().level() != Level::WARN {
+            return;
+        }
+
+        COLLECTED.with_borrow_mut(|collected| {
+            if let Some(warnings) = collected {
+                let mut warning = Warning {
+                    broken_link: event.metadata().target() == BROKEN_LINK_TARGET,
+                    ..Warning::default()
+                };
+                event.record(&mut warning);
+                warnings.push(warning);
+            }
+        });
+    }
+}
+
+/// Log a summary of the warnings of a build, with the number of times each
+/// message was logged, and fail if warnings are denied. Broken links fail with
+/// their own class, so that scripts can tell them from other warnings.
+pub fn summarize(warnings: &[Warning], deny: bool) -> anyhow::Result<()> {
+    if warnings.is_empty() {
+        return Ok(());
+    }
+
+    // Diagnostics span several lines, so they are grouped by their first
+    let mut counts = BTreeMap::<&str, usize>::new();
+    for warning in warnings {
+        let message = warning.message.lines().next().unwrap_or_default();
+        *counts.entry(message).or_default() += 1;
+    }
+    for (message, count) in counts {
+        info!(count, "Warning: {message}");
+    }
+
+    if deny {
+        let class = if warnings.iter().any(|warning| warning.broken_link) {
+            FailureClass::BrokenLinks
+        } else {
+            FailureClass::Check
+        };
+        return Err(class.error(format!(
+            "build produced {} warnings, which are denied by `--deny-warnings`",
+            warnings.len()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Run `f` and return the warnings that were logged on this thread while it
+/// ran, along with its result. Collections can be nested, and the warnings
+/// only go to the innermost one.
+pub fn collect<R>(f: impl FnOnce() -> R) -> (R, Vec<Warning>) {
+    let outer = COLLECTED.replace(Some(vec![]));
+    let result = f();
+    let warnings = COLLECTED.replace(outer).unwrap_or_default();
+    (result, warnings)
+}
@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, bail};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::build::{
+    config::{SiteConfig, WebringConfig},
+    remote::{self, Remote},
+};
+
+/// A member of a webring, as listed in its manifest.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+enum ManifestMember {
+    Url(String),
+    Site {
+        url: String,
+        #[serde(default)]
+        name: Option<String>,
+    },
+}
+
+/// The manifest of a webring, either a list of members or an object with the
+/// list under `members` or `sites`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Manifest {
+    Members(Vec<ManifestMember>),
+    Object {
+        #[serde(alias = "sites")]
+        members: Vec<ManifestMember>,
+    },
+}
+
+/// A site in a webring, passed to templates.
+#[derive(Debug, Clone, Serialize)]
+pub struct Neighbor {
+    pub url: String,
+    pub name: Option<String>,
+}
+
+impl From<&ManifestMember> for Neighbor {
+    fn from(member: &ManifestMember) -> Self {
+        match member {
+            ManifestMember::Url(url) => Neighbor {
+                url: url.clone(),
+                name: None,
+            },
+            ManifestMember::Site { url, name } => Neighbor {
+                url: url.clone(),
+                name: name.clone(),
+            },
+        }
+    }
+}
+
+/// The neighbors of the site in a webring, available to templates as
+/// `webrings.<name>`.
+///
+/// The neighbors are missing when the manifest couldn't be fetched or doesn't
+/// list the site, so that templates can still link to the ring itself.
+#[derive(Debug, Default, Serialize)]
+pub struct Webring {
+    /// URL of the page of the ring, from the config
+    pub home: Option<String>,
+    pub prev: Option<Neighbor>,
+    pub next: Option<Neighbor>,
+    /// A member other than the site, which changes once a day, so that
+    /// building twice on the same day gives the same output
+    pub random: Option<Neighbor>,
+    pub num_members: usize,
+}
+
+/// Compare URLs loosely, ignoring the scheme, case, and a trailing slash.
+fn normalize_url(url: &str) -> String {
+    let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+    url.trim_end_matches('/').to_ascii_lowercase()
+}
+
+/// Find the neighbors of the member URL in a webring.
+fn find_neighbors(
+    name: &str,
+    members: &[ManifestMember],
+    member_url: &str,
+    home: Option<String>,
+) -> anyhow::Result<Webring> {
+    let member_url = normalize_url(member_url);
+    let Some(position) = members
+        .iter()
+        .position(|member| normalize_url(&Neighbor::from(member).url) == member_url)
+    else {
+        bail!("the site is not listed in webring [{name}]");
+    };
+
+    let num_members = members.len();
+    let neighbor = |offset: usize| {
+        (num_members > 1).then(|| Neighbor::from(&members[(position + offset) % num_members]))
+    };
+    let random = if num_members > 1 {
+        let seed = Sha256::new()
+            .chain_update(name)
+            .chain_update([0])
+            .chain_update(Utc::now().date_naive().to_string())
+            .finalize();
+        let seed = u64::from_le_bytes(seed[..8].try_into().expect("hash is longer than a u64"));
+        // Pick from every member but the site itself
+        let offset = 1 + (seed % (num_members as u64 - 1)) as usize;
+        neighbor(offset)
+    } else {
+        None
+    };
+
+    Ok(Webring {
+        home,
+        prev: neighbor(num_members - 1),
+        next: neighbor(1),
+        random,
+        num_members,
+    })
+}
+
+fn load_webring(
+    name: &str,
+    config: &WebringConfig,
+    member_url: &str,
+    remote: &Remote,
+) -> anyhow::Result<Webring> {
+    let body = remote::fetch(remote, &config.manifest)?;
+    let members = match serde_json::from_str(&body)
+        .context(format!("invalid manifest for webring [{name}]"))?
+    {
+        Manifest::Members(members) | Manifest::Object { members } => members,
+    };
+    find_neighbors(name, &members, member_url, config.home.clone())
+}
+
+/// Fetch the manifest of every configured webring and find the neighbors of
+/// the site in each.
+///
+/// A ring that can't be loaded is only warned about, so that a build doesn't
+/// fail because a ring is down.
+pub fn collect_webrings(config: &SiteConfig, remote: &Remote) -> BTreeMap<String, Webring> {
+    let mut webrings = BTreeMap::new();
+    for (name, webring) in &config.webrings {
+        let Some(member_url) = webring.url.as_deref().or(config.base_url.as_deref()) else {
+            warn!(
+                webring = name,
+                "Webring has no URL for the site, set `url` or `base_url`"
+            );
+            webrings.insert(name.clone(), Webring::default());
+            continue;
+        };
+
+        let ring = match load_webring(name, webring, member_url, remote) {
+            Ok(ring) => ring,
+            Err(err) => {
+                warn!(
+                    webring = name,
+                    err = format!("{err:#}"),
+                    "Failed to load webring"
+                );
+                Webring {
+                    home: webring.home.clone(),
+                    ..Webring::default()
+                }
+            },
+        };
+        debug!(
+            webring = name,
+            num_members = ring.num_members,
+            "Loaded webring"
+        );
+        webrings.insert(name.clone(), ring);
+    }
+    webrings
+}
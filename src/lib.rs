@@ -0,0 +1,18 @@
+//! A blazing fast static site generator.
+//!
+//! The `www` binary is a thin command line interface over this library. Other
+//! programs can build a site with [`SiteBuilder`], or run the commands of the
+//! binary through their argument types.
+
+mod build;
+
+pub use build::{
+    BenchCmd, BuildCmd, BuildStats, CheckCmd, CleanCmd, CountingAllocator, DeployCmd, DiffCmd,
+    ExportCmd, ImportCmd, ManCmd, PROGRESS_TARGET, PageWritten, ServeCmd, SiteBuild, SiteBuilder,
+    TimingLayer, USAGE_EXIT_CODE, Warning, WarningLayer, bench, build, check, clean, deploy, diff,
+    exit_code, exit_statuses, export, import, man, serve,
+};
+
+/// The cipher of protected pages, exposed for its known-answer tests
+#[doc(hidden)]
+pub use build::protect;
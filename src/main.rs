@@ -1,44 +1,180 @@
+use std::{
+    env,
+    fs::File,
+    path::{Path, PathBuf},
+    process::{self, ExitCode},
+    sync::Mutex,
+};
+
 use anyhow::Context;
-use argh::FromArgs;
+use argh::{ArgsInfo, FromArgs};
 use tracing::debug;
+use tracing_subscriber::{
+    Layer,
+    filter::{LevelFilter, Targets, filter_fn},
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+};
 
-use crate::build::BuildCmd;
+use www::{
+    BenchCmd, BuildCmd, CheckCmd, CleanCmd, CountingAllocator, DeployCmd, DiffCmd, ExportCmd,
+    ImportCmd, ManCmd, PROGRESS_TARGET, ServeCmd, TimingLayer, USAGE_EXIT_CODE, WarningLayer,
+};
 
-mod build;
+/// Allocations are counted for the stats of `www bench`
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
 
 /// A blazing fast static site generator.
-#[derive(FromArgs, Debug)]
+#[derive(FromArgs, ArgsInfo, Debug)]
+#[argh(
+    error_code(1, "An unclassified error."),
+    error_code(2, "The arguments are invalid."),
+    error_code(3, "The site config is invalid."),
+    error_code(4, "A content file failed to render."),
+    error_code(5, "A template failed to parse or render."),
+    error_code(6, "A check of the output failed, like denied warnings."),
+    error_code(7, "An external program failed."),
+    error_code(8, "A link points at a missing fragment, and warnings are denied.")
+)]
 struct Cli {
-    /// be verbose
+    /// be verbose, more so when repeated: `-v` shows the progress of a build,
+    /// `-vv` debug logs and `-vvv` trace logs, including template contexts and
+    /// djot events
     #[argh(switch, short = 'v')]
-    verbose: bool,
+    verbose: u8,
+
+    /// also write debug logs to this file, whatever the verbosity
+    #[argh(option)]
+    log_file: Option<PathBuf>,
 
     #[argh(subcommand)]
     subcommand: SubCommand,
 }
 
-#[derive(FromArgs, Debug)]
+#[derive(FromArgs, ArgsInfo, Debug)]
 #[argh(subcommand)]
 enum SubCommand {
     Build(BuildCmd),
+    Export(ExportCmd),
+    Deploy(DeployCmd),
+    Serve(ServeCmd),
+    Check(CheckCmd),
+    Clean(CleanCmd),
+    Diff(DiffCmd),
+    Import(ImportCmd),
+    Man(ManCmd),
+    Bench(BenchCmd),
+}
+
+/// Parse the arguments of the process like `argh::from_env`, after splitting
+/// up repeated short switches like `-vv`, which argh doesn't support.
+fn parse_cli() -> Cli {
+    let args = env::args_os()
+        .map(|arg| arg.into_string())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|arg| {
+            eprintln!("Invalid utf8: {}", arg.to_string_lossy());
+            process::exit(1)
+        });
+    let Some((program, args)) = args.split_first() else {
+        eprintln!("No program name, argv is empty");
+        process::exit(1)
+    };
+
+    let args = args
+        .iter()
+        .flat_map(|arg| match arg.strip_prefix('-') {
+            Some(switches) if switches.len() > 1 && switches.chars().all(|c| c == 'v') => {
+                vec!["-v"; switches.len()]
+            },
+            _ => vec![arg.as_str()],
+        })
+        .collect::<Vec<_>>();
+    let command = Path::new(program)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(program);
+    Cli::from_args(&[command], &args).unwrap_or_else(|early_exit| {
+        process::exit(match early_exit.status {
+            Ok(()) => {
+                println!("{}", early_exit.output);
+                0
+            },
+            Err(()) => {
+                eprintln!(
+                    "{}\nRun {command} --help for more information.",
+                    early_exit.output
+                );
+                USAGE_EXIT_CODE.into()
+            },
+        })
+    })
 }
 
-fn main() -> anyhow::Result<()> {
-    let cli: Cli = argh::from_env();
+fn main() -> ExitCode {
+    let cli = parse_cli();
+
+    // Only the most verbose level shows logs of dependencies below info
+    let log_filter = match cli.verbose {
+        0 => Targets::new()
+            .with_default(LevelFilter::INFO)
+            .with_target(PROGRESS_TARGET, LevelFilter::OFF),
+        1 => Targets::new().with_default(LevelFilter::INFO),
+        2 => Targets::new()
+            .with_default(LevelFilter::INFO)
+            .with_target("www", LevelFilter::DEBUG),
+        _ => Targets::new().with_default(LevelFilter::TRACE),
+    };
 
-    let log_level = if cli.verbose {
-        tracing::Level::DEBUG
-    } else {
-        tracing::Level::INFO
+    let log_file = match cli.log_file.as_ref().map(File::create).transpose() {
+        Ok(log_file) => log_file,
+        Err(err) => {
+            eprintln!("Error: failed to create log file: {err}");
+            return ExitCode::FAILURE;
+        },
     };
 
-    tracing_subscriber::fmt().with_max_level(log_level).init();
+    // Logs go to stderr, so that stdout is left for machine-readable output
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_filter(log_filter),
+        )
+        .with(log_file.map(|log_file| {
+            tracing_subscriber::fmt::layer()
+                .with_writer(Mutex::new(log_file))
+                .with_ansi(false)
+                .with_filter(LevelFilter::DEBUG)
+        }))
+        .with(WarningLayer.with_filter(LevelFilter::WARN))
+        .with(TimingLayer.with_filter(filter_fn(TimingLayer::is_timed)))
+        .init();
 
     debug!(?cli, "Parsed CLI arguments");
 
     let context = format!("failed to execute subcommand '{:?}'", cli.subcommand);
-    match cli.subcommand {
-        SubCommand::Build(cmd) => build::build(cmd),
+    let result = match cli.subcommand {
+        SubCommand::Build(cmd) => www::build(cmd),
+        SubCommand::Export(cmd) => www::export(cmd),
+        SubCommand::Deploy(cmd) => www::deploy(cmd),
+        SubCommand::Serve(cmd) => www::serve(cmd),
+        SubCommand::Check(cmd) => www::check(cmd),
+        SubCommand::Clean(cmd) => www::clean(cmd),
+        SubCommand::Diff(cmd) => www::diff(cmd),
+        SubCommand::Import(cmd) => www::import(cmd),
+        SubCommand::Bench(cmd) => www::bench(cmd),
+        SubCommand::Man(cmd) => www::man(cmd, "www", &Cli::get_args_info()),
+    }
+    .context(context);
+
+    // The exit code tells scripts what kind of problem stopped the command
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            ExitCode::from(www::exit_code(&err))
+        },
     }
-    .context(context)
 }
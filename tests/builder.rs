@@ -0,0 +1,112 @@
+mod common;
+
+use std::{sync::Once, thread};
+
+use common::TestSite;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use www::WarningLayer;
+
+/// Install the layer that collects warnings, once for every test of the binary
+fn collect_warnings() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        tracing_subscriber::registry().with(WarningLayer).init();
+    });
+}
+
+/// A site with a home page and a post, where the post has `num_warnings` empty
+/// headings
+fn site_with_warnings(name: &str, num_warnings: usize) -> TestSite {
+    let site = TestSite::new(name);
+    site.write("content/index.dj", "# Home\n\n[Post](/blog/post.html)\n");
+    site.write(
+        "content/blog/post.dj",
+        &format!("# Post\n\n{}Text\n", "##\n\n".repeat(num_warnings)),
+    );
+    site
+}
+
+#[test]
+fn builds_site_and_reports_pages() {
+    collect_warnings();
+    let site = site_with_warnings("builder-pages", 0);
+
+    let mut written = vec![];
+    let build = site
+        .builder()
+        .on_page(|page| written.push(page.url_path.display().to_string()))
+        .build()
+        .expect("site builds");
+
+    assert_eq!(build.stats.pages, 2);
+    written.sort();
+    assert_eq!(written, ["/blog/post.html", "/index.html"]);
+    let urls = build.page_urls.values().cloned().collect::<Vec<_>>();
+    assert_eq!(urls, ["/blog/post.html", "/index.html"]);
+    assert!(build.warnings.is_empty(), "{:?}", build.warnings);
+    assert!(site.read_output("blog/post.html").contains("<h1>Post</h1>"));
+}
+
+#[test]
+fn collects_warnings_of_build() {
+    collect_warnings();
+    let site = site_with_warnings("builder-warnings", 2);
+
+    let build = site.builder().build().expect("site builds");
+
+    let messages = build
+        .warnings
+        .iter()
+        .map(|warning| warning.message.lines().next().unwrap_or_default())
+        .collect::<Vec<_>>();
+    assert_eq!(messages, ["Heading is empty", "Heading is empty"]);
+}
+
+#[test]
+fn denied_warnings_fail_build() {
+    collect_warnings();
+    let site = site_with_warnings("builder-denied", 1);
+
+    let err = site
+        .builder()
+        .deny_warnings(true)
+        .build()
+        .expect_err("warnings are denied");
+    assert_eq!(www::exit_code(&err), 6, "{err:#}");
+}
+
+#[test]
+fn concurrent_builds_keep_their_own_warnings() {
+    collect_warnings();
+    let sites = (0..4)
+        .map(|idx| site_with_warnings(&format!("builder-concurrent-{idx}"), idx))
+        .collect::<Vec<_>>();
+
+    thread::scope(|scope| {
+        let handles = sites
+            .iter()
+            .map(|site| scope.spawn(|| site.builder().build().expect("site builds")))
+            .collect::<Vec<_>>();
+        for (idx, handle) in handles.into_iter().enumerate() {
+            let build = handle.join().expect("build does not panic");
+            assert_eq!(build.warnings.len(), idx, "{:?}", build.warnings);
+        }
+    });
+}
+
+#[test]
+fn denied_broken_links_fail_build_with_own_code() {
+    collect_warnings();
+    let site = site_with_warnings("builder-broken-link", 0);
+    site.write(
+        "content/index.dj",
+        "# Home\n\n[Post](/blog/post.html#missing)\n",
+    );
+
+    let err = site
+        .builder()
+        .deny_warnings(true)
+        .build()
+        .expect_err("warnings are denied");
+    assert_eq!(www::exit_code(&err), 8, "{err:#}");
+}
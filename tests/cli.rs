@@ -0,0 +1,25 @@
+use std::process::Command;
+
+#[test]
+fn help_lists_every_exit_status() {
+    let output = Command::new(env!("CARGO_BIN_EXE_www"))
+        .arg("--help")
+        .output()
+        .expect("www runs");
+    let help = String::from_utf8(output.stdout).expect("help is UTF-8");
+
+    let listed = help
+        .lines()
+        .skip_while(|line| *line != "Error codes:")
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    // Success isn't an error, so argh doesn't list it
+    let expected = www::exit_statuses()
+        .into_iter()
+        .filter(|(code, _)| *code != 0)
+        .map(|(code, description)| format!("{code} {description}"))
+        .collect::<Vec<_>>();
+    assert_eq!(listed, expected, "{help}");
+}
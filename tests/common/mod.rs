@@ -0,0 +1,69 @@
+//! A site written to a temporary directory, for tests that build it.
+
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// A site config that leaves the output unformatted, since tests can't rely
+/// on prettier being installed
+const SITE_CONFIG: &str = r#"{"formatters": {"html": [], "css": [], "js": [], "json": []}}"#;
+
+/// A page template that only shows the content of the page
+const PAGE_TEMPLATE: &str = "<html><body>{{ content | safe }}</body></html>\n";
+
+/// A site with an input and an output directory, which are removed when it is
+/// dropped.
+pub struct TestSite {
+    root: PathBuf,
+}
+
+impl TestSite {
+    /// A site with a config and a page template, but without any content. The
+    /// name has to be unique among the tests of a test binary.
+    pub fn new(name: &str) -> Self {
+        let root = env::temp_dir().join(format!("www-test-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let site = Self { root };
+        site.write("site.json", SITE_CONFIG);
+        site.write("templates/page.html", PAGE_TEMPLATE);
+        site
+    }
+
+    /// Write a file of the site, relative to its input directory.
+    pub fn write(&self, path: impl AsRef<Path>, content: &str) {
+        let path = self.input_path().join(path);
+        fs::create_dir_all(path.parent().expect("site files have a parent"))
+            .expect("site directory can be created");
+        fs::write(path, content).expect("site file can be written");
+    }
+
+    /// Read a file of the built site, relative to its output directory.
+    pub fn read_output(&self, path: impl AsRef<Path>) -> String {
+        let path = self.output_path().join(path);
+        fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("failed to read [{}]: {err}", path.display()))
+    }
+
+    pub fn input_path(&self) -> PathBuf {
+        self.root.join("site")
+    }
+
+    pub fn output_path(&self) -> PathBuf {
+        self.root.join("output")
+    }
+
+    /// A builder for the site, which doesn't use the network.
+    pub fn builder(&self) -> www::SiteBuilder<'static> {
+        www::SiteBuilder::new(self.input_path(), self.output_path()).offline(true)
+    }
+}
+
+impl Drop for TestSite {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::TestSite;
+
+const IMAGES: &str = "![A cat](/cat.png)\n![A dog](/dog.png)\n";
+
+#[test]
+fn gallery_div_with_class_after_fence() {
+    let site = TestSite::new("gallery-fence");
+    site.write(
+        "content/index.dj",
+        &format!("# Home\n\n::: gallery\n{IMAGES}:::\n"),
+    );
+    site.builder().build().expect("site builds");
+
+    let html = site.read_output("index.html");
+    assert!(html.contains(r#"<div class="gallery">"#), "{html}");
+    assert!(
+        html.contains(r#"<a href="/cat.png" data-lightbox="gallery-1">"#),
+        "{html}"
+    );
+    assert!(html.contains(r#"alt="A dog""#), "{html}");
+}
+
+#[test]
+fn gallery_div_with_class_attribute() {
+    let site = TestSite::new("gallery-attribute");
+    site.write(
+        "content/index.dj",
+        &format!("# Home\n\n{{.gallery .wide}}\n:::\n{IMAGES}:::\n"),
+    );
+    site.builder().build().expect("site builds");
+
+    let html = site.read_output("index.html");
+    assert!(html.contains(r#"<div class="gallery wide">"#), "{html}");
+    assert!(
+        html.contains(r#"<a href="/cat.png" data-lightbox="gallery-1">"#),
+        "{html}"
+    );
+}
+
+#[test]
+fn gallery_div_with_nested_div() {
+    let site = TestSite::new("gallery-nested");
+    site.write(
+        "content/index.dj",
+        &format!("# Home\n\n{{.gallery}}\n::::\n{IMAGES}\n:::\nnot an image\n:::\n::::\n\nAfter\n"),
+    );
+    site.builder().build().expect("site builds");
+
+    let html = site.read_output("index.html");
+    assert!(html.contains(r#"<div class="gallery">"#), "{html}");
+    assert!(html.contains("<p>After</p>"), "{html}");
+    assert!(!html.contains("not an image"), "{html}");
+}
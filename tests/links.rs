@@ -0,0 +1,29 @@
+mod common;
+
+use std::fs;
+
+use common::TestSite;
+
+#[test]
+fn link_graph_quotes_titles_for_dot() {
+    let site = TestSite::new("links-dot");
+    site.write("content/index.dj", "# Home\n\n[Post](/post.html)\n");
+    site.write("content/post.dj", "# Zero\u{200b}width back\\slash\n");
+    let graph_path = site.output_path().with_file_name("links.dot");
+
+    site.builder()
+        .link_graph(&graph_path)
+        .build()
+        .expect("site builds");
+
+    let dot = fs::read_to_string(&graph_path).expect("link graph is written");
+    assert!(
+        dot.contains("  \"/post.html\" [label=\"Zero\u{200b}width back\\\\slash\"];"),
+        "{dot}"
+    );
+    assert!(
+        dot.contains("  \"/index.html\" -> \"/post.html\";"),
+        "{dot}"
+    );
+    assert!(!dot.contains("\\u{"), "{dot}");
+}
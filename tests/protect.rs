@@ -0,0 +1,86 @@
+use www::protect::{Aes256, IV_LEN, hmac_sha256, pbkdf2_sha256};
+
+fn hex(text: &str) -> Vec<u8> {
+    (0..text.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(&text[idx..idx + 2], 16).unwrap())
+        .collect()
+}
+
+/// FIPS 197, appendix C.3
+#[test]
+fn aes256_block() {
+    let key = hex("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f");
+    let block = hex("00112233445566778899aabbccddeeff");
+    let cipher = Aes256::new(&key.try_into().unwrap());
+    assert_eq!(
+        cipher.encrypt_block(&block.try_into().unwrap()).to_vec(),
+        hex("8ea2b7ca516745bfeafc49904b496089")
+    );
+}
+
+/// The GCM specification by McGrew and Viega, test cases 13 and 14
+#[test]
+fn gcm_zero_key() {
+    let cipher = Aes256::new(&[0; 32]);
+    assert_eq!(
+        cipher.encrypt_gcm(&[0; IV_LEN], &[]),
+        hex("530f8afbc74536b9a963b4f1c4cb738b")
+    );
+    assert_eq!(
+        cipher.encrypt_gcm(&[0; IV_LEN], &[0; 16]),
+        hex("cea7403d4d606b6e074ec5d3baf39d18d0d1c8a799996bf0265b98b5d48ab919")
+    );
+}
+
+/// The GCM specification by McGrew and Viega, test case 15, along with
+/// its plaintext cut short of a whole block
+#[test]
+fn gcm_partial_block() {
+    let key = hex("feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308");
+    let iv = hex("cafebabefacedbaddecaf888");
+    let plaintext = hex(concat!(
+        "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72",
+        "1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b391aafd255"
+    ));
+    let cipher = Aes256::new(&key.try_into().unwrap());
+    let iv = iv.try_into().unwrap();
+    assert_eq!(
+        cipher.encrypt_gcm(&iv, &plaintext),
+        hex(concat!(
+            "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa",
+            "8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662898015ad",
+            "b094dac5d93471bdec1a502270e3cc6c"
+        ))
+    );
+    assert_eq!(
+        cipher.encrypt_gcm(&iv, &plaintext[..60]),
+        hex(concat!(
+            "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa",
+            "8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662",
+            "eb9f796c8d356fc31a8433884b696f4f"
+        ))
+    );
+}
+
+/// RFC 4231, test case 1
+#[test]
+fn hmac() {
+    assert_eq!(
+        hmac_sha256(&[0x0b; 20], b"Hi There").to_vec(),
+        hex("b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7")
+    );
+}
+
+/// RFC 7914, section 11, and the common 4096 iteration vector
+#[test]
+fn pbkdf2() {
+    assert_eq!(
+        pbkdf2_sha256(b"passwd", b"salt", 1).to_vec(),
+        hex("55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc")
+    );
+    assert_eq!(
+        pbkdf2_sha256(b"password", b"salt", 4096).to_vec(),
+        hex("c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a")
+    );
+}